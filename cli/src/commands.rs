@@ -0,0 +1,1088 @@
+//! The tool's command line, and the instructions each pool command sends.
+
+use std::path::{Path, PathBuf};
+
+use anchor_lang::AnchorSerialize;
+use clap::{ArgGroup, Args, Parser, Subcommand};
+use reward_pool_client::{
+    accept_config_admin, allow_list_proof, allow_list_root, cancel_config_admin_transfer,
+    initialize_config, pool_signer_address, propose_config_admin, set_global_freeze, update_config,
+    Pool, PoolGate, PoolKeys, ProgramConfig,
+};
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::rent::Rent;
+use solana_sdk::system_instruction;
+use spl_associated_token_account::{create_associated_token_account, get_associated_token_address};
+
+use crate::Result;
+
+#[derive(Parser, Debug)]
+#[clap(name = "reward-pool", version, about = "Operates reward pools")]
+pub struct Cli {
+    /// RPC URL, or cluster: mainnet-beta, devnet, testnet or localhost
+    #[clap(short = 'u', long, global = true, default_value = "localhost")]
+    pub url: String,
+    /// Keypair of the wallet acting and paying fees [default: ~/.config/solana/id.json]
+    #[clap(short = 'k', long, global = true)]
+    pub keypair: Option<PathBuf>,
+    /// Print the transaction unsigned and base64 encoded, for a multisig to sign, rather
+    /// than sending it
+    #[clap(long, global = true)]
+    pub unsigned: bool,
+    /// Wallet acting in place of the keypair's, with --unsigned
+    #[clap(long, global = true, requires = "unsigned")]
+    pub signer: Option<Pubkey>,
+    /// Fee payer in place of the wallet acting, with --unsigned
+    #[clap(long, global = true, requires = "unsigned")]
+    pub fee_payer: Option<Pubkey>,
+    /// Recent blockhash in place of the cluster's latest, with --unsigned
+    #[clap(long, global = true, requires = "unsigned")]
+    pub blockhash: Option<Hash>,
+    #[clap(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Prints a pool or user account, or the program config
+    #[clap(subcommand)]
+    Show(Show),
+    /// Projects the rates and period ends funding a pool would give, sending nothing
+    Simulate(Simulate),
+    #[clap(flatten)]
+    Config(ConfigCommand),
+    #[clap(flatten)]
+    Pool(Box<PoolCommand>),
+}
+
+/// The pool a command acts on.
+#[derive(Args, Debug)]
+pub struct PoolArg {
+    /// Address of the pool
+    pub pool: Pubkey,
+    /// Read the pool from a file of its account data, as `solana account --output-file`
+    /// writes, rather than from the cluster
+    #[clap(long)]
+    pub pool_file: Option<PathBuf>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Show {
+    /// Prints a pool
+    Pool {
+        #[clap(flatten)]
+        pool: PoolArg,
+        /// Unix time to show the pool at [default: now]
+        #[clap(long)]
+        at: Option<u64>,
+    },
+    /// Prints a user, along with the rewards it could claim
+    User {
+        #[clap(flatten)]
+        pool: PoolArg,
+        /// Owner of the user account
+        owner: Pubkey,
+        /// Read the user from a file of its account data rather than from the cluster
+        #[clap(long)]
+        user_file: Option<PathBuf>,
+        /// Unix time to show the user at [default: now]
+        #[clap(long)]
+        at: Option<u64>,
+    },
+    /// Prints the program config
+    Config,
+}
+
+/// Commands on the program config, which every pool takes its deposit and shortest
+/// reward period from.
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommand {
+    /// Creates the program config, as the program's upgrade authority
+    InitializeConfig {
+        /// Mint of the deposit taken to initialize or unpause a pool
+        deposit_mint: Pubkey,
+        deposit_requirement: u64,
+        /// Shortest reward period in seconds
+        min_duration: u64,
+        /// Config admin [default: the wallet acting]
+        #[clap(long)]
+        admin: Option<Pubkey>,
+    },
+    /// Updates the program config, as its admin
+    UpdateConfig {
+        /// Mint of the deposit taken to initialize or unpause a pool
+        deposit_mint: Pubkey,
+        deposit_requirement: u64,
+        /// Shortest reward period in seconds
+        min_duration: u64,
+    },
+    /// Stops staking, funding and claiming in every pool, leaving unstaking open
+    FreezeProgram,
+    /// Lifts a freeze of every pool
+    UnfreezeProgram,
+    /// Proposes a new config admin, which takes over once it accepts
+    ProposeConfigAdmin { new_admin: Pubkey },
+    /// Cancels a pending config admin transfer
+    CancelConfigAdminTransfer,
+    /// Takes over the program config as its pending admin
+    AcceptConfigAdmin,
+}
+
+impl ConfigCommand {
+    /// The instructions of the command, sent by `actor`.
+    pub fn instructions(&self, actor: Pubkey) -> Vec<Instruction> {
+        match self {
+            ConfigCommand::InitializeConfig {
+                deposit_mint,
+                deposit_requirement,
+                min_duration,
+                admin,
+            } => vec![initialize_config(
+                actor,
+                admin.unwrap_or(actor),
+                *deposit_mint,
+                *deposit_requirement,
+                *min_duration,
+            )],
+            ConfigCommand::UpdateConfig {
+                deposit_mint,
+                deposit_requirement,
+                min_duration,
+            } => vec![update_config(
+                actor,
+                *deposit_mint,
+                *deposit_requirement,
+                *min_duration,
+            )],
+            ConfigCommand::FreezeProgram => vec![set_global_freeze(actor, true)],
+            ConfigCommand::UnfreezeProgram => vec![set_global_freeze(actor, false)],
+            ConfigCommand::ProposeConfigAdmin { new_admin } => {
+                vec![propose_config_admin(actor, *new_admin)]
+            }
+            ConfigCommand::CancelConfigAdminTransfer => vec![cancel_config_admin_transfer(actor)],
+            ConfigCommand::AcceptConfigAdmin => vec![accept_config_admin(actor)],
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+#[clap(group(ArgGroup::new("funding").required(true).args(&["fund", "top-up"])))]
+pub struct Simulate {
+    #[clap(flatten)]
+    pub pool: PoolArg,
+    /// Amounts funded, one per reward, starting a new reward period
+    #[clap(long, value_delimiter = ',')]
+    pub fund: Option<Vec<u64>>,
+    /// Amounts topped up, one per reward, keeping the current period's end
+    #[clap(long, value_delimiter = ',')]
+    pub top_up: Option<Vec<u64>>,
+    /// Unix time of the funding [default: now]
+    #[clap(long)]
+    pub at: Option<u64>,
+    /// Reward vault balances, only read by V1 pools [default: fetched]
+    #[clap(long, value_delimiter = ',')]
+    pub vault_amounts: Option<Vec<u64>>,
+}
+
+/// Token accounts default to the associated token accounts of the wallet acting.
+#[derive(Subcommand, Debug)]
+pub enum PoolCommand {
+    /// Creates and initializes an A/B pool, its vaults owned by the pool signer
+    InitializePool {
+        /// Keypair of the new pool account
+        pool_keypair: PathBuf,
+        staking_mint: Pubkey,
+        reward_a_mint: Pubkey,
+        /// Same as reward A for single reward pools
+        reward_b_mint: Pubkey,
+        /// Reward period duration in seconds
+        reward_duration: u64,
+        #[clap(flatten)]
+        init: InitArgs,
+        #[clap(long, default_value = "0")]
+        early_exit_penalty_bps: u16,
+        /// Seconds over which the early exit penalty decays
+        #[clap(long, default_value = "0")]
+        early_exit_penalty_window: u64,
+        /// Token account receiving early exit penalties [default: shared by stakers]
+        #[clap(long)]
+        penalty_treasury: Option<Pubkey>,
+    },
+    /// Creates and initializes a multi reward pool sharing one reward schedule
+    InitializePoolV3 {
+        pool_keypair: PathBuf,
+        staking_mint: Pubkey,
+        reward_duration: u64,
+        #[clap(flatten)]
+        init: InitArgs,
+    },
+    /// Creates and initializes a multi reward pool with a schedule per reward
+    InitializePoolV4 {
+        pool_keypair: PathBuf,
+        staking_mint: Pubkey,
+        reward_duration: u64,
+        #[clap(flatten)]
+        init: InitArgs,
+    },
+    /// Adds a reward to a multi reward pool, creating its vault
+    AddReward {
+        #[clap(flatten)]
+        pool: PoolArg,
+        reward_mint: Pubkey,
+    },
+    /// Sets the duration of a reward of a V4 pool
+    SetRewardDuration {
+        #[clap(flatten)]
+        pool: PoolArg,
+        reward_index: u8,
+        reward_duration: u64,
+    },
+    /// Creates the user account of the wallet acting
+    CreateUser {
+        #[clap(flatten)]
+        pool: PoolArg,
+        /// Allow-list the pool is gated by, to prove the wallet is on it
+        #[clap(long)]
+        allow_list: Option<PathBuf>,
+        /// Token of a gated pool's mint or collection the wallet holds
+        #[clap(long)]
+        token_mint: Option<Pubkey>,
+        /// Account holding that token [default: the wallet's associated token account]
+        #[clap(long)]
+        token_account: Option<Pubkey>,
+    },
+    /// Pauses a pool, returning its xSTEP deposit
+    Pause {
+        #[clap(flatten)]
+        pool: PoolArg,
+        #[clap(long)]
+        x_token_receiver: Option<Pubkey>,
+        /// Mint of the pool's deposit [default: the program config's]
+        #[clap(long)]
+        deposit_mint: Option<Pubkey>,
+    },
+    /// Unpauses a pool, depositing xSTEP into a new vault
+    Unpause {
+        #[clap(flatten)]
+        pool: PoolArg,
+        #[clap(long)]
+        x_token_depositor: Option<Pubkey>,
+        /// Mint of the deposit taken [default: the program config's]
+        #[clap(long)]
+        deposit_mint: Option<Pubkey>,
+    },
+    /// Stakes tokens of the wallet acting
+    Stake {
+        #[clap(flatten)]
+        pool: PoolArg,
+        amount: u64,
+        #[clap(long)]
+        token_account: Option<Pubkey>,
+    },
+    /// Stakes tokens for another wallet, opening their user account if needed
+    StakeFor {
+        #[clap(flatten)]
+        pool: PoolArg,
+        /// Wallet credited with the stake
+        owner: Pubkey,
+        amount: u64,
+        #[clap(long)]
+        token_account: Option<Pubkey>,
+        /// Allow-list the pool is gated by, to prove the owner is on it
+        #[clap(long)]
+        allow_list: Option<PathBuf>,
+    },
+    /// Unstakes tokens, into unbonding on pools with an unbonding period
+    Unstake {
+        #[clap(flatten)]
+        pool: PoolArg,
+        amount: u64,
+        #[clap(long)]
+        token_account: Option<Pubkey>,
+    },
+    /// Withdraws all stake without settling rewards, forfeiting those pending
+    EmergencyUnstake {
+        #[clap(flatten)]
+        pool: PoolArg,
+        #[clap(long)]
+        token_account: Option<Pubkey>,
+    },
+    /// Withdraws stake whose unbonding period has passed
+    WithdrawUnbonded {
+        #[clap(flatten)]
+        pool: PoolArg,
+        #[clap(long)]
+        token_account: Option<Pubkey>,
+    },
+    /// Restakes tokens still unbonding
+    CancelUnbonding {
+        #[clap(flatten)]
+        pool: PoolArg,
+    },
+    /// Claims early exit penalty rewards, paid in the staking token
+    ClaimPenaltyRewards {
+        #[clap(flatten)]
+        pool: PoolArg,
+        #[clap(long)]
+        token_account: Option<Pubkey>,
+    },
+    /// Locks staked tokens for a duration, boosting their rewards
+    LockStake {
+        #[clap(flatten)]
+        pool: PoolArg,
+        amount: u64,
+        lock_duration: u64,
+    },
+    /// Releases the expired lock of a user's stake
+    ReleaseLock {
+        #[clap(flatten)]
+        pool: PoolArg,
+        /// Owner of the user [default: the wallet acting]
+        #[clap(long)]
+        owner: Option<Pubkey>,
+    },
+    /// Configures the lock durations and boost of a pool
+    ConfigureLockup {
+        #[clap(flatten)]
+        pool: PoolArg,
+        min_lock_duration: u64,
+        max_lock_duration: u64,
+        max_lock_boost_bps: u16,
+    },
+    /// Sets the unbonding period of a pool
+    SetUnbondingPeriod {
+        #[clap(flatten)]
+        pool: PoolArg,
+        unbonding_period: u64,
+    },
+    /// Sets the most a user may stake and the most staked in total, zero for no cap
+    SetStakeCaps {
+        #[clap(flatten)]
+        pool: PoolArg,
+        max_stake_per_user: u64,
+        max_total_stake: u64,
+    },
+    /// Sets the token account receiving early exit penalties
+    SetPenaltyTreasury {
+        #[clap(flatten)]
+        pool: PoolArg,
+        /// [default: shared by stakers]
+        penalty_treasury: Option<Pubkey>,
+    },
+    /// Authorizes a wallet to fund a pool
+    AuthorizeFunder {
+        #[clap(flatten)]
+        pool: PoolArg,
+        funder: Pubkey,
+    },
+    /// Removes a funder of a pool
+    DeauthorizeFunder {
+        #[clap(flatten)]
+        pool: PoolArg,
+        funder: Pubkey,
+    },
+    /// Proposes a new pool authority, which takes over once it accepts
+    ProposeAuthority {
+        #[clap(flatten)]
+        pool: PoolArg,
+        new_authority: Pubkey,
+    },
+    /// Cancels a pending authority transfer
+    CancelAuthorityTransfer {
+        #[clap(flatten)]
+        pool: PoolArg,
+    },
+    /// Takes over a pool as its pending authority
+    AcceptAuthority {
+        #[clap(flatten)]
+        pool: PoolArg,
+    },
+    /// Funds an A/B pool, starting a new reward period
+    Fund {
+        #[clap(flatten)]
+        pool: PoolArg,
+        amount_a: u64,
+        amount_b: u64,
+        #[clap(flatten)]
+        from: FundingAccounts,
+    },
+    /// Funds the current reward period of an A/B pool without moving its end
+    TopUp {
+        #[clap(flatten)]
+        pool: PoolArg,
+        amount_a: u64,
+        amount_b: u64,
+        #[clap(flatten)]
+        from: FundingAccounts,
+    },
+    /// Queues a reward period of an A/B pool
+    FundScheduled {
+        #[clap(flatten)]
+        pool: PoolArg,
+        amount_a: u64,
+        amount_b: u64,
+        start_ts: u64,
+        end_ts: u64,
+        #[clap(flatten)]
+        from: FundingAccounts,
+    },
+    /// Claims the rewards of an A/B pool
+    Claim {
+        #[clap(flatten)]
+        pool: PoolArg,
+        /// Accounts receiving reward A and B
+        #[clap(long, value_delimiter = ',')]
+        reward_accounts: Option<Vec<Pubkey>>,
+    },
+    /// Stakes reward A, for pools rewarding the staking token
+    Compound {
+        #[clap(flatten)]
+        pool: PoolArg,
+    },
+    /// Funds a multi reward pool
+    FundV3 {
+        #[clap(flatten)]
+        pool: PoolArg,
+        /// Amounts funded, one per reward
+        #[clap(value_delimiter = ',', required = true)]
+        amounts: Vec<u64>,
+        #[clap(flatten)]
+        from: FundingAccounts,
+    },
+    /// Claims rewards of a multi reward pool
+    ClaimV3 {
+        #[clap(flatten)]
+        pool: PoolArg,
+        /// Indexes of the rewards claimed
+        #[clap(value_delimiter = ',', required = true)]
+        reward_indexes: Vec<u8>,
+        /// Accounts receiving each of the pool's rewards
+        #[clap(long, value_delimiter = ',')]
+        reward_accounts: Option<Vec<Pubkey>>,
+    },
+    /// Claims all of a user's rewards on their behalf, into the associated token accounts
+    /// of their reward recipient
+    ClaimFor {
+        #[clap(flatten)]
+        pool: PoolArg,
+        /// Owner of the user account
+        owner: Pubkey,
+        /// The user's reward recipient [default: the owner]
+        #[clap(long)]
+        recipient: Option<Pubkey>,
+    },
+    /// Sets the wallet rewards claimed on your behalf are paid to
+    SetRewardRecipient {
+        #[clap(flatten)]
+        pool: PoolArg,
+        /// The recipient, the default key for yourself
+        reward_recipient: Pubkey,
+    },
+    /// Sweeps staking tokens sent straight to the staking vault
+    SweepDonatedStake {
+        #[clap(flatten)]
+        pool: PoolArg,
+        #[clap(long)]
+        receiver: Option<Pubkey>,
+    },
+    /// Sweeps rewards a vault holds beyond what is owed or still to be emitted
+    SweepUnallocatedRewards {
+        #[clap(flatten)]
+        pool: PoolArg,
+        reward_index: u8,
+        #[clap(long)]
+        receiver: Option<Pubkey>,
+    },
+    /// Closes the user account of the wallet acting
+    CloseUser {
+        #[clap(flatten)]
+        pool: PoolArg,
+    },
+    /// Closes an A/B pool, refunding its vaults
+    ClosePool {
+        #[clap(flatten)]
+        pool: PoolArg,
+        #[clap(flatten)]
+        refund: RefundAccounts,
+    },
+    /// Closes a multi reward pool, refunding its vaults
+    ClosePoolV3 {
+        #[clap(flatten)]
+        pool: PoolArg,
+        #[clap(flatten)]
+        refund: RefundAccounts,
+    },
+    /// Grows a pool created by an earlier version of the program
+    MigratePool {
+        #[clap(flatten)]
+        pool: PoolArg,
+    },
+    /// Grows a user created by an earlier version of the program
+    MigrateUser {
+        #[clap(flatten)]
+        pool: PoolArg,
+        /// Owner of the user [default: the wallet acting]
+        #[clap(long)]
+        owner: Option<Pubkey>,
+    },
+}
+
+#[derive(Args, Debug)]
+#[clap(group(ArgGroup::new("gate").args(&[
+    "gate-authority",
+    "gate-allow-list",
+    "gate-mint",
+    "gate-collection",
+])))]
+pub struct InitArgs {
+    /// Pool authority [default: the wallet acting]
+    #[clap(long)]
+    pub authority: Option<Pubkey>,
+    /// Account the xSTEP deposit is taken from
+    #[clap(long)]
+    pub x_token_depositor: Option<Pubkey>,
+    /// Mint of the deposit taken [default: the program config's]
+    #[clap(long)]
+    pub deposit_mint: Option<Pubkey>,
+    /// Only users this authority co-signs for may join
+    #[clap(long)]
+    pub gate_authority: Option<Pubkey>,
+    /// Only owners in this file, one address per line, may join
+    #[clap(long)]
+    pub gate_allow_list: Option<PathBuf>,
+    /// Only holders of this mint may join
+    #[clap(long)]
+    pub gate_mint: Option<Pubkey>,
+    /// Only holders of an NFT of this verified collection may join
+    #[clap(long)]
+    pub gate_collection: Option<Pubkey>,
+    /// The most a user may stake, 0 for no cap
+    #[clap(long, default_value = "0")]
+    pub max_stake_per_user: u64,
+    /// The most staked in the pool in total, 0 for no cap
+    #[clap(long, default_value = "0")]
+    pub max_total_stake: u64,
+}
+
+impl InitArgs {
+    /// The gate the pool is initialized with, and the key it checks against.
+    pub fn gate(&self) -> Result<(PoolGate, Pubkey)> {
+        Ok(match self {
+            InitArgs {
+                gate_authority: Some(authority),
+                ..
+            } => (PoolGate::Authority, *authority),
+            InitArgs {
+                gate_allow_list: Some(path),
+                ..
+            } => (
+                PoolGate::MerkleRoot,
+                allow_list_root(&read_allow_list(path)?),
+            ),
+            InitArgs {
+                gate_mint: Some(mint),
+                ..
+            } => (PoolGate::Mint, *mint),
+            InitArgs {
+                gate_collection: Some(collection),
+                ..
+            } => (PoolGate::Collection, *collection),
+            _ => (PoolGate::Open, Pubkey::default()),
+        })
+    }
+}
+
+/// The owners on an allow-list file, one address per line.
+pub fn read_allow_list(path: &Path) -> Result<Vec<Pubkey>> {
+    std::fs::read_to_string(path)?
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            line.parse()
+                .map_err(|_| format!("{}: not an address: {}", path.display(), line).into())
+        })
+        .collect()
+}
+
+#[derive(Args, Debug)]
+pub struct FundingAccounts {
+    /// Accounts funding each reward
+    #[clap(long, value_delimiter = ',')]
+    pub from: Option<Vec<Pubkey>>,
+}
+
+#[derive(Args, Debug)]
+pub struct RefundAccounts {
+    /// Wallet refunded the pool's rent and, by default, its vaults [default: the wallet
+    /// acting]
+    #[clap(long)]
+    pub refundee: Option<Pubkey>,
+    #[clap(long)]
+    pub staking_refundee: Option<Pubkey>,
+    /// Accounts refunded each reward vault
+    #[clap(long, value_delimiter = ',')]
+    pub reward_refundees: Option<Vec<Pubkey>>,
+}
+
+fn ata(owner: &Pubkey, mint: &Pubkey) -> Pubkey {
+    get_associated_token_address(owner, mint)
+}
+
+/// The given token accounts, one per mint, or the wallet's associated token accounts.
+fn atas_or(
+    accounts: &Option<Vec<Pubkey>>,
+    owner: &Pubkey,
+    mints: &[Pubkey],
+) -> Result<Vec<Pubkey>> {
+    match accounts {
+        Some(accounts) if accounts.len() != mints.len() => Err(format!(
+            "{} token accounts given for the pool's {} rewards",
+            accounts.len(),
+            mints.len()
+        )
+        .into()),
+        Some(accounts) => Ok(accounts.clone()),
+        None => Ok(mints.iter().map(|mint| ata(owner, mint)).collect()),
+    }
+}
+
+/// Instructions creating a pool account and the vaults its pool signer owns, for the
+/// keys of a new pool.
+fn create_pool(
+    payer: &Pubkey,
+    pool: &Pubkey,
+    init: &InitArgs,
+    deposit_mint: Pubkey,
+    staking_mint: Pubkey,
+    reward_mints: Vec<Pubkey>,
+) -> Result<(Vec<Instruction>, PoolKeys)> {
+    let (pool_signer, _) = pool_signer_address(pool);
+    let space = 8 + Pool::default().try_to_vec()?.len();
+    let mut instructions = vec![system_instruction::create_account(
+        payer,
+        pool,
+        Rent::default().minimum_balance(space),
+        space as u64,
+        &reward_pool_client::ID,
+    )];
+
+    //pools rewarding the staking token, or a single reward, share vaults
+    let mut vault_mints: Vec<Pubkey> = vec![];
+    for mint in [deposit_mint, staking_mint].iter().chain(&reward_mints) {
+        if !vault_mints.contains(mint) {
+            vault_mints.push(*mint);
+        }
+    }
+    for mint in vault_mints {
+        instructions.push(create_associated_token_account(payer, &pool_signer, &mint));
+    }
+
+    let mut keys = PoolKeys::new(
+        *pool,
+        init.authority.unwrap_or(*payer),
+        ata(&pool_signer, &deposit_mint),
+        staking_mint,
+        ata(&pool_signer, &staking_mint),
+        reward_mints.clone(),
+        reward_mints.iter().map(|m| ata(&pool_signer, m)).collect(),
+    );
+    (keys.gate, keys.gate_key) = init.gate()?;
+    keys.max_stake_per_user = init.max_stake_per_user;
+    keys.max_total_stake = init.max_total_stake;
+    Ok((instructions, keys))
+}
+
+impl PoolCommand {
+    /// The existing pool acted on, `None` for commands initializing one.
+    pub fn pool(&self) -> Option<&PoolArg> {
+        use PoolCommand::*;
+        match self {
+            InitializePool { .. } | InitializePoolV3 { .. } | InitializePoolV4 { .. } => None,
+            AddReward { pool, .. }
+            | SetRewardDuration { pool, .. }
+            | CreateUser { pool, .. }
+            | Pause { pool, .. }
+            | Unpause { pool, .. }
+            | Stake { pool, .. }
+            | Unstake { pool, .. }
+            | StakeFor { pool, .. }
+            | EmergencyUnstake { pool, .. }
+            | WithdrawUnbonded { pool, .. }
+            | CancelUnbonding { pool }
+            | ClaimPenaltyRewards { pool, .. }
+            | LockStake { pool, .. }
+            | ReleaseLock { pool, .. }
+            | ConfigureLockup { pool, .. }
+            | SetUnbondingPeriod { pool, .. }
+            | SetStakeCaps { pool, .. }
+            | SetPenaltyTreasury { pool, .. }
+            | AuthorizeFunder { pool, .. }
+            | DeauthorizeFunder { pool, .. }
+            | ProposeAuthority { pool, .. }
+            | CancelAuthorityTransfer { pool }
+            | AcceptAuthority { pool }
+            | Fund { pool, .. }
+            | TopUp { pool, .. }
+            | FundScheduled { pool, .. }
+            | Claim { pool, .. }
+            | Compound { pool }
+            | FundV3 { pool, .. }
+            | ClaimV3 { pool, .. }
+            | ClaimFor { pool, .. }
+            | SetRewardRecipient { pool, .. }
+            | SweepDonatedStake { pool, .. }
+            | SweepUnallocatedRewards { pool, .. }
+            | CloseUser { pool }
+            | ClosePool { pool, .. }
+            | ClosePoolV3 { pool, .. }
+            | MigratePool { pool }
+            | MigrateUser { pool, .. } => Some(pool),
+        }
+    }
+
+    /// The keypair of the pool being initialized, which signs for its account.
+    pub fn pool_keypair(&self) -> Option<&PathBuf> {
+        use PoolCommand::*;
+        match self {
+            InitializePool { pool_keypair, .. }
+            | InitializePoolV3 { pool_keypair, .. }
+            | InitializePoolV4 { pool_keypair, .. } => Some(pool_keypair),
+            _ => None,
+        }
+    }
+
+    /// Whether the command takes its deposit mint from the program config, for lack of
+    /// `--deposit-mint`.
+    pub fn needs_config(&self) -> bool {
+        use PoolCommand::*;
+        match self {
+            InitializePool { init, .. }
+            | InitializePoolV3 { init, .. }
+            | InitializePoolV4 { init, .. } => init.deposit_mint.is_none(),
+            Pause {
+                x_token_receiver,
+                deposit_mint,
+                ..
+            } => x_token_receiver.is_none() && deposit_mint.is_none(),
+            Unpause { deposit_mint, .. } => deposit_mint.is_none(),
+            _ => false,
+        }
+    }
+
+    /// The instructions of the command, sent by `actor`. `pool` is the key of the pool
+    /// being initialized, or of the existing pool acted on along with its account.
+    /// `config` is the program config, needed when [`PoolCommand::needs_config`].
+    pub fn instructions(
+        &self,
+        actor: Pubkey,
+        pool: Pubkey,
+        state: Option<&Pool>,
+        config: Option<&ProgramConfig>,
+    ) -> Result<Vec<Instruction>> {
+        use PoolCommand::*;
+
+        let deposit_mint = |deposit_mint: &Option<Pubkey>| -> Result<Pubkey> {
+            deposit_mint
+                .or_else(|| config.map(|config| config.deposit_mint))
+                .ok_or_else(|| "the program config is needed for the deposit mint".into())
+        };
+
+        match self {
+            InitializePool {
+                staking_mint,
+                reward_a_mint,
+                reward_b_mint,
+                reward_duration,
+                init,
+                early_exit_penalty_bps,
+                early_exit_penalty_window,
+                penalty_treasury,
+                ..
+            } => {
+                let deposit_mint = deposit_mint(&init.deposit_mint)?;
+                let (mut instructions, mut keys) = create_pool(
+                    &actor,
+                    &pool,
+                    init,
+                    deposit_mint,
+                    *staking_mint,
+                    vec![*reward_a_mint, *reward_b_mint],
+                )?;
+                keys.penalty_treasury = *penalty_treasury;
+                instructions.push(
+                    keys.initialize_pool(
+                        init.x_token_depositor
+                            .unwrap_or_else(|| ata(&actor, &deposit_mint)),
+                        actor,
+                        *reward_duration,
+                        *early_exit_penalty_bps,
+                        *early_exit_penalty_window,
+                    ),
+                );
+                return Ok(instructions);
+            }
+            InitializePoolV3 {
+                staking_mint,
+                reward_duration,
+                init,
+                ..
+            }
+            | InitializePoolV4 {
+                staking_mint,
+                reward_duration,
+                init,
+                ..
+            } => {
+                let deposit_mint = deposit_mint(&init.deposit_mint)?;
+                let (mut instructions, keys) =
+                    create_pool(&actor, &pool, init, deposit_mint, *staking_mint, vec![])?;
+                let depositor = init
+                    .x_token_depositor
+                    .unwrap_or_else(|| ata(&actor, &deposit_mint));
+                instructions.push(if matches!(self, InitializePoolV3 { .. }) {
+                    keys.initialize_pool_v3(depositor, actor, *reward_duration)
+                } else {
+                    keys.initialize_pool_v4(depositor, actor, *reward_duration)
+                });
+                return Ok(instructions);
+            }
+            _ => {}
+        }
+
+        let state = state.ok_or("the pool's account is needed")?;
+        let keys = PoolKeys::from_pool(pool, state);
+        let staking_account =
+            |account: &Option<Pubkey>| account.unwrap_or_else(|| ata(&actor, &keys.staking_mint));
+
+        let instruction = match self {
+            InitializePool { .. } | InitializePoolV3 { .. } | InitializePoolV4 { .. } => {
+                unreachable!()
+            }
+            AddReward { reward_mint, .. } => {
+                let vault = ata(&keys.pool_signer, reward_mint);
+                return Ok(vec![
+                    create_associated_token_account(&actor, &keys.pool_signer, reward_mint),
+                    keys.add_reward(*reward_mint, vault),
+                ]);
+            }
+            SetRewardDuration {
+                reward_index,
+                reward_duration,
+                ..
+            } => keys.set_reward_duration(*reward_index, *reward_duration),
+            CreateUser {
+                allow_list,
+                token_mint,
+                token_account,
+                ..
+            } => match keys.gate {
+                PoolGate::MerkleRoot => {
+                    let path = allow_list
+                        .as_ref()
+                        .ok_or("the pool is gated by an allow-list, see --allow-list")?;
+                    let proof = allow_list_proof(&read_allow_list(path)?, &actor)
+                        .ok_or_else(|| format!("{} is not on the allow-list", actor))?;
+                    keys.create_user_with_proof(actor, proof)
+                }
+                PoolGate::Mint | PoolGate::Collection => {
+                    let mint = match (keys.gate, token_mint) {
+                        (PoolGate::Mint, _) => keys.gate_key,
+                        (_, Some(mint)) => *mint,
+                        _ => {
+                            return Err("the pool is gated by a collection, see --token-mint".into())
+                        }
+                    };
+                    let account = token_account.unwrap_or_else(|| ata(&actor, &mint));
+                    keys.create_user_holding(actor, account, mint)
+                }
+                _ => keys.create_user(actor),
+            },
+            Pause {
+                x_token_receiver,
+                deposit_mint: mint,
+                ..
+            } => keys.pause(match x_token_receiver {
+                Some(receiver) => *receiver,
+                None => ata(&actor, &deposit_mint(mint)?),
+            }),
+            Unpause {
+                x_token_depositor,
+                deposit_mint: mint,
+                ..
+            } => {
+                let mint = deposit_mint(mint)?;
+                let vault = ata(&keys.pool_signer, &mint);
+                let depositor = x_token_depositor.unwrap_or_else(|| ata(&actor, &mint));
+                return Ok(vec![
+                    create_associated_token_account(&actor, &keys.pool_signer, &mint),
+                    keys.unpause(vault, depositor, actor),
+                ]);
+            }
+            Stake {
+                amount,
+                token_account,
+                ..
+            } => keys.stake(actor, staking_account(token_account), *amount),
+            StakeFor {
+                owner,
+                amount,
+                token_account,
+                allow_list,
+                ..
+            } => {
+                let proof = match allow_list {
+                    Some(path) => allow_list_proof(&read_allow_list(path)?, owner)
+                        .ok_or_else(|| format!("{} is not on the allow-list", owner))?,
+                    None => vec![],
+                };
+                keys.stake_for(
+                    actor,
+                    staking_account(token_account),
+                    *owner,
+                    *amount,
+                    proof,
+                )
+            }
+            Unstake {
+                amount,
+                token_account,
+                ..
+            } => keys.unstake(actor, staking_account(token_account), *amount),
+            EmergencyUnstake { token_account, .. } => {
+                keys.emergency_unstake(actor, staking_account(token_account))
+            }
+            WithdrawUnbonded { token_account, .. } => {
+                keys.withdraw_unbonded(actor, staking_account(token_account))
+            }
+            CancelUnbonding { .. } => keys.cancel_unbonding(actor),
+            ClaimPenaltyRewards { token_account, .. } => {
+                keys.claim_penalty_rewards(actor, staking_account(token_account))
+            }
+            LockStake {
+                amount,
+                lock_duration,
+                ..
+            } => keys.lock_stake(actor, *amount, *lock_duration),
+            ReleaseLock { owner, .. } => keys.release_lock(owner.unwrap_or(actor)),
+            ConfigureLockup {
+                min_lock_duration,
+                max_lock_duration,
+                max_lock_boost_bps,
+                ..
+            } => keys.configure_lockup(*min_lock_duration, *max_lock_duration, *max_lock_boost_bps),
+            SetUnbondingPeriod {
+                unbonding_period, ..
+            } => keys.set_unbonding_period(*unbonding_period),
+            SetStakeCaps {
+                max_stake_per_user,
+                max_total_stake,
+                ..
+            } => keys.set_stake_caps(*max_stake_per_user, *max_total_stake),
+            SetPenaltyTreasury {
+                penalty_treasury, ..
+            } => keys.set_penalty_treasury(*penalty_treasury),
+            AuthorizeFunder { funder, .. } => keys.authorize_funder(*funder),
+            DeauthorizeFunder { funder, .. } => keys.deauthorize_funder(*funder),
+            ProposeAuthority { new_authority, .. } => keys.propose_authority(*new_authority),
+            CancelAuthorityTransfer { .. } => keys.cancel_authority_transfer(),
+            AcceptAuthority { .. } => keys.accept_authority(actor),
+            Fund {
+                amount_a,
+                amount_b,
+                from,
+                ..
+            } => {
+                let from = atas_or(&from.from, &actor, &keys.reward_mints)?;
+                keys.fund(actor, from[0], from[1], *amount_a, *amount_b)
+            }
+            TopUp {
+                amount_a,
+                amount_b,
+                from,
+                ..
+            } => {
+                let from = atas_or(&from.from, &actor, &keys.reward_mints)?;
+                keys.top_up(actor, from[0], from[1], *amount_a, *amount_b)
+            }
+            FundScheduled {
+                amount_a,
+                amount_b,
+                start_ts,
+                end_ts,
+                from,
+                ..
+            } => {
+                let from = atas_or(&from.from, &actor, &keys.reward_mints)?;
+                keys.fund_scheduled(
+                    actor, from[0], from[1], *amount_a, *amount_b, *start_ts, *end_ts,
+                )
+            }
+            Claim {
+                reward_accounts, ..
+            } => {
+                let to = atas_or(reward_accounts, &actor, &keys.reward_mints)?;
+                keys.claim(actor, to[0], to[1])
+            }
+            Compound { .. } => keys.compound(actor),
+            FundV3 { amounts, from, .. } => {
+                let from = atas_or(&from.from, &actor, &keys.reward_mints)?;
+                keys.fund_v3(actor, &from, amounts.clone())
+            }
+            ClaimV3 {
+                reward_indexes,
+                reward_accounts,
+                ..
+            } => {
+                let to = atas_or(reward_accounts, &actor, &keys.reward_mints)?;
+                keys.claim_v3(actor, &to, reward_indexes.clone())
+            }
+            ClaimFor {
+                owner, recipient, ..
+            } => keys.claim_for(*owner, actor, recipient.unwrap_or(*owner)),
+            SetRewardRecipient {
+                reward_recipient, ..
+            } => keys.set_reward_recipient(actor, *reward_recipient),
+            SweepDonatedStake { receiver, .. } => keys
+                .sweep_donated_stake(receiver.unwrap_or_else(|| ata(&actor, &keys.staking_mint))),
+            SweepUnallocatedRewards {
+                reward_index,
+                receiver,
+                ..
+            } => {
+                let mint = keys
+                    .reward_mints
+                    .get(*reward_index as usize)
+                    .ok_or_else(|| format!("pool has no reward {}", reward_index))?;
+                keys.sweep_unallocated_rewards(
+                    *reward_index,
+                    receiver.unwrap_or_else(|| ata(&actor, mint)),
+                )
+            }
+            CloseUser { .. } => keys.close_user(actor),
+            ClosePool { refund, .. } | ClosePoolV3 { refund, .. } => {
+                let refundee = refund.refundee.unwrap_or(actor);
+                let staking_refundee = refund
+                    .staking_refundee
+                    .unwrap_or_else(|| ata(&refundee, &keys.staking_mint));
+                let reward_refundees =
+                    atas_or(&refund.reward_refundees, &refundee, &keys.reward_mints)?;
+                if matches!(self, ClosePool { .. }) {
+                    keys.close_pool(
+                        refundee,
+                        staking_refundee,
+                        reward_refundees[0],
+                        reward_refundees[1],
+                    )
+                } else {
+                    keys.close_pool_v3(refundee, staking_refundee, &reward_refundees)
+                }
+            }
+            MigratePool { .. } => keys.migrate_pool(actor),
+            MigrateUser { owner, .. } => keys.migrate_user(owner.unwrap_or(actor), actor),
+        };
+        Ok(vec![instruction])
+    }
+}
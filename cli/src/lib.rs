@@ -0,0 +1,10 @@
+//! The reward pool command line tool: builds and sends, or prints for a multisig to
+//! sign, every `reward_pool` instruction, prints pools and users, and projects funding.
+
+pub mod commands;
+pub mod rpc;
+pub mod show;
+pub mod simulate;
+pub mod transaction;
+
+pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
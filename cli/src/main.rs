@@ -0,0 +1,197 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use clap::Parser;
+use reward_pool_cli::commands::{Cli, Command, PoolArg, Show, Simulate};
+use reward_pool_cli::rpc::RpcClient;
+use reward_pool_cli::show::{format_config, format_pool, format_user};
+use reward_pool_cli::simulate::{format_projection, project_funding, Funding};
+use reward_pool_cli::transaction::{build_transaction, encode_transaction};
+use reward_pool_cli::Result;
+use reward_pool_client::{
+    config_address, decode_config, decode_pool, decode_user, user_address, Pool, PoolVersion,
+    ProgramConfig,
+};
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{read_keypair_file, Keypair, Signer};
+
+fn main() {
+    if let Err(e) = run(Cli::parse()) {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
+    let rpc = RpcClient::new(&cli.url);
+    match &cli.command {
+        Command::Show(Show::Pool { pool, at }) => {
+            let state = load_pool(&rpc, pool)?;
+            print!("{}", format_pool(&pool.pool, &state, now(*at)));
+        }
+        Command::Show(Show::User {
+            pool,
+            owner,
+            user_file,
+            at,
+        }) => {
+            let state = load_pool(&rpc, pool)?;
+            let (key, _) = user_address(owner, &pool.pool);
+            let data = match user_file {
+                Some(path) => fs::read(path)?,
+                None => rpc
+                    .get_account_data(&key)?
+                    .ok_or_else(|| format!("user {} not found", key))?,
+            };
+            let user = decode_user(&data)?;
+            print!("{}", format_user(&key, &user, &state, now(*at)));
+        }
+        Command::Show(Show::Config) => {
+            let (key, _) = config_address();
+            print!("{}", format_config(&key, &load_config(&rpc)?));
+        }
+        Command::Simulate(simulate) => run_simulate(&rpc, simulate)?,
+        Command::Config(command) => {
+            let (keypair, actor) = wallet(&cli)?;
+            send(
+                &cli,
+                &rpc,
+                &command.instructions(actor),
+                &actor,
+                keypair.as_ref(),
+                vec![],
+            )?;
+        }
+        Command::Pool(command) => {
+            let (keypair, actor) = wallet(&cli)?;
+            let pool_keypair = command
+                .pool_keypair()
+                .map(|path| read_keypair(path))
+                .transpose()?;
+            let (pool, state) = match (command.pool(), &pool_keypair) {
+                (Some(arg), _) => (arg.pool, Some(load_pool(&rpc, arg)?)),
+                (None, Some(pool_keypair)) => (pool_keypair.pubkey(), None),
+                (None, None) => unreachable!(),
+            };
+            let config = command
+                .needs_config()
+                .then(|| load_config(&rpc))
+                .transpose()?;
+            let instructions =
+                command.instructions(actor, pool, state.as_ref(), config.as_ref())?;
+            send(
+                &cli,
+                &rpc,
+                &instructions,
+                &actor,
+                keypair.as_ref(),
+                pool_keypair.iter().collect(),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// The keypair of the wallet acting, and its key. An unsigned transaction for another
+/// signer needs no keypair at all.
+fn wallet(cli: &Cli) -> Result<(Option<Keypair>, Pubkey)> {
+    let keypair = match (cli.unsigned, cli.signer) {
+        (true, Some(_)) => None,
+        _ => Some(read_keypair(
+            &cli.keypair.clone().unwrap_or_else(default_keypair_path),
+        )?),
+    };
+    let actor = match (&cli.signer, &keypair) {
+        (Some(signer), _) => *signer,
+        (None, Some(keypair)) => keypair.pubkey(),
+        (None, None) => unreachable!(),
+    };
+    Ok((keypair, actor))
+}
+
+/// Sends the instructions signed by the wallet acting, or prints them as an unsigned
+/// transaction.
+fn send<'a>(
+    cli: &Cli,
+    rpc: &RpcClient,
+    instructions: &[Instruction],
+    actor: &Pubkey,
+    keypair: Option<&'a Keypair>,
+    mut signers: Vec<&'a Keypair>,
+) -> Result<()> {
+    if cli.unsigned {
+        let payer = cli.fee_payer.unwrap_or(*actor);
+        let blockhash = match cli.blockhash {
+            Some(blockhash) => blockhash,
+            None => rpc.get_latest_blockhash()?,
+        };
+        let transaction = build_transaction(instructions, &payer, blockhash, signers)?;
+        println!("{}", encode_transaction(&transaction)?);
+    } else {
+        signers.extend(keypair);
+        let blockhash = rpc.get_latest_blockhash()?;
+        let transaction = build_transaction(instructions, actor, blockhash, signers)?;
+        println!("{}", rpc.send_and_confirm(&transaction)?);
+    }
+    Ok(())
+}
+
+fn run_simulate(rpc: &RpcClient, simulate: &Simulate) -> Result<()> {
+    let pool = load_pool(rpc, &simulate.pool)?;
+    let funding = match (&simulate.fund, &simulate.top_up) {
+        (Some(amounts), _) => Funding::Fund(amounts.clone()),
+        (None, Some(amounts)) => Funding::TopUp(amounts.clone()),
+        (None, None) => unreachable!(),
+    };
+    let vault_amounts = match &simulate.vault_amounts {
+        Some(amounts) => amounts.clone(),
+        None if pool.version == PoolVersion::V1 => pool
+            .reward_vaults()
+            .iter()
+            .map(|vault| rpc.get_token_balance(vault))
+            .collect::<Result<_>>()?,
+        None => vec![],
+    };
+    let at = now(simulate.at);
+    let projected = project_funding(&pool, &funding, &vault_amounts, at)?;
+    print!("{}", format_projection(&pool, &projected, at));
+    Ok(())
+}
+
+fn load_pool(rpc: &RpcClient, arg: &PoolArg) -> Result<Pool> {
+    let data = match &arg.pool_file {
+        Some(path) => fs::read(path)?,
+        None => rpc
+            .get_account_data(&arg.pool)?
+            .ok_or_else(|| format!("pool {} not found", arg.pool))?,
+    };
+    Ok(decode_pool(&data)?)
+}
+
+fn load_config(rpc: &RpcClient) -> Result<ProgramConfig> {
+    let (key, _) = config_address();
+    let data = rpc
+        .get_account_data(&key)?
+        .ok_or_else(|| format!("program config {} not found", key))?;
+    Ok(decode_config(&data)?)
+}
+
+fn read_keypair(path: &Path) -> Result<Keypair> {
+    read_keypair_file(path).map_err(|e| format!("reading {}: {}", path.display(), e).into())
+}
+
+fn default_keypair_path() -> PathBuf {
+    let home = std::env::var_os("HOME").unwrap_or_default();
+    PathBuf::from(home).join(".config/solana/id.json")
+}
+
+fn now(at: Option<u64>) -> u64 {
+    at.unwrap_or_else(|| {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("system clock before 1970")
+            .as_secs()
+    })
+}
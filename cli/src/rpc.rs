@@ -0,0 +1,112 @@
+//! The few JSON RPC calls the tool makes of a Solana node.
+
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use serde_json::{json, Value};
+use solana_sdk::hash::Hash;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::transaction::Transaction;
+
+use crate::Result;
+
+/// How long a sent transaction is waited on to confirm.
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(60);
+
+pub struct RpcClient {
+    url: String,
+}
+
+impl RpcClient {
+    /// A client of the node at `url`, or of a cluster named as the solana CLI names them:
+    /// `mainnet-beta`, `devnet`, `testnet`, `localhost` or their first letter.
+    pub fn new(url: &str) -> Self {
+        let url = match url {
+            "m" | "mainnet-beta" => "https://api.mainnet-beta.solana.com",
+            "d" | "devnet" => "https://api.devnet.solana.com",
+            "t" | "testnet" => "https://api.testnet.solana.com",
+            "l" | "localhost" => "http://localhost:8899",
+            url => url,
+        };
+        RpcClient {
+            url: url.to_string(),
+        }
+    }
+
+    fn call(&self, method: &str, params: Value) -> Result<Value> {
+        let response: Value = ureq::post(&self.url)
+            .send_json(json!({
+                "jsonrpc": "2.0",
+                "id": 1,
+                "method": method,
+                "params": params,
+            }))?
+            .into_json()?;
+        if let Some(error) = response.get("error") {
+            return Err(format!("{} failed: {}", method, error).into());
+        }
+        Ok(response["result"].clone())
+    }
+
+    /// The data of an account, `None` when it does not exist.
+    pub fn get_account_data(&self, key: &Pubkey) -> Result<Option<Vec<u8>>> {
+        let result = self.call(
+            "getAccountInfo",
+            json!([key.to_string(), {"encoding": "base64", "commitment": "confirmed"}]),
+        )?;
+        match result["value"]["data"][0].as_str() {
+            Some(data) => Ok(Some(base64::decode(data)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// The balance of a token account.
+    pub fn get_token_balance(&self, key: &Pubkey) -> Result<u64> {
+        let result = self.call(
+            "getTokenAccountBalance",
+            json!([key.to_string(), {"commitment": "confirmed"}]),
+        )?;
+        result["value"]["amount"]
+            .as_str()
+            .ok_or_else(|| format!("{} is not a token account", key))?
+            .parse()
+            .map_err(Into::into)
+    }
+
+    pub fn get_latest_blockhash(&self) -> Result<Hash> {
+        let result = self.call("getLatestBlockhash", json!([{"commitment": "confirmed"}]))?;
+        result["value"]["blockhash"]
+            .as_str()
+            .ok_or("no blockhash returned")?
+            .parse()
+            .map_err(|e| format!("invalid blockhash: {:?}", e).into())
+    }
+
+    /// Sends a signed transaction, waiting for it to confirm.
+    pub fn send_and_confirm(&self, transaction: &Transaction) -> Result<Signature> {
+        let data = base64::encode(bincode::serialize(transaction)?);
+        let signature: Signature = self
+            .call(
+                "sendTransaction",
+                json!([data, {"encoding": "base64", "preflightCommitment": "confirmed"}]),
+            )?
+            .as_str()
+            .ok_or("no signature returned")?
+            .parse()?;
+
+        let start = Instant::now();
+        while start.elapsed() < CONFIRM_TIMEOUT {
+            let result = self.call("getSignatureStatuses", json!([[signature.to_string()]]))?;
+            let status = &result["value"][0];
+            if !status["err"].is_null() {
+                return Err(format!("transaction {} failed: {}", signature, status["err"]).into());
+            }
+            if let Some("confirmed" | "finalized") = status["confirmationStatus"].as_str() {
+                return Ok(signature);
+            }
+            sleep(Duration::from_millis(500));
+        }
+        Err(format!("transaction {} was not confirmed in time", signature).into())
+    }
+}
@@ -0,0 +1,299 @@
+//! Pretty printing of pool and user accounts.
+
+use std::fmt::Write;
+
+use reward_pool_client::reward_pool::calculator::pending_rewards;
+use reward_pool_client::PoolVersion;
+use reward_pool_client::{Pool, PoolGate, ProgramConfig, User};
+use solana_sdk::pubkey::Pubkey;
+
+const SECONDS_IN_DAY: u64 = 24 * 60 * 60;
+const SECONDS_IN_YEAR: u64 = 365 * SECONDS_IN_DAY;
+
+/// A unix timestamp along with how far it is from `now`, e.g. `1641081600 (in 1d 0h)`.
+pub fn format_time(ts: u64, now: u64) -> String {
+    if ts == 0 {
+        return "-".to_string();
+    }
+    let (seconds, suffix) = if ts >= now {
+        (ts - now, format!("in {}", format_duration(ts - now)))
+    } else {
+        (now - ts, format!("{} ago", format_duration(now - ts)))
+    };
+    if seconds == 0 {
+        return format!("{} (now)", ts);
+    }
+    format!("{} ({})", ts, suffix)
+}
+
+/// A number of seconds in days and hours, or minutes and seconds when under an hour.
+pub fn format_duration(seconds: u64) -> String {
+    if seconds < 60 * 60 {
+        format!("{}m {}s", seconds / 60, seconds % 60)
+    } else {
+        format!(
+            "{}d {}h",
+            seconds / SECONDS_IN_DAY,
+            seconds % SECONDS_IN_DAY / (60 * 60)
+        )
+    }
+}
+
+/// The amount a reward rate emits in a day. V1 pools hold a per second rate, later
+/// versions an annual one.
+pub fn daily_emission(version: &PoolVersion, rate: u64) -> u64 {
+    let per_day = rate as u128 * SECONDS_IN_DAY as u128;
+    if *version == PoolVersion::V1 {
+        per_day as u64
+    } else {
+        (per_day / SECONDS_IN_YEAR as u128) as u64
+    }
+}
+
+/// The rate, reward per token stored and schedule of each of a pool's rewards.
+struct RewardState {
+    rate: u64,
+    per_token_stored: u128,
+    reward_duration: u64,
+    reward_duration_end: u64,
+}
+
+fn reward_states(pool: &Pool) -> Vec<RewardState> {
+    if pool.version.is_multi_reward() {
+        let independent = pool.version.has_independent_schedules();
+        pool.reward_infos()
+            .iter()
+            .map(|r| RewardState {
+                rate: r.rate,
+                per_token_stored: r.per_token_stored,
+                reward_duration: if independent {
+                    r.reward_duration
+                } else {
+                    pool.reward_duration
+                },
+                reward_duration_end: if independent {
+                    r.reward_duration_end
+                } else {
+                    pool.reward_duration_end
+                },
+            })
+            .collect()
+    } else {
+        [
+            (pool.reward_a_rate, pool.reward_a_per_token_stored),
+            (pool.reward_b_rate, pool.reward_b_per_token_stored),
+        ]
+        .iter()
+        .map(|(rate, per_token_stored)| RewardState {
+            rate: *rate,
+            per_token_stored: *per_token_stored,
+            reward_duration: pool.reward_duration,
+            reward_duration_end: pool.reward_duration_end,
+        })
+        .collect()
+    }
+}
+
+/// The rate and period end of each of a pool's rewards, in the order the pool holds them.
+pub fn reward_schedules(pool: &Pool) -> Vec<(u64, u64)> {
+    reward_states(pool)
+        .iter()
+        .map(|r| (r.rate, r.reward_duration_end))
+        .collect()
+}
+
+pub fn format_config(key: &Pubkey, config: &ProgramConfig) -> String {
+    let mut out = String::new();
+    let w = &mut out;
+    writeln!(w, "Program config {}", key).unwrap();
+    writeln!(w, "  admin:               {}", config.admin).unwrap();
+    if config.pending_admin != Pubkey::default() {
+        writeln!(w, "  pending admin:       {}", config.pending_admin).unwrap();
+    }
+    writeln!(w, "  frozen:              {}", config.global_freeze).unwrap();
+    writeln!(w, "  deposit mint:        {}", config.deposit_mint).unwrap();
+    writeln!(w, "  deposit requirement: {}", config.deposit_requirement).unwrap();
+    writeln!(
+        w,
+        "  min duration:        {}",
+        format_duration(config.min_duration)
+    )
+    .unwrap();
+    out
+}
+
+pub fn format_pool(key: &Pubkey, pool: &Pool, now: u64) -> String {
+    let mut out = String::new();
+    let w = &mut out;
+    writeln!(w, "Pool {}", key).unwrap();
+    writeln!(w, "  version:            {:?}", pool.version).unwrap();
+    writeln!(w, "  authority:          {}", pool.authority).unwrap();
+    if pool.pending_authority != Pubkey::default() {
+        writeln!(w, "  pending authority:  {}", pool.pending_authority).unwrap();
+    }
+    if pool.gate != PoolGate::Open {
+        writeln!(w, "  gate:               {:?} {}", pool.gate, pool.gate_key).unwrap();
+    }
+    writeln!(w, "  paused:             {}", pool.paused).unwrap();
+    writeln!(w, "  xSTEP vault:        {}", pool.x_token_pool_vault).unwrap();
+    writeln!(w, "  staking mint:       {}", pool.staking_mint).unwrap();
+    writeln!(w, "  staking vault:      {}", pool.staking_vault).unwrap();
+    writeln!(w, "  total staked:       {}", pool.total_staked).unwrap();
+    writeln!(w, "  users staked:       {}", pool.user_stake_count).unwrap();
+    writeln!(
+        w,
+        "  reward duration:    {}",
+        format_duration(pool.reward_duration)
+    )
+    .unwrap();
+    writeln!(
+        w,
+        "  reward period end:  {}",
+        format_time(pool.reward_duration_end, now)
+    )
+    .unwrap();
+    writeln!(
+        w,
+        "  last update:        {}",
+        format_time(pool.last_update_time, now)
+    )
+    .unwrap();
+    for funder in pool.funders.iter().filter(|f| **f != Pubkey::default()) {
+        writeln!(w, "  funder:             {}", funder).unwrap();
+    }
+
+    let mints = pool.reward_mints();
+    let vaults = pool.reward_vaults();
+    for (i, reward) in reward_states(pool).iter().enumerate() {
+        writeln!(w, "  reward {}", i).unwrap();
+        writeln!(w, "    mint:             {}", mints[i]).unwrap();
+        writeln!(w, "    vault:            {}", vaults[i]).unwrap();
+        writeln!(
+            w,
+            "    rate:             {} ({} a day)",
+            reward.rate,
+            daily_emission(&pool.version, reward.rate)
+        )
+        .unwrap();
+        writeln!(w, "    per token stored: {}", reward.per_token_stored).unwrap();
+        if pool.version.has_independent_schedules() {
+            writeln!(
+                w,
+                "    duration:         {}",
+                format_duration(reward.reward_duration)
+            )
+            .unwrap();
+            writeln!(
+                w,
+                "    period end:       {}",
+                format_time(reward.reward_duration_end, now)
+            )
+            .unwrap();
+        }
+    }
+
+    if pool.scheduled_start_ts > 0 {
+        writeln!(
+            w,
+            "  scheduled period:   {} to {}, {} A and {} B",
+            format_time(pool.scheduled_start_ts, now),
+            format_time(pool.scheduled_end_ts, now),
+            pool.scheduled_reward_a_amount,
+            pool.scheduled_reward_b_amount,
+        )
+        .unwrap();
+    }
+    if pool.version == PoolVersion::V5 {
+        writeln!(
+            w,
+            "  lockup:             {} to {}, up to {} bps boost",
+            format_duration(pool.min_lock_duration),
+            format_duration(pool.max_lock_duration),
+            pool.max_lock_boost_bps,
+        )
+        .unwrap();
+        writeln!(w, "  total boost weight: {}", pool.total_boost_weight).unwrap();
+    }
+    if pool.unbonding_period > 0 {
+        writeln!(
+            w,
+            "  unbonding period:   {}",
+            format_duration(pool.unbonding_period)
+        )
+        .unwrap();
+        writeln!(w, "  total unbonding:    {}", pool.total_unbonding).unwrap();
+    }
+    if pool.max_stake_per_user > 0 {
+        writeln!(w, "  max stake per user: {}", pool.max_stake_per_user).unwrap();
+    }
+    if pool.max_total_stake > 0 {
+        writeln!(w, "  max total stake:    {}", pool.max_total_stake).unwrap();
+    }
+    if pool.early_exit_penalty_bps > 0 {
+        writeln!(
+            w,
+            "  early exit penalty: {} bps over {}",
+            pool.early_exit_penalty_bps,
+            format_duration(pool.early_exit_penalty_window),
+        )
+        .unwrap();
+        if pool.penalty_treasury != Pubkey::default() {
+            writeln!(w, "  penalty treasury:   {}", pool.penalty_treasury).unwrap();
+        } else {
+            writeln!(w, "  penalty rewards:    {}", pool.total_penalty_rewards).unwrap();
+        }
+    }
+    out
+}
+
+/// Formats a user, along with the rewards it could claim at `now`.
+pub fn format_user(key: &Pubkey, user: &User, pool: &Pool, now: u64) -> String {
+    let mut out = String::new();
+    let w = &mut out;
+    writeln!(w, "User {}", key).unwrap();
+    writeln!(w, "  owner:              {}", user.owner).unwrap();
+    writeln!(w, "  pool:               {}", user.pool).unwrap();
+    writeln!(w, "  staked:             {}", user.balance_staked).unwrap();
+    writeln!(
+        w,
+        "  staked since:       {}",
+        format_time(user.stake_ts, now)
+    )
+    .unwrap();
+    if user.balance_locked > 0 {
+        writeln!(
+            w,
+            "  locked:             {} until {}",
+            user.balance_locked,
+            format_time(user.lock_end_ts, now)
+        )
+        .unwrap();
+        writeln!(w, "  boost weight:       {}", user.boost_weight).unwrap();
+    }
+    if user.balance_unbonding > 0 {
+        writeln!(
+            w,
+            "  unbonding:          {} until {}",
+            user.balance_unbonding,
+            format_time(user.unbonding_end_ts, now)
+        )
+        .unwrap();
+    }
+    if user.penalty_reward_pending > 0 {
+        writeln!(w, "  penalty rewards:    {}", user.penalty_reward_pending).unwrap();
+    }
+    if user.reward_recipient != Pubkey::default() {
+        writeln!(w, "  reward recipient:   {}", user.reward_recipient).unwrap();
+    }
+
+    let mints = pool.reward_mints();
+    match pending_rewards(pool, user, now) {
+        Ok(pending) => {
+            for (mint, amount) in mints.iter().zip(pending) {
+                writeln!(w, "  claimable:          {} of {}", amount, mint).unwrap();
+            }
+        }
+        Err(e) => writeln!(w, "  claimable:          unknown, {}", e).unwrap(),
+    }
+    out
+}
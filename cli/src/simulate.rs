@@ -0,0 +1,92 @@
+//! Offline projection of funding a pool, run through the program's own reward math.
+
+use std::fmt::Write;
+
+use reward_pool_client::reward_pool::calculator::accrue_rewards;
+use reward_pool_client::reward_pool::{self, ErrorCode};
+use reward_pool_client::Pool;
+
+use crate::show::{daily_emission, format_time, reward_schedules};
+
+/// A funding of a pool, an amount per reward in the order the pool holds them.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Funding {
+    /// `fund`, or `fund_v3` on multi reward pools, starting a new reward period
+    Fund(Vec<u64>),
+    /// `top_up`, keeping the current period's end
+    TopUp(Vec<u64>),
+}
+
+/// The pool as the funding would leave it at `current_time`. `reward_vault_amounts` are
+/// only read by V1 pools, which are upgraded by funding.
+pub fn project_funding(
+    pool: &Pool,
+    funding: &Funding,
+    reward_vault_amounts: &[u64],
+    current_time: u64,
+) -> reward_pool::Result<Pool> {
+    let mut pool = pool.clone();
+    let reward_count = pool.reward_mints().len();
+    let amounts = match funding {
+        Funding::Fund(amounts) | Funding::TopUp(amounts) => amounts,
+    };
+    if amounts.len() > reward_count {
+        return Err(ErrorCode::InvalidRewardIndex.into());
+    }
+    //A/B pools are funded with both amounts
+    let mut amounts = amounts.clone();
+    if !pool.version.is_multi_reward() {
+        amounts.resize(reward_count, 0);
+    }
+
+    accrue_rewards(&mut pool, None, current_time)?;
+    match funding {
+        Funding::Fund(_) => {
+            pool.start_reward_period(reward_vault_amounts, &amounts, current_time)?
+        }
+        Funding::TopUp(_) => {
+            if pool.version.is_multi_reward() {
+                return Err(ErrorCode::PoolVersionNotSupported.into());
+            }
+            pool.top_up_reward_period(&amounts, current_time)?
+        }
+    }
+    Ok(pool)
+}
+
+/// Each reward's rate, daily emission and period end, before and after a projection.
+pub fn format_projection(before: &Pool, after: &Pool, current_time: u64) -> String {
+    let mut out = String::new();
+    let w = &mut out;
+    if before.version != after.version {
+        writeln!(
+            w,
+            "pool upgrades from {:?} to {:?}",
+            before.version, after.version
+        )
+        .unwrap();
+    }
+    let mints = after.reward_mints();
+    let schedules = reward_schedules(before)
+        .into_iter()
+        .zip(reward_schedules(after));
+    for (i, ((rate, end), (new_rate, new_end))) in schedules.enumerate() {
+        writeln!(w, "reward {} ({})", i, mints[i]).unwrap();
+        writeln!(w, "  rate:       {} -> {}", rate, new_rate).unwrap();
+        writeln!(
+            w,
+            "  daily:      {} -> {}",
+            daily_emission(&before.version, rate),
+            daily_emission(&after.version, new_rate)
+        )
+        .unwrap();
+        writeln!(
+            w,
+            "  period end: {} -> {}",
+            format_time(end, current_time),
+            format_time(new_end, current_time)
+        )
+        .unwrap();
+    }
+    out
+}
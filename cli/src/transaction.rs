@@ -0,0 +1,32 @@
+//! Assembling the transactions the tool sends or prints.
+
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Keypair;
+use solana_sdk::transaction::Transaction;
+
+use crate::Result;
+
+/// A transaction of `instructions` paid for by `payer`, signed by the given keypairs.
+/// Signatures of any other signer are left empty, to be added by whoever holds its key.
+pub fn build_transaction(
+    instructions: &[Instruction],
+    payer: &Pubkey,
+    blockhash: Hash,
+    signers: Vec<&Keypair>,
+) -> Result<Transaction> {
+    let mut transaction = Transaction::new_with_payer(instructions, Some(payer));
+    transaction.try_partial_sign(&signers, blockhash)?;
+    Ok(transaction)
+}
+
+/// A transaction serialized and base64 encoded, as `sendTransaction` and most multisig
+/// tooling take it.
+pub fn encode_transaction(transaction: &Transaction) -> Result<String> {
+    Ok(base64::encode(bincode::serialize(transaction)?))
+}
+
+pub fn decode_transaction(encoded: &str) -> Result<Transaction> {
+    Ok(bincode::deserialize(&base64::decode(encoded.trim())?)?)
+}
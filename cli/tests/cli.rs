@@ -0,0 +1,427 @@
+#[path = "../../programs/reward-pool/tests/harness/mod.rs"]
+mod harness;
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::Instruction;
+use clap::Parser;
+use reward_pool::{ErrorCode, PoolGate};
+use reward_pool_cli::commands::{Cli, Command};
+use reward_pool_cli::show::{format_config, format_pool, format_user, reward_schedules};
+use reward_pool_cli::simulate::{format_projection, project_funding, Funding};
+use reward_pool_cli::transaction::{build_transaction, decode_transaction, encode_transaction};
+use reward_pool_client::*;
+use solana_sdk::hash::Hash;
+use solana_sdk::signature::{Keypair, Signature, Signer};
+use spl_associated_token_account::get_associated_token_address;
+
+use harness::fixture::{PoolFixture, X_STEP_TOKEN_MINT_PUBKEY};
+use harness::ProgramTest;
+
+const DAY: u64 = 86_400;
+
+fn pool(t: &ProgramTest, key: &Pubkey) -> Pool {
+    decode_pool(&t.account(key).unwrap().data).unwrap()
+}
+
+fn user(t: &ProgramTest, key: &Pubkey) -> User {
+    decode_user(&t.account(key).unwrap().data).unwrap()
+}
+
+fn keys(f: &PoolFixture) -> PoolKeys {
+    PoolKeys::new(
+        f.pool,
+        f.authority,
+        f.x_token_pool_vault,
+        f.staking_mint,
+        f.staking_vault,
+        f.reward_mints.clone(),
+        f.reward_vaults.clone(),
+    )
+}
+
+fn config(t: &ProgramTest) -> ProgramConfig {
+    decode_config(&t.account(&config_address().0).unwrap().data).unwrap()
+}
+
+/// The instructions a command line sends as `actor`, reading the pool and program config
+/// from the ledger.
+fn instructions(t: &ProgramTest, actor: Pubkey, args: &[String]) -> Vec<Instruction> {
+    let cli = Cli::try_parse_from(["reward-pool".to_string()].iter().chain(args)).unwrap();
+    let command = match cli.command {
+        Command::Pool(command) => command,
+        command => panic!("not a pool command: {:?}", command),
+    };
+    let pool_key = command.pool().unwrap().pool;
+    command
+        .instructions(actor, pool_key, Some(&pool(t, &pool_key)), Some(&config(t)))
+        .unwrap()
+}
+
+fn run(t: &mut ProgramTest, actor: Pubkey, args: &[String]) {
+    for instruction in instructions(t, actor, args) {
+        t.process(instruction).unwrap();
+    }
+}
+
+macro_rules! args {
+    ($($arg:expr),* $(,)?) => {
+        [$($arg.to_string()),*]
+    };
+}
+
+fn assert_projected(projected: &Pool, actual: &Pool) {
+    assert_eq!(projected.version, actual.version);
+    assert_eq!(projected.last_update_time, actual.last_update_time);
+    assert_eq!(reward_schedules(projected), reward_schedules(actual));
+}
+
+#[test]
+fn initializes_a_pool_with_its_vaults() {
+    let actor = Pubkey::new_unique();
+    let pool = Pubkey::new_unique();
+    let staking_mint = Pubkey::new_unique();
+    let reward_mint = Pubkey::new_unique();
+    let cli = Cli::try_parse_from(args![
+        "reward-pool",
+        "initialize-pool",
+        "pool.json",
+        staking_mint,
+        reward_mint,
+        reward_mint,
+        DAY,
+        "--early-exit-penalty-bps",
+        500,
+    ])
+    .unwrap();
+    let command = match cli.command {
+        Command::Pool(command) => command,
+        command => panic!("not a pool command: {:?}", command),
+    };
+    assert!(command.pool().is_none());
+    //the deposit mint is the program config's, without --deposit-mint
+    assert!(command.needs_config());
+    assert!(command.instructions(actor, pool, None, None).is_err());
+    let x_token_mint: Pubkey = X_STEP_TOKEN_MINT_PUBKEY.parse().unwrap();
+    let config = ProgramConfig {
+        deposit_mint: x_token_mint,
+        ..ProgramConfig::default()
+    };
+    let instructions = command
+        .instructions(actor, pool, None, Some(&config))
+        .unwrap();
+
+    //the pool account, then vaults for xSTEP, staking and the one reward mint
+    assert_eq!(instructions.len(), 5);
+    assert_eq!(
+        instructions[0].program_id,
+        anchor_lang::solana_program::system_program::ID
+    );
+    assert!(instructions[1..4]
+        .iter()
+        .all(|ix| ix.program_id == spl_associated_token_account::id()));
+    let (pool_signer, _) = pool_signer_address(&pool);
+    let ata = |owner: &Pubkey, mint: &Pubkey| get_associated_token_address(owner, mint);
+    let keys = PoolKeys::new(
+        pool,
+        actor,
+        ata(&pool_signer, &x_token_mint),
+        staking_mint,
+        ata(&pool_signer, &staking_mint),
+        vec![reward_mint, reward_mint],
+        vec![ata(&pool_signer, &reward_mint); 2],
+    );
+    assert_eq!(
+        instructions[4],
+        keys.initialize_pool(ata(&actor, &x_token_mint), actor, DAY, 500, 0)
+    );
+}
+
+#[test]
+fn drives_an_a_b_pool() {
+    let mut t = ProgramTest::new();
+    let f = PoolFixture::new(&mut t, 2);
+    t.process(keys(&f).initialize_pool(f.x_token_depositor, f.authority, DAY, 0, 0))
+        .unwrap();
+
+    let funder = f.create_wallet(&mut t, 0, 1_000_000_000);
+    run(
+        &mut t,
+        f.authority,
+        &args!["authorize-funder", f.pool, funder.owner],
+    );
+    let u = f.create_wallet(&mut t, 1000, 0);
+    run(&mut t, u.owner, &args!["create-user", f.pool]);
+    run(
+        &mut t,
+        u.owner,
+        &args!["stake", f.pool, 1000, "--token-account", u.staking_account],
+    );
+
+    //the simulation agrees with the program on funding and topping up
+    let from = format!(
+        "{},{}",
+        funder.reward_accounts[0], funder.reward_accounts[1]
+    );
+    let before = pool(&t, &f.pool);
+    let projected = project_funding(
+        &before,
+        &Funding::Fund(vec![86_400_000, 43_200_000]),
+        &[],
+        t.now(),
+    )
+    .unwrap();
+    run(
+        &mut t,
+        funder.owner,
+        &args!["fund", f.pool, 86_400_000, 43_200_000, "--from", from],
+    );
+    assert_projected(&projected, &pool(&t, &f.pool));
+    let projection = format_projection(&before, &projected, t.now());
+    assert!(projection.contains(&format!("reward 0 ({})", f.reward_mints[0])));
+    assert!(projection.contains("daily:      0 -> 86400000"));
+
+    t.warp_forward(DAY / 2);
+    let projected = project_funding(
+        &pool(&t, &f.pool),
+        &Funding::TopUp(vec![43_200_000]),
+        &[],
+        t.now(),
+    )
+    .unwrap();
+    run(
+        &mut t,
+        funder.owner,
+        &args!["top-up", f.pool, 43_200_000, 0, "--from", from],
+    );
+    assert_projected(&projected, &pool(&t, &f.pool));
+
+    let shown = format_user(&u.user, &user(&t, &u.user), &pool(&t, &f.pool), t.now());
+    assert!(shown.contains(&format!(
+        "claimable:          43200000 of {}",
+        f.reward_mints[0]
+    )));
+    assert!(shown.contains(&format!(
+        "claimable:          21600000 of {}",
+        f.reward_mints[1]
+    )));
+    let rewards_to = format!("{},{}", u.reward_accounts[0], u.reward_accounts[1]);
+    run(
+        &mut t,
+        u.owner,
+        &args!["claim", f.pool, "--reward-accounts", rewards_to],
+    );
+    assert_eq!(t.token_balance(&u.reward_accounts[0]), 43_200_000);
+    assert_eq!(t.token_balance(&u.reward_accounts[1]), 21_600_000);
+
+    let treasury = t.create_token_account(&f.staking_mint, &f.authority, 0);
+    run(
+        &mut t,
+        f.authority,
+        &args!["set-penalty-treasury", f.pool, treasury],
+    );
+    let shown = format_pool(&f.pool, &pool(&t, &f.pool), t.now());
+    assert!(shown.contains(&format!("funder:             {}", funder.owner)));
+    assert!(shown.contains("total staked:       1000"));
+    assert_eq!(pool(&t, &f.pool).penalty_treasury, treasury);
+    run(&mut t, f.authority, &args!["set-penalty-treasury", f.pool]);
+    assert_eq!(pool(&t, &f.pool).penalty_treasury, Pubkey::default());
+}
+
+#[test]
+fn simulates_funding_a_multi_reward_pool() {
+    let mut t = ProgramTest::new();
+    let f = PoolFixture::new(&mut t, 2);
+    let mut keys = keys(&f);
+    keys.reward_mints.clear();
+    keys.reward_vaults.clear();
+    t.process(keys.initialize_pool_v4(f.x_token_depositor, f.authority, DAY))
+        .unwrap();
+    for (mint, vault) in f.reward_mints.iter().zip(&f.reward_vaults) {
+        t.process(keys.add_reward(*mint, *vault)).unwrap();
+    }
+    run(
+        &mut t,
+        f.authority,
+        &args!["set-reward-duration", f.pool, 1, 5 * DAY],
+    );
+    let funder = f.create_wallet(&mut t, 0, 1_000_000_000);
+    run(
+        &mut t,
+        f.authority,
+        &args!["authorize-funder", f.pool, funder.owner],
+    );
+
+    let before = pool(&t, &f.pool);
+    assert_eq!(
+        project_funding(&before, &Funding::TopUp(vec![1, 1]), &[], t.now())
+            .map(|_| ())
+            .map_err(ProgramError::from),
+        Err(ErrorCode::PoolVersionNotSupported.into())
+    );
+    assert_eq!(
+        project_funding(&before, &Funding::Fund(vec![1, 1, 1]), &[], t.now())
+            .map(|_| ())
+            .map_err(ProgramError::from),
+        Err(ErrorCode::InvalidRewardIndex.into())
+    );
+
+    let projected = project_funding(
+        &before,
+        &Funding::Fund(vec![86_400_000, 432_000_000]),
+        &[],
+        t.now(),
+    )
+    .unwrap();
+    let from = format!(
+        "{},{}",
+        funder.reward_accounts[0], funder.reward_accounts[1]
+    );
+    run(
+        &mut t,
+        funder.owner,
+        &args!["fund-v3", f.pool, "86400000,432000000", "--from", from],
+    );
+    let after = pool(&t, &f.pool);
+    assert_projected(&projected, &after);
+    assert_eq!(
+        reward_schedules(&after)
+            .iter()
+            .map(|(_, end)| *end)
+            .collect::<Vec<_>>(),
+        vec![t.now() + DAY, t.now() + 5 * DAY]
+    );
+}
+
+#[test]
+fn round_trips_unsigned_transactions() {
+    let payer = Pubkey::new_unique();
+    let pool = Keypair::new();
+    let blockhash = Hash::new_unique();
+    let cli = Cli::try_parse_from(args![
+        "reward-pool",
+        "--unsigned",
+        "--signer",
+        payer,
+        "initialize-pool-v3",
+        "pool.json",
+        Pubkey::new_unique(),
+        DAY,
+        "--deposit-mint",
+        Pubkey::new_unique(),
+    ])
+    .unwrap();
+    assert!(cli.unsigned);
+    let command = match cli.command {
+        Command::Pool(command) => command,
+        command => panic!("not a pool command: {:?}", command),
+    };
+    assert!(!command.needs_config());
+    let instructions = command
+        .instructions(cli.signer.unwrap(), pool.pubkey(), None, None)
+        .unwrap();
+
+    //the pool keypair signs for its new account, leaving the payer's signature to a multisig
+    let transaction = build_transaction(&instructions, &payer, blockhash, vec![&pool]).unwrap();
+    let decoded = decode_transaction(&encode_transaction(&transaction).unwrap()).unwrap();
+    assert_eq!(decoded, transaction);
+    assert_eq!(decoded.message.account_keys[0], payer);
+    assert_eq!(decoded.message.recent_blockhash, blockhash);
+    assert_eq!(decoded.signatures.len(), 2);
+    assert_eq!(decoded.signatures[0], Signature::default());
+    assert_eq!(decoded.message.account_keys[1], pool.pubkey());
+    assert!(decoded.signatures[1].verify(pool.pubkey().as_ref(), &decoded.message_data()));
+
+    //an unsigned transaction needs a signer to act for
+    assert!(Cli::try_parse_from(args![
+        "reward-pool",
+        "--signer",
+        payer,
+        "create-user",
+        payer
+    ])
+    .is_err());
+}
+
+#[test]
+fn creates_users_on_gated_pools() {
+    let mut t = ProgramTest::new();
+    let pass = t.create_mint(0);
+    let f = PoolFixture::new(&mut t, 2).with_gate(PoolGate::Mint, pass);
+    t.process(f.initialize_pool(DAY, 0, 0, None)).unwrap();
+    let u = f.create_wallet(&mut t, 0, 0);
+    let held = t.create_token_account(&pass, &u.owner, 1);
+    run(
+        &mut t,
+        u.owner,
+        &args!["create-user", f.pool, "--token-account", held],
+    );
+    assert_eq!(user(&t, &u.user).owner, u.owner);
+    let shown = format_pool(&f.pool, &pool(&t, &f.pool), t.now());
+    assert!(shown.contains(&format!("gate:               Mint {}", pass)));
+
+    //joining an allow-listed pool takes the list to prove membership
+    let f = PoolFixture::new(&mut t, 2).with_gate(PoolGate::MerkleRoot, Pubkey::new_unique());
+    t.process(f.initialize_pool(DAY, 0, 0, None)).unwrap();
+    let cli = Cli::try_parse_from(args!["reward-pool", "create-user", f.pool]).unwrap();
+    let command = match cli.command {
+        Command::Pool(command) => command,
+        command => panic!("not a pool command: {:?}", command),
+    };
+    assert!(command
+        .instructions(u.owner, f.pool, Some(&pool(&t, &f.pool)), None)
+        .is_err());
+}
+
+/// The instructions a config command line sends as `actor`.
+fn config_instructions(actor: Pubkey, args: &[String]) -> Vec<Instruction> {
+    let cli = Cli::try_parse_from(["reward-pool".to_string()].iter().chain(args)).unwrap();
+    match cli.command {
+        Command::Config(command) => command.instructions(actor),
+        command => panic!("not a config command: {:?}", command),
+    }
+}
+
+#[test]
+fn manages_the_program_config() {
+    let mut t = ProgramTest::new();
+    let upgrade_authority = t.create_wallet();
+    let admin = t.create_wallet();
+    t.set_upgrade_authority(&upgrade_authority);
+    let deposit_mint = t.create_mint(6);
+
+    let ix = config_instructions(
+        upgrade_authority,
+        &args![
+            "initialize-config",
+            deposit_mint,
+            1000,
+            DAY,
+            "--admin",
+            admin
+        ],
+    );
+    t.process(ix[0].clone()).unwrap();
+    let ix = config_instructions(admin, &args!["update-config", deposit_mint, 2000, 2 * DAY]);
+    t.process(ix[0].clone()).unwrap();
+
+    let (key, _) = config_address();
+    let config = decode_config(&t.account(&key).unwrap().data).unwrap();
+    assert_eq!(config.admin, admin);
+    assert_eq!(config.deposit_requirement, 2000);
+    let shown = format_config(&key, &config);
+    assert!(shown.contains(&format!("deposit mint:        {}", deposit_mint)));
+    assert!(shown.contains("min duration:        2d 0h"));
+
+    let ix = config_instructions(admin, &args!["freeze-program"]);
+    t.process(ix[0].clone()).unwrap();
+    let ix = config_instructions(admin, &args!["propose-config-admin", upgrade_authority]);
+    t.process(ix[0].clone()).unwrap();
+    let config = decode_config(&t.account(&key).unwrap().data).unwrap();
+    let shown = format_config(&key, &config);
+    assert!(shown.contains("frozen:              true"));
+    assert!(shown.contains(&format!("pending admin:       {}", upgrade_authority)));
+    let ix = config_instructions(upgrade_authority, &args!["accept-config-admin"]);
+    t.process(ix[0].clone()).unwrap();
+    let config = decode_config(&t.account(&key).unwrap().data).unwrap();
+    assert_eq!(config.admin, upgrade_authority);
+}
@@ -0,0 +1,59 @@
+//! Allow-lists of pools gated by a Merkle root.
+//!
+//! Leaves are `keccak(owner)` and each node hashes its two children in sorted order, so
+//! a proof is just the sibling hashes from leaf to root. A node without a sibling is
+//! carried up to the next level as it is.
+
+use anchor_lang::prelude::Pubkey;
+use anchor_lang::solana_program::keccak;
+
+fn levels(owners: &[Pubkey]) -> Vec<Vec<[u8; 32]>> {
+    let mut leaves: Vec<[u8; 32]> = owners
+        .iter()
+        .map(|owner| keccak::hashv(&[owner.as_ref()]).0)
+        .collect();
+    leaves.sort_unstable();
+    leaves.dedup();
+
+    let mut levels = vec![leaves];
+    while levels.last().unwrap().len() > 1 {
+        let next = levels
+            .last()
+            .unwrap()
+            .chunks(2)
+            .map(|pair| match pair {
+                [a, b] if a <= b => keccak::hashv(&[a, b]).0,
+                [a, b] => keccak::hashv(&[b, a]).0,
+                [a] => *a,
+                _ => unreachable!(),
+            })
+            .collect();
+        levels.push(next);
+    }
+    levels
+}
+
+/// The root of the allow-list of `owners`, the gate key of a pool gated by it.
+pub fn allow_list_root(owners: &[Pubkey]) -> Pubkey {
+    let root = levels(owners)
+        .last()
+        .and_then(|level| level.first().copied())
+        .unwrap_or_default();
+    Pubkey::new_from_array(root)
+}
+
+/// The proof `owner` is on the allow-list of `owners`, if they are.
+pub fn allow_list_proof(owners: &[Pubkey], owner: &Pubkey) -> Option<Vec<[u8; 32]>> {
+    let levels = levels(owners);
+    let leaf = keccak::hashv(&[owner.as_ref()]).0;
+    let mut index = levels[0].binary_search(&leaf).ok()?;
+
+    let mut proof = vec![];
+    for level in &levels[..levels.len() - 1] {
+        if let Some(sibling) = level.get(index ^ 1) {
+            proof.push(*sibling);
+        }
+        index /= 2;
+    }
+    Some(proof)
+}
@@ -0,0 +1,1003 @@
+//! Builders for every `reward_pool` instruction.
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{instruction::Instruction, system_program};
+use anchor_lang::{InstructionData, ToAccountMetas};
+use anchor_spl::associated_token::get_associated_token_address;
+use reward_pool::{accounts, instruction, Pool, PoolGate};
+
+use crate::pda::{config_address, metadata_address, pool_signer_address, user_address};
+
+fn build(accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: reward_pool::ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+fn with_remaining(mut ix: Instruction, remaining: &[Pubkey]) -> Instruction {
+    ix.accounts
+        .extend(remaining.iter().map(|key| AccountMeta::new(*key, false)));
+    ix
+}
+
+/// Creates the program config, signed by the program's upgrade authority.
+pub fn initialize_config(
+    upgrade_authority: Pubkey,
+    admin: Pubkey,
+    deposit_mint: Pubkey,
+    deposit_requirement: u64,
+    min_duration: u64,
+) -> Instruction {
+    build(
+        accounts::InitializeConfig {
+            config: config_address().0,
+            program_data: reward_pool::program_data_address(),
+            upgrade_authority,
+            system_program: system_program::ID,
+        },
+        instruction::InitializeConfig {
+            admin,
+            deposit_mint,
+            deposit_requirement,
+            min_duration,
+        },
+    )
+}
+
+/// Updates the program config, signed by its admin.
+pub fn update_config(
+    admin: Pubkey,
+    deposit_mint: Pubkey,
+    deposit_requirement: u64,
+    min_duration: u64,
+) -> Instruction {
+    build(
+        accounts::UpdateConfig {
+            config: config_address().0,
+            admin,
+        },
+        instruction::UpdateConfig {
+            deposit_mint,
+            deposit_requirement,
+            min_duration,
+        },
+    )
+}
+
+/// Freezes or unfreezes every pool, signed by the config admin.
+pub fn set_global_freeze(admin: Pubkey, global_freeze: bool) -> Instruction {
+    build(
+        accounts::UpdateConfig {
+            config: config_address().0,
+            admin,
+        },
+        instruction::SetGlobalFreeze { global_freeze },
+    )
+}
+
+/// Proposes a new config admin, signed by the current one.
+pub fn propose_config_admin(admin: Pubkey, new_admin: Pubkey) -> Instruction {
+    build(
+        accounts::UpdateConfig {
+            config: config_address().0,
+            admin,
+        },
+        instruction::ProposeConfigAdmin { new_admin },
+    )
+}
+
+/// Cancels a pending config admin transfer, signed by the config admin.
+pub fn cancel_config_admin_transfer(admin: Pubkey) -> Instruction {
+    build(
+        accounts::UpdateConfig {
+            config: config_address().0,
+            admin,
+        },
+        instruction::CancelConfigAdminTransfer {},
+    )
+}
+
+/// Takes over the program config, signed by the pending admin.
+pub fn accept_config_admin(pending_admin: Pubkey) -> Instruction {
+    build(
+        accounts::AcceptConfigAdmin {
+            config: config_address().0,
+            pending_admin,
+        },
+        instruction::AcceptConfigAdmin {},
+    )
+}
+
+/// The addresses of a pool that its instructions take. Reward mints and vaults are in
+/// the order the pool holds them; reward A then reward B for pools prior to V3.
+///
+/// Builders take the accounts of the wallet acting on the pool, deriving the pool signer
+/// and user addresses. A signer is expected for the owner, funder or authority acting.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PoolKeys {
+    pub pool: Pubkey,
+    pub pool_signer: Pubkey,
+    pub nonce: u8,
+    pub authority: Pubkey,
+    pub x_token_pool_vault: Pubkey,
+    pub staking_mint: Pubkey,
+    pub staking_vault: Pubkey,
+    pub reward_mints: Vec<Pubkey>,
+    pub reward_vaults: Vec<Pubkey>,
+    /// Token account receiving early exit penalties, if the pool has one.
+    pub penalty_treasury: Option<Pubkey>,
+    /// Who may join the pool, and the key checked against.
+    pub gate: PoolGate,
+    pub gate_key: Pubkey,
+    /// Stake caps per user and in total, zero for none.
+    pub max_stake_per_user: u64,
+    pub max_total_stake: u64,
+}
+
+impl PoolKeys {
+    /// The keys of a pool yet to be initialized. Its vaults must be owned by the pool
+    /// signer, see [`pool_signer_address`].
+    pub fn new(
+        pool: Pubkey,
+        authority: Pubkey,
+        x_token_pool_vault: Pubkey,
+        staking_mint: Pubkey,
+        staking_vault: Pubkey,
+        reward_mints: Vec<Pubkey>,
+        reward_vaults: Vec<Pubkey>,
+    ) -> Self {
+        let (pool_signer, nonce) = pool_signer_address(&pool);
+        PoolKeys {
+            pool,
+            pool_signer,
+            nonce,
+            authority,
+            x_token_pool_vault,
+            staking_mint,
+            staking_vault,
+            reward_mints,
+            reward_vaults,
+            penalty_treasury: None,
+            gate: PoolGate::Open,
+            gate_key: Pubkey::default(),
+            max_stake_per_user: 0,
+            max_total_stake: 0,
+        }
+    }
+
+    /// The keys of an initialized pool, read from its account.
+    pub fn from_pool(pool: Pubkey, state: &Pool) -> Self {
+        let (pool_signer, _) = pool_signer_address(&pool);
+        PoolKeys {
+            pool,
+            pool_signer,
+            nonce: state.nonce,
+            authority: state.authority,
+            x_token_pool_vault: state.x_token_pool_vault,
+            staking_mint: state.staking_mint,
+            staking_vault: state.staking_vault,
+            reward_mints: state.reward_mints(),
+            reward_vaults: state.reward_vaults(),
+            penalty_treasury: (state.penalty_treasury != Pubkey::default())
+                .then_some(state.penalty_treasury),
+            gate: state.gate,
+            gate_key: state.gate_key,
+            max_stake_per_user: state.max_stake_per_user,
+            max_total_stake: state.max_total_stake,
+        }
+    }
+
+    /// The address of `owner`'s user account on the pool.
+    pub fn user(&self, owner: &Pubkey) -> Pubkey {
+        user_address(owner, &self.pool).0
+    }
+
+    /// Initializes an A/B pool over the first two reward mints and vaults, depositing
+    /// xSTEP from `x_token_depositor`, passing the penalty treasury when the pool has one.
+    pub fn initialize_pool(
+        &self,
+        x_token_depositor: Pubkey,
+        x_token_deposit_authority: Pubkey,
+        reward_duration: u64,
+        early_exit_penalty_bps: u16,
+        early_exit_penalty_window: u64,
+    ) -> Instruction {
+        let remaining: Vec<Pubkey> = self.penalty_treasury.into_iter().collect();
+        let ix = build(
+            accounts::InitializePool {
+                authority: self.authority,
+                x_token_pool_vault: self.x_token_pool_vault,
+                x_token_depositor,
+                x_token_deposit_authority,
+                staking_mint: self.staking_mint,
+                staking_vault: self.staking_vault,
+                reward_a_mint: self.reward_mints[0],
+                reward_a_vault: self.reward_vaults[0],
+                reward_b_mint: self.reward_mints[1],
+                reward_b_vault: self.reward_vaults[1],
+                pool_signer: self.pool_signer,
+                pool: self.pool,
+                token_program: spl_token::ID,
+                config: config_address().0,
+            },
+            instruction::InitializePool {
+                pool_nonce: self.nonce,
+                reward_duration,
+                early_exit_penalty_bps,
+                early_exit_penalty_window,
+                gate: self.gate,
+                gate_key: self.gate_key,
+                max_stake_per_user: self.max_stake_per_user,
+                max_total_stake: self.max_total_stake,
+            },
+        );
+        with_remaining(ix, &remaining)
+    }
+
+    fn initialize_pool_v3_accounts(
+        &self,
+        x_token_depositor: Pubkey,
+        x_token_deposit_authority: Pubkey,
+    ) -> accounts::InitializePoolV3 {
+        accounts::InitializePoolV3 {
+            authority: self.authority,
+            x_token_pool_vault: self.x_token_pool_vault,
+            x_token_depositor,
+            x_token_deposit_authority,
+            staking_mint: self.staking_mint,
+            staking_vault: self.staking_vault,
+            pool_signer: self.pool_signer,
+            pool: self.pool,
+            token_program: spl_token::ID,
+            config: config_address().0,
+        }
+    }
+
+    /// Initializes a multi reward pool sharing one reward schedule.
+    pub fn initialize_pool_v3(
+        &self,
+        x_token_depositor: Pubkey,
+        x_token_deposit_authority: Pubkey,
+        reward_duration: u64,
+    ) -> Instruction {
+        build(
+            self.initialize_pool_v3_accounts(x_token_depositor, x_token_deposit_authority),
+            instruction::InitializePoolV3 {
+                pool_nonce: self.nonce,
+                reward_duration,
+                gate: self.gate,
+                gate_key: self.gate_key,
+                max_stake_per_user: self.max_stake_per_user,
+                max_total_stake: self.max_total_stake,
+            },
+        )
+    }
+
+    /// Initializes a multi reward pool with a schedule per reward.
+    pub fn initialize_pool_v4(
+        &self,
+        x_token_depositor: Pubkey,
+        x_token_deposit_authority: Pubkey,
+        reward_duration: u64,
+    ) -> Instruction {
+        build(
+            self.initialize_pool_v3_accounts(x_token_depositor, x_token_deposit_authority),
+            instruction::InitializePoolV4 {
+                pool_nonce: self.nonce,
+                reward_duration,
+                gate: self.gate,
+                gate_key: self.gate_key,
+                max_stake_per_user: self.max_stake_per_user,
+                max_total_stake: self.max_total_stake,
+            },
+        )
+    }
+
+    /// Adds a reward to a multi reward pool. The keys do not hold it until read from the
+    /// pool again, or pushed onto `reward_mints` and `reward_vaults`.
+    pub fn add_reward(&self, reward_mint: Pubkey, reward_vault: Pubkey) -> Instruction {
+        build(
+            accounts::AddReward {
+                pool: self.pool,
+                authority: self.authority,
+                reward_mint,
+                reward_vault,
+                pool_signer: self.pool_signer,
+            },
+            instruction::AddReward {},
+        )
+    }
+
+    pub fn set_reward_duration(&self, reward_index: u8, reward_duration: u64) -> Instruction {
+        build(
+            accounts::SetRewardDuration {
+                pool: self.pool,
+                authority: self.authority,
+                config: config_address().0,
+            },
+            instruction::SetRewardDuration {
+                reward_index,
+                reward_duration,
+            },
+        )
+    }
+
+    fn create_user_accounts(&self, owner: Pubkey) -> accounts::CreateUser {
+        accounts::CreateUser {
+            pool: self.pool,
+            user: self.user(&owner),
+            owner,
+            system_program: system_program::ID,
+        }
+    }
+
+    /// Creates `owner`'s user account. On a pool gated by an authority, the authority
+    /// co-signs.
+    pub fn create_user(&self, owner: Pubkey) -> Instruction {
+        let (_, nonce) = user_address(&owner, &self.pool);
+        let mut ix = build(
+            self.create_user_accounts(owner),
+            instruction::CreateUser { _nonce: nonce },
+        );
+        if self.gate == PoolGate::Authority {
+            ix.accounts
+                .push(AccountMeta::new_readonly(self.gate_key, true));
+        }
+        ix
+    }
+
+    /// Creates `owner`'s user account on a pool gated by a mint or collection, showing
+    /// `token_account` holds a token of `token_mint`.
+    pub fn create_user_holding(
+        &self,
+        owner: Pubkey,
+        token_account: Pubkey,
+        token_mint: Pubkey,
+    ) -> Instruction {
+        let mut ix = self.create_user(owner);
+        ix.accounts
+            .push(AccountMeta::new_readonly(token_account, false));
+        if self.gate == PoolGate::Collection {
+            ix.accounts.push(AccountMeta::new_readonly(
+                metadata_address(&token_mint),
+                false,
+            ));
+        }
+        ix
+    }
+
+    /// Creates `owner`'s user account on a pool gated by a Merkle root, see
+    /// [`allow_list_proof`](crate::allow_list_proof).
+    pub fn create_user_with_proof(&self, owner: Pubkey, proof: Vec<[u8; 32]>) -> Instruction {
+        build(
+            self.create_user_accounts(owner),
+            instruction::CreateUserWithProof { proof },
+        )
+    }
+
+    /// Pauses the pool, returning its xSTEP deposit to `x_token_receiver`.
+    pub fn pause(&self, x_token_receiver: Pubkey) -> Instruction {
+        build(
+            accounts::Pause {
+                x_token_pool_vault: self.x_token_pool_vault,
+                x_token_receiver,
+                pool: self.pool,
+                authority: self.authority,
+                pool_signer: self.pool_signer,
+                token_program: spl_token::ID,
+            },
+            instruction::Pause {},
+        )
+    }
+
+    /// Unpauses the pool, depositing xSTEP into a new vault as pausing closed the last.
+    pub fn unpause(
+        &self,
+        x_token_pool_vault: Pubkey,
+        x_token_depositor: Pubkey,
+        x_token_deposit_authority: Pubkey,
+    ) -> Instruction {
+        build(
+            accounts::Unpause {
+                x_token_pool_vault,
+                x_token_depositor,
+                x_token_deposit_authority,
+                pool: self.pool,
+                authority: self.authority,
+                pool_signer: self.pool_signer,
+                token_program: spl_token::ID,
+                config: config_address().0,
+            },
+            instruction::Unpause {},
+        )
+    }
+
+    fn stake_accounts(&self, owner: Pubkey, stake_from_account: Pubkey) -> accounts::Stake {
+        accounts::Stake {
+            pool: self.pool,
+            staking_vault: self.staking_vault,
+            user: self.user(&owner),
+            owner,
+            stake_from_account,
+            pool_signer: self.pool_signer,
+            token_program: spl_token::ID,
+            config: config_address().0,
+        }
+    }
+
+    fn unstake_accounts(&self, owner: Pubkey, stake_to_account: Pubkey) -> accounts::Unstake {
+        accounts::Unstake {
+            pool: self.pool,
+            staking_vault: self.staking_vault,
+            user: self.user(&owner),
+            owner,
+            stake_from_account: stake_to_account,
+            pool_signer: self.pool_signer,
+            token_program: spl_token::ID,
+        }
+    }
+
+    pub fn stake(&self, owner: Pubkey, stake_from_account: Pubkey, amount: u64) -> Instruction {
+        build(
+            self.stake_accounts(owner, stake_from_account),
+            instruction::Stake { amount },
+        )
+    }
+
+    /// Stakes `payer`'s tokens from `stake_from_account` for `owner`, opening their user
+    /// account if needed. On a gated pool `proof` or the gate's accounts, as for
+    /// [`create_user`](Self::create_user), let a new owner in.
+    pub fn stake_for(
+        &self,
+        payer: Pubkey,
+        stake_from_account: Pubkey,
+        owner: Pubkey,
+        amount: u64,
+        proof: Vec<[u8; 32]>,
+    ) -> Instruction {
+        let mut ix = build(
+            accounts::StakeFor {
+                pool: self.pool,
+                staking_vault: self.staking_vault,
+                user: self.user(&owner),
+                owner,
+                payer,
+                stake_from_account,
+                pool_signer: self.pool_signer,
+                token_program: spl_token::ID,
+                system_program: system_program::ID,
+                config: config_address().0,
+            },
+            instruction::StakeFor { amount, proof },
+        );
+        if self.gate == PoolGate::Authority {
+            ix.accounts
+                .push(AccountMeta::new_readonly(self.gate_key, true));
+        }
+        ix
+    }
+
+    /// Unstakes into `stake_to_account`, passing the penalty treasury when the pool has one.
+    pub fn unstake(&self, owner: Pubkey, stake_to_account: Pubkey, spt_amount: u64) -> Instruction {
+        let remaining: Vec<Pubkey> = self.penalty_treasury.into_iter().collect();
+        with_remaining(
+            build(
+                self.unstake_accounts(owner, stake_to_account),
+                instruction::Unstake { spt_amount },
+            ),
+            &remaining,
+        )
+    }
+
+    /// Withdraws all stake into `stake_to_account` without settling rewards, forfeiting
+    /// those pending.
+    pub fn emergency_unstake(&self, owner: Pubkey, stake_to_account: Pubkey) -> Instruction {
+        build(
+            self.unstake_accounts(owner, stake_to_account),
+            instruction::EmergencyUnstake {},
+        )
+    }
+
+    pub fn withdraw_unbonded(&self, owner: Pubkey, stake_to_account: Pubkey) -> Instruction {
+        build(
+            self.unstake_accounts(owner, stake_to_account),
+            instruction::WithdrawUnbonded {},
+        )
+    }
+
+    pub fn cancel_unbonding(&self, owner: Pubkey) -> Instruction {
+        build(
+            accounts::CancelUnbonding {
+                pool: self.pool,
+                user: self.user(&owner),
+                owner,
+                config: config_address().0,
+            },
+            instruction::CancelUnbonding {},
+        )
+    }
+
+    /// Claims early exit penalty rewards, paid in the staking token.
+    pub fn claim_penalty_rewards(&self, owner: Pubkey, stake_to_account: Pubkey) -> Instruction {
+        build(
+            self.stake_accounts(owner, stake_to_account),
+            instruction::ClaimPenaltyRewards {},
+        )
+    }
+
+    pub fn lock_stake(&self, owner: Pubkey, amount: u64, lock_duration: u64) -> Instruction {
+        build(
+            accounts::LockStake {
+                pool: self.pool,
+                user: self.user(&owner),
+                owner,
+                config: config_address().0,
+            },
+            instruction::LockStake {
+                amount,
+                lock_duration,
+            },
+        )
+    }
+
+    /// Releases the expired lock of `owner`'s stake; anyone may send it.
+    pub fn release_lock(&self, owner: Pubkey) -> Instruction {
+        build(
+            accounts::ReleaseLock {
+                pool: self.pool,
+                user: self.user(&owner),
+            },
+            instruction::ReleaseLock {},
+        )
+    }
+
+    pub fn configure_lockup(
+        &self,
+        min_lock_duration: u64,
+        max_lock_duration: u64,
+        max_lock_boost_bps: u16,
+    ) -> Instruction {
+        build(
+            accounts::ConfigureLockup {
+                pool: self.pool,
+                authority: self.authority,
+                staking_vault: self.staking_vault,
+            },
+            instruction::ConfigureLockup {
+                min_lock_duration,
+                max_lock_duration,
+                max_lock_boost_bps,
+            },
+        )
+    }
+
+    pub fn set_unbonding_period(&self, unbonding_period: u64) -> Instruction {
+        build(
+            accounts::SetUnbondingPeriod {
+                pool: self.pool,
+                authority: self.authority,
+            },
+            instruction::SetUnbondingPeriod { unbonding_period },
+        )
+    }
+
+    pub fn set_stake_caps(&self, max_stake_per_user: u64, max_total_stake: u64) -> Instruction {
+        build(
+            accounts::SetStakeCaps {
+                pool: self.pool,
+                authority: self.authority,
+            },
+            instruction::SetStakeCaps {
+                max_stake_per_user,
+                max_total_stake,
+            },
+        )
+    }
+
+    /// Sends early exit penalties to `penalty_treasury`, a token account of the staking
+    /// mint, or shares them among the stakers when `None`.
+    pub fn set_penalty_treasury(&self, penalty_treasury: Option<Pubkey>) -> Instruction {
+        let remaining: Vec<Pubkey> = penalty_treasury.into_iter().collect();
+        with_remaining(
+            build(
+                accounts::SetPenaltyTreasury {
+                    pool: self.pool,
+                    authority: self.authority,
+                },
+                instruction::SetPenaltyTreasury {},
+            ),
+            &remaining,
+        )
+    }
+
+    pub fn authorize_funder(&self, funder_to_add: Pubkey) -> Instruction {
+        build(
+            accounts::FunderChange {
+                pool: self.pool,
+                authority: self.authority,
+            },
+            instruction::AuthorizeFunder { funder_to_add },
+        )
+    }
+
+    pub fn deauthorize_funder(&self, funder_to_remove: Pubkey) -> Instruction {
+        build(
+            accounts::FunderChange {
+                pool: self.pool,
+                authority: self.authority,
+            },
+            instruction::DeauthorizeFunder { funder_to_remove },
+        )
+    }
+
+    pub fn propose_authority(&self, new_authority: Pubkey) -> Instruction {
+        build(
+            accounts::AuthorityChange {
+                pool: self.pool,
+                authority: self.authority,
+            },
+            instruction::ProposeAuthority { new_authority },
+        )
+    }
+
+    pub fn cancel_authority_transfer(&self) -> Instruction {
+        build(
+            accounts::AuthorityChange {
+                pool: self.pool,
+                authority: self.authority,
+            },
+            instruction::CancelAuthorityTransfer {},
+        )
+    }
+
+    pub fn accept_authority(&self, pending_authority: Pubkey) -> Instruction {
+        build(
+            accounts::AcceptAuthority {
+                pool: self.pool,
+                pending_authority,
+            },
+            instruction::AcceptAuthority {},
+        )
+    }
+
+    fn fund_accounts(&self, funder: Pubkey, from_a: Pubkey, from_b: Pubkey) -> accounts::Fund {
+        accounts::Fund {
+            pool: self.pool,
+            staking_vault: self.staking_vault,
+            reward_a_vault: self.reward_vaults[0],
+            reward_b_vault: self.reward_vaults[1],
+            funder,
+            from_a,
+            from_b,
+            pool_signer: self.pool_signer,
+            token_program: spl_token::ID,
+            config: config_address().0,
+        }
+    }
+
+    /// Funds an A/B pool from the funder's reward A and B token accounts, starting a new
+    /// reward period.
+    pub fn fund(
+        &self,
+        funder: Pubkey,
+        from_a: Pubkey,
+        from_b: Pubkey,
+        amount_a: u64,
+        amount_b: u64,
+    ) -> Instruction {
+        build(
+            self.fund_accounts(funder, from_a, from_b),
+            instruction::Fund { amount_a, amount_b },
+        )
+    }
+
+    /// Funds the current reward period of an A/B pool without moving its end.
+    pub fn top_up(
+        &self,
+        funder: Pubkey,
+        from_a: Pubkey,
+        from_b: Pubkey,
+        amount_a: u64,
+        amount_b: u64,
+    ) -> Instruction {
+        build(
+            self.fund_accounts(funder, from_a, from_b),
+            instruction::TopUp { amount_a, amount_b },
+        )
+    }
+
+    /// Queues a reward period of an A/B pool running from `start_ts` to `end_ts`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn fund_scheduled(
+        &self,
+        funder: Pubkey,
+        from_a: Pubkey,
+        from_b: Pubkey,
+        amount_a: u64,
+        amount_b: u64,
+        start_ts: u64,
+        end_ts: u64,
+    ) -> Instruction {
+        build(
+            self.fund_accounts(funder, from_a, from_b),
+            instruction::FundScheduled {
+                amount_a,
+                amount_b,
+                start_ts,
+                end_ts,
+            },
+        )
+    }
+
+    /// Claims the rewards of an A/B pool into the owner's reward A and B token accounts.
+    pub fn claim(
+        &self,
+        owner: Pubkey,
+        reward_a_account: Pubkey,
+        reward_b_account: Pubkey,
+    ) -> Instruction {
+        build(
+            accounts::ClaimReward {
+                pool: self.pool,
+                staking_vault: self.staking_vault,
+                reward_a_vault: self.reward_vaults[0],
+                reward_b_vault: self.reward_vaults[1],
+                user: self.user(&owner),
+                owner,
+                reward_a_account,
+                reward_b_account,
+                pool_signer: self.pool_signer,
+                token_program: spl_token::ID,
+                config: config_address().0,
+            },
+            instruction::Claim {},
+        )
+    }
+
+    /// Stakes the owner's reward A, for pools rewarding the staking token.
+    pub fn compound(&self, owner: Pubkey) -> Instruction {
+        build(
+            accounts::Compound {
+                pool: self.pool,
+                staking_vault: self.staking_vault,
+                reward_a_vault: self.reward_vaults[0],
+                reward_b_vault: self.reward_vaults[1],
+                user: self.user(&owner),
+                owner,
+                pool_signer: self.pool_signer,
+                token_program: spl_token::ID,
+                config: config_address().0,
+            },
+            instruction::Compound {},
+        )
+    }
+
+    /// Funds a multi reward pool, an amount per reward taken from the funder's token
+    /// account of the same index. Accounts are only passed for non zero amounts.
+    pub fn fund_v3(
+        &self,
+        funder: Pubkey,
+        from_accounts: &[Pubkey],
+        amounts: Vec<u64>,
+    ) -> Instruction {
+        let remaining: Vec<Pubkey> = amounts
+            .iter()
+            .zip(self.reward_vaults.iter().zip(from_accounts))
+            .filter(|(amount, _)| **amount > 0)
+            .flat_map(|(_, (vault, from))| [*vault, *from])
+            .collect();
+        with_remaining(
+            build(
+                accounts::FundV3 {
+                    pool: self.pool,
+                    staking_vault: self.staking_vault,
+                    funder,
+                    pool_signer: self.pool_signer,
+                    token_program: spl_token::ID,
+                    config: config_address().0,
+                },
+                instruction::FundV3 { amounts },
+            ),
+            &remaining,
+        )
+    }
+
+    /// Claims the given rewards of a multi reward pool, each into the owner's token
+    /// account at the reward's index of `reward_accounts`.
+    pub fn claim_v3(
+        &self,
+        owner: Pubkey,
+        reward_accounts: &[Pubkey],
+        reward_indexes: Vec<u8>,
+    ) -> Instruction {
+        let remaining: Vec<Pubkey> = reward_indexes
+            .iter()
+            .flat_map(|i| {
+                [
+                    self.reward_vaults[*i as usize],
+                    reward_accounts[*i as usize],
+                ]
+            })
+            .collect();
+        with_remaining(
+            build(
+                accounts::ClaimRewardV3 {
+                    pool: self.pool,
+                    staking_vault: self.staking_vault,
+                    user: self.user(&owner),
+                    owner,
+                    pool_signer: self.pool_signer,
+                    token_program: spl_token::ID,
+                    config: config_address().0,
+                },
+                instruction::ClaimV3 { reward_indexes },
+            ),
+            &remaining,
+        )
+    }
+
+    /// Claims all of `owner`'s rewards as `claimer`, into the associated token accounts of
+    /// `recipient`, the user's reward recipient or the owner when it has none.
+    pub fn claim_for(&self, owner: Pubkey, claimer: Pubkey, recipient: Pubkey) -> Instruction {
+        let remaining: Vec<Pubkey> = self
+            .reward_vaults
+            .iter()
+            .zip(&self.reward_mints)
+            .flat_map(|(vault, mint)| [*vault, get_associated_token_address(&recipient, mint)])
+            .collect();
+        with_remaining(
+            build(
+                accounts::ClaimFor {
+                    pool: self.pool,
+                    staking_vault: self.staking_vault,
+                    user: self.user(&owner),
+                    claimer,
+                    pool_signer: self.pool_signer,
+                    token_program: spl_token::ID,
+                    config: config_address().0,
+                },
+                instruction::ClaimFor {},
+            ),
+            &remaining,
+        )
+    }
+
+    /// Sets the wallet rewards claimed on `owner`'s behalf are paid to, the default key
+    /// for the owner.
+    pub fn set_reward_recipient(&self, owner: Pubkey, reward_recipient: Pubkey) -> Instruction {
+        build(
+            accounts::SetRewardRecipient {
+                user: self.user(&owner),
+                owner,
+            },
+            instruction::SetRewardRecipient { reward_recipient },
+        )
+    }
+
+    /// Sweeps staking tokens sent straight to the staking vault into `receiver`.
+    pub fn sweep_donated_stake(&self, receiver: Pubkey) -> Instruction {
+        build(
+            accounts::SweepDonatedStake {
+                pool: self.pool,
+                authority: self.authority,
+                staking_vault: self.staking_vault,
+                receiver,
+                pool_signer: self.pool_signer,
+                token_program: spl_token::ID,
+            },
+            instruction::SweepDonatedStake {},
+        )
+    }
+
+    /// Sweeps the reward at `reward_index` its vault holds beyond what is owed to stakers
+    /// or still to be emitted into `receiver`.
+    pub fn sweep_unallocated_rewards(&self, reward_index: u8, receiver: Pubkey) -> Instruction {
+        build(
+            accounts::SweepUnallocatedRewards {
+                pool: self.pool,
+                authority: self.authority,
+                staking_vault: self.staking_vault,
+                reward_vault: self.reward_vaults[reward_index as usize],
+                receiver,
+                pool_signer: self.pool_signer,
+                token_program: spl_token::ID,
+            },
+            instruction::SweepUnallocatedRewards { reward_index },
+        )
+    }
+
+    pub fn close_user(&self, owner: Pubkey) -> Instruction {
+        build(
+            accounts::CloseUser {
+                pool: self.pool,
+                user: self.user(&owner),
+                owner,
+            },
+            instruction::CloseUser {},
+        )
+    }
+
+    /// Closes an A/B pool, refunding its vaults to the refundee's token accounts.
+    pub fn close_pool(
+        &self,
+        refundee: Pubkey,
+        staking_refundee: Pubkey,
+        reward_a_refundee: Pubkey,
+        reward_b_refundee: Pubkey,
+    ) -> Instruction {
+        build(
+            accounts::ClosePool {
+                refundee,
+                staking_refundee,
+                reward_a_refundee,
+                reward_b_refundee,
+                pool: self.pool,
+                authority: self.authority,
+                staking_vault: self.staking_vault,
+                reward_a_vault: self.reward_vaults[0],
+                reward_b_vault: self.reward_vaults[1],
+                pool_signer: self.pool_signer,
+                token_program: spl_token::ID,
+            },
+            instruction::ClosePool {},
+        )
+    }
+
+    /// Closes a multi reward pool, refunding each reward vault to the refundee's token
+    /// account of the same index.
+    pub fn close_pool_v3(
+        &self,
+        refundee: Pubkey,
+        staking_refundee: Pubkey,
+        reward_refundees: &[Pubkey],
+    ) -> Instruction {
+        let remaining: Vec<Pubkey> = self
+            .reward_vaults
+            .iter()
+            .zip(reward_refundees)
+            .flat_map(|(vault, refundee)| [*vault, *refundee])
+            .collect();
+        with_remaining(
+            build(
+                accounts::ClosePoolV3 {
+                    refundee,
+                    staking_refundee,
+                    pool: self.pool,
+                    authority: self.authority,
+                    staking_vault: self.staking_vault,
+                    pool_signer: self.pool_signer,
+                    token_program: spl_token::ID,
+                },
+                instruction::ClosePoolV3 {},
+            ),
+            &remaining,
+        )
+    }
+
+    /// Grows a pool created by an earlier version of the program, `payer` covering rent.
+    pub fn migrate_pool(&self, payer: Pubkey) -> Instruction {
+        build(
+            accounts::MigratePool {
+                pool: self.pool,
+                payer,
+                system_program: system_program::ID,
+            },
+            instruction::MigratePool {},
+        )
+    }
+
+    /// Grows `owner`'s user account if created by an earlier version of the program,
+    /// `payer` covering rent.
+    pub fn migrate_user(&self, owner: Pubkey, payer: Pubkey) -> Instruction {
+        build(
+            accounts::MigrateUser {
+                user: self.user(&owner),
+                payer,
+                system_program: system_program::ID,
+            },
+            instruction::MigrateUser {},
+        )
+    }
+}
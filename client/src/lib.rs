@@ -0,0 +1,26 @@
+//! A Rust client for the reward pool program.
+//!
+//! [`PoolKeys`] builds every `reward_pool` instruction from a pool's addresses, deriving
+//! the pool signer and user accounts along the way. Pool and user accounts are decoded
+//! with [`decode_pool`] and [`decode_user`], and [`pending_rewards`] runs the program's
+//! own reward math to tell what a claim would pay.
+
+pub mod gate;
+pub mod instructions;
+pub mod pda;
+pub mod rewards;
+pub mod state;
+
+pub use gate::*;
+pub use instructions::{
+    accept_config_admin, cancel_config_admin_transfer, initialize_config, propose_config_admin,
+    set_global_freeze, update_config, PoolKeys,
+};
+pub use pda::*;
+pub use rewards::*;
+pub use state::*;
+
+pub use reward_pool;
+pub use reward_pool::{
+    program_data_address, Pool, PoolGate, PoolVersion, ProgramConfig, RewardInfo, User, ID,
+};
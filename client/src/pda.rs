@@ -0,0 +1,26 @@
+//! Program derived addresses of the reward pool program.
+
+use anchor_lang::prelude::Pubkey;
+
+/// The address of the program config, and its bump.
+pub fn config_address() -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[reward_pool::CONFIG_SEED], &reward_pool::ID)
+}
+
+/// The address owning a pool's vaults, and its bump; the `pool_nonce` a pool is
+/// initialized with.
+pub fn pool_signer_address(pool: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[pool.as_ref()], &reward_pool::ID)
+}
+
+/// The address of the user account `owner` stakes into `pool` with, and its bump.
+pub fn user_address(owner: &Pubkey, pool: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[owner.as_ref(), pool.as_ref()], &reward_pool::ID)
+}
+
+/// The Metaplex metadata address of `mint`, which a collection gated pool reads the
+/// collection of an NFT from.
+pub fn metadata_address(mint: &Pubkey) -> Pubkey {
+    let program = reward_pool::token_metadata::ID;
+    Pubkey::find_program_address(&[b"metadata", program.as_ref(), mint.as_ref()], &program).0
+}
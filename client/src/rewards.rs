@@ -0,0 +1,17 @@
+//! Rewards pending claim, calculated with the program's own reward math.
+
+use anchor_lang::prelude::Pubkey;
+use reward_pool::{Pool, User};
+
+pub use reward_pool::calculator::pending_rewards;
+
+/// The rewards a user could claim at `current_time`, paired with the mint each is paid
+/// in. Pools prior to V3 pay reward A then reward B.
+pub fn pending_rewards_by_mint(
+    pool: &Pool,
+    user: &User,
+    current_time: u64,
+) -> reward_pool::Result<Vec<(Pubkey, u64)>> {
+    let pending = pending_rewards(pool, user, current_time)?;
+    Ok(pool.reward_mints().into_iter().zip(pending).collect())
+}
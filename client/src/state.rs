@@ -0,0 +1,32 @@
+//! Decoding of pool and user account data.
+
+use anchor_lang::prelude::*;
+use anchor_lang::AccountDeserialize;
+use reward_pool::{Pool, ProgramConfig, User};
+
+/// Decodes a pool account's data.
+pub fn decode_pool(data: &[u8]) -> std::result::Result<Pool, ProgramError> {
+    decode(data, 8 + Pool::default().try_to_vec()?.len())
+}
+
+/// Decodes a user account's data.
+pub fn decode_user(data: &[u8]) -> std::result::Result<User, ProgramError> {
+    decode(data, 8 + User::default().try_to_vec()?.len())
+}
+
+/// Decodes the program config account's data.
+pub fn decode_config(data: &[u8]) -> std::result::Result<ProgramConfig, ProgramError> {
+    ProgramConfig::try_deserialize(&mut &data[..])
+}
+
+/// Accounts created by an earlier version of the program are shorter than the current
+/// layout until migrated. Their data is read as `migrate_pool` / `migrate_user` would
+/// leave it, with the fields added since zeroed.
+fn decode<T: AccountDeserialize>(data: &[u8], len: usize) -> std::result::Result<T, ProgramError> {
+    if data.len() >= len {
+        return T::try_deserialize(&mut &data[..]);
+    }
+    let mut grown = data.to_vec();
+    grown.resize(len, 0);
+    T::try_deserialize(&mut &grown[..])
+}
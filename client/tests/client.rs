@@ -0,0 +1,215 @@
+#[path = "../../programs/reward-pool/tests/harness/mod.rs"]
+mod harness;
+
+use anchor_lang::prelude::*;
+use reward_pool_client::*;
+
+use harness::fixture::PoolFixture;
+use harness::ProgramTest;
+
+const DAY: u64 = 86_400;
+
+fn pool(t: &ProgramTest, key: &Pubkey) -> Pool {
+    decode_pool(&t.account(key).unwrap().data).unwrap()
+}
+
+fn user(t: &ProgramTest, key: &Pubkey) -> User {
+    decode_user(&t.account(key).unwrap().data).unwrap()
+}
+
+fn keys(f: &PoolFixture) -> PoolKeys {
+    PoolKeys::new(
+        f.pool,
+        f.authority,
+        f.x_token_pool_vault,
+        f.staking_mint,
+        f.staking_vault,
+        f.reward_mints.clone(),
+        f.reward_vaults.clone(),
+    )
+}
+
+#[test]
+fn drives_an_a_b_pool() {
+    let mut t = ProgramTest::new();
+    let f = PoolFixture::new(&mut t, 2);
+    let treasury_owner = t.create_wallet();
+    let treasury = t.create_token_account(&f.staking_mint, &treasury_owner, 0);
+    let mut keys = keys(&f);
+    keys.penalty_treasury = Some(treasury);
+    assert_eq!((keys.pool_signer, keys.nonce), (f.pool_signer, f.nonce));
+
+    t.process(keys.initialize_pool(f.x_token_depositor, f.authority, DAY, 1_000, DAY))
+        .unwrap();
+    assert_eq!(PoolKeys::from_pool(f.pool, &pool(&t, &f.pool)), keys);
+
+    let funder = f.create_wallet(&mut t, 0, 1_000_000_000);
+    t.process(keys.authorize_funder(funder.owner)).unwrap();
+    let u = f.create_wallet(&mut t, 1000, 0);
+    assert_eq!(keys.user(&u.owner), u.user);
+    t.process(keys.create_user(u.owner)).unwrap();
+    t.process(keys.stake(u.owner, u.staking_account, 1000))
+        .unwrap();
+    assert_eq!(user(&t, &u.user).balance_staked, 1000);
+
+    t.process(keys.fund(
+        funder.owner,
+        funder.reward_accounts[0],
+        funder.reward_accounts[1],
+        86_400_000,
+        43_200_000,
+    ))
+    .unwrap();
+
+    t.warp_forward(DAY / 3);
+    let pending = pending_rewards_by_mint(&pool(&t, &f.pool), &user(&t, &u.user), t.now()).unwrap();
+    assert_eq!(
+        pending,
+        vec![
+            (f.reward_mints[0], 28_800_000),
+            (f.reward_mints[1], 14_400_000)
+        ]
+    );
+    t.process(keys.claim(u.owner, u.reward_accounts[0], u.reward_accounts[1]))
+        .unwrap();
+    assert_eq!(t.token_balance(&u.reward_accounts[0]), 28_800_000);
+    assert_eq!(t.token_balance(&u.reward_accounts[1]), 14_400_000);
+
+    //a third of the way through the window two thirds of the 10% penalty remain
+    t.process(keys.unstake(u.owner, u.staking_account, 1000))
+        .unwrap();
+    assert_eq!(t.token_balance(&treasury), 66);
+    assert_eq!(t.token_balance(&u.staking_account), 934);
+
+    t.warp_forward(DAY);
+    t.process(keys.claim(u.owner, u.reward_accounts[0], u.reward_accounts[1]))
+        .unwrap();
+    t.process(keys.close_user(u.owner)).unwrap();
+    assert!(t.account(&u.user).is_none());
+    t.process(keys.pause(f.x_token_depositor)).unwrap();
+    let refundee = f.create_wallet(&mut t, 0, 0);
+    t.process(keys.close_pool(
+        refundee.owner,
+        refundee.staking_account,
+        refundee.reward_accounts[0],
+        refundee.reward_accounts[1],
+    ))
+    .unwrap();
+    assert!(t.account(&f.pool).is_none());
+}
+
+#[test]
+fn drives_a_multi_reward_pool() {
+    let mut t = ProgramTest::new();
+    let f = PoolFixture::new(&mut t, 3);
+    let mut keys = keys(&f);
+    keys.reward_mints.clear();
+    keys.reward_vaults.clear();
+
+    t.process(keys.initialize_pool_v4(f.x_token_depositor, f.authority, DAY))
+        .unwrap();
+    for (mint, vault) in f.reward_mints.iter().zip(&f.reward_vaults) {
+        t.process(keys.add_reward(*mint, *vault)).unwrap();
+    }
+    t.process(keys.set_reward_duration(2, 5 * DAY)).unwrap();
+    //rewards added are picked up from the pool
+    let keys = PoolKeys::from_pool(f.pool, &pool(&t, &f.pool));
+    assert_eq!(keys.reward_mints, f.reward_mints);
+    assert_eq!(keys.reward_vaults, f.reward_vaults);
+
+    let funder = f.create_wallet(&mut t, 0, 1_000_000_000);
+    t.process(keys.authorize_funder(funder.owner)).unwrap();
+    let u = f.create_wallet(&mut t, 500, 0);
+    t.process(keys.create_user(u.owner)).unwrap();
+    t.process(keys.stake(u.owner, u.staking_account, 500))
+        .unwrap();
+    t.process(keys.fund_v3(
+        funder.owner,
+        &funder.reward_accounts,
+        vec![86_400_000, 0, 432_000_000],
+    ))
+    .unwrap();
+
+    t.warp_forward(DAY / 2);
+    let pending = pending_rewards(&pool(&t, &f.pool), &user(&t, &u.user), t.now()).unwrap();
+    assert_eq!(pending, vec![43_200_000, 0, 43_200_000]);
+    t.process(keys.claim_v3(u.owner, &u.reward_accounts, vec![0, 2]))
+        .unwrap();
+    let claimed: Vec<u64> = u
+        .reward_accounts
+        .iter()
+        .map(|account| t.token_balance(account))
+        .collect();
+    assert_eq!(claimed, pending);
+}
+
+#[test]
+fn decodes_accounts_from_earlier_versions_as_migrated() {
+    let mut t = ProgramTest::new();
+    let f = PoolFixture::new(&mut t, 2);
+    let keys = keys(&f);
+    t.process(keys.initialize_pool(f.x_token_depositor, f.authority, DAY, 0, 0))
+        .unwrap();
+    let u = f.create_wallet(&mut t, 1000, 0);
+    t.process(keys.create_user(u.owner)).unwrap();
+    t.process(keys.stake(u.owner, u.staking_account, 1000))
+        .unwrap();
+
+    //stand in for accounts created before the latest fields were appended
+    for (key, trimmed) in [(f.pool, 200), (u.user, 40)] {
+        let account = t.account(&key).unwrap().clone();
+        let len = account.data.len() - trimmed;
+        let lamports = Rent::default().minimum_balance(len);
+        t.set_account(key, lamports, account.data[..len].to_vec(), account.owner);
+    }
+    let old_pool = pool(&t, &f.pool);
+    let old_user = user(&t, &u.user);
+    assert_eq!(old_pool.total_staked, 1000);
+    assert_eq!(old_user.balance_staked, 1000);
+
+    t.process(keys.migrate_pool(u.owner)).unwrap();
+    t.process(keys.migrate_user(u.owner, u.owner)).unwrap();
+    assert_eq!(
+        old_pool.try_to_vec().unwrap(),
+        pool(&t, &f.pool).try_to_vec().unwrap()
+    );
+    assert_eq!(
+        old_user.try_to_vec().unwrap(),
+        user(&t, &u.user).try_to_vec().unwrap()
+    );
+
+    //accounts of another type are not decoded
+    assert!(decode_pool(&t.account(&u.user).unwrap().data).is_err());
+}
+
+#[test]
+fn joins_an_allow_list_gated_pool() {
+    let mut t = ProgramTest::new();
+    let f = PoolFixture::new(&mut t, 2);
+    let wallets: Vec<_> = (0..5).map(|_| f.create_wallet(&mut t, 0, 0)).collect();
+    let (listed, unlisted) = wallets.split_at(3);
+    let owners: Vec<Pubkey> = listed.iter().map(|w| w.owner).collect();
+    let mut keys = keys(&f);
+    keys.gate = PoolGate::MerkleRoot;
+    keys.gate_key = allow_list_root(&owners);
+    t.process(keys.initialize_pool_v3(f.x_token_depositor, f.authority, DAY))
+        .unwrap();
+    let read = PoolKeys::from_pool(f.pool, &pool(&t, &f.pool));
+    assert_eq!((read.gate, read.gate_key), (keys.gate, keys.gate_key));
+
+    //an odd leaf is carried up without a sibling, so proofs differ in length
+    for owner in &owners {
+        let proof = allow_list_proof(&owners, owner).unwrap();
+        t.process(keys.create_user_with_proof(*owner, proof))
+            .unwrap();
+    }
+    assert_eq!(pool(&t, &f.pool).user_stake_count, 3);
+
+    let outsider = unlisted[0].owner;
+    assert_eq!(allow_list_proof(&owners, &outsider), None);
+    let borrowed = allow_list_proof(&owners, &owners[0]).unwrap();
+    assert!(t
+        .process(keys.create_user_with_proof(outsider, borrowed))
+        .is_err());
+    assert!(t.process(keys.create_user(unlisted[1].owner)).is_err());
+}
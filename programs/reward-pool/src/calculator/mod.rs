@@ -1,40 +1,215 @@
 use crate::*;
 use pool_v1::*;
 use pool_v2::*;
+use pool_v3::*;
+use pool_v4::*;
+use pool_v5::*;
 
 mod pool_v1;
 mod pool_v2;
+mod pool_v3;
+mod pool_v4;
+mod pool_v5;
 
 /// Retrieve a calculator for a specific pool
-pub fn get_calculator(pool: &Account<Pool>) -> Box<dyn RewardCalculator> {
+pub fn get_calculator(pool: &Pool) -> Box<dyn RewardCalculator> {
     match pool.version {
         PoolVersion::V1 => Box::new(RewardCalculatorV1),
         PoolVersion::V2 => Box::new(RewardCalculatorV2),
+        PoolVersion::V3 => Box::new(RewardCalculatorV3),
+        PoolVersion::V4 => Box::new(RewardCalculatorV4),
+        PoolVersion::V5 => Box::new(RewardCalculatorV5),
     }
 }
 
-/// A reward calculator handles the calculations of reward rates and user reward amounts
+/// Brings the pool's reward per token, and optionally the user's earnings, up to
+/// `current_time`. A scheduled reward period that has started is activated on the way.
+/// A user's expired lock is released, its boost earning up to the lock end, or to the
+/// pool's last update if that is later.
+pub fn accrue_rewards(pool: &mut Pool, user: Option<&mut User>, current_time: u64) -> Result<()> {
+    match user {
+        Some(u) if u.lock_expired(current_time) => {
+            let lock_end_ts = u.lock_end_ts.max(pool.last_update_time);
+            accrue_rewards_to(pool, Some(u), lock_end_ts)?;
+            u.release_lock(pool)?;
+            accrue_rewards_to(pool, Some(u), current_time)
+        }
+        user => accrue_rewards_to(pool, user, current_time),
+    }
+}
+
+fn accrue_rewards_to(pool: &mut Pool, user: Option<&mut User>, current_time: u64) -> Result<()> {
+    let calc = get_calculator(pool);
+    let per_token_before = pool.reward_per_token_stored();
+
+    //a scheduled reward period takes over once it starts, so settle rewards up to its start
+    if pool.scheduled_start_ts > 0 && current_time >= pool.scheduled_start_ts {
+        let start_ts = pool.scheduled_start_ts;
+        let reward_per_token = calc.reward_per_token(pool, pool.total_staked, start_ts)?;
+        pool.set_reward_per_token_stored(&reward_per_token);
+        pool.set_last_update_time(start_ts);
+
+        pool.activate_scheduled_period()?;
+    }
+
+    let reward_per_token = calc.reward_per_token(pool, pool.total_staked, current_time)?;
+    pool.set_reward_per_token_stored(&reward_per_token);
+    pool.add_reward_owed(&per_token_before)?;
+
+    pool.set_last_update_time(current_time);
+
+    if let Some(u) = user {
+        let earned = calc.user_earned_amount(pool, u)?;
+        u.set_reward_earned(pool, &earned);
+        u.set_penalty_reward_earned(pool)?;
+    }
+
+    Ok(())
+}
+
+/// The annual rate emitting `amount` over `duration` seconds. The amount is scaled to a
+/// year before dividing, so all but a fraction of a token is emitted over the duration.
+pub(crate) fn annual_rate_over(amount: u64, duration: u64) -> Result<u64> {
+    (amount as u128)
+        .checked_mul(SECONDS_IN_YEAR.into())
+        .ok_or(ErrorCode::MathOverflow)?
+        .checked_div(duration.into())
+        .ok_or(ErrorCode::MathOverflow)?
+        .try_into()
+        .map_err(|_| ErrorCode::RateTooHigh.into()) //back to u64
+}
+
+/// The annual rate emitting `funding_amount` over `duration`, plus what remains of the
+/// period ending at `reward_period_end` at the current annual `rate`
+pub(crate) fn annual_rate(
+    funding_amount: u64,
+    rate: u64,
+    reward_period_end: u64,
+    duration: u64,
+    current_time: u64,
+) -> Result<u64> {
+    let leftover: u64 = if current_time >= reward_period_end {
+        0
+    } else {
+        let remaining_seconds = reward_period_end
+            .checked_sub(current_time)
+            .ok_or(ErrorCode::MathOverflow)?;
+        (remaining_seconds as u128)
+            .checked_mul(rate.into())
+            .ok_or(ErrorCode::MathOverflow)?
+            .checked_div(SECONDS_IN_YEAR.into())
+            .ok_or(ErrorCode::MathOverflow)?
+            .try_into()
+            .map_err(|_| ErrorCode::MathOverflow)? //back to u64
+    };
+
+    annual_rate_over(
+        funding_amount
+            .checked_add(leftover)
+            .ok_or(ErrorCode::RateTooHigh)?,
+        duration,
+    )
+}
+
+/// The amount an annual `rate` has yet to emit from `current_time` to `reward_period_end`,
+/// rounded up
+pub(crate) fn remaining_emission(
+    rate: u64,
+    reward_period_end: u64,
+    current_time: u64,
+) -> Result<u64> {
+    let remaining_seconds = reward_period_end.saturating_sub(current_time);
+    (remaining_seconds as u128)
+        .checked_mul(rate.into())
+        .ok_or(ErrorCode::MathOverflow)?
+        .checked_add((SECONDS_IN_YEAR - 1).into())
+        .ok_or(ErrorCode::MathOverflow)?
+        .checked_div(SECONDS_IN_YEAR.into())
+        .ok_or(ErrorCode::MathOverflow)?
+        .try_into()
+        .map_err(|_| ErrorCode::MathOverflow.into()) //back to u64
+}
+
+/// The rewards a user could claim at `current_time`, per reward in the order the pool
+/// holds them; exactly what a claim at that time pays, vault balances allowing.
+#[cfg(feature = "client")]
+pub fn pending_rewards(pool: &Pool, user: &User, current_time: u64) -> Result<Vec<u64>> {
+    let mut pool = pool.clone();
+    let mut user = user.clone();
+    accrue_rewards(&mut pool, Some(&mut user), current_time)?;
+
+    if pool.version.is_multi_reward() {
+        Ok(user.reward_per_token_pending[..pool.reward_infos().len()].to_vec())
+    } else {
+        Ok(vec![
+            user.reward_a_per_token_pending,
+            user.reward_b_per_token_pending,
+        ])
+    }
+}
+
+/// The amount earned by `weight` as the reward per token went from `per_token_complete`
+/// to `per_token_stored`, on top of the amount already pending
+pub(crate) fn earned_amount(
+    weight: u128,
+    per_token_stored: u128,
+    per_token_complete: u128,
+    pending: u64,
+) -> Result<u64> {
+    weight
+        .checked_mul(
+            per_token_stored
+                .checked_sub(per_token_complete)
+                .ok_or(ErrorCode::MathOverflow)?,
+        )
+        .ok_or(ErrorCode::MathOverflow)?
+        .checked_div(PRECISION)
+        .ok_or(ErrorCode::MathOverflow)?
+        .checked_add(pending.into())
+        .ok_or(ErrorCode::MathOverflow)?
+        .try_into()
+        .map_err(|_| ErrorCode::MathOverflow.into()) //back to u64
+}
+
+/// A reward calculator handles the calculations of reward rates and user reward amounts.
+/// Values are per reward, in the order the pool holds them; A then B for pools prior to V3.
+/// Calculators work on plain pool and user data at an explicit time, so they run the same
+/// off chain. Math that does not fit fails with `MathOverflow`, or `RateTooHigh` for rates,
+/// rather than panicking.
 pub trait RewardCalculator {
-    /// Calculates the current reward per token that should have been paid out
+    /// Calculates the current reward per token that should have been paid out, emissions
+    /// stopping at the end of the reward period
     fn reward_per_token(
         &self,
-        pool: &Account<Pool>,
+        pool: &Pool,
         total_staked: u64,
-        last_time_reward_applicable: u64,
-    ) -> (u128, u128);
+        current_time: u64,
+    ) -> Result<Vec<u128>>;
 
     /// Calculates the rate per token after a funding, assuming the reward end date will be updated
     /// **This call may mutate the pool to a new version**; it is the time that upgrades are applied
     /// as of V2.
     fn rate_after_funding(
         &self,
-        pool: &mut Account<Pool>,
-        reward_a_vault: &Account<TokenAccount>,
-        reward_b_vault: &Account<TokenAccount>,
-        funding_amount_a: u64,
-        funding_amount_b: u64,
-    ) -> Result<(u64, u64)>;
+        pool: &mut Pool,
+        reward_vault_amounts: &[u64],
+        funding_amounts: &[u64],
+        current_time: u64,
+    ) -> Result<Vec<u64>>;
+
+    /// Calculates the rate per token after a funding that keeps the current reward end date,
+    /// such that the remaining rewards plus the funding are emitted by then
+    fn rate_after_top_up(
+        &self,
+        pool: &Pool,
+        funding_amounts: &[u64],
+        current_time: u64,
+    ) -> Result<Vec<u64>>;
 
     /// Calculates the amount that a user earned
-    fn user_earned_amount(&self, pool: &Account<Pool>, user: &Account<User>) -> (u64, u64);
+    fn user_earned_amount(&self, pool: &Pool, user: &User) -> Result<Vec<u64>>;
+
+    /// Calculates the rewards still to be emitted from `current_time` to the end of the
+    /// reward period, rounded up
+    fn remaining_rewards(&self, pool: &Pool, current_time: u64) -> Result<Vec<u64>>;
 }
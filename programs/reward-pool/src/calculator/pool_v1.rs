@@ -5,63 +5,65 @@ pub struct RewardCalculatorV1;
 impl RewardCalculator for RewardCalculatorV1 {
     fn reward_per_token(
         &self,
-        pool: &Account<Pool>,
+        pool: &Pool,
         total_staked: u64,
-        last_time_reward_applicable: u64,
-    ) -> (u128, u128) {
+        current_time: u64,
+    ) -> Result<Vec<u128>> {
+        let last_time_reward_applicable =
+            last_time_reward_applicable(current_time, pool.reward_duration_end);
+
         if total_staked == 0 {
-            return (
+            return Ok(vec![
                 pool.reward_a_per_token_stored,
                 pool.reward_b_per_token_stored,
-            );
+            ]);
         }
 
+        let time_period = (last_time_reward_applicable as u128)
+            .checked_sub(pool.last_update_time as u128)
+            .ok_or(ErrorCode::ClockWentBackwards)?;
+
         let a = pool
             .reward_a_per_token_stored
             .checked_add(
-                (last_time_reward_applicable as u128)
-                    .checked_sub(pool.last_update_time as u128)
-                    .unwrap()
+                time_period
                     .checked_mul(pool.reward_a_rate as u128)
-                    .unwrap()
+                    .ok_or(ErrorCode::MathOverflow)?
                     .checked_mul(PRECISION)
-                    .unwrap()
+                    .ok_or(ErrorCode::MathOverflow)?
                     .checked_div(total_staked as u128)
-                    .unwrap(),
+                    .ok_or(ErrorCode::MathOverflow)?,
             )
-            .unwrap();
+            .ok_or(ErrorCode::MathOverflow)?;
 
         let b = pool
             .reward_b_per_token_stored
             .checked_add(
-                (last_time_reward_applicable as u128)
-                    .checked_sub(pool.last_update_time as u128)
-                    .unwrap()
+                time_period
                     .checked_mul(pool.reward_b_rate as u128)
-                    .unwrap()
+                    .ok_or(ErrorCode::MathOverflow)?
                     .checked_mul(PRECISION)
-                    .unwrap()
+                    .ok_or(ErrorCode::MathOverflow)?
                     .checked_div(total_staked as u128)
-                    .unwrap(),
+                    .ok_or(ErrorCode::MathOverflow)?,
             )
-            .unwrap();
+            .ok_or(ErrorCode::MathOverflow)?;
 
-        (a, b)
+        Ok(vec![a, b])
     }
 
     fn rate_after_funding(
         &self,
-        pool: &mut Account<Pool>,
-        reward_a_vault: &Account<TokenAccount>,
-        reward_b_vault: &Account<TokenAccount>,
-        funding_amount_a: u64,
-        funding_amount_b: u64,
-    ) -> Result<(u64, u64)> {
+        pool: &mut Pool,
+        reward_vault_amounts: &[u64],
+        funding_amounts: &[u64],
+        current_time: u64,
+    ) -> Result<Vec<u64>> {
         //a little inception here, a pool V1 funding needs to handle the upgrade of the pool
         //to V2.  However at the same time it needs to handle the reason that pool V2 exists
         //which is to fix a bug that caused some funds to get stuck and not emit.
 
-        pool.upgrade_if_needed();
+        pool.upgrade_if_needed()?;
 
         //rescuing borked funds
         //V1 farms calculated rate using lamports per second resulting in farms with a rate of 0
@@ -69,25 +71,31 @@ impl RewardCalculator for RewardCalculatorV1 {
         //the rate is updated based on the *vault contents*, not the computed emissions.
         //As such, we add the vault contents to the funding amount.
 
-        let mut funding_amount_a = funding_amount_a;
-        let mut funding_amount_b = funding_amount_b;
+        let reward_a_vault_amount = reward_vault_amounts[0];
+        let reward_b_vault_amount = reward_vault_amounts[1];
+        let mut funding_amount_a = funding_amounts[0];
+        let mut funding_amount_b = funding_amounts[1];
 
         if pool.reward_a_rate == 0                  //are not emitting
             && pool.reward_a_per_token_stored == 0  //never owed anyone anything
-            && reward_a_vault.amount > 0
+            && reward_a_vault_amount > 0
         //yet the fault has funds
         {
-            funding_amount_a = funding_amount_a.checked_add(reward_a_vault.amount).unwrap();
+            funding_amount_a = funding_amount_a
+                .checked_add(reward_a_vault_amount)
+                .ok_or(ErrorCode::MathOverflow)?;
             msg!("applying fix for reward a - {}", funding_amount_a);
         }
 
         if pool.reward_b_rate == 0                       //are not emitting
             && pool.reward_b_per_token_stored == 0       //never owed anyone anything
-            && reward_b_vault.amount > 0                 //yet the fault has funds
+            && reward_b_vault_amount > 0                 //yet the fault has funds
             && pool.reward_a_mint != pool.reward_b_mint
         //not a single reward asset pool
         {
-            funding_amount_b = funding_amount_b.checked_add(reward_b_vault.amount).unwrap();
+            funding_amount_b = funding_amount_b
+                .checked_add(reward_b_vault_amount)
+                .ok_or(ErrorCode::MathOverflow)?;
             msg!("applying fix for reward b - {}", funding_amount_b);
         }
 
@@ -95,42 +103,51 @@ impl RewardCalculator for RewardCalculatorV1 {
         let calc = get_calculator(pool);
         calc.rate_after_funding(
             pool,
-            reward_a_vault,
-            reward_b_vault,
-            funding_amount_a,
-            funding_amount_b,
+            reward_vault_amounts,
+            &[funding_amount_a, funding_amount_b],
+            current_time,
         )
     }
 
-    fn user_earned_amount(&self, pool: &Account<Pool>, user: &Account<User>) -> (u64, u64) {
-        let a: u64 = (user.balance_staked as u128)
-            .checked_mul(
-                (pool.reward_a_per_token_stored as u128)
-                    .checked_sub(user.reward_a_per_token_complete as u128)
-                    .unwrap(),
-            )
-            .unwrap()
-            .checked_div(PRECISION)
-            .unwrap()
-            .checked_add(user.reward_a_per_token_pending as u128)
-            .unwrap()
-            .try_into()
-            .unwrap();
-
-        let b: u64 = (user.balance_staked as u128)
-            .checked_mul(
-                (pool.reward_b_per_token_stored as u128)
-                    .checked_sub(user.reward_b_per_token_complete as u128)
-                    .unwrap(),
-            )
-            .unwrap()
-            .checked_div(PRECISION)
-            .unwrap()
-            .checked_add(user.reward_b_per_token_pending as u128)
-            .unwrap()
-            .try_into()
-            .unwrap();
-
-        (a, b)
+    fn rate_after_top_up(
+        &self,
+        _pool: &Pool,
+        _funding_amounts: &[u64],
+        _current_time: u64,
+    ) -> Result<Vec<u64>> {
+        //a V1 pool must be upgraded by a regular funding first
+        Err(ErrorCode::PoolVersionNotSupported.into())
+    }
+
+    fn user_earned_amount(&self, pool: &Pool, user: &User) -> Result<Vec<u64>> {
+        let a = earned_amount(
+            user.balance_staked.into(),
+            pool.reward_a_per_token_stored,
+            user.reward_a_per_token_complete,
+            user.reward_a_per_token_pending,
+        )?;
+        let b = earned_amount(
+            user.balance_staked.into(),
+            pool.reward_b_per_token_stored,
+            user.reward_b_per_token_complete,
+            user.reward_b_per_token_pending,
+        )?;
+
+        Ok(vec![a, b])
+    }
+
+    fn remaining_rewards(&self, pool: &Pool, current_time: u64) -> Result<Vec<u64>> {
+        //the rate is per second
+        let remaining_seconds = pool.reward_duration_end.saturating_sub(current_time);
+        let remaining = |rate: u64| -> Result<u64> {
+            remaining_seconds
+                .checked_mul(rate)
+                .ok_or_else(|| ErrorCode::MathOverflow.into())
+        };
+
+        Ok(vec![
+            remaining(pool.reward_a_rate)?,
+            remaining(pool.reward_b_rate)?,
+        ])
     }
 }
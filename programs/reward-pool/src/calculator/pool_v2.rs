@@ -5,143 +5,162 @@ const SECONDS_IN_YEAR: u64 = 365 * 24 * 60 * 60;
 
 pub struct RewardCalculatorV2;
 
-impl RewardCalculator for RewardCalculatorV2 {
-    fn reward_per_token(
+impl RewardCalculatorV2 {
+    /// The reward per token with rewards shared by `total_weight`, which for pools with
+    /// lock boosts can exceed what fits in a u64
+    pub(crate) fn reward_per_weight(
         &self,
-        pool: &Account<Pool>,
-        total_staked: u64,
-        last_time_reward_applicable: u64,
-    ) -> (u128, u128) {
-        if total_staked == 0 {
-            return (
+        pool: &Pool,
+        total_weight: u128,
+        current_time: u64,
+    ) -> Result<Vec<u128>> {
+        let last_time_reward_applicable =
+            last_time_reward_applicable(current_time, pool.reward_duration_end);
+
+        if total_weight == 0 {
+            return Ok(vec![
                 pool.reward_a_per_token_stored,
                 pool.reward_b_per_token_stored,
-            );
+            ]);
         }
 
         let time_period = U192::from(last_time_reward_applicable)
             .checked_sub(pool.last_update_time.into())
-            .unwrap();
+            .ok_or(ErrorCode::ClockWentBackwards)?;
         let a = pool
             .reward_a_per_token_stored
             .checked_add(
                 time_period
                     .checked_mul(pool.reward_a_rate.into())
-                    .unwrap()
+                    .ok_or(ErrorCode::MathOverflow)?
                     .checked_mul(PRECISION.into())
-                    .unwrap()
+                    .ok_or(ErrorCode::MathOverflow)?
                     .checked_div(SECONDS_IN_YEAR.into())
-                    .unwrap()
-                    .checked_div(total_staked.into())
-                    .unwrap()
+                    .ok_or(ErrorCode::MathOverflow)?
+                    .checked_div(total_weight.into())
+                    .ok_or(ErrorCode::MathOverflow)?
                     .try_into()
-                    .unwrap(), //back to u128
+                    .map_err(|_| ErrorCode::MathOverflow)?, //back to u128
             )
-            .unwrap();
+            .ok_or(ErrorCode::MathOverflow)?;
 
         let b = pool
             .reward_b_per_token_stored
             .checked_add(
                 time_period
                     .checked_mul(pool.reward_b_rate.into())
-                    .unwrap()
+                    .ok_or(ErrorCode::MathOverflow)?
                     .checked_mul(PRECISION.into())
-                    .unwrap()
+                    .ok_or(ErrorCode::MathOverflow)?
                     .checked_div(SECONDS_IN_YEAR.into())
-                    .unwrap()
-                    .checked_div(total_staked.into())
-                    .unwrap()
+                    .ok_or(ErrorCode::MathOverflow)?
+                    .checked_div(total_weight.into())
+                    .ok_or(ErrorCode::MathOverflow)?
                     .try_into()
-                    .unwrap(), //back to u128
+                    .map_err(|_| ErrorCode::MathOverflow)?, //back to u128
             )
-            .unwrap();
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        Ok(vec![a, b])
+    }
+}
 
-        (a, b)
+impl RewardCalculator for RewardCalculatorV2 {
+    fn reward_per_token(
+        &self,
+        pool: &Pool,
+        total_staked: u64,
+        current_time: u64,
+    ) -> Result<Vec<u128>> {
+        self.reward_per_weight(pool, total_staked.into(), current_time)
     }
 
     fn rate_after_funding(
         &self,
-        pool: &mut Account<Pool>,
-        _reward_a_vault: &Account<TokenAccount>,
-        _reward_b_vault: &Account<TokenAccount>,
-        funding_amount_a: u64,
-        funding_amount_b: u64,
-    ) -> Result<(u64, u64)> {
-        let current_time = clock::Clock::get()
-            .unwrap()
-            .unix_timestamp
-            .try_into()
-            .unwrap();
-        let reward_period_end = pool.reward_duration_end;
+        pool: &mut Pool,
+        _reward_vault_amounts: &[u64],
+        funding_amounts: &[u64],
+        current_time: u64,
+    ) -> Result<Vec<u64>> {
+        let rate = |funding_amount: u64, rate: u64| -> Result<u64> {
+            annual_rate(
+                funding_amount,
+                rate,
+                pool.reward_duration_end,
+                pool.reward_duration,
+                current_time,
+            )
+        };
+
+        Ok(vec![
+            rate(funding_amounts[0], pool.reward_a_rate)?,
+            rate(funding_amounts[1], pool.reward_b_rate)?,
+        ])
+    }
 
-        let annual_multiplier = SECONDS_IN_YEAR.checked_div(pool.reward_duration).unwrap();
-        let a: u64;
-        let b: u64;
+    fn rate_after_top_up(
+        &self,
+        pool: &Pool,
+        funding_amounts: &[u64],
+        current_time: u64,
+    ) -> Result<Vec<u64>> {
+        let reward_period_end = pool.reward_duration_end;
 
         if current_time >= reward_period_end {
-            a = funding_amount_a.checked_mul(annual_multiplier).unwrap();
-            b = funding_amount_b.checked_mul(annual_multiplier).unwrap();
-        } else {
-            let remaining_seconds = reward_period_end.checked_sub(current_time).unwrap();
-            let leftover_a: u64 = (remaining_seconds as u128)
-                .checked_mul(pool.reward_a_rate.into())
-                .unwrap()
-                .checked_div(SECONDS_IN_YEAR.into())
-                .unwrap()
-                .try_into()
-                .unwrap(); //back to u64
-            let leftover_b: u64 = (remaining_seconds as u128)
-                .checked_mul(pool.reward_b_rate.into())
-                .unwrap()
+            return Err(ErrorCode::RewardPeriodEnded.into());
+        }
+
+        let remaining_seconds = reward_period_end
+            .checked_sub(current_time)
+            .ok_or(ErrorCode::MathOverflow)?;
+        let rate_with_funding = |rate: u64, funding_amount: u64| -> Result<u64> {
+            //unfunded rewards keep their rate exactly
+            if funding_amount == 0 {
+                return Ok(rate);
+            }
+
+            (remaining_seconds as u128)
+                .checked_mul(rate.into())
+                .ok_or(ErrorCode::MathOverflow)?
                 .checked_div(SECONDS_IN_YEAR.into())
-                .unwrap()
+                .ok_or(ErrorCode::MathOverflow)?
+                .checked_add(funding_amount.into())
+                .ok_or(ErrorCode::MathOverflow)?
+                .checked_mul(SECONDS_IN_YEAR.into())
+                .ok_or(ErrorCode::MathOverflow)?
+                .checked_div(remaining_seconds.into())
+                .ok_or(ErrorCode::MathOverflow)?
                 .try_into()
-                .unwrap(); //back to u64
-
-            a = funding_amount_a
-                .checked_add(leftover_a)
-                .unwrap()
-                .checked_mul(annual_multiplier)
-                .unwrap();
-            b = funding_amount_b
-                .checked_add(leftover_b)
-                .unwrap()
-                .checked_mul(annual_multiplier)
-                .unwrap();
-        }
+                .map_err(|_| ErrorCode::RateTooHigh.into()) //back to u64
+        };
 
-        Ok((a, b))
+        Ok(vec![
+            rate_with_funding(pool.reward_a_rate, funding_amounts[0])?,
+            rate_with_funding(pool.reward_b_rate, funding_amounts[1])?,
+        ])
     }
 
-    fn user_earned_amount(&self, pool: &Account<Pool>, user: &Account<User>) -> (u64, u64) {
-        let a: u64 = (user.balance_staked as u128)
-            .checked_mul(
-                (pool.reward_a_per_token_stored as u128)
-                    .checked_sub(user.reward_a_per_token_complete as u128)
-                    .unwrap(),
-            )
-            .unwrap()
-            .checked_div(PRECISION)
-            .unwrap()
-            .checked_add(user.reward_a_per_token_pending as u128)
-            .unwrap()
-            .try_into()
-            .unwrap(); //back to u64
-
-        let b: u64 = (user.balance_staked as u128)
-            .checked_mul(
-                (pool.reward_b_per_token_stored as u128)
-                    .checked_sub(user.reward_b_per_token_complete as u128)
-                    .unwrap(),
-            )
-            .unwrap()
-            .checked_div(PRECISION)
-            .unwrap()
-            .checked_add(user.reward_b_per_token_pending as u128)
-            .unwrap()
-            .try_into()
-            .unwrap(); //back to u64
-
-        (a, b)
+    fn user_earned_amount(&self, pool: &Pool, user: &User) -> Result<Vec<u64>> {
+        let a = earned_amount(
+            user.balance_staked.into(),
+            pool.reward_a_per_token_stored,
+            user.reward_a_per_token_complete,
+            user.reward_a_per_token_pending,
+        )?;
+        let b = earned_amount(
+            user.balance_staked.into(),
+            pool.reward_b_per_token_stored,
+            user.reward_b_per_token_complete,
+            user.reward_b_per_token_pending,
+        )?;
+
+        Ok(vec![a, b])
+    }
+
+    fn remaining_rewards(&self, pool: &Pool, current_time: u64) -> Result<Vec<u64>> {
+        Ok(vec![
+            remaining_emission(pool.reward_a_rate, pool.reward_duration_end, current_time)?,
+            remaining_emission(pool.reward_b_rate, pool.reward_duration_end, current_time)?,
+        ])
     }
 }
@@ -0,0 +1,114 @@
+use crate::calculator::*;
+use spl_math::uint::U192;
+
+pub struct RewardCalculatorV3;
+
+impl RewardCalculator for RewardCalculatorV3 {
+    fn reward_per_token(
+        &self,
+        pool: &Pool,
+        total_staked: u64,
+        current_time: u64,
+    ) -> Result<Vec<u128>> {
+        let last_time_reward_applicable =
+            last_time_reward_applicable(current_time, pool.reward_duration_end);
+
+        let rewards = pool.reward_infos();
+
+        if total_staked == 0 {
+            return Ok(rewards.iter().map(|r| r.per_token_stored).collect());
+        }
+
+        let time_period = U192::from(last_time_reward_applicable)
+            .checked_sub(pool.last_update_time.into())
+            .ok_or(ErrorCode::ClockWentBackwards)?;
+
+        rewards
+            .iter()
+            .map(|r| accrued_per_token(r.per_token_stored, time_period, r.rate, total_staked))
+            .collect()
+    }
+
+    fn rate_after_funding(
+        &self,
+        pool: &mut Pool,
+        _reward_vault_amounts: &[u64],
+        funding_amounts: &[u64],
+        current_time: u64,
+    ) -> Result<Vec<u64>> {
+        let reward_period_end = pool.reward_duration_end;
+
+        pool.reward_infos()
+            .iter()
+            .enumerate()
+            .map(|(i, r)| {
+                //rewards not being funded still have their leftovers spread over the new period
+                let funding_amount = funding_amounts.get(i).copied().unwrap_or(0);
+
+                annual_rate(
+                    funding_amount,
+                    r.rate,
+                    reward_period_end,
+                    pool.reward_duration,
+                    current_time,
+                )
+            })
+            .collect()
+    }
+
+    fn rate_after_top_up(
+        &self,
+        _pool: &Pool,
+        _funding_amounts: &[u64],
+        _current_time: u64,
+    ) -> Result<Vec<u64>> {
+        //multi reward pools are funded with `fund_v3`
+        Err(ErrorCode::PoolVersionNotSupported.into())
+    }
+
+    fn user_earned_amount(&self, pool: &Pool, user: &User) -> Result<Vec<u64>> {
+        pool.reward_infos()
+            .iter()
+            .enumerate()
+            .map(|(i, r)| {
+                earned_amount(
+                    user.balance_staked.into(),
+                    r.per_token_stored,
+                    user.reward_per_token_complete[i],
+                    user.reward_per_token_pending[i],
+                )
+            })
+            .collect()
+    }
+
+    fn remaining_rewards(&self, pool: &Pool, current_time: u64) -> Result<Vec<u64>> {
+        pool.reward_infos()
+            .iter()
+            .map(|r| remaining_emission(r.rate, pool.reward_duration_end, current_time))
+            .collect()
+    }
+}
+
+/// Reward per token stored after emitting at an annual `rate` over `time_period`
+pub(crate) fn accrued_per_token(
+    per_token_stored: u128,
+    time_period: U192,
+    rate: u64,
+    total_staked: u64,
+) -> Result<u128> {
+    per_token_stored
+        .checked_add(
+            time_period
+                .checked_mul(rate.into())
+                .ok_or(ErrorCode::MathOverflow)?
+                .checked_mul(PRECISION.into())
+                .ok_or(ErrorCode::MathOverflow)?
+                .checked_div(SECONDS_IN_YEAR.into())
+                .ok_or(ErrorCode::MathOverflow)?
+                .checked_div(total_staked.into())
+                .ok_or(ErrorCode::MathOverflow)?
+                .try_into()
+                .map_err(|_| ErrorCode::MathOverflow)?, //back to u128
+        )
+        .ok_or_else(|| ErrorCode::MathOverflow.into())
+}
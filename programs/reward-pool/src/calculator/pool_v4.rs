@@ -0,0 +1,84 @@
+use crate::calculator::*;
+use pool_v3::{accrued_per_token, RewardCalculatorV3};
+use spl_math::uint::U192;
+
+pub struct RewardCalculatorV4;
+
+impl RewardCalculator for RewardCalculatorV4 {
+    fn reward_per_token(
+        &self,
+        pool: &Pool,
+        total_staked: u64,
+        current_time: u64,
+    ) -> Result<Vec<u128>> {
+        let rewards = pool.reward_infos();
+
+        if total_staked == 0 {
+            return Ok(rewards.iter().map(|r| r.per_token_stored).collect());
+        }
+
+        //each reward emits over its own period
+        rewards
+            .iter()
+            .map(|r| {
+                let last_time_reward_applicable =
+                    last_time_reward_applicable(current_time, r.reward_duration_end);
+                let time_period = U192::from(last_time_reward_applicable)
+                    .checked_sub(r.last_update_time.into())
+                    .ok_or(ErrorCode::ClockWentBackwards)?;
+
+                accrued_per_token(r.per_token_stored, time_period, r.rate, total_staked)
+            })
+            .collect()
+    }
+
+    fn rate_after_funding(
+        &self,
+        pool: &mut Pool,
+        _reward_vault_amounts: &[u64],
+        funding_amounts: &[u64],
+        current_time: u64,
+    ) -> Result<Vec<u64>> {
+        pool.reward_infos()
+            .iter()
+            .enumerate()
+            .map(|(i, r)| {
+                //rewards not being funded keep their schedule, and so their rate
+                let funding_amount = funding_amounts.get(i).copied().unwrap_or(0);
+                if funding_amount == 0 {
+                    return Ok(r.rate);
+                }
+
+                annual_rate(
+                    funding_amount,
+                    r.rate,
+                    r.reward_duration_end,
+                    r.reward_duration,
+                    current_time,
+                )
+            })
+            .collect()
+    }
+
+    fn rate_after_top_up(
+        &self,
+        _pool: &Pool,
+        _funding_amounts: &[u64],
+        _current_time: u64,
+    ) -> Result<Vec<u64>> {
+        //multi reward pools are funded with `fund_v3`
+        Err(ErrorCode::PoolVersionNotSupported.into())
+    }
+
+    fn user_earned_amount(&self, pool: &Pool, user: &User) -> Result<Vec<u64>> {
+        //earnings accrue per reward exactly as V3, only the emission schedule differs
+        RewardCalculatorV3.user_earned_amount(pool, user)
+    }
+
+    fn remaining_rewards(&self, pool: &Pool, current_time: u64) -> Result<Vec<u64>> {
+        pool.reward_infos()
+            .iter()
+            .map(|r| remaining_emission(r.rate, r.reward_duration_end, current_time))
+            .collect()
+    }
+}
@@ -0,0 +1,64 @@
+use crate::calculator::*;
+use pool_v2::RewardCalculatorV2;
+
+pub struct RewardCalculatorV5;
+
+impl RewardCalculator for RewardCalculatorV5 {
+    fn reward_per_token(
+        &self,
+        pool: &Pool,
+        total_staked: u64,
+        current_time: u64,
+    ) -> Result<Vec<u128>> {
+        //rewards are shared by weight, which is the staked amount plus lock boosts
+        let total_weight = u128::from(total_staked) + u128::from(pool.total_boost_weight);
+        RewardCalculatorV2.reward_per_weight(pool, total_weight, current_time)
+    }
+
+    fn rate_after_funding(
+        &self,
+        pool: &mut Pool,
+        reward_vault_amounts: &[u64],
+        funding_amounts: &[u64],
+        current_time: u64,
+    ) -> Result<Vec<u64>> {
+        RewardCalculatorV2.rate_after_funding(
+            pool,
+            reward_vault_amounts,
+            funding_amounts,
+            current_time,
+        )
+    }
+
+    fn rate_after_top_up(
+        &self,
+        pool: &Pool,
+        funding_amounts: &[u64],
+        current_time: u64,
+    ) -> Result<Vec<u64>> {
+        RewardCalculatorV2.rate_after_top_up(pool, funding_amounts, current_time)
+    }
+
+    fn user_earned_amount(&self, pool: &Pool, user: &User) -> Result<Vec<u64>> {
+        let weight = user.reward_weight();
+
+        let a = earned_amount(
+            weight,
+            pool.reward_a_per_token_stored,
+            user.reward_a_per_token_complete,
+            user.reward_a_per_token_pending,
+        )?;
+        let b = earned_amount(
+            weight,
+            pool.reward_b_per_token_stored,
+            user.reward_b_per_token_complete,
+            user.reward_b_per_token_pending,
+        )?;
+
+        Ok(vec![a, b])
+    }
+
+    fn remaining_rewards(&self, pool: &Pool, current_time: u64) -> Result<Vec<u64>> {
+        RewardCalculatorV2.remaining_rewards(pool, current_time)
+    }
+}
@@ -0,0 +1,59 @@
+use crate::*;
+use anchor_lang::solana_program::bpf_loader_upgradeable;
+
+/// Seed of the program config address
+pub const CONFIG_SEED: &[u8] = b"config";
+
+/// The address of the account holding the program's upgrade authority
+pub fn program_data_address() -> Pubkey {
+    Pubkey::find_program_address(&[crate::ID.as_ref()], &bpf_loader_upgradeable::ID).0
+}
+
+/// Checks `upgrade_authority` is the one the program's data holds. With the `localnet`
+/// feature a program without program data, as the local test validator loads it, lets
+/// anyone create the config.
+pub(crate) fn check_upgrade_authority(
+    program_data: &AccountInfo,
+    upgrade_authority: &Pubkey,
+) -> Result<()> {
+    #[cfg(feature = "localnet")]
+    if program_data.data_is_empty() {
+        return Ok(());
+    }
+
+    let program_data: Account<ProgramData> = Account::try_from(program_data)?;
+    if program_data.upgrade_authority_address != Some(*upgrade_authority) {
+        return Err(ErrorCode::NotUpgradeAuthority.into());
+    }
+
+    Ok(())
+}
+
+impl ProgramConfig {
+    /// Sets the deposit pools take and the shortest reward period they may be funded for
+    pub fn set(
+        &mut self,
+        deposit_mint: Pubkey,
+        deposit_requirement: u64,
+        min_duration: u64,
+    ) -> Result<()> {
+        //a zero length period would divide rewards by zero
+        if min_duration == 0 {
+            return Err(ErrorCode::DurationTooShort.into());
+        }
+        self.deposit_mint = deposit_mint;
+        self.deposit_requirement = deposit_requirement;
+        self.min_duration = min_duration;
+
+        Ok(())
+    }
+
+    /// Checks a reward period is at least the shortest allowed
+    pub fn check_duration(&self, reward_duration: u64) -> Result<()> {
+        if reward_duration < self.min_duration {
+            return Err(ErrorCode::DurationTooShort.into());
+        }
+
+        Ok(())
+    }
+}
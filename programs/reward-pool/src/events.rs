@@ -0,0 +1,567 @@
+use crate::*;
+
+/// A pool was initialized
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoolInitialized {
+    pub pool: Pubkey,
+    pub authority: Pubkey,
+    pub staking_mint: Pubkey,
+    /// The reward mints, A then B for pools prior to V3, which add theirs with `add_reward`
+    pub reward_mints: Vec<Pubkey>,
+    pub reward_duration: u64,
+}
+
+/// A user staked into a pool
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Staked {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub balance_staked: u64,
+    pub total_staked: u64,
+    pub reward_a_per_token_stored: u128,
+    pub reward_b_per_token_stored: u128,
+}
+
+/// A user unstaked from a pool. The penalty is part of the amount, the rest is either
+/// returned or unbonding.
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unstaked {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub penalty: u64,
+    pub unbonding: bool,
+    pub balance_staked: u64,
+    pub total_staked: u64,
+    pub reward_a_per_token_stored: u128,
+    pub reward_b_per_token_stored: u128,
+}
+
+/// A user withdrew all their stake without accruing rewards, forfeiting those earned.
+/// As for `Unstaked`, the penalty is part of the amount, but it stays in the staking vault.
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmergencyUnstaked {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub penalty: u64,
+    pub unbonding: bool,
+    pub total_staked: u64,
+}
+
+/// A user withdrew their unbonding tokens once unbonded
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnbondedWithdrawn {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+}
+
+/// A user put their unbonding tokens back into stake
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnbondingCancelled {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub balance_staked: u64,
+    pub total_staked: u64,
+}
+
+/// A user locked stake, or extended their lock, for a boost to their reward weight
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct StakeLocked {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub balance_locked: u64,
+    pub lock_end_ts: u64,
+    pub boost_weight: u64,
+    pub total_boost_weight: u64,
+}
+
+/// A user's expired lock was released, removing its boost, by `release_lock` or by any
+/// instruction updating the user's rewards
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct LockReleased {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub owner: Pubkey,
+    pub boost_weight: u64,
+    pub total_boost_weight: u64,
+}
+
+/// A pool's lock up config was set, `version` is V5 once lock ups are enabled
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct LockupConfigured {
+    pub pool: Pubkey,
+    pub min_lock_duration: u64,
+    pub max_lock_duration: u64,
+    pub max_lock_boost_bps: u16,
+    pub version: PoolVersion,
+}
+
+/// A pool's unbonding period was set
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnbondingPeriodSet {
+    pub pool: Pubkey,
+    pub unbonding_period: u64,
+}
+
+/// A pool's stake caps were set, zero for no cap
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct StakeCapsSet {
+    pub pool: Pubkey,
+    pub max_stake_per_user: u64,
+    pub max_total_stake: u64,
+}
+
+/// A pool's penalty treasury was set, the default address when penalties are shared by
+/// the stakers
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PenaltyTreasurySet {
+    pub pool: Pubkey,
+    pub penalty_treasury: Pubkey,
+}
+
+/// A reward was added to a multi reward pool
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RewardAdded {
+    pub pool: Pubkey,
+    pub reward_index: u8,
+    pub reward_mint: Pubkey,
+    pub reward_vault: Pubkey,
+    pub reward_duration: u64,
+}
+
+/// The reward duration of a reward of a V4 pool was set
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RewardDurationSet {
+    pub pool: Pubkey,
+    pub reward_index: u8,
+    pub reward_duration: u64,
+}
+
+/// A pool was funded, starting a new reward period
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Funded {
+    pub pool: Pubkey,
+    pub funder: Pubkey,
+    pub amount_a: u64,
+    pub amount_b: u64,
+    pub reward_a_rate: u64,
+    pub reward_b_rate: u64,
+    pub reward_a_per_token_stored: u128,
+    pub reward_b_per_token_stored: u128,
+    pub reward_duration_end: u64,
+}
+
+/// The current reward period was topped up, keeping its end date
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToppedUp {
+    pub pool: Pubkey,
+    pub funder: Pubkey,
+    pub amount_a: u64,
+    pub amount_b: u64,
+    pub reward_a_rate: u64,
+    pub reward_b_rate: u64,
+    pub reward_a_per_token_stored: u128,
+    pub reward_b_per_token_stored: u128,
+    pub reward_duration_end: u64,
+}
+
+/// A reward period was funded to start later
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FundingScheduled {
+    pub pool: Pubkey,
+    pub funder: Pubkey,
+    pub amount_a: u64,
+    pub amount_b: u64,
+    pub start_ts: u64,
+    pub end_ts: u64,
+}
+
+/// A multi reward pool was funded, amounts, rates and rewards per token in the order the
+/// pool holds its rewards
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FundedV3 {
+    pub pool: Pubkey,
+    pub funder: Pubkey,
+    pub amounts: Vec<u64>,
+    pub reward_rates: Vec<u64>,
+    pub reward_per_token_stored: Vec<u128>,
+}
+
+/// A user claimed their rewards
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Claimed {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub owner: Pubkey,
+    pub amount_a: u64,
+    pub amount_b: u64,
+    pub reward_a_per_token_stored: u128,
+    pub reward_b_per_token_stored: u128,
+}
+
+/// A user claimed rewards of a multi reward pool, amounts in the order of the claimed
+/// reward indexes
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClaimedV3 {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub owner: Pubkey,
+    pub reward_indexes: Vec<u8>,
+    pub amounts: Vec<u64>,
+    pub reward_per_token_stored: Vec<u128>,
+}
+
+/// A user staked their pending rewards of the staking token
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Compounded {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub balance_staked: u64,
+    pub total_staked: u64,
+}
+
+/// A user claimed their share of early exit penalties
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PenaltyRewardsClaimed {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+}
+
+/// Rewards were claimed on a user's behalf into their reward recipient's associated token
+/// accounts, amounts in the order the pool holds its rewards
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClaimedFor {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub owner: Pubkey,
+    pub recipient: Pubkey,
+    pub claimer: Pubkey,
+    pub amounts: Vec<u64>,
+}
+
+/// A user set the wallet rewards claimed on their behalf are paid to
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct RewardRecipientSet {
+    pub user: Pubkey,
+    pub owner: Pubkey,
+    pub reward_recipient: Pubkey,
+}
+
+/// A pool was paused
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoolPaused {
+    pub pool: Pubkey,
+}
+
+/// A paused pool was unpaused
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoolUnpaused {
+    pub pool: Pubkey,
+}
+
+/// A funder was authorized to fund a pool
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunderAuthorized {
+    pub pool: Pubkey,
+    pub funder: Pubkey,
+}
+
+/// A funder was deauthorized from funding a pool
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunderDeauthorized {
+    pub pool: Pubkey,
+    pub funder: Pubkey,
+}
+
+/// A new authority was proposed for a pool
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthorityProposed {
+    pub pool: Pubkey,
+    pub authority: Pubkey,
+    pub pending_authority: Pubkey,
+}
+
+/// A pending authority transfer was cancelled
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthorityTransferCancelled {
+    pub pool: Pubkey,
+    pub pending_authority: Pubkey,
+}
+
+/// The pending authority accepted, taking over a pool
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuthorityTransferred {
+    pub pool: Pubkey,
+    pub previous_authority: Pubkey,
+    pub authority: Pubkey,
+}
+
+/// Staking tokens donated to the staking vault were swept out
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct DonatedStakeSwept {
+    pub pool: Pubkey,
+    pub receiver: Pubkey,
+    pub amount: u64,
+}
+
+/// Reward tokens neither owed nor still to be emitted were swept out of a reward vault
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnallocatedRewardsSwept {
+    pub pool: Pubkey,
+    pub reward_index: u8,
+    pub receiver: Pubkey,
+    pub amount: u64,
+}
+
+/// A user account was closed
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct UserClosed {
+    pub pool: Pubkey,
+    pub user: Pubkey,
+    pub owner: Pubkey,
+}
+
+/// A pool was closed, its vaults emptied into the refundee's token accounts
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoolClosed {
+    pub pool: Pubkey,
+    pub refundee: Pubkey,
+}
+
+/// The program config was initialized or updated
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigUpdated {
+    pub admin: Pubkey,
+    pub deposit_mint: Pubkey,
+    pub deposit_requirement: u64,
+    pub min_duration: u64,
+}
+
+/// Every pool was frozen or unfrozen
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlobalFreezeSet {
+    pub admin: Pubkey,
+    pub global_freeze: bool,
+}
+
+/// A new admin was proposed for the program config
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigAdminProposed {
+    pub admin: Pubkey,
+    pub pending_admin: Pubkey,
+}
+
+/// A pending config admin transfer was cancelled
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigAdminTransferCancelled {
+    pub pending_admin: Pubkey,
+}
+
+/// The pending admin accepted, taking over the program config
+#[event]
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigAdminTransferred {
+    pub previous_admin: Pubkey,
+    pub admin: Pubkey,
+}
+
+#[cfg(feature = "client")]
+pub use decoder::*;
+
+#[cfg(feature = "client")]
+mod decoder {
+    use super::*;
+    use anchor_lang::Discriminator;
+
+    const PROGRAM_LOG: &str = "Program log: ";
+
+    /// An event emitted by the reward pool program
+    #[derive(Debug, Clone, PartialEq)]
+    pub enum PoolEvent {
+        PoolInitialized(PoolInitialized),
+        Staked(Staked),
+        Unstaked(Unstaked),
+        EmergencyUnstaked(EmergencyUnstaked),
+        UnbondedWithdrawn(UnbondedWithdrawn),
+        UnbondingCancelled(UnbondingCancelled),
+        StakeLocked(StakeLocked),
+        LockReleased(LockReleased),
+        LockupConfigured(LockupConfigured),
+        UnbondingPeriodSet(UnbondingPeriodSet),
+        StakeCapsSet(StakeCapsSet),
+        PenaltyTreasurySet(PenaltyTreasurySet),
+        RewardAdded(RewardAdded),
+        RewardDurationSet(RewardDurationSet),
+        Funded(Funded),
+        ToppedUp(ToppedUp),
+        FundingScheduled(FundingScheduled),
+        FundedV3(FundedV3),
+        Claimed(Claimed),
+        ClaimedV3(ClaimedV3),
+        Compounded(Compounded),
+        PenaltyRewardsClaimed(PenaltyRewardsClaimed),
+        ClaimedFor(ClaimedFor),
+        RewardRecipientSet(RewardRecipientSet),
+        PoolPaused(PoolPaused),
+        PoolUnpaused(PoolUnpaused),
+        FunderAuthorized(FunderAuthorized),
+        FunderDeauthorized(FunderDeauthorized),
+        AuthorityProposed(AuthorityProposed),
+        AuthorityTransferCancelled(AuthorityTransferCancelled),
+        AuthorityTransferred(AuthorityTransferred),
+        DonatedStakeSwept(DonatedStakeSwept),
+        UnallocatedRewardsSwept(UnallocatedRewardsSwept),
+        UserClosed(UserClosed),
+        PoolClosed(PoolClosed),
+        ConfigUpdated(ConfigUpdated),
+        GlobalFreezeSet(GlobalFreezeSet),
+        ConfigAdminProposed(ConfigAdminProposed),
+        ConfigAdminTransferCancelled(ConfigAdminTransferCancelled),
+        ConfigAdminTransferred(ConfigAdminTransferred),
+    }
+
+    /// Decodes an event from the data `emit!` logs, the event discriminator followed by
+    /// the borsh serialized event
+    pub fn decode_event_data(data: &[u8]) -> Option<PoolEvent> {
+        if data.len() < 8 {
+            return None;
+        }
+        let (discriminator, mut event) = data.split_at(8);
+
+        macro_rules! decode {
+            ($($name:ident),*) => {
+                $(
+                    if discriminator == $name::discriminator() {
+                        return $name::deserialize(&mut event).ok().map(PoolEvent::$name);
+                    }
+                )*
+            };
+        }
+        decode!(
+            PoolInitialized,
+            Staked,
+            Unstaked,
+            EmergencyUnstaked,
+            UnbondedWithdrawn,
+            UnbondingCancelled,
+            StakeLocked,
+            LockReleased,
+            LockupConfigured,
+            UnbondingPeriodSet,
+            StakeCapsSet,
+            PenaltyTreasurySet,
+            RewardAdded,
+            RewardDurationSet,
+            Funded,
+            ToppedUp,
+            FundingScheduled,
+            FundedV3,
+            Claimed,
+            ClaimedV3,
+            Compounded,
+            PenaltyRewardsClaimed,
+            ClaimedFor,
+            RewardRecipientSet,
+            PoolPaused,
+            PoolUnpaused,
+            FunderAuthorized,
+            FunderDeauthorized,
+            AuthorityProposed,
+            AuthorityTransferCancelled,
+            AuthorityTransferred,
+            DonatedStakeSwept,
+            UnallocatedRewardsSwept,
+            UserClosed,
+            PoolClosed,
+            ConfigUpdated,
+            GlobalFreezeSet,
+            ConfigAdminProposed,
+            ConfigAdminTransferCancelled,
+            ConfigAdminTransferred
+        );
+        None
+    }
+
+    /// Decodes the events in a transaction's log messages, in the order they were emitted.
+    /// Only logs of the reward pool program itself are decoded, so an event logged by another
+    /// program it invokes, or that invokes it, is never mistaken for one of its own.
+    pub fn decode_events<S: AsRef<str>>(logs: &[S]) -> Vec<PoolEvent> {
+        let invoke = format!("Program {} invoke [", ID);
+        //whether each program of the invocation stack is this one, innermost last
+        let mut invoked: Vec<bool> = vec![];
+        let mut events = vec![];
+
+        for log in logs.iter().map(AsRef::as_ref) {
+            if let Some(message) = log.strip_prefix(PROGRAM_LOG) {
+                if invoked.last() == Some(&true) {
+                    let event = base64::decode(message)
+                        .ok()
+                        .and_then(|data| decode_event_data(&data));
+                    events.extend(event);
+                }
+            } else if log.starts_with("Program ") && log.contains(" invoke [") {
+                invoked.push(log.starts_with(&invoke));
+            } else if log.starts_with("Program ")
+                && (log.ends_with(" success") || log.contains(" failed: "))
+            {
+                invoked.pop();
+            }
+        }
+
+        events
+    }
+}
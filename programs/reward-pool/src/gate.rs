@@ -0,0 +1,133 @@
+use crate::*;
+use anchor_lang::solana_program::keccak;
+use borsh::{BorshDeserialize, BorshSerialize};
+
+/// The Metaplex token metadata program, which holds the collection an NFT belongs to
+pub mod token_metadata {
+    anchor_lang::declare_id!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+}
+
+#[derive(Clone, Copy, BorshSerialize, BorshDeserialize, PartialEq)]
+#[repr(u8)]
+#[derive(Debug)]
+/// Who may open a user account on a pool, chosen when the pool is initialized.
+/// `Pool.gate_key` holds the key the gate checks against.
+pub enum PoolGate {
+    /// anyone may join
+    Open = 0,
+    /// the gate key must co-sign `create_user`
+    Authority = 1,
+    /// the owner must prove membership of the allow-list whose Merkle root is the gate
+    /// key, a tree of `keccak(owner)` leaves hashed in sorted pairs
+    MerkleRoot = 2,
+    /// the owner must hold a token of the gate key mint
+    Mint = 3,
+    /// the owner must hold an NFT of the verified collection whose mint is the gate key
+    Collection = 4,
+}
+
+impl Default for PoolGate {
+    /// zeroed account data is an open pool
+    fn default() -> Self {
+        PoolGate::Open
+    }
+}
+
+impl Pool {
+    /// Sets the gate of a new pool, failing if it has no key to check against
+    pub fn set_gate(&mut self, gate: PoolGate, gate_key: Pubkey) -> Result<()> {
+        if gate != PoolGate::Open && gate_key == Pubkey::default() {
+            return Err(ErrorCode::InvalidGate.into());
+        }
+        self.gate = gate;
+        self.gate_key = if gate == PoolGate::Open {
+            Pubkey::default()
+        } else {
+            gate_key
+        };
+
+        Ok(())
+    }
+
+    /// Checks `owner` may join the pool. A co-signing gate authority, the owner's token
+    /// account or, for a collection, the token account then the NFT's metadata are
+    /// expected first in `remaining_accounts`.
+    pub fn check_gate(
+        &self,
+        owner: &Pubkey,
+        proof: &[[u8; 32]],
+        remaining_accounts: &[AccountInfo],
+    ) -> Result<()> {
+        let allowed = match self.gate {
+            PoolGate::Open => true,
+            PoolGate::Authority => remaining_accounts
+                .iter()
+                .any(|a| a.is_signer && *a.key == self.gate_key),
+            PoolGate::MerkleRoot => {
+                let leaf = keccak::hashv(&[owner.as_ref()]).0;
+                let root = proof.iter().fold(leaf, |node, sibling| {
+                    if node <= *sibling {
+                        keccak::hashv(&[&node, sibling]).0
+                    } else {
+                        keccak::hashv(&[sibling, &node]).0
+                    }
+                });
+                root == self.gate_key.to_bytes()
+            }
+            PoolGate::Mint => match remaining_accounts.first() {
+                Some(token_account) => held_mint(token_account, owner)? == Some(self.gate_key),
+                None => false,
+            },
+            PoolGate::Collection => match remaining_accounts {
+                [token_account, metadata, ..] => match held_mint(token_account, owner)? {
+                    Some(mint) => verified_collection(metadata, &mint)? == Some(self.gate_key),
+                    None => false,
+                },
+                _ => false,
+            },
+        };
+        if !allowed {
+            return Err(ErrorCode::NotAllowedToJoin.into());
+        }
+
+        Ok(())
+    }
+}
+
+/// The mint of a token `owner` holds in `token_account`, if any
+fn held_mint(token_account: &AccountInfo, owner: &Pubkey) -> Result<Option<Pubkey>> {
+    let token_account: Account<TokenAccount> = Account::try_from(token_account)?;
+    Ok((token_account.owner == *owner && token_account.amount > 0).then(|| token_account.mint))
+}
+
+/// The verified collection of `mint`, read from its Metaplex metadata account
+fn verified_collection(metadata: &AccountInfo, mint: &Pubkey) -> Result<Option<Pubkey>> {
+    let (address, _) = Pubkey::find_program_address(
+        &[b"metadata", token_metadata::ID.as_ref(), mint.as_ref()],
+        &token_metadata::ID,
+    );
+    if *metadata.key != address || *metadata.owner != token_metadata::ID {
+        return Ok(None);
+    }
+
+    //key, update authority and mint, then the variable length fields up to the collection
+    let data = metadata.try_borrow_data()?;
+    let mut rest: &[u8] = data.get(65..).unwrap_or_default();
+    let read = |rest: &mut &[u8]| -> std::io::Result<Option<Pubkey>> {
+        for _ in 0..3 {
+            String::deserialize(rest)?;
+        }
+        u16::deserialize(rest)?;
+        Option::<Vec<([u8; 32], bool, u8)>>::deserialize(rest)?;
+        bool::deserialize(rest)?;
+        bool::deserialize(rest)?;
+        Option::<u8>::deserialize(rest)?;
+        Option::<u8>::deserialize(rest)?;
+        let collection = Option::<(bool, Pubkey)>::deserialize(rest)?;
+        Ok(collection
+            .filter(|(verified, _)| *verified)
+            .map(|(_, key)| key))
+    };
+
+    Ok(read(&mut rest).unwrap_or(None))
+}
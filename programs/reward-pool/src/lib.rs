@@ -3,94 +3,599 @@ use std::convert::TryInto;
 use std::fmt::Debug;
 
 use anchor_lang::prelude::*;
-use anchor_lang::solana_program::{clock, program_option::COption, sysvar};
+use anchor_lang::solana_program::{clock, program_option::COption};
+use anchor_lang::Discriminator;
+use anchor_spl::associated_token::get_associated_token_address;
 use anchor_spl::token::{self, Mint, Token, TokenAccount};
+use spl_math::uint::U192;
 
 use crate::calculator::*;
-use crate::constants::*;
+use crate::events::*;
 use crate::version::*;
 
+#[cfg(feature = "client")]
+pub mod calculator;
+#[cfg(not(feature = "client"))]
 mod calculator;
+mod config;
+pub mod events;
+mod gate;
 mod version;
 
+pub use crate::config::{program_data_address, CONFIG_SEED};
+pub use crate::gate::{token_metadata, PoolGate};
+pub use crate::version::PoolVersion;
+
 #[cfg(not(feature = "test-id"))]
 declare_id!("SRwd1XTVscKXu9nMU8f6MfEf9cAzGPmbMe69CFmHvAH");
 #[cfg(feature = "test-id")]
 declare_id!("Dev9TukuTHwNmYm2NUcXQ9iuNL8UrP3TnZCj1Y7UjV18");
 
-#[cfg(not(feature = "local-testing"))]
-mod constants {
-    pub const X_STEP_TOKEN_MINT_PUBKEY: &str = "xStpgUCss9piqeFUk2iLVcvJEGhAdJxJQuwLkXP555G";
-    pub const X_STEP_DEPOSIT_REQUIREMENT: u64 = 10_000_000_000_000;
-    pub const MIN_DURATION: u64 = 86400;
-}
+const PRECISION: u128 = u64::MAX as u128;
+const BASIS_POINTS: u16 = 10_000;
 
-#[cfg(feature = "local-testing")]
-mod constants {
-    pub const X_STEP_TOKEN_MINT_PUBKEY: &str = "tEsTL8G8drugWztoCKrPpEAXV21qEajfHg4q45KYs6s";
-    pub const X_STEP_DEPOSIT_REQUIREMENT: u64 = 10_000_000_000_000;
-    pub const MIN_DURATION: u64 = 1;
-}
+/// The largest lock boost a pool can configure, 4x on top of the locked stake. A user's
+/// boost and the pool's total boost are u64s, so locks that would overflow them are
+/// refused; weights are summed with the stake in a u128, which always fits.
+pub const MAX_LOCK_BOOST_BPS: u16 = 4 * BASIS_POINTS;
 
-const PRECISION: u128 = u64::MAX as u128;
+/// The most reward streams a multi reward pool can hold
+pub const MAX_REWARDS: usize = 8;
 
 /// Updates the pool with the total reward per token that is due stakers
 /// Using the calculator specific to that pool version which uses the reward
-/// rate on the pool.
+/// rate on the pool and the total staked tracked on the pool.
 /// Optionally updates user with pending rewards and "complete" rewards.
 /// A new user to the pool has their completed set to current amount due
 /// such that they start earning from that point. Hence "complete" is a
 /// bit misleading - it does not mean actually earned.
+/// A user's expired lock is released on the way.
 pub fn update_rewards(
     pool: &mut Box<Account<Pool>>,
     user: Option<&mut Box<Account<User>>>,
-    total_staked: u64,
 ) -> Result<()> {
-    let last_time_reward_applicable = last_time_reward_applicable(pool.reward_duration_end);
+    let current_time = current_timestamp()?;
+    let user = match user {
+        Some(user) => user,
+        None => return accrue_rewards(pool, None, current_time),
+    };
+
+    let lock_expired = user.lock_expired(current_time);
+    let boost_weight = user.boost_weight;
+    accrue_rewards(pool, Some(user), current_time)?;
+
+    if lock_expired {
+        emit!(LockReleased {
+            pool: pool.key(),
+            user: user.key(),
+            owner: user.owner,
+            boost_weight,
+            total_boost_weight: pool.total_boost_weight,
+        });
+    }
+
+    Ok(())
+}
+
+/// The cluster's unix time, which is never before the epoch
+fn current_timestamp() -> Result<u64> {
+    Ok(clock::Clock::get()?
+        .unix_timestamp
+        .try_into()
+        .map_err(|_| ErrorCode::ClockWentBackwards)?)
+}
+
+/// Takes the early exit penalty from stake leaving the pool, already taken off the user's
+/// and pool's stake, then starts the rest unbonding or pays it out. When the pool has a
+/// penalty treasury it is the first remaining account. Returns the penalty.
+fn exit_stake<'info>(
+    accounts: &mut Unstake<'info>,
+    remaining_accounts: &[AccountInfo<'info>],
+    amount: u64,
+    current_time: u64,
+) -> Result<u64> {
+    let pool = &mut accounts.pool;
+    let user = &mut accounts.user;
+    let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+    let pool_signer = &[&seeds[..]];
+
+    let penalty = pool.early_exit_penalty(user, amount, current_time)?;
+    let amount = amount.checked_sub(penalty).ok_or(ErrorCode::MathOverflow)?;
+    if penalty > 0 {
+        if pool.penalty_treasury == Pubkey::default() {
+            pool.distribute_penalty(penalty)?;
+        } else {
+            let treasury = remaining_accounts
+                .first()
+                .ok_or(ErrorCode::InvalidPenaltyTreasury)?;
+            if treasury.key() != pool.penalty_treasury {
+                return Err(ErrorCode::InvalidPenaltyTreasury.into());
+            }
+
+            let cpi_ctx = CpiContext::new_with_signer(
+                accounts.token_program.to_account_info(),
+                token::Transfer {
+                    from: accounts.staking_vault.to_account_info(),
+                    to: treasury.clone(),
+                    authority: accounts.pool_signer.to_account_info(),
+                },
+                pool_signer,
+            );
+            token::transfer(cpi_ctx, penalty)?;
+        }
+    }
+
+    pay_out_stake(accounts, amount, current_time)?;
+
+    Ok(penalty)
+}
+
+/// Starts stake that has left the pool unbonding, or pays it out to the user when the pool
+/// has no unbonding period.
+fn pay_out_stake(accounts: &mut Unstake, amount: u64, current_time: u64) -> Result<()> {
+    let pool = &mut accounts.pool;
+    let user = &mut accounts.user;
+    let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+    let pool_signer = &[&seeds[..]];
+
+    if pool.unbonding_period > 0 {
+        //unstaking again restarts the unbonding period for all unbonding tokens
+        user.balance_unbonding = user
+            .balance_unbonding
+            .checked_add(amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+        user.unbonding_end_ts = current_time
+            .checked_add(pool.unbonding_period)
+            .ok_or(ErrorCode::MathOverflow)?;
+        pool.total_unbonding = pool
+            .total_unbonding
+            .checked_add(amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        return Ok(());
+    }
+
+    // Transfer tokens from the pool vault to user vault.
+    let cpi_ctx = CpiContext::new_with_signer(
+        accounts.token_program.to_account_info(),
+        token::Transfer {
+            from: accounts.staking_vault.to_account_info(),
+            to: accounts.stake_from_account.to_account_info(),
+            authority: accounts.pool_signer.to_account_info(),
+        },
+        pool_signer,
+    );
+    token::transfer(cpi_ctx, amount)?;
+
+    Ok(())
+}
+
+/// The address of a penalty treasury, checked to be a token account of the pool's staking
+/// mint so that early exit penalties can be paid into it. Without one, the default address
+/// has penalties shared by the stakers.
+fn penalty_treasury_address(pool: &Pool, treasury: Option<&AccountInfo>) -> Result<Pubkey> {
+    let treasury = match treasury {
+        Some(treasury) => treasury,
+        None => return Ok(Pubkey::default()),
+    };
+    let token_account: Account<TokenAccount> =
+        Account::try_from(treasury).map_err(|_| ErrorCode::InvalidPenaltyTreasury)?;
+    if token_account.mint != pool.staking_mint {
+        return Err(ErrorCode::InvalidPenaltyTreasury.into());
+    }
+
+    Ok(treasury.key())
+}
+
+/// The min of current time and reward duration end, such that after the reward
+/// period ends, this always returns the period end time
+fn last_time_reward_applicable(current_time: u64, reward_duration_end: u64) -> u64 {
+    std::cmp::min(current_time, reward_duration_end)
+}
 
-    let calc = get_calculator(pool);
-    let (reward_a, reward_b) =
-        calc.reward_per_token(pool, total_staked, last_time_reward_applicable);
+/// Transfers a funder's reward A and B tokens into the pool's reward vaults
+fn transfer_funding(accounts: &Fund, amount_a: u64, amount_b: u64) -> Result<()> {
+    // Transfer reward A tokens into the A vault.
+    if amount_a > 0 {
+        let cpi_ctx = CpiContext::new(
+            accounts.token_program.to_account_info(),
+            token::Transfer {
+                from: accounts.from_a.to_account_info(),
+                to: accounts.reward_a_vault.to_account_info(),
+                authority: accounts.funder.to_account_info(),
+            },
+        );
+
+        token::transfer(cpi_ctx, amount_a)?;
+    }
+
+    // Transfer reward B tokens into the B vault.
+    if amount_b > 0 {
+        let cpi_ctx = CpiContext::new(
+            accounts.token_program.to_account_info(),
+            token::Transfer {
+                from: accounts.from_b.to_account_info(),
+                to: accounts.reward_b_vault.to_account_info(),
+                authority: accounts.funder.to_account_info(),
+            },
+        );
 
-    pool.reward_a_per_token_stored = reward_a;
-    if pool.reward_a_vault != pool.reward_b_vault {
-        pool.reward_b_per_token_stored = reward_b;
+        token::transfer(cpi_ctx, amount_b)?;
     }
 
-    pool.last_update_time = last_time_reward_applicable;
+    Ok(())
+}
+
+/// Initializes a pool that holds its rewards in `Pool.rewards`
+#[allow(clippy::too_many_arguments)]
+fn initialize_multi_reward_pool(
+    accounts: &mut InitializePoolV3,
+    pool_nonce: u8,
+    reward_duration: u64,
+    version: PoolVersion,
+    gate: PoolGate,
+    gate_key: Pubkey,
+    max_stake_per_user: u64,
+    max_total_stake: u64,
+) -> Result<()> {
+    accounts.config.check_duration(reward_duration)?;
+
+    //xstep lockup
+    let cpi_ctx = CpiContext::new(
+        accounts.token_program.to_account_info(),
+        token::Transfer {
+            from: accounts.x_token_depositor.to_account_info(),
+            to: accounts.x_token_pool_vault.to_account_info(),
+            authority: accounts.x_token_deposit_authority.to_account_info(),
+        },
+    );
+    token::transfer(cpi_ctx, accounts.config.deposit_requirement)?;
+
+    let pool = &mut accounts.pool;
+
+    pool.authority = accounts.authority.key();
+    pool.nonce = pool_nonce;
+    pool.paused = false;
+    pool.x_token_pool_vault = accounts.x_token_pool_vault.key();
+    pool.staking_mint = accounts.staking_mint.key();
+    pool.staking_vault = accounts.staking_vault.key();
+    pool.reward_duration = reward_duration;
+    pool.reward_duration_end = 0;
+    pool.last_update_time = 0;
+    pool.user_stake_count = 0;
+    pool.version = version;
+    pool.total_staked = 0;
+    pool.total_staked_tracked = true;
+    pool.reward_owed_tracked = u8::MAX;
+    pool.rewards = [RewardInfo::default(); MAX_REWARDS];
+    pool.set_gate(gate, gate_key)?;
+    pool.max_stake_per_user = max_stake_per_user;
+    pool.max_total_stake = max_total_stake;
+
+    emit!(PoolInitialized {
+        pool: pool.key(),
+        authority: pool.authority,
+        staking_mint: pool.staking_mint,
+        reward_mints: vec![],
+        reward_duration,
+    });
+
+    Ok(())
+}
+
+/// Empties a vault owned by the pool signer into the refundee, then closes it
+/// sending its rent to the lamports refundee
+fn close_vault<'info>(
+    token_program: &AccountInfo<'info>,
+    vault: &AccountInfo<'info>,
+    vault_amount: u64,
+    refundee: &AccountInfo<'info>,
+    lamports_refundee: &AccountInfo<'info>,
+    pool_signer: &AccountInfo<'info>,
+    signer_seeds: &[&[u8]],
+) -> Result<()> {
+    let ix = spl_token::instruction::transfer(
+        &spl_token::ID,
+        vault.key,
+        refundee.key,
+        pool_signer.key,
+        &[pool_signer.key],
+        vault_amount,
+    )?;
+    solana_program::program::invoke_signed(
+        &ix,
+        &[
+            token_program.clone(),
+            vault.clone(),
+            refundee.clone(),
+            pool_signer.clone(),
+        ],
+        &[signer_seeds],
+    )?;
+    let ix = spl_token::instruction::close_account(
+        &spl_token::ID,
+        vault.key,
+        lamports_refundee.key,
+        pool_signer.key,
+        &[pool_signer.key],
+    )?;
+    solana_program::program::invoke_signed(
+        &ix,
+        &[
+            token_program.clone(),
+            vault.clone(),
+            lamports_refundee.clone(),
+            pool_signer.clone(),
+        ],
+        &[signer_seeds],
+    )?;
 
-    if let Some(u) = user {
-        let (a, b) = calc.user_earned_amount(pool, u);
+    Ok(())
+}
 
-        u.reward_a_per_token_pending = a;
-        u.reward_a_per_token_complete = pool.reward_a_per_token_stored;
+/// Grows an account created by an earlier program version to the current size of
+/// its type, zero filling the new fields. The payer funds any additional rent.
+fn grow_account<'info>(
+    account: &AccountInfo<'info>,
+    discriminator: [u8; 8],
+    new_len: usize,
+    payer: &AccountInfo<'info>,
+    system_program: &AccountInfo<'info>,
+) -> Result<()> {
+    if account.try_borrow_data()?[..8] != discriminator {
+        return Err(ErrorCode::InvalidAccountToMigrate.into());
+    }
+    if account.data_len() >= new_len {
+        return Ok(());
+    }
 
-        u.reward_b_per_token_pending = b;
-        u.reward_b_per_token_complete = pool.reward_b_per_token_stored;
+    let rent_due = Rent::get()?
+        .minimum_balance(new_len)
+        .saturating_sub(account.lamports());
+    if rent_due > 0 {
+        let ix = solana_program::system_instruction::transfer(payer.key, account.key, rent_due);
+        solana_program::program::invoke(
+            &ix,
+            &[payer.clone(), account.clone(), system_program.clone()],
+        )?;
     }
 
+    account.realloc(new_len, true)?;
+
+    Ok(())
+}
+
+/// Opens the user account of `ctx`'s owner once the pool's gate lets them in
+fn create_user_account(ctx: Context<CreateUser>, proof: &[[u8; 32]]) -> Result<()> {
+    open_user(
+        &mut ctx.accounts.pool,
+        &mut ctx.accounts.user,
+        ctx.accounts.owner.key,
+        *ctx.bumps.get("user").unwrap(),
+        proof,
+        ctx.remaining_accounts,
+    )
+}
+
+/// Opens `user` for `owner` once the pool's gate lets them in
+fn open_user(
+    pool: &mut Account<Pool>,
+    user: &mut User,
+    owner: &Pubkey,
+    nonce: u8,
+    proof: &[[u8; 32]],
+    remaining_accounts: &[AccountInfo],
+) -> Result<()> {
+    pool.check_gate(owner, proof, remaining_accounts)?;
+
+    user.pool = pool.key();
+    user.owner = *owner;
+    user.reward_a_per_token_complete = 0;
+    user.reward_b_per_token_complete = 0;
+    user.reward_a_per_token_pending = 0;
+    user.reward_b_per_token_pending = 0;
+    user.balance_staked = 0;
+    user.nonce = nonce;
+
+    pool.user_stake_count = pool
+        .user_stake_count
+        .checked_add(1)
+        .ok_or(ErrorCode::MathOverflow)?;
+
     Ok(())
 }
 
-/// The min of current time and reward duration end, such that after the pool reward
-/// period ends, this always returns the pool end time
-fn last_time_reward_applicable(reward_duration_end: u64) -> u64 {
-    let c = clock::Clock::get().unwrap();
-    std::cmp::min(c.unix_timestamp.try_into().unwrap(), reward_duration_end)
+/// Credits `amount` to the stake of `user`, whose tokens are then transferred into the
+/// staking vault. The stake time is averaged by amount whoever pays, so a deposit only
+/// moves the owner's early exit penalty window in proportion to its size.
+fn credit_stake(
+    pool: &mut Box<Account<Pool>>,
+    user: &mut Box<Account<User>>,
+    staking_vault: &TokenAccount,
+    config: &ProgramConfig,
+    amount: u64,
+) -> Result<()> {
+    if amount == 0 {
+        return Err(ErrorCode::AmountMustBeGreaterThanZero.into());
+    }
+
+    if config.global_freeze {
+        return Err(ErrorCode::GlobalFreeze.into());
+    }
+
+    if pool.paused {
+        return Err(ErrorCode::PoolPaused.into());
+    }
+
+    pool.track_total_staked_if_needed(staking_vault);
+    pool.check_stake_caps(user.balance_staked, amount)?;
+
+    update_rewards(pool, Some(user))?;
+
+    user.record_stake(amount, current_timestamp()?)?;
+    user.balance_staked = user
+        .balance_staked
+        .checked_add(amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+    pool.total_staked = pool
+        .total_staked
+        .checked_add(amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    emit!(Staked {
+        pool: pool.key(),
+        user: user.key(),
+        owner: user.owner,
+        amount,
+        balance_staked: user.balance_staked,
+        total_staked: pool.total_staked,
+        reward_a_per_token_stored: pool.reward_a_per_token_stored,
+        reward_b_per_token_stored: pool.reward_b_per_token_stored,
+    });
+
+    Ok(())
 }
 
 #[program]
 pub mod reward_pool {
     use super::*;
 
-    /// Initializes a new pool
+    /// Creates the program config, signed by the program's upgrade authority. Pools take
+    /// their deposit and shortest reward period from it.
+    pub fn initialize_config(
+        ctx: Context<InitializeConfig>,
+        admin: Pubkey,
+        deposit_mint: Pubkey,
+        deposit_requirement: u64,
+        min_duration: u64,
+    ) -> Result<()> {
+        config::check_upgrade_authority(
+            &ctx.accounts.program_data,
+            ctx.accounts.upgrade_authority.key,
+        )?;
+
+        let config = &mut ctx.accounts.config;
+        config.admin = admin;
+        config.nonce = *ctx.bumps.get("config").unwrap();
+        config.set(deposit_mint, deposit_requirement, min_duration)?;
+
+        emit!(ConfigUpdated {
+            admin,
+            deposit_mint,
+            deposit_requirement,
+            min_duration,
+        });
+
+        Ok(())
+    }
+
+    /// Updates the program config. Pools already initialized keep the deposit they took,
+    /// the new one is taken as they unpause.
+    pub fn update_config(
+        ctx: Context<UpdateConfig>,
+        deposit_mint: Pubkey,
+        deposit_requirement: u64,
+        min_duration: u64,
+    ) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.set(deposit_mint, deposit_requirement, min_duration)?;
+
+        emit!(ConfigUpdated {
+            admin: config.admin,
+            deposit_mint,
+            deposit_requirement,
+            min_duration,
+        });
+
+        Ok(())
+    }
+
+    /// Freezes or unfreezes every pool. While frozen nothing can be staked, funded or
+    /// claimed, but users can still unstake.
+    pub fn set_global_freeze(ctx: Context<UpdateConfig>, global_freeze: bool) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        config.global_freeze = global_freeze;
+
+        emit!(GlobalFreezeSet {
+            admin: config.admin,
+            global_freeze,
+        });
+
+        Ok(())
+    }
+
+    /// Proposes a new admin for the program config, which takes over once it accepts.
+    /// Proposing again replaces the pending admin.
+    pub fn propose_config_admin(ctx: Context<UpdateConfig>, new_admin: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        if new_admin == config.admin || new_admin == Pubkey::default() {
+            return Err(ErrorCode::InvalidNewAuthority.into());
+        }
+        config.pending_admin = new_admin;
+
+        emit!(ConfigAdminProposed {
+            admin: config.admin,
+            pending_admin: new_admin,
+        });
+
+        Ok(())
+    }
+
+    /// Cancels a pending config admin transfer
+    pub fn cancel_config_admin_transfer(ctx: Context<UpdateConfig>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        if config.pending_admin == Pubkey::default() {
+            return Err(ErrorCode::NoPendingAuthority.into());
+        }
+        let pending_admin = config.pending_admin;
+        config.pending_admin = Pubkey::default();
+
+        emit!(ConfigAdminTransferCancelled { pending_admin });
+
+        Ok(())
+    }
+
+    /// The pending admin takes over the program config.
+    pub fn accept_config_admin(ctx: Context<AcceptConfigAdmin>) -> Result<()> {
+        let config = &mut ctx.accounts.config;
+        let new_admin = ctx.accounts.pending_admin.key();
+        if config.pending_admin != new_admin {
+            return Err(ErrorCode::NotPendingAuthority.into());
+        }
+        let previous_admin = config.admin;
+        config.admin = new_admin;
+        config.pending_admin = Pubkey::default();
+
+        emit!(ConfigAdminTransferred {
+            previous_admin,
+            admin: new_admin,
+        });
+
+        Ok(())
+    }
+
+    /// Initializes a new pool. Unstaking within `early_exit_penalty_window` of staking
+    /// is penalized by up to `early_exit_penalty_bps`, decaying linearly over the window.
+    /// Penalties go to a treasury token account of the staking mint if one is passed as the
+    /// only remaining account, otherwise they are shared by the remaining stakers. Joining the pool is restricted by `gate`, checked
+    /// against `gate_key`. Stake is capped per user and in total where the caps are non zero.
+    #[allow(clippy::too_many_arguments)]
     pub fn initialize_pool(
         ctx: Context<InitializePool>,
         pool_nonce: u8,
         reward_duration: u64,
+        early_exit_penalty_bps: u16,
+        early_exit_penalty_window: u64,
+        gate: PoolGate,
+        gate_key: Pubkey,
+        max_stake_per_user: u64,
+        max_total_stake: u64,
     ) -> Result<()> {
-        if reward_duration < MIN_DURATION {
-            return Err(ErrorCode::DurationTooShort.into());
+        ctx.accounts.config.check_duration(reward_duration)?;
+        if early_exit_penalty_bps > BASIS_POINTS
+            || (early_exit_penalty_bps > 0 && early_exit_penalty_window == 0)
+        {
+            return Err(ErrorCode::InvalidEarlyExitPenalty.into());
         }
 
         //xstep lockup
@@ -102,7 +607,7 @@ pub mod reward_pool {
                 authority: ctx.accounts.x_token_deposit_authority.to_account_info(),
             },
         );
-        token::transfer(cpi_ctx, constants::X_STEP_DEPOSIT_REQUIREMENT)?;
+        token::transfer(cpi_ctx, ctx.accounts.config.deposit_requirement)?;
 
         let pool = &mut ctx.accounts.pool;
 
@@ -125,28 +630,144 @@ pub mod reward_pool {
         pool.reward_b_per_token_stored = 0;
         pool.user_stake_count = 0;
         pool.version = PoolVersion::V2;
+        pool.total_staked = 0;
+        pool.total_staked_tracked = true;
+        pool.reward_owed_tracked = u8::MAX;
+        pool.early_exit_penalty_bps = early_exit_penalty_bps;
+        pool.early_exit_penalty_window = early_exit_penalty_window;
+        pool.penalty_treasury = penalty_treasury_address(pool, ctx.remaining_accounts.first())?;
+        pool.set_gate(gate, gate_key)?;
+        pool.max_stake_per_user = max_stake_per_user;
+        pool.max_total_stake = max_total_stake;
+
+        emit!(PoolInitialized {
+            pool: pool.key(),
+            authority: pool.authority,
+            staking_mint: pool.staking_mint,
+            reward_mints: vec![pool.reward_a_mint, pool.reward_b_mint],
+            reward_duration,
+        });
 
         Ok(())
     }
 
-    /// Initialize a user staking account
-    pub fn create_user(ctx: Context<CreateUser>, _nonce: u8) -> Result<()> {
-        let user = &mut ctx.accounts.user;
-        user.pool = *ctx.accounts.pool.to_account_info().key;
-        user.owner = *ctx.accounts.owner.key;
-        user.reward_a_per_token_complete = 0;
-        user.reward_b_per_token_complete = 0;
-        user.reward_a_per_token_pending = 0;
-        user.reward_b_per_token_pending = 0;
-        user.balance_staked = 0;
-        user.nonce = *ctx.bumps.get("user").unwrap();
+    /// Initializes a new pool that emits up to `MAX_REWARDS` reward tokens.
+    /// Rewards are added to the pool with `add_reward`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn initialize_pool_v3(
+        ctx: Context<InitializePoolV3>,
+        pool_nonce: u8,
+        reward_duration: u64,
+        gate: PoolGate,
+        gate_key: Pubkey,
+        max_stake_per_user: u64,
+        max_total_stake: u64,
+    ) -> Result<()> {
+        initialize_multi_reward_pool(
+            ctx.accounts,
+            pool_nonce,
+            reward_duration,
+            PoolVersion::V3,
+            gate,
+            gate_key,
+            max_stake_per_user,
+            max_total_stake,
+        )
+    }
+
+    /// Initializes a new pool that emits up to `MAX_REWARDS` reward tokens, each on
+    /// its own schedule. `reward_duration` is the initial duration of added rewards.
+    #[allow(clippy::too_many_arguments)]
+    pub fn initialize_pool_v4(
+        ctx: Context<InitializePoolV3>,
+        pool_nonce: u8,
+        reward_duration: u64,
+        gate: PoolGate,
+        gate_key: Pubkey,
+        max_stake_per_user: u64,
+        max_total_stake: u64,
+    ) -> Result<()> {
+        initialize_multi_reward_pool(
+            ctx.accounts,
+            pool_nonce,
+            reward_duration,
+            PoolVersion::V4,
+            gate,
+            gate_key,
+            max_stake_per_user,
+            max_total_stake,
+        )
+    }
+
+    /// Adds a reward token to a multi reward pool
+    pub fn add_reward(ctx: Context<AddReward>) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        let reward_mint = ctx.accounts.reward_mint.key();
+
+        if pool.reward_infos().iter().any(|r| r.mint == reward_mint) {
+            return Err(ErrorCode::RewardAlreadyAdded.into());
+        }
+        let idx = pool.reward_infos().len();
+        if idx >= MAX_REWARDS {
+            return Err(ErrorCode::MaxRewards.into());
+        }
+
+        pool.rewards[idx] = RewardInfo {
+            mint: reward_mint,
+            vault: ctx.accounts.reward_vault.key(),
+            reward_duration: pool.reward_duration,
+            ..RewardInfo::default()
+        };
+
+        emit!(RewardAdded {
+            pool: pool.key(),
+            reward_index: idx as u8,
+            reward_mint,
+            reward_vault: pool.rewards[idx].vault,
+            reward_duration: pool.reward_duration,
+        });
+
+        Ok(())
+    }
+
+    /// Sets the period a reward of a V4 pool is linearly distributed over.
+    /// Takes effect from the next funding of that reward.
+    pub fn set_reward_duration(
+        ctx: Context<SetRewardDuration>,
+        reward_index: u8,
+        reward_duration: u64,
+    ) -> Result<()> {
+        ctx.accounts.config.check_duration(reward_duration)?;
 
         let pool = &mut ctx.accounts.pool;
-        pool.user_stake_count = pool.user_stake_count.checked_add(1).unwrap();
+        let idx = reward_index as usize;
+        if idx >= pool.reward_infos().len() {
+            return Err(ErrorCode::InvalidRewardIndex.into());
+        }
+        pool.rewards[idx].reward_duration = reward_duration;
+
+        emit!(RewardDurationSet {
+            pool: pool.key(),
+            reward_index,
+            reward_duration,
+        });
 
         Ok(())
     }
 
+    /// Initialize a user staking account. On a gated pool the gate authority co-signs,
+    /// or the owner's token account and, for a collection, the NFT's metadata account
+    /// follow as remaining accounts.
+    pub fn create_user(ctx: Context<CreateUser>, _nonce: u8) -> Result<()> {
+        create_user_account(ctx, &[])
+    }
+
+    /// Initialize a user staking account on a pool gated by a Merkle root, proving the
+    /// owner is on its allow-list with the sibling hashes from leaf to root.
+    pub fn create_user_with_proof(ctx: Context<CreateUser>, proof: Vec<[u8; 32]>) -> Result<()> {
+        create_user_account(ctx, &proof)
+    }
+
     /// Pauses the pool and refunds the xSTEP deposit.
     pub fn pause(ctx: Context<Pause>) -> Result<()> {
         let pool = &mut ctx.accounts.pool;
@@ -180,6 +801,8 @@ pub mod reward_pool {
 
         pool.x_token_pool_vault = Pubkey::default();
 
+        emit!(PoolPaused { pool: pool.key() });
+
         Ok(())
     }
 
@@ -201,43 +824,73 @@ pub mod reward_pool {
                 authority: ctx.accounts.x_token_deposit_authority.to_account_info(),
             },
         );
-        token::transfer(cpi_ctx, X_STEP_DEPOSIT_REQUIREMENT)?;
+        token::transfer(cpi_ctx, ctx.accounts.config.deposit_requirement)?;
+
+        emit!(PoolUnpaused {
+            pool: ctx.accounts.pool.key(),
+        });
 
         Ok(())
     }
 
     /// A user stakes tokens in the pool.
     pub fn stake(ctx: Context<Stake>, amount: u64) -> Result<()> {
-        if amount == 0 {
-            return Err(ErrorCode::AmountMustBeGreaterThanZero.into());
-        }
-
-        let pool = &mut ctx.accounts.pool;
+        credit_stake(
+            &mut ctx.accounts.pool,
+            &mut ctx.accounts.user,
+            &ctx.accounts.staking_vault,
+            &ctx.accounts.config,
+            amount,
+        )?;
 
-        if pool.paused {
-            return Err(ErrorCode::PoolPaused.into());
+        // Transfer tokens into the stake vault.
+        {
+            let cpi_ctx = CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                token::Transfer {
+                    from: ctx.accounts.stake_from_account.to_account_info(),
+                    to: ctx.accounts.staking_vault.to_account_info(),
+                    authority: ctx.accounts.owner.to_account_info(), //todo use user account as signer
+                },
+            );
+            token::transfer(cpi_ctx, amount)?;
         }
 
-        let total_staked = ctx.accounts.staking_vault.amount;
+        Ok(())
+    }
 
-        let user_opt = Some(&mut ctx.accounts.user);
-        update_rewards(pool, user_opt, total_staked).unwrap();
+    /// A payer stakes their tokens into `owner`'s position, such as a vault aggregator or
+    /// an airdrop, opening the owner's user account if they have none. Opening it passes
+    /// the pool's gate for the owner as `create_user_with_proof` does. Only the owner may
+    /// unstake, and the deposit is averaged into the owner's early exit penalty window.
+    pub fn stake_for(ctx: Context<StakeFor>, amount: u64, proof: Vec<[u8; 32]>) -> Result<()> {
+        if ctx.accounts.user.owner == Pubkey::default() {
+            open_user(
+                &mut ctx.accounts.pool,
+                &mut ctx.accounts.user,
+                ctx.accounts.owner.key,
+                *ctx.bumps.get("user").unwrap(),
+                &proof,
+                ctx.remaining_accounts,
+            )?;
+        }
 
-        ctx.accounts.user.balance_staked = ctx
-            .accounts
-            .user
-            .balance_staked
-            .checked_add(amount)
-            .unwrap();
+        credit_stake(
+            &mut ctx.accounts.pool,
+            &mut ctx.accounts.user,
+            &ctx.accounts.staking_vault,
+            &ctx.accounts.config,
+            amount,
+        )?;
 
-        // Transfer tokens into the stake vault.
+        // Transfer the payer's tokens into the stake vault.
         {
             let cpi_ctx = CpiContext::new(
                 ctx.accounts.token_program.to_account_info(),
                 token::Transfer {
                     from: ctx.accounts.stake_from_account.to_account_info(),
                     to: ctx.accounts.staking_vault.to_account_info(),
-                    authority: ctx.accounts.owner.to_account_info(), //todo use user account as signer
+                    authority: ctx.accounts.payer.to_account_info(),
                 },
             );
             token::transfer(cpi_ctx, amount)?;
@@ -246,27 +899,136 @@ pub mod reward_pool {
         Ok(())
     }
 
-    /// A user unstakes tokens in the pool.
-    pub fn unstake(ctx: Context<Stake>, spt_amount: u64) -> Result<()> {
+    /// A user unstakes tokens in the pool. If the pool has an unbonding period the tokens
+    /// stop earning and are held until withdrawn with `withdraw_unbonded` after it passes.
+    /// Early exit penalties are taken from the tokens unstaked; when the pool has a penalty
+    /// treasury it is passed as the only remaining account.
+    pub fn unstake<'info>(
+        ctx: Context<'_, '_, '_, 'info, Unstake<'info>>,
+        spt_amount: u64,
+    ) -> Result<()> {
         if spt_amount == 0 {
             return Err(ErrorCode::AmountMustBeGreaterThanZero.into());
         }
 
         let pool = &mut ctx.accounts.pool;
-        let total_staked = ctx.accounts.staking_vault.amount;
 
         if ctx.accounts.user.balance_staked < spt_amount {
             return Err(ErrorCode::InsufficientFundUnstake.into());
         }
 
+        pool.track_total_staked_if_needed(&ctx.accounts.staking_vault);
+
         let user_opt = Some(&mut ctx.accounts.user);
-        update_rewards(pool, user_opt, total_staked).unwrap();
-        ctx.accounts.user.balance_staked = ctx
-            .accounts
-            .user
+        update_rewards(pool, user_opt)?;
+
+        //an expired lock was released with the update
+        let current_time: u64 = current_timestamp()?;
+        let user = &mut ctx.accounts.user;
+        if user
+            .balance_staked
+            .checked_sub(user.balance_locked)
+            .ok_or(ErrorCode::MathOverflow)?
+            < spt_amount
+        {
+            return Err(ErrorCode::StakeLocked.into());
+        }
+
+        user.balance_staked = user
             .balance_staked
             .checked_sub(spt_amount)
-            .unwrap();
+            .ok_or(ErrorCode::MathOverflow)?;
+        pool.total_staked = pool
+            .total_staked
+            .checked_sub(spt_amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        let penalty = exit_stake(
+            ctx.accounts,
+            ctx.remaining_accounts,
+            spt_amount,
+            current_time,
+        )?;
+        let pool = &ctx.accounts.pool;
+        let user = &ctx.accounts.user;
+        emit!(Unstaked {
+            pool: pool.key(),
+            user: user.key(),
+            owner: user.owner,
+            amount: spt_amount,
+            penalty,
+            unbonding: pool.unbonding_period > 0,
+            balance_staked: user.balance_staked,
+            total_staked: pool.total_staked,
+            reward_a_per_token_stored: pool.reward_a_per_token_stored,
+            reward_b_per_token_stored: pool.reward_b_per_token_stored,
+        });
+
+        Ok(())
+    }
+
+    /// A user withdraws all their stake without accruing rewards, so principal can be
+    /// recovered even if the reward math fails. Rewards earned and any lock are forfeited.
+    /// The early exit penalty is left in the staking vault for `sweep_donated_stake`,
+    /// neither shared nor sent to the penalty treasury, so that no step beyond returning
+    /// the stake can fail; the unbonding period applies as for `unstake`.
+    pub fn emergency_unstake(ctx: Context<Unstake>) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        let user = &mut ctx.accounts.user;
+
+        let amount = user.balance_staked;
+        if amount == 0 {
+            return Err(ErrorCode::AmountMustBeGreaterThanZero.into());
+        }
+
+        let current_time: u64 = current_timestamp()?;
+        pool.track_total_staked_if_needed(&ctx.accounts.staking_vault);
+
+        user.forfeit_rewards(pool);
+        user.forfeit_lock(pool);
+        user.balance_staked = 0;
+        pool.total_staked = pool.total_staked.saturating_sub(amount);
+        let penalty = pool
+            .early_exit_penalty(user, amount, current_time)
+            .unwrap_or(0);
+
+        pay_out_stake(ctx.accounts, amount.saturating_sub(penalty), current_time)?;
+        let pool = &ctx.accounts.pool;
+        let user = &ctx.accounts.user;
+        emit!(EmergencyUnstaked {
+            pool: pool.key(),
+            user: user.key(),
+            owner: user.owner,
+            amount,
+            penalty,
+            unbonding: pool.unbonding_period > 0,
+            total_staked: pool.total_staked,
+        });
+
+        Ok(())
+    }
+
+    /// A user withdraws their unbonding tokens once the unbonding period has passed.
+    pub fn withdraw_unbonded(ctx: Context<Unstake>) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        let user = &mut ctx.accounts.user;
+
+        let amount = user.balance_unbonding;
+        if amount == 0 {
+            return Err(ErrorCode::AmountMustBeGreaterThanZero.into());
+        }
+
+        let current_time: u64 = current_timestamp()?;
+        if user.unbonding_end_ts > current_time {
+            return Err(ErrorCode::UnbondingNotComplete.into());
+        }
+
+        user.balance_unbonding = 0;
+        user.unbonding_end_ts = 0;
+        pool.total_unbonding = pool
+            .total_unbonding
+            .checked_sub(amount)
+            .ok_or(ErrorCode::MathOverflow)?;
 
         // Transfer tokens from the pool vault to user vault.
         {
@@ -282,34 +1044,331 @@ pub mod reward_pool {
                 },
                 pool_signer,
             );
-            token::transfer(cpi_ctx, spt_amount)?;
+            token::transfer(cpi_ctx, amount)?;
         }
 
+        emit!(UnbondedWithdrawn {
+            pool: pool.key(),
+            user: user.key(),
+            owner: ctx.accounts.owner.key(),
+            amount,
+        });
+
         Ok(())
     }
 
-    /// Authorize additional funders for the pool
-    pub fn authorize_funder(ctx: Context<FunderChange>, funder_to_add: Pubkey) -> Result<()> {
-        if funder_to_add == ctx.accounts.pool.authority {
-            return Err(ErrorCode::FunderAlreadyAuthorized.into());
-        }
-        let funders = &mut ctx.accounts.pool.funders;
-        if funders.iter().any(|x| *x == funder_to_add) {
-            return Err(ErrorCode::FunderAlreadyAuthorized.into());
-        }
-        let default_pubkey = Pubkey::default();
-        if let Some(idx) = funders.iter().position(|x| *x == default_pubkey) {
-            funders[idx] = funder_to_add;
-        } else {
-            return Err(ErrorCode::MaxFunders.into());
+    /// A user puts their unbonding tokens back into stake.
+    pub fn cancel_unbonding(ctx: Context<CancelUnbonding>) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+
+        if pool.paused {
+            return Err(ErrorCode::PoolPaused.into());
         }
-        Ok(())
-    }
 
-    /// Deauthorize funders for the pool
-    pub fn deauthorize_funder(ctx: Context<FunderChange>, funder_to_remove: Pubkey) -> Result<()> {
-        if funder_to_remove == ctx.accounts.pool.authority {
-            return Err(ErrorCode::CannotDeauthorizePoolAuthority.into());
+        let amount = ctx.accounts.user.balance_unbonding;
+        if amount == 0 {
+            return Err(ErrorCode::AmountMustBeGreaterThanZero.into());
+        }
+        pool.check_stake_caps(ctx.accounts.user.balance_staked, amount)?;
+
+        let user_opt = Some(&mut ctx.accounts.user);
+        update_rewards(pool, user_opt)?;
+
+        let user = &mut ctx.accounts.user;
+        user.balance_staked = user
+            .balance_staked
+            .checked_add(amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+        user.balance_unbonding = 0;
+        user.unbonding_end_ts = 0;
+        pool.total_staked = pool
+            .total_staked
+            .checked_add(amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+        pool.total_unbonding = pool
+            .total_unbonding
+            .checked_sub(amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        emit!(UnbondingCancelled {
+            pool: pool.key(),
+            user: user.key(),
+            owner: ctx.accounts.owner.key(),
+            amount,
+            balance_staked: user.balance_staked,
+            total_staked: pool.total_staked,
+        });
+
+        Ok(())
+    }
+
+    /// A user claims their share of early exit penalties paid by other stakers.
+    pub fn claim_penalty_rewards(ctx: Context<Stake>) -> Result<()> {
+        if ctx.accounts.config.global_freeze {
+            return Err(ErrorCode::GlobalFreeze.into());
+        }
+
+        let pool = &mut ctx.accounts.pool;
+
+        pool.track_total_staked_if_needed(&ctx.accounts.staking_vault);
+
+        let user_opt = Some(&mut ctx.accounts.user);
+        update_rewards(pool, user_opt)?;
+
+        let user = &mut ctx.accounts.user;
+        let amount = user.penalty_reward_pending;
+        if amount == 0 {
+            return Err(ErrorCode::AmountMustBeGreaterThanZero.into());
+        }
+        user.penalty_reward_pending = 0;
+        pool.total_penalty_rewards = pool
+            .total_penalty_rewards
+            .checked_sub(amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        // Transfer tokens from the pool vault to user vault.
+        {
+            let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+            let pool_signer = &[&seeds[..]];
+
+            let cpi_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                token::Transfer {
+                    from: ctx.accounts.staking_vault.to_account_info(),
+                    to: ctx.accounts.stake_from_account.to_account_info(),
+                    authority: ctx.accounts.pool_signer.to_account_info(),
+                },
+                pool_signer,
+            );
+            token::transfer(cpi_ctx, amount)?;
+        }
+
+        emit!(PenaltyRewardsClaimed {
+            pool: pool.key(),
+            user: user.key(),
+            owner: ctx.accounts.owner.key(),
+            amount,
+        });
+
+        Ok(())
+    }
+
+    /// Locks some of a user's stake until `lock_duration` from now for a boost to their
+    /// reward weight, growing linearly with the duration up to the pool's max boost.
+    /// Locking again extends the lock to cover all locked stake, it cannot be shortened.
+    pub fn lock_stake(ctx: Context<LockStake>, amount: u64, lock_duration: u64) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        let user = &mut ctx.accounts.user;
+
+        if pool.version != PoolVersion::V5 {
+            return Err(ErrorCode::PoolVersionNotSupported.into());
+        }
+        if pool.paused {
+            return Err(ErrorCode::PoolPaused.into());
+        }
+        if lock_duration < pool.min_lock_duration || lock_duration > pool.max_lock_duration {
+            return Err(ErrorCode::InvalidLockDuration.into());
+        }
+
+        //an expired lock is released, so locking again starts afresh
+        update_rewards(pool, Some(user))?;
+
+        let current_time: u64 = current_timestamp()?;
+        if amount == 0 && user.balance_locked == 0 {
+            return Err(ErrorCode::AmountMustBeGreaterThanZero.into());
+        }
+        let unlocked = user
+            .balance_staked
+            .checked_sub(user.balance_locked)
+            .ok_or(ErrorCode::MathOverflow)?;
+        if amount > unlocked {
+            return Err(ErrorCode::InsufficientStakeToLock.into());
+        }
+        let lock_end_ts = current_time
+            .checked_add(lock_duration)
+            .ok_or(ErrorCode::MathOverflow)?;
+        if lock_end_ts < user.lock_end_ts {
+            return Err(ErrorCode::LockCannotBeShortened.into());
+        }
+
+        let balance_locked = user
+            .balance_locked
+            .checked_add(amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+        let boost_weight: u64 = U192::from(balance_locked)
+            .checked_mul(pool.max_lock_boost_bps.into())
+            .ok_or(ErrorCode::MathOverflow)?
+            .checked_mul(lock_duration.into())
+            .ok_or(ErrorCode::MathOverflow)?
+            .checked_div(pool.max_lock_duration.into())
+            .ok_or(ErrorCode::MathOverflow)?
+            .checked_div(BASIS_POINTS.into())
+            .ok_or(ErrorCode::MathOverflow)?
+            .try_into()
+            .map_err(|_| ErrorCode::MathOverflow)?;
+
+        pool.total_boost_weight = pool
+            .total_boost_weight
+            .checked_sub(user.boost_weight)
+            .ok_or(ErrorCode::MathOverflow)?
+            .checked_add(boost_weight)
+            .ok_or(ErrorCode::MathOverflow)?;
+        user.balance_locked = balance_locked;
+        user.lock_end_ts = lock_end_ts;
+        user.boost_weight = boost_weight;
+
+        emit!(StakeLocked {
+            pool: pool.key(),
+            user: user.key(),
+            owner: ctx.accounts.owner.key(),
+            amount,
+            balance_locked,
+            lock_end_ts,
+            boost_weight,
+            total_boost_weight: pool.total_boost_weight,
+        });
+
+        Ok(())
+    }
+
+    /// Releases a user's expired lock, removing its boost from the pool's total weight.
+    /// A boost only earns up to its lock end, and the user's own instructions release it,
+    /// but anyone may release an expired lock so it stops diluting other stakers.
+    pub fn release_lock(ctx: Context<ReleaseLock>) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        let user = &mut ctx.accounts.user;
+
+        if !user.lock_expired(current_timestamp()?) {
+            return Err(ErrorCode::LockNotExpired.into());
+        }
+
+        update_rewards(pool, Some(user))
+    }
+
+    /// Enables lock ups on the pool, upgrading it to V5, or updates their config. Boosts of
+    /// existing locks are kept until released.
+    pub fn configure_lockup(
+        ctx: Context<ConfigureLockup>,
+        min_lock_duration: u64,
+        max_lock_duration: u64,
+        max_lock_boost_bps: u16,
+    ) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+
+        //V1 pools are upgraded, and have their stuck funds rescued, by `fund`
+        if !pool.version.has_annual_rate() {
+            return Err(ErrorCode::PoolVersionNotSupported.into());
+        }
+        if max_lock_duration == 0 || min_lock_duration > max_lock_duration {
+            return Err(ErrorCode::InvalidLockDuration.into());
+        }
+        if max_lock_boost_bps > MAX_LOCK_BOOST_BPS {
+            return Err(ErrorCode::InvalidLockBoost.into());
+        }
+
+        pool.track_total_staked_if_needed(&ctx.accounts.staking_vault);
+        update_rewards(pool, None)?;
+
+        pool.min_lock_duration = min_lock_duration;
+        pool.max_lock_duration = max_lock_duration;
+        pool.max_lock_boost_bps = max_lock_boost_bps;
+
+        if pool.version == PoolVersion::V2 {
+            //without boosts the weight of all stake is its amount, so V2 rewards carry over
+            pool.version = PoolVersion::V5;
+            msg!("pool upgraded to v5");
+        }
+
+        emit!(LockupConfigured {
+            pool: pool.key(),
+            min_lock_duration,
+            max_lock_duration,
+            max_lock_boost_bps,
+            version: pool.version.clone(),
+        });
+
+        Ok(())
+    }
+
+    /// Sets how long unstaked tokens unbond before they can be withdrawn, 0 pays out on
+    /// unstake. Applies to tokens unstaked from then on.
+    pub fn set_unbonding_period(
+        ctx: Context<SetUnbondingPeriod>,
+        unbonding_period: u64,
+    ) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        pool.unbonding_period = unbonding_period;
+
+        emit!(UnbondingPeriodSet {
+            pool: pool.key(),
+            unbonding_period,
+        });
+
+        Ok(())
+    }
+
+    /// Sets the most a user may stake and the most staked in the pool in total, zero for
+    /// no cap. Stake already over a lowered cap stays, only new stake is refused.
+    pub fn set_stake_caps(
+        ctx: Context<SetStakeCaps>,
+        max_stake_per_user: u64,
+        max_total_stake: u64,
+    ) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        pool.max_stake_per_user = max_stake_per_user;
+        pool.max_total_stake = max_total_stake;
+
+        emit!(StakeCapsSet {
+            pool: pool.key(),
+            max_stake_per_user,
+            max_total_stake,
+        });
+
+        Ok(())
+    }
+
+    /// Sets the token account early exit penalties are paid into, passed as the only
+    /// remaining account, or shares them among the stakers when none is passed.
+    pub fn set_penalty_treasury(ctx: Context<SetPenaltyTreasury>) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        pool.penalty_treasury = penalty_treasury_address(pool, ctx.remaining_accounts.first())?;
+
+        emit!(PenaltyTreasurySet {
+            pool: pool.key(),
+            penalty_treasury: pool.penalty_treasury,
+        });
+
+        Ok(())
+    }
+
+    /// Authorize additional funders for the pool
+    pub fn authorize_funder(ctx: Context<FunderChange>, funder_to_add: Pubkey) -> Result<()> {
+        if funder_to_add == ctx.accounts.pool.authority {
+            return Err(ErrorCode::FunderAlreadyAuthorized.into());
+        }
+        let funders = &mut ctx.accounts.pool.funders;
+        if funders.contains(&funder_to_add) {
+            return Err(ErrorCode::FunderAlreadyAuthorized.into());
+        }
+        let default_pubkey = Pubkey::default();
+        if let Some(idx) = funders.iter().position(|x| *x == default_pubkey) {
+            funders[idx] = funder_to_add;
+        } else {
+            return Err(ErrorCode::MaxFunders.into());
+        }
+
+        emit!(FunderAuthorized {
+            pool: ctx.accounts.pool.key(),
+            funder: funder_to_add,
+        });
+
+        Ok(())
+    }
+
+    /// Deauthorize funders for the pool
+    pub fn deauthorize_funder(ctx: Context<FunderChange>, funder_to_remove: Pubkey) -> Result<()> {
+        if funder_to_remove == ctx.accounts.pool.authority {
+            return Err(ErrorCode::CannotDeauthorizePoolAuthority.into());
         }
         let funders = &mut ctx.accounts.pool.funders;
         if let Some(idx) = funders.iter().position(|x| *x == funder_to_remove) {
@@ -317,78 +1376,225 @@ pub mod reward_pool {
         } else {
             return Err(ErrorCode::CannotDeauthorizeMissingAuthority.into());
         }
+
+        emit!(FunderDeauthorized {
+            pool: ctx.accounts.pool.key(),
+            funder: funder_to_remove,
+        });
+
+        Ok(())
+    }
+
+    /// Proposes a new authority for the pool, which takes over once it accepts.
+    /// Proposing again replaces the pending authority.
+    pub fn propose_authority(ctx: Context<AuthorityChange>, new_authority: Pubkey) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        if new_authority == pool.authority || new_authority == Pubkey::default() {
+            return Err(ErrorCode::InvalidNewAuthority.into());
+        }
+        pool.pending_authority = new_authority;
+
+        emit!(AuthorityProposed {
+            pool: pool.key(),
+            authority: pool.authority,
+            pending_authority: new_authority,
+        });
+
+        Ok(())
+    }
+
+    /// Cancels a pending authority transfer
+    pub fn cancel_authority_transfer(ctx: Context<AuthorityChange>) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        if pool.pending_authority == Pubkey::default() {
+            return Err(ErrorCode::NoPendingAuthority.into());
+        }
+        let pending_authority = pool.pending_authority;
+        pool.pending_authority = Pubkey::default();
+
+        emit!(AuthorityTransferCancelled {
+            pool: pool.key(),
+            pending_authority,
+        });
+
+        Ok(())
+    }
+
+    /// The pending authority takes over the pool. Funders are kept, other than the new
+    /// authority itself, which funds as the authority from now on.
+    pub fn accept_authority(ctx: Context<AcceptAuthority>) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        let new_authority = ctx.accounts.pending_authority.key();
+        if pool.pending_authority != new_authority {
+            return Err(ErrorCode::NotPendingAuthority.into());
+        }
+        let previous_authority = pool.authority;
+        pool.authority = new_authority;
+        pool.pending_authority = Pubkey::default();
+        if let Some(idx) = pool.funders.iter().position(|x| *x == new_authority) {
+            pool.funders[idx] = Pubkey::default();
+        }
+
+        emit!(AuthorityTransferred {
+            pool: pool.key(),
+            previous_authority,
+            authority: new_authority,
+        });
+
         Ok(())
     }
 
     /// Fund the pool with rewards.  This resets the clock on the end date, pushing it out to the set duration
-    /// And linearly redistributes remaining rewards.
+    /// And linearly redistributes remaining rewards. Use `top_up` to keep the current end date.
     pub fn fund(ctx: Context<Fund>, amount_a: u64, amount_b: u64) -> Result<()> {
+        if ctx.accounts.pool.version.is_multi_reward() {
+            return Err(ErrorCode::PoolVersionNotSupported.into());
+        }
+
         //if vault a and b are the same, we just use a
         if amount_b > 0 && ctx.accounts.reward_a_vault.key() == ctx.accounts.reward_b_vault.key() {
             return Err(ErrorCode::SingleStakeTokenBCannotBeFunded.into());
         }
 
         let pool = &mut ctx.accounts.pool;
-        let total_staked = ctx.accounts.staking_vault.amount;
-        update_rewards(pool, None, total_staked).unwrap();
-
-        let calc = get_calculator(pool);
-        let (reward_a_rate, reward_b_rate) = calc.rate_after_funding(
-            pool,
-            &ctx.accounts.reward_a_vault,
-            &ctx.accounts.reward_b_vault,
+        pool.track_total_staked_if_needed(&ctx.accounts.staking_vault);
+        update_rewards(pool, None)?;
+
+        let current_time = current_timestamp()?;
+        pool.start_reward_period(
+            &[
+                ctx.accounts.reward_a_vault.amount,
+                ctx.accounts.reward_b_vault.amount,
+            ],
+            &[amount_a, amount_b],
+            current_time,
+        )?;
+
+        transfer_funding(ctx.accounts, amount_a, amount_b)?;
+
+        let pool = &ctx.accounts.pool;
+        emit!(Funded {
+            pool: pool.key(),
+            funder: ctx.accounts.funder.key(),
             amount_a,
             amount_b,
-        )?;
-        pool.reward_a_rate = reward_a_rate;
-        pool.reward_b_rate = reward_b_rate;
+            reward_a_rate: pool.reward_a_rate,
+            reward_b_rate: pool.reward_b_rate,
+            reward_a_per_token_stored: pool.reward_a_per_token_stored,
+            reward_b_per_token_stored: pool.reward_b_per_token_stored,
+            reward_duration_end: pool.reward_duration_end,
+        });
 
-        // Transfer reward A tokens into the A vault.
-        if amount_a > 0 {
-            let cpi_ctx = CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
-                token::Transfer {
-                    from: ctx.accounts.from_a.to_account_info(),
-                    to: ctx.accounts.reward_a_vault.to_account_info(),
-                    authority: ctx.accounts.funder.to_account_info(),
-                },
-            );
+        Ok(())
+    }
 
-            token::transfer(cpi_ctx, amount_a)?;
+    /// Fund the current reward period without moving its end date, the rate rises such
+    /// that the remaining rewards plus the funding are emitted by the existing end.
+    pub fn top_up(ctx: Context<Fund>, amount_a: u64, amount_b: u64) -> Result<()> {
+        if ctx.accounts.pool.version.is_multi_reward() {
+            return Err(ErrorCode::PoolVersionNotSupported.into());
         }
 
-        // Transfer reward B tokens into the B vault.
-        if amount_b > 0 {
-            let cpi_ctx = CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
-                token::Transfer {
-                    from: ctx.accounts.from_b.to_account_info(),
-                    to: ctx.accounts.reward_b_vault.to_account_info(),
-                    authority: ctx.accounts.funder.to_account_info(),
-                },
-            );
+        //if vault a and b are the same, we just use a
+        if amount_b > 0 && ctx.accounts.reward_a_vault.key() == ctx.accounts.reward_b_vault.key() {
+            return Err(ErrorCode::SingleStakeTokenBCannotBeFunded.into());
+        }
+
+        let pool = &mut ctx.accounts.pool;
+        pool.track_total_staked_if_needed(&ctx.accounts.staking_vault);
+        update_rewards(pool, None)?;
+
+        let current_time = current_timestamp()?;
+        pool.top_up_reward_period(&[amount_a, amount_b], current_time)?;
+
+        transfer_funding(ctx.accounts, amount_a, amount_b)?;
+
+        let pool = &ctx.accounts.pool;
+        emit!(ToppedUp {
+            pool: pool.key(),
+            funder: ctx.accounts.funder.key(),
+            amount_a,
+            amount_b,
+            reward_a_rate: pool.reward_a_rate,
+            reward_b_rate: pool.reward_b_rate,
+            reward_a_per_token_stored: pool.reward_a_per_token_stored,
+            reward_b_per_token_stored: pool.reward_b_per_token_stored,
+            reward_duration_end: pool.reward_duration_end,
+        });
+
+        Ok(())
+    }
 
-            token::transfer(cpi_ctx, amount_b)?;
+    /// Fund the pool with a reward period that starts emitting at `start_ts` and ends at
+    /// `end_ts`. Tokens are transferred now and the period is queued on the pool, taking over
+    /// from the current period, along with its remaining rewards, once `start_ts` passes.
+    /// Only one period can be queued at a time.
+    pub fn fund_scheduled(
+        ctx: Context<Fund>,
+        amount_a: u64,
+        amount_b: u64,
+        start_ts: u64,
+        end_ts: u64,
+    ) -> Result<()> {
+        //V1 pools are upgraded, and have their stuck funds rescued, by `fund`
+        if !ctx.accounts.pool.version.has_annual_rate() {
+            return Err(ErrorCode::PoolVersionNotSupported.into());
         }
 
-        let current_time = clock::Clock::get()
-            .unwrap()
-            .unix_timestamp
-            .try_into()
-            .unwrap();
-        pool.last_update_time = current_time;
-        pool.reward_duration_end = current_time.checked_add(pool.reward_duration).unwrap();
+        if amount_a == 0 && amount_b == 0 {
+            return Err(ErrorCode::AmountMustBeGreaterThanZero.into());
+        }
+
+        //if vault a and b are the same, we just use a
+        if amount_b > 0 && ctx.accounts.reward_a_vault.key() == ctx.accounts.reward_b_vault.key() {
+            return Err(ErrorCode::SingleStakeTokenBCannotBeFunded.into());
+        }
+
+        let current_time = current_timestamp()?;
+        if start_ts < current_time || end_ts <= start_ts {
+            return Err(ErrorCode::InvalidRewardPeriod.into());
+        }
+        ctx.accounts.config.check_duration(
+            end_ts
+                .checked_sub(start_ts)
+                .ok_or(ErrorCode::MathOverflow)?,
+        )?;
+
+        if ctx.accounts.pool.scheduled_start_ts > 0 {
+            return Err(ErrorCode::RewardPeriodAlreadyScheduled.into());
+        }
+
+        transfer_funding(ctx.accounts, amount_a, amount_b)?;
+
+        let pool = &mut ctx.accounts.pool;
+        pool.scheduled_reward_a_amount = amount_a;
+        pool.scheduled_reward_b_amount = amount_b;
+        pool.scheduled_start_ts = start_ts;
+        pool.scheduled_end_ts = end_ts;
+
+        emit!(FundingScheduled {
+            pool: pool.key(),
+            funder: ctx.accounts.funder.key(),
+            amount_a,
+            amount_b,
+            start_ts,
+            end_ts,
+        });
 
         Ok(())
     }
 
     /// A user claiming rewards
     pub fn claim(ctx: Context<ClaimReward>) -> Result<()> {
-        let total_staked = ctx.accounts.staking_vault.amount;
+        if ctx.accounts.pool.version.is_multi_reward() {
+            return Err(ErrorCode::PoolVersionNotSupported.into());
+        }
 
         let pool = &mut ctx.accounts.pool;
+        pool.track_total_staked_if_needed(&ctx.accounts.staking_vault);
+
         let user_opt = Some(&mut ctx.accounts.user);
-        update_rewards(pool, user_opt, total_staked).unwrap();
+        update_rewards(pool, user_opt)?;
 
         let seeds = &[
             ctx.accounts.pool.to_account_info().key.as_ref(),
@@ -396,11 +1602,15 @@ pub mod reward_pool {
         ];
         let pool_signer = &[&seeds[..]];
 
+        let mut claimed_a = 0;
+        let mut claimed_b = 0;
+
         if ctx.accounts.user.reward_a_per_token_pending > 0 {
             let mut reward_amount = ctx.accounts.user.reward_a_per_token_pending;
             let vault_balance = ctx.accounts.reward_a_vault.amount;
 
             ctx.accounts.user.reward_a_per_token_pending = 0;
+            ctx.accounts.pool.settle_reward_owed(0, reward_amount);
             if vault_balance < reward_amount {
                 reward_amount = vault_balance;
             }
@@ -417,6 +1627,7 @@ pub mod reward_pool {
                 );
                 token::transfer(cpi_ctx, reward_amount)?;
             }
+            claimed_a = reward_amount;
         }
 
         if ctx.accounts.user.reward_b_per_token_pending > 0 {
@@ -424,6 +1635,7 @@ pub mod reward_pool {
             let vault_balance = ctx.accounts.reward_b_vault.amount;
 
             ctx.accounts.user.reward_b_per_token_pending = 0;
+            ctx.accounts.pool.settle_reward_owed(1, reward_amount);
             if vault_balance < reward_amount {
                 reward_amount = vault_balance;
             }
@@ -440,255 +1652,1377 @@ pub mod reward_pool {
                 );
                 token::transfer(cpi_ctx, reward_amount)?;
             }
+            claimed_b = reward_amount;
         }
 
+        let pool = &ctx.accounts.pool;
+        emit!(Claimed {
+            pool: pool.key(),
+            user: ctx.accounts.user.key(),
+            owner: ctx.accounts.owner.key(),
+            amount_a: claimed_a,
+            amount_b: claimed_b,
+            reward_a_per_token_stored: pool.reward_a_per_token_stored,
+            reward_b_per_token_stored: pool.reward_b_per_token_stored,
+        });
+
         Ok(())
     }
 
-    /// Closes a users stake account. Validation is done to ensure this is only allowed when
-    /// the user has nothing staked and no rewards pending.
-    pub fn close_user(ctx: Context<CloseUser>) -> Result<()> {
+    /// A user stakes their pending rewards of the staking token, for pools rewarding the
+    /// token that is staked. Other rewards are left pending.
+    pub fn compound(ctx: Context<Compound>) -> Result<()> {
+        if ctx.accounts.pool.version.is_multi_reward() {
+            return Err(ErrorCode::PoolVersionNotSupported.into());
+        }
+
         let pool = &mut ctx.accounts.pool;
-        pool.user_stake_count = pool.user_stake_count.checked_sub(1).unwrap();
-        Ok(())
-    }
+        if pool.paused {
+            return Err(ErrorCode::PoolPaused.into());
+        }
 
-    /// Closes a pool account. Only able to be done when there are no users staked.
-    pub fn close_pool(ctx: Context<ClosePool>) -> Result<()> {
-        let pool = &ctx.accounts.pool;
+        let compound_a = pool.reward_a_mint == pool.staking_mint;
+        //a single stake pool's B is its A
+        let compound_b =
+            pool.reward_b_mint == pool.staking_mint && pool.reward_b_vault != pool.reward_a_vault;
+        if !compound_a && !compound_b {
+            return Err(ErrorCode::NoCompoundableReward.into());
+        }
 
-        let signer_seeds = &[
-            pool.to_account_info().key.as_ref(),
+        pool.track_total_staked_if_needed(&ctx.accounts.staking_vault);
+
+        let user_opt = Some(&mut ctx.accounts.user);
+        update_rewards(pool, user_opt)?;
+
+        let seeds = &[
+            ctx.accounts.pool.to_account_info().key.as_ref(),
             &[ctx.accounts.pool.nonce],
         ];
+        let pool_signer = &[&seeds[..]];
 
-        //instead of closing these vaults, we could technically just
-        //set_authority on them. it's not very ata clean, but it'd work
-        //if size of tx is an issue, thats an approach
+        let mut compound_amount: u64 = 0;
 
-        //close staking vault
-        let ix = spl_token::instruction::transfer(
-            &spl_token::ID,
-            ctx.accounts.staking_vault.to_account_info().key,
-            ctx.accounts.staking_refundee.to_account_info().key,
-            ctx.accounts.pool_signer.key,
-            &[ctx.accounts.pool_signer.key],
-            ctx.accounts.staking_vault.amount,
-        )?;
-        solana_program::program::invoke_signed(
-            &ix,
-            &[
-                ctx.accounts.token_program.to_account_info(),
-                ctx.accounts.staking_vault.to_account_info(),
-                ctx.accounts.staking_refundee.to_account_info(),
-                ctx.accounts.pool_signer.to_account_info(),
-            ],
-            &[signer_seeds],
-        )?;
-        let ix = spl_token::instruction::close_account(
-            &spl_token::ID,
-            ctx.accounts.staking_vault.to_account_info().key,
-            ctx.accounts.refundee.key,
-            ctx.accounts.pool_signer.key,
-            &[ctx.accounts.pool_signer.key],
-        )?;
-        solana_program::program::invoke_signed(
-            &ix,
-            &[
+        if compound_a && ctx.accounts.user.reward_a_per_token_pending > 0 {
+            let mut reward_amount = ctx.accounts.user.reward_a_per_token_pending;
+            let vault_balance = ctx.accounts.reward_a_vault.amount;
+
+            ctx.accounts.user.reward_a_per_token_pending = 0;
+            ctx.accounts.pool.settle_reward_owed(0, reward_amount);
+            if vault_balance < reward_amount {
+                reward_amount = vault_balance;
+            }
+
+            if reward_amount > 0 {
+                let cpi_ctx = CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    token::Transfer {
+                        from: ctx.accounts.reward_a_vault.to_account_info(),
+                        to: ctx.accounts.staking_vault.to_account_info(),
+                        authority: ctx.accounts.pool_signer.to_account_info(),
+                    },
+                    pool_signer,
+                );
+                token::transfer(cpi_ctx, reward_amount)?;
+                compound_amount = compound_amount
+                    .checked_add(reward_amount)
+                    .ok_or(ErrorCode::MathOverflow)?;
+            }
+        }
+
+        if compound_b && ctx.accounts.user.reward_b_per_token_pending > 0 {
+            let mut reward_amount = ctx.accounts.user.reward_b_per_token_pending;
+            let vault_balance = ctx.accounts.reward_b_vault.amount;
+
+            ctx.accounts.user.reward_b_per_token_pending = 0;
+            ctx.accounts.pool.settle_reward_owed(1, reward_amount);
+            if vault_balance < reward_amount {
+                reward_amount = vault_balance;
+            }
+
+            if reward_amount > 0 {
+                let cpi_ctx = CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    token::Transfer {
+                        from: ctx.accounts.reward_b_vault.to_account_info(),
+                        to: ctx.accounts.staking_vault.to_account_info(),
+                        authority: ctx.accounts.pool_signer.to_account_info(),
+                    },
+                    pool_signer,
+                );
+                token::transfer(cpi_ctx, reward_amount)?;
+                compound_amount = compound_amount
+                    .checked_add(reward_amount)
+                    .ok_or(ErrorCode::MathOverflow)?;
+            }
+        }
+
+        if compound_amount > 0 {
+            let current_time = current_timestamp()?;
+            ctx.accounts
+                .pool
+                .check_stake_caps(ctx.accounts.user.balance_staked, compound_amount)?;
+            let user = &mut ctx.accounts.user;
+            user.record_stake(compound_amount, current_time)?;
+            user.balance_staked = user
+                .balance_staked
+                .checked_add(compound_amount)
+                .ok_or(ErrorCode::MathOverflow)?;
+
+            let pool = &mut ctx.accounts.pool;
+            pool.total_staked = pool
+                .total_staked
+                .checked_add(compound_amount)
+                .ok_or(ErrorCode::MathOverflow)?;
+        }
+
+        emit!(Compounded {
+            pool: ctx.accounts.pool.key(),
+            user: ctx.accounts.user.key(),
+            owner: ctx.accounts.owner.key(),
+            amount: compound_amount,
+            balance_staked: ctx.accounts.user.balance_staked,
+            total_staked: ctx.accounts.pool.total_staked,
+        });
+
+        Ok(())
+    }
+
+    /// Fund a multi reward pool with any of its rewards. `amounts` are ordered as the pool's
+    /// rewards; for each non zero amount the reward vault and the account to fund from are
+    /// passed, in that order, as remaining accounts. Like `fund`, this resets the clock on
+    /// the end date for all rewards and linearly redistributes remaining rewards; on a V4
+    /// pool only the funded rewards have their end date reset.
+    pub fn fund_v3<'info>(
+        ctx: Context<'_, '_, '_, 'info, FundV3<'info>>,
+        amounts: Vec<u64>,
+    ) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        if !pool.version.is_multi_reward() {
+            return Err(ErrorCode::PoolVersionNotSupported.into());
+        }
+        if amounts.len() > pool.reward_infos().len() {
+            return Err(ErrorCode::InvalidRewardIndex.into());
+        }
+        let funded_count = amounts.iter().filter(|a| **a > 0).count();
+        if ctx.remaining_accounts.len() != funded_count * 2 {
+            return Err(ErrorCode::InvalidRewardAccounts.into());
+        }
+
+        pool.track_total_staked_if_needed(&ctx.accounts.staking_vault);
+        update_rewards(pool, None)?;
+
+        let current_time = current_timestamp()?;
+        pool.start_reward_period(&[], &amounts, current_time)?;
+
+        // Transfer reward tokens into their vaults.
+        let mut reward_accounts = ctx.remaining_accounts.chunks(2);
+        for (i, amount) in amounts.iter().enumerate().filter(|(_, a)| **a > 0) {
+            let accounts = reward_accounts
+                .next()
+                .ok_or(ErrorCode::InvalidRewardAccounts)?;
+            let (reward_vault, from) = (&accounts[0], &accounts[1]);
+            if reward_vault.key() != pool.rewards[i].vault {
+                return Err(ErrorCode::InvalidRewardAccounts.into());
+            }
+
+            let cpi_ctx = CpiContext::new(
                 ctx.accounts.token_program.to_account_info(),
-                ctx.accounts.staking_vault.to_account_info(),
-                ctx.accounts.refundee.to_account_info(),
-                ctx.accounts.pool_signer.to_account_info(),
-            ],
-            &[signer_seeds],
+                token::Transfer {
+                    from: from.clone(),
+                    to: reward_vault.clone(),
+                    authority: ctx.accounts.funder.to_account_info(),
+                },
+            );
+
+            token::transfer(cpi_ctx, *amount)?;
+        }
+
+        emit!(FundedV3 {
+            pool: pool.key(),
+            funder: ctx.accounts.funder.key(),
+            reward_rates: pool.reward_infos().iter().map(|r| r.rate).collect(),
+            reward_per_token_stored: pool.reward_per_token_stored(),
+            amounts,
+        });
+
+        Ok(())
+    }
+
+    /// A user claiming any of the rewards of a multi reward pool. For each reward index
+    /// the reward vault and the account receiving the reward are passed, in that order,
+    /// as remaining accounts.
+    pub fn claim_v3<'info>(
+        ctx: Context<'_, '_, '_, 'info, ClaimRewardV3<'info>>,
+        reward_indexes: Vec<u8>,
+    ) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        if !pool.version.is_multi_reward() {
+            return Err(ErrorCode::PoolVersionNotSupported.into());
+        }
+        if ctx.remaining_accounts.len() != reward_indexes.len() * 2 {
+            return Err(ErrorCode::InvalidRewardAccounts.into());
+        }
+
+        pool.track_total_staked_if_needed(&ctx.accounts.staking_vault);
+
+        let user_opt = Some(&mut ctx.accounts.user);
+        update_rewards(pool, user_opt)?;
+
+        let seeds = &[
+            ctx.accounts.pool.to_account_info().key.as_ref(),
+            &[ctx.accounts.pool.nonce],
+        ];
+        let pool_signer = &[&seeds[..]];
+
+        let mut claimed = Vec::with_capacity(reward_indexes.len());
+        for (idx, accounts) in reward_indexes
+            .iter()
+            .map(|i| *i as usize)
+            .zip(ctx.remaining_accounts.chunks(2))
+        {
+            if idx >= ctx.accounts.pool.reward_infos().len() {
+                return Err(ErrorCode::InvalidRewardIndex.into());
+            }
+            let (reward_vault, reward_account) = (&accounts[0], &accounts[1]);
+            if reward_vault.key() != ctx.accounts.pool.rewards[idx].vault {
+                return Err(ErrorCode::InvalidRewardAccounts.into());
+            }
+
+            let mut reward_amount = ctx.accounts.user.reward_per_token_pending[idx];
+            if reward_amount == 0 {
+                claimed.push(0);
+                continue;
+            }
+            let vault_balance = Account::<TokenAccount>::try_from(reward_vault)?.amount;
+
+            ctx.accounts.user.reward_per_token_pending[idx] = 0;
+            ctx.accounts.pool.settle_reward_owed(idx, reward_amount);
+            if vault_balance < reward_amount {
+                reward_amount = vault_balance;
+            }
+
+            if reward_amount > 0 {
+                let cpi_ctx = CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    token::Transfer {
+                        from: reward_vault.clone(),
+                        to: reward_account.clone(),
+                        authority: ctx.accounts.pool_signer.to_account_info(),
+                    },
+                    pool_signer,
+                );
+                token::transfer(cpi_ctx, reward_amount)?;
+            }
+            claimed.push(reward_amount);
+        }
+
+        emit!(ClaimedV3 {
+            pool: ctx.accounts.pool.key(),
+            user: ctx.accounts.user.key(),
+            owner: ctx.accounts.owner.key(),
+            reward_indexes,
+            amounts: claimed,
+            reward_per_token_stored: ctx.accounts.pool.reward_per_token_stored(),
+        });
+
+        Ok(())
+    }
+
+    /// Anyone claiming all of a user's rewards on their behalf, such as a keeper or an
+    /// aggregator. Rewards are only paid into the associated token accounts of the user's
+    /// reward recipient, the owner unless they set one. For each of the pool's rewards, in
+    /// order, the reward vault and the account receiving the reward are passed as remaining
+    /// accounts.
+    pub fn claim_for<'info>(ctx: Context<'_, '_, '_, 'info, ClaimFor<'info>>) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        let vaults = pool.reward_vaults();
+        let mints = pool.reward_mints();
+        if ctx.remaining_accounts.len() != vaults.len() * 2 {
+            return Err(ErrorCode::InvalidRewardAccounts.into());
+        }
+
+        pool.track_total_staked_if_needed(&ctx.accounts.staking_vault);
+
+        let user_opt = Some(&mut ctx.accounts.user);
+        update_rewards(pool, user_opt)?;
+
+        let seeds = &[
+            ctx.accounts.pool.to_account_info().key.as_ref(),
+            &[ctx.accounts.pool.nonce],
+        ];
+        let pool_signer = &[&seeds[..]];
+
+        let recipient = ctx.accounts.user.reward_wallet();
+        let mut amounts = vec![0; vaults.len()];
+        for (idx, accounts) in ctx.remaining_accounts.chunks(2).enumerate() {
+            let (reward_vault, reward_account) = (&accounts[0], &accounts[1]);
+            if reward_vault.key() != vaults[idx]
+                || reward_account.key() != get_associated_token_address(&recipient, &mints[idx])
+            {
+                return Err(ErrorCode::InvalidRewardAccounts.into());
+            }
+
+            let pending = ctx
+                .accounts
+                .user
+                .reward_pending_mut(&ctx.accounts.pool, idx);
+            let mut reward_amount = *pending;
+            if reward_amount == 0 {
+                continue;
+            }
+            let vault_balance = Account::<TokenAccount>::try_from(reward_vault)?.amount;
+
+            *pending = 0;
+            ctx.accounts.pool.settle_reward_owed(idx, reward_amount);
+            if vault_balance < reward_amount {
+                reward_amount = vault_balance;
+            }
+
+            if reward_amount > 0 {
+                let cpi_ctx = CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    token::Transfer {
+                        from: reward_vault.clone(),
+                        to: reward_account.clone(),
+                        authority: ctx.accounts.pool_signer.to_account_info(),
+                    },
+                    pool_signer,
+                );
+                token::transfer(cpi_ctx, reward_amount)?;
+            }
+            amounts[idx] = reward_amount;
+        }
+
+        emit!(ClaimedFor {
+            pool: ctx.accounts.pool.key(),
+            user: ctx.accounts.user.key(),
+            owner: ctx.accounts.user.owner,
+            recipient,
+            claimer: ctx.accounts.claimer.key(),
+            amounts,
+        });
+
+        Ok(())
+    }
+
+    /// A user sets the wallet rewards claimed on their behalf are paid to, such as a cold
+    /// wallet or a vault program. The default key pays the owner again.
+    pub fn set_reward_recipient(
+        ctx: Context<SetRewardRecipient>,
+        reward_recipient: Pubkey,
+    ) -> Result<()> {
+        let user = &mut ctx.accounts.user;
+        user.reward_recipient = reward_recipient;
+
+        emit!(RewardRecipientSet {
+            user: user.key(),
+            owner: user.owner,
+            reward_recipient,
+        });
+
+        Ok(())
+    }
+
+    /// Sweeps staking tokens that were sent directly to the staking vault, and so are not
+    /// accounted for in the pool's total staked, unbonding or penalty rewards, out to the
+    /// authority. Once every user has left, penalty rewards lost to rounding can no longer
+    /// be claimed and are swept too.
+    pub fn sweep_donated_stake(ctx: Context<SweepDonatedStake>) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        pool.track_total_staked_if_needed(&ctx.accounts.staking_vault);
+        if pool.total_staked == 0 && pool.user_stake_count == 0 {
+            pool.total_penalty_rewards = 0;
+        }
+
+        let donated_amount = ctx
+            .accounts
+            .staking_vault
+            .amount
+            .checked_sub(pool.total_staked)
+            .and_then(|amount| amount.checked_sub(pool.total_unbonding))
+            .and_then(|amount| amount.checked_sub(pool.total_penalty_rewards))
+            .ok_or(ErrorCode::StakingVaultShortfall)?;
+        if donated_amount == 0 {
+            return Err(ErrorCode::NoDonatedStakeToSweep.into());
+        }
+
+        let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+        let pool_signer = &[&seeds[..]];
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            token::Transfer {
+                from: ctx.accounts.staking_vault.to_account_info(),
+                to: ctx.accounts.receiver.to_account_info(),
+                authority: ctx.accounts.pool_signer.to_account_info(),
+            },
+            pool_signer,
+        );
+        token::transfer(cpi_ctx, donated_amount)?;
+
+        emit!(DonatedStakeSwept {
+            pool: pool.key(),
+            receiver: ctx.accounts.receiver.key(),
+            amount: donated_amount,
+        });
+
+        Ok(())
+    }
+
+    /// Sweeps the tokens a reward vault holds beyond the rewards owed to users and those
+    /// still to be emitted, such as rounding dust or rewards emitted while nothing was
+    /// staked. Pools created before owed rewards were tracked count everything in the
+    /// vault that is not still to be emitted as owed on their first sweep, sweeping nothing.
+    pub fn sweep_unallocated_rewards(
+        ctx: Context<SweepUnallocatedRewards>,
+        reward_index: u8,
+    ) -> Result<()> {
+        let vault = ctx.accounts.reward_vault.key();
+        let vaults = ctx.accounts.pool.reward_vaults();
+        if vaults.get(reward_index as usize) != Some(&vault) {
+            return Err(ErrorCode::InvalidRewardIndex.into());
+        }
+        if vault == ctx.accounts.pool.staking_vault {
+            return Err(ErrorCode::CannotSweepStakingVault.into());
+        }
+
+        let pool = &mut ctx.accounts.pool;
+        pool.track_total_staked_if_needed(&ctx.accounts.staking_vault);
+        update_rewards(pool, None)?;
+
+        let current_time = current_timestamp()?;
+        let remaining = get_calculator(pool).remaining_rewards(pool, current_time)?;
+        let scheduled = [
+            pool.scheduled_reward_a_amount,
+            pool.scheduled_reward_b_amount,
+        ];
+
+        //a single reward pool's A and B share a vault
+        let sharing: Vec<usize> = vaults
+            .iter()
+            .enumerate()
+            .filter(|(_, v)| **v == vault)
+            .map(|(i, _)| i)
+            .collect();
+        let mut unemitted: u64 = 0;
+        for &i in &sharing {
+            unemitted = unemitted
+                .checked_add(remaining[i])
+                .ok_or(ErrorCode::MathOverflow)?
+                .checked_add(scheduled.get(i).copied().unwrap_or(0))
+                .ok_or(ErrorCode::MathOverflow)?;
+        }
+
+        let vault_amount = ctx.accounts.reward_vault.amount;
+        let tracked_mask = sharing.iter().fold(0u8, |mask, i| mask | 1 << i);
+        if pool.reward_owed_tracked & tracked_mask != tracked_mask {
+            for &i in &sharing {
+                pool.reward_owed[i] = 0;
+            }
+            pool.reward_owed[sharing[0]] = vault_amount.saturating_sub(unemitted);
+            pool.reward_owed_tracked |= tracked_mask;
+
+            msg!(
+                "pool reward owed tracked - {}",
+                pool.reward_owed[sharing[0]]
+            );
+            return Ok(());
+        }
+
+        let mut allocated = unemitted;
+        for &i in &sharing {
+            allocated = allocated
+                .checked_add(pool.reward_owed[i])
+                .ok_or(ErrorCode::MathOverflow)?;
+        }
+        let unallocated_amount = vault_amount.saturating_sub(allocated);
+        if unallocated_amount == 0 {
+            return Err(ErrorCode::NoUnallocatedRewardsToSweep.into());
+        }
+
+        let seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+        let pool_signer = &[&seeds[..]];
+        let cpi_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            token::Transfer {
+                from: ctx.accounts.reward_vault.to_account_info(),
+                to: ctx.accounts.receiver.to_account_info(),
+                authority: ctx.accounts.pool_signer.to_account_info(),
+            },
+            pool_signer,
+        );
+        token::transfer(cpi_ctx, unallocated_amount)?;
+
+        emit!(UnallocatedRewardsSwept {
+            pool: pool.key(),
+            reward_index,
+            receiver: ctx.accounts.receiver.key(),
+            amount: unallocated_amount,
+        });
+
+        Ok(())
+    }
+
+    /// Closes a users stake account. Validation is done to ensure this is only allowed when
+    /// the user has nothing staked and no rewards pending.
+    pub fn close_user(ctx: Context<CloseUser>) -> Result<()> {
+        let pool = &mut ctx.accounts.pool;
+        pool.user_stake_count = pool
+            .user_stake_count
+            .checked_sub(1)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        emit!(UserClosed {
+            pool: pool.key(),
+            user: ctx.accounts.user.key(),
+            owner: ctx.accounts.owner.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Closes a pool account. Only able to be done when there are no users staked.
+    pub fn close_pool(ctx: Context<ClosePool>) -> Result<()> {
+        let pool = &ctx.accounts.pool;
+
+        let signer_seeds = &[
+            pool.to_account_info().key.as_ref(),
+            &[ctx.accounts.pool.nonce],
+        ];
+
+        //instead of closing these vaults, we could technically just
+        //set_authority on them. it's not very ata clean, but it'd work
+        //if size of tx is an issue, thats an approach
+
+        //close staking vault
+        close_vault(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.staking_vault.to_account_info(),
+            ctx.accounts.staking_vault.amount,
+            &ctx.accounts.staking_refundee.to_account_info(),
+            &ctx.accounts.refundee.to_account_info(),
+            &ctx.accounts.pool_signer.to_account_info(),
+            signer_seeds,
         )?;
 
         //close token a vault
-        let ix = spl_token::instruction::transfer(
-            &spl_token::ID,
-            ctx.accounts.reward_a_vault.to_account_info().key,
-            ctx.accounts.reward_a_refundee.to_account_info().key,
-            ctx.accounts.pool_signer.key,
-            &[ctx.accounts.pool_signer.key],
+        close_vault(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.reward_a_vault.to_account_info(),
             ctx.accounts.reward_a_vault.amount,
-        )?;
-        solana_program::program::invoke_signed(
-            &ix,
-            &[
-                ctx.accounts.token_program.to_account_info(),
-                ctx.accounts.reward_a_vault.to_account_info(),
-                ctx.accounts.reward_a_refundee.to_account_info(),
-                ctx.accounts.pool_signer.to_account_info(),
-            ],
-            &[signer_seeds],
-        )?;
-        let ix = spl_token::instruction::close_account(
-            &spl_token::ID,
-            ctx.accounts.reward_a_vault.to_account_info().key,
-            ctx.accounts.refundee.key,
-            ctx.accounts.pool_signer.key,
-            &[ctx.accounts.pool_signer.key],
-        )?;
-        solana_program::program::invoke_signed(
-            &ix,
-            &[
-                ctx.accounts.token_program.to_account_info(),
-                ctx.accounts.reward_a_vault.to_account_info(),
-                ctx.accounts.refundee.to_account_info(),
-                ctx.accounts.pool_signer.to_account_info(),
-            ],
-            &[signer_seeds],
+            &ctx.accounts.reward_a_refundee.to_account_info(),
+            &ctx.accounts.refundee.to_account_info(),
+            &ctx.accounts.pool_signer.to_account_info(),
+            signer_seeds,
         )?;
 
         if pool.reward_a_vault != pool.reward_b_vault {
             //close token b vault
-            let ix = spl_token::instruction::transfer(
-                &spl_token::ID,
-                ctx.accounts.reward_b_vault.to_account_info().key,
-                ctx.accounts.reward_b_refundee.to_account_info().key,
-                ctx.accounts.pool_signer.key,
-                &[ctx.accounts.pool_signer.key],
+            close_vault(
+                &ctx.accounts.token_program.to_account_info(),
+                &ctx.accounts.reward_b_vault.to_account_info(),
                 ctx.accounts.reward_b_vault.amount,
-            )?;
-            solana_program::program::invoke_signed(
-                &ix,
-                &[
-                    ctx.accounts.token_program.to_account_info(),
-                    ctx.accounts.reward_b_vault.to_account_info(),
-                    ctx.accounts.reward_b_refundee.to_account_info(),
-                    ctx.accounts.pool_signer.to_account_info(),
-                ],
-                &[signer_seeds],
-            )?;
-            let ix = spl_token::instruction::close_account(
-                &spl_token::ID,
-                ctx.accounts.reward_b_vault.to_account_info().key,
-                ctx.accounts.refundee.key,
-                ctx.accounts.pool_signer.key,
-                &[ctx.accounts.pool_signer.key],
-            )?;
-            solana_program::program::invoke_signed(
-                &ix,
-                &[
-                    ctx.accounts.token_program.to_account_info(),
-                    ctx.accounts.reward_b_vault.to_account_info(),
-                    ctx.accounts.refundee.to_account_info(),
-                    ctx.accounts.pool_signer.to_account_info(),
-                ],
-                &[signer_seeds],
+                &ctx.accounts.reward_b_refundee.to_account_info(),
+                &ctx.accounts.refundee.to_account_info(),
+                &ctx.accounts.pool_signer.to_account_info(),
+                signer_seeds,
             )?;
         }
 
+        emit!(PoolClosed {
+            pool: pool.key(),
+            refundee: ctx.accounts.refundee.key(),
+        });
+
         Ok(())
     }
+
+    /// Closes a multi reward pool account. Only able to be done when there are no users staked.
+    /// For each of the pool's rewards the reward vault and its refundee are passed, in that
+    /// order, as remaining accounts.
+    pub fn close_pool_v3<'info>(ctx: Context<'_, '_, '_, 'info, ClosePoolV3<'info>>) -> Result<()> {
+        let pool = &ctx.accounts.pool;
+        if !pool.version.is_multi_reward() {
+            return Err(ErrorCode::PoolVersionNotSupported.into());
+        }
+        if ctx.remaining_accounts.len() != pool.reward_infos().len() * 2 {
+            return Err(ErrorCode::InvalidRewardAccounts.into());
+        }
+
+        let signer_seeds = &[pool.to_account_info().key.as_ref(), &[pool.nonce]];
+
+        //close staking vault
+        close_vault(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.staking_vault.to_account_info(),
+            ctx.accounts.staking_vault.amount,
+            &ctx.accounts.staking_refundee.to_account_info(),
+            &ctx.accounts.refundee.to_account_info(),
+            &ctx.accounts.pool_signer.to_account_info(),
+            signer_seeds,
+        )?;
+
+        //close reward vaults
+        for (reward, accounts) in pool
+            .reward_infos()
+            .iter()
+            .zip(ctx.remaining_accounts.chunks(2))
+        {
+            let (reward_vault, reward_refundee) = (&accounts[0], &accounts[1]);
+            if reward_vault.key() != reward.vault {
+                return Err(ErrorCode::InvalidRewardAccounts.into());
+            }
+
+            close_vault(
+                &ctx.accounts.token_program.to_account_info(),
+                reward_vault,
+                Account::<TokenAccount>::try_from(reward_vault)?.amount,
+                reward_refundee,
+                &ctx.accounts.refundee.to_account_info(),
+                &ctx.accounts.pool_signer.to_account_info(),
+                signer_seeds,
+            )?;
+        }
+
+        emit!(PoolClosed {
+            pool: pool.key(),
+            refundee: ctx.accounts.refundee.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Grows a pool created by an earlier version of the program to the current pool size
+    pub fn migrate_pool(ctx: Context<MigratePool>) -> Result<()> {
+        grow_account(
+            &ctx.accounts.pool.to_account_info(),
+            Pool::discriminator(),
+            8 + Pool::default().try_to_vec().unwrap().len(),
+            &ctx.accounts.payer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+        )
+    }
+
+    /// Grows a user created by an earlier version of the program to the current user size
+    pub fn migrate_user(ctx: Context<MigrateUser>) -> Result<()> {
+        grow_account(
+            &ctx.accounts.user.to_account_info(),
+            User::discriminator(),
+            8 + User::default().try_to_vec().unwrap().len(),
+            &ctx.accounts.payer.to_account_info(),
+            &ctx.accounts.system_program.to_account_info(),
+        )
+    }
+}
+
+#[derive(Accounts)]
+pub struct InitializeConfig<'info> {
+    #[account(
+        init,
+        payer = upgrade_authority,
+        seeds = [CONFIG_SEED],
+        bump,
+    )]
+    config: Box<Account<'info, ProgramConfig>>,
+
+    #[account(address = program_data_address())]
+    program_data: UncheckedAccount<'info>,
+    #[account(mut)]
+    upgrade_authority: Signer<'info>,
+
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump = config.nonce,
+        has_one = admin,
+    )]
+    config: Box<Account<'info, ProgramConfig>>,
+    admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptConfigAdmin<'info> {
+    #[account(
+        mut,
+        seeds = [CONFIG_SEED],
+        bump = config.nonce,
+    )]
+    config: Box<Account<'info, ProgramConfig>>,
+    pending_admin: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(pool_nonce: u8)]
+pub struct InitializePool<'info> {
+    authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = x_token_pool_vault.mint == config.deposit_mint,
+        constraint = x_token_pool_vault.owner == pool_signer.key(),
+        constraint = x_token_pool_vault.amount == 0,
+    )]
+    x_token_pool_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        constraint = x_token_depositor.mint == config.deposit_mint
+    )]
+    x_token_depositor: Box<Account<'info, TokenAccount>>,
+    x_token_deposit_authority: Signer<'info>,
+
+    staking_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        constraint = staking_vault.mint == staking_mint.key(),
+        constraint = staking_vault.owner == pool_signer.key(),
+        //strangely, spl maintains this on owner reassignment for non-native accounts
+        //we don't want to be given an account that someone else could close when empty
+        //because in our "pool close" operation we want to assert it is still open
+        constraint = staking_vault.close_authority == COption::None,
+    )]
+    staking_vault: Box<Account<'info, TokenAccount>>,
+
+    reward_a_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        constraint = reward_a_vault.mint == reward_a_mint.key(),
+        constraint = reward_a_vault.owner == pool_signer.key(),
+        constraint = reward_a_vault.close_authority == COption::None,
+    )]
+    reward_a_vault: Box<Account<'info, TokenAccount>>,
+
+    reward_b_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        constraint = reward_b_vault.mint == reward_b_mint.key(),
+        constraint = reward_b_vault.owner == pool_signer.key(),
+        constraint = reward_b_vault.close_authority == COption::None,
+    )]
+    reward_b_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        seeds = [
+            pool.to_account_info().key.as_ref()
+        ],
+        bump = pool_nonce,
+    )]
+    pool_signer: UncheckedAccount<'info>,
+
+    #[account(zero)]
+    pool: Box<Account<'info, Pool>>,
+
+    token_program: Program<'info, Token>,
+
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.nonce,
+    )]
+    config: Box<Account<'info, ProgramConfig>>,
+}
+
+#[derive(Accounts)]
+#[instruction(pool_nonce: u8)]
+pub struct InitializePoolV3<'info> {
+    authority: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        constraint = x_token_pool_vault.mint == config.deposit_mint,
+        constraint = x_token_pool_vault.owner == pool_signer.key(),
+        constraint = x_token_pool_vault.amount == 0,
+    )]
+    x_token_pool_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        constraint = x_token_depositor.mint == config.deposit_mint
+    )]
+    x_token_depositor: Box<Account<'info, TokenAccount>>,
+    x_token_deposit_authority: Signer<'info>,
+
+    staking_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        constraint = staking_vault.mint == staking_mint.key(),
+        constraint = staking_vault.owner == pool_signer.key(),
+        //strangely, spl maintains this on owner reassignment for non-native accounts
+        //we don't want to be given an account that someone else could close when empty
+        //because in our "pool close" operation we want to assert it is still open
+        constraint = staking_vault.close_authority == COption::None,
+    )]
+    staking_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        seeds = [
+            pool.to_account_info().key.as_ref()
+        ],
+        bump = pool_nonce,
+    )]
+    pool_signer: UncheckedAccount<'info>,
+
+    #[account(zero)]
+    pool: Box<Account<'info, Pool>>,
+
+    token_program: Program<'info, Token>,
+
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.nonce,
+    )]
+    config: Box<Account<'info, ProgramConfig>>,
+}
+
+#[derive(Accounts)]
+pub struct AddReward<'info> {
+    #[account(
+        mut,
+        has_one = authority,
+        constraint = pool.version.is_multi_reward(),
+    )]
+    pool: Box<Account<'info, Pool>>,
+    authority: Signer<'info>,
+
+    reward_mint: Box<Account<'info, Mint>>,
+
+    #[account(
+        constraint = reward_vault.mint == reward_mint.key(),
+        constraint = reward_vault.owner == pool_signer.key(),
+        constraint = reward_vault.close_authority == COption::None,
+    )]
+    reward_vault: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        seeds = [
+            pool.to_account_info().key.as_ref()
+        ],
+        bump = pool.nonce,
+    )]
+    pool_signer: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetRewardDuration<'info> {
+    #[account(
+        mut,
+        has_one = authority,
+        constraint = pool.version.has_independent_schedules(),
+    )]
+    pool: Box<Account<'info, Pool>>,
+    authority: Signer<'info>,
+
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.nonce,
+    )]
+    config: Box<Account<'info, ProgramConfig>>,
+}
+
+#[derive(Accounts)]
+pub struct CreateUser<'info> {
+    // Stake instance.
+    #[account(
+        mut,
+        constraint = !pool.paused,
+    )]
+    pool: Box<Account<'info, Pool>>,
+    // Member.
+    #[account(
+        init,
+        payer = owner,
+        seeds = [
+            owner.key.as_ref(),
+            pool.to_account_info().key.as_ref()
+        ],
+        bump,
+    )]
+    user: Box<Account<'info, User>>,
+    owner: Signer<'info>,
+    // Misc.
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Pause<'info> {
+    #[account(mut)]
+    x_token_pool_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    x_token_receiver: Box<Account<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        has_one = authority,
+        has_one = x_token_pool_vault,
+        constraint = !pool.paused,
+        constraint = pool.reward_duration_end < current_timestamp()?,
+        constraint = pool.scheduled_end_ts < current_timestamp()?,
+        //constraint = pool.reward_duration_end > 0,
+    )]
+    pool: Box<Account<'info, Pool>>,
+    authority: Signer<'info>,
+
+    #[account(
+        seeds = [
+            pool.to_account_info().key.as_ref()
+        ],
+        bump = pool.nonce,
+    )]
+    pool_signer: UncheckedAccount<'info>,
+    token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct Unpause<'info> {
+    #[account(
+        mut,
+        constraint = x_token_pool_vault.mint == config.deposit_mint,
+        constraint = x_token_pool_vault.owner == pool_signer.key(),
+    )]
+    x_token_pool_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        constraint = x_token_depositor.mint == config.deposit_mint
+    )]
+    x_token_depositor: Box<Account<'info, TokenAccount>>,
+    x_token_deposit_authority: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = authority,
+        constraint = pool.paused,
+    )]
+    pool: Box<Account<'info, Pool>>,
+    authority: Signer<'info>,
+
+    #[account(
+        seeds = [
+            pool.to_account_info().key.as_ref()
+        ],
+        bump = pool.nonce,
+    )]
+    pool_signer: UncheckedAccount<'info>,
+    token_program: Program<'info, Token>,
+
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.nonce,
+    )]
+    config: Box<Account<'info, ProgramConfig>>,
+}
+
+#[derive(Accounts)]
+pub struct Stake<'info> {
+    // Global accounts for the staking instance.
+    #[account(
+        mut,
+        has_one = staking_vault,
+    )]
+    pool: Box<Account<'info, Pool>>,
+    #[account(
+        mut,
+        constraint = staking_vault.owner == *pool_signer.key,
+    )]
+    staking_vault: Box<Account<'info, TokenAccount>>,
+
+    // User.
+    #[account(
+        mut,
+        has_one = owner,
+        has_one = pool,
+        seeds = [
+            owner.key.as_ref(),
+            pool.to_account_info().key.as_ref()
+        ],
+        bump = user.nonce,
+    )]
+    user: Box<Account<'info, User>>,
+    owner: Signer<'info>,
+    #[account(mut)]
+    stake_from_account: Box<Account<'info, TokenAccount>>,
+
+    // Program signers.
+    #[account(
+        seeds = [
+            pool.to_account_info().key.as_ref()
+        ],
+        bump = pool.nonce,
+    )]
+    pool_signer: UncheckedAccount<'info>,
+
+    // Misc.
+    token_program: Program<'info, Token>,
+
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.nonce,
+    )]
+    config: Box<Account<'info, ProgramConfig>>,
+}
+
+// Exits take no program config, so that they stay open through a global freeze.
+#[derive(Accounts)]
+pub struct Unstake<'info> {
+    // Global accounts for the staking instance.
+    #[account(
+        mut,
+        has_one = staking_vault,
+    )]
+    pool: Box<Account<'info, Pool>>,
+    #[account(
+        mut,
+        constraint = staking_vault.owner == *pool_signer.key,
+    )]
+    staking_vault: Box<Account<'info, TokenAccount>>,
+
+    // User.
+    #[account(
+        mut,
+        has_one = owner,
+        has_one = pool,
+        seeds = [
+            owner.key.as_ref(),
+            pool.to_account_info().key.as_ref()
+        ],
+        bump = user.nonce,
+    )]
+    user: Box<Account<'info, User>>,
+    owner: Signer<'info>,
+    #[account(mut)]
+    stake_from_account: Box<Account<'info, TokenAccount>>,
+
+    // Program signers.
+    #[account(
+        seeds = [
+            pool.to_account_info().key.as_ref()
+        ],
+        bump = pool.nonce,
+    )]
+    pool_signer: UncheckedAccount<'info>,
+
+    // Misc.
+    token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct StakeFor<'info> {
+    // Global accounts for the staking instance.
+    #[account(
+        mut,
+        has_one = staking_vault,
+    )]
+    pool: Box<Account<'info, Pool>>,
+    #[account(
+        mut,
+        constraint = staking_vault.owner == *pool_signer.key,
+    )]
+    staking_vault: Box<Account<'info, TokenAccount>>,
+
+    // User, opened for the owner if they have none.
+    #[account(
+        init_if_needed,
+        payer = payer,
+        seeds = [
+            owner.key.as_ref(),
+            pool.to_account_info().key.as_ref()
+        ],
+        bump,
+    )]
+    user: Box<Account<'info, User>>,
+    owner: UncheckedAccount<'info>,
+    #[account(mut)]
+    payer: Signer<'info>,
+    #[account(mut)]
+    stake_from_account: Box<Account<'info, TokenAccount>>,
+
+    // Program signers.
+    #[account(
+        seeds = [
+            pool.to_account_info().key.as_ref()
+        ],
+        bump = pool.nonce,
+    )]
+    pool_signer: UncheckedAccount<'info>,
+
+    // Misc.
+    token_program: Program<'info, Token>,
+    system_program: Program<'info, System>,
+
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.nonce,
+    )]
+    config: Box<Account<'info, ProgramConfig>>,
+}
+
+#[derive(Accounts)]
+pub struct FunderChange<'info> {
+    // Global accounts for the staking instance.
+    #[account(
+        mut,
+        has_one = authority,
+    )]
+    pool: Box<Account<'info, Pool>>,
+    authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AuthorityChange<'info> {
+    #[account(
+        mut,
+        has_one = authority,
+    )]
+    pool: Box<Account<'info, Pool>>,
+    authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAuthority<'info> {
+    #[account(mut)]
+    pool: Box<Account<'info, Pool>>,
+    pending_authority: Signer<'info>,
 }
 
 #[derive(Accounts)]
-#[instruction(pool_nonce: u8)]
-pub struct InitializePool<'info> {
-    authority: UncheckedAccount<'info>,
+pub struct LockStake<'info> {
+    #[account(mut)]
+    pool: Box<Account<'info, Pool>>,
+    #[account(
+        mut,
+        has_one = owner,
+        has_one = pool,
+        seeds = [
+            owner.key.as_ref(),
+            pool.to_account_info().key.as_ref()
+        ],
+        bump = user.nonce,
+    )]
+    user: Box<Account<'info, User>>,
+    owner: Signer<'info>,
+
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.nonce,
+        constraint = !config.global_freeze @ ErrorCode::GlobalFreeze,
+    )]
+    config: Box<Account<'info, ProgramConfig>>,
+}
 
+#[derive(Accounts)]
+pub struct ReleaseLock<'info> {
+    #[account(mut)]
+    pool: Box<Account<'info, Pool>>,
     #[account(
         mut,
-        constraint = x_token_pool_vault.mint == X_STEP_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap(),
-        constraint = x_token_pool_vault.owner == pool_signer.key(),
-        constraint = x_token_pool_vault.amount == 0,
+        has_one = pool,
     )]
-    x_token_pool_vault: Box<Account<'info, TokenAccount>>,
+    user: Box<Account<'info, User>>,
+}
 
+#[derive(Accounts)]
+pub struct CancelUnbonding<'info> {
+    #[account(mut)]
+    pool: Box<Account<'info, Pool>>,
     #[account(
         mut,
-        constraint = x_token_depositor.mint == X_STEP_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap()
+        has_one = owner,
+        has_one = pool,
+        seeds = [
+            owner.key.as_ref(),
+            pool.to_account_info().key.as_ref()
+        ],
+        bump = user.nonce,
     )]
-    x_token_depositor: Box<Account<'info, TokenAccount>>,
-    x_token_deposit_authority: Signer<'info>,
+    user: Box<Account<'info, User>>,
+    owner: Signer<'info>,
 
-    staking_mint: Box<Account<'info, Mint>>,
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.nonce,
+        constraint = !config.global_freeze @ ErrorCode::GlobalFreeze,
+    )]
+    config: Box<Account<'info, ProgramConfig>>,
+}
 
+#[derive(Accounts)]
+pub struct SetUnbondingPeriod<'info> {
     #[account(
-        constraint = staking_vault.mint == staking_mint.key(),
-        constraint = staking_vault.owner == pool_signer.key(),
-        //strangely, spl maintains this on owner reassignment for non-native accounts
-        //we don't want to be given an account that someone else could close when empty
-        //because in our "pool close" operation we want to assert it is still open
-        constraint = staking_vault.close_authority == COption::None,
+        mut,
+        has_one = authority,
     )]
-    staking_vault: Box<Account<'info, TokenAccount>>,
+    pool: Box<Account<'info, Pool>>,
+    authority: Signer<'info>,
+}
 
-    reward_a_mint: Box<Account<'info, Mint>>,
+#[derive(Accounts)]
+pub struct SetStakeCaps<'info> {
+    #[account(
+        mut,
+        has_one = authority,
+    )]
+    pool: Box<Account<'info, Pool>>,
+    authority: Signer<'info>,
+}
 
+#[derive(Accounts)]
+pub struct SetPenaltyTreasury<'info> {
     #[account(
-        constraint = reward_a_vault.mint == reward_a_mint.key(),
-        constraint = reward_a_vault.owner == pool_signer.key(),
-        constraint = reward_a_vault.close_authority == COption::None,
+        mut,
+        has_one = authority,
     )]
-    reward_a_vault: Box<Account<'info, TokenAccount>>,
+    pool: Box<Account<'info, Pool>>,
+    authority: Signer<'info>,
+}
 
-    reward_b_mint: Box<Account<'info, Mint>>,
+#[derive(Accounts)]
+pub struct ConfigureLockup<'info> {
+    #[account(
+        mut,
+        has_one = authority,
+        has_one = staking_vault,
+    )]
+    pool: Box<Account<'info, Pool>>,
+    authority: Signer<'info>,
+    staking_vault: Box<Account<'info, TokenAccount>>,
+}
 
+#[derive(Accounts)]
+pub struct Fund<'info> {
+    // Global accounts for the staking instance.
     #[account(
-        constraint = reward_b_vault.mint == reward_b_mint.key(),
-        constraint = reward_b_vault.owner == pool_signer.key(),
-        constraint = reward_b_vault.close_authority == COption::None,
+        mut,
+        has_one = staking_vault,
+        has_one = reward_a_vault,
+        has_one = reward_b_vault,
+        constraint = !pool.paused,
     )]
+    pool: Box<Account<'info, Pool>>,
+    #[account(mut)]
+    staking_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    reward_a_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
     reward_b_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        //require signed funder auth - otherwise constant micro fund could hold funds hostage
+        constraint = funder.key() == pool.authority || pool.funders.iter().any(|x| *x == funder.key()),
+    )]
+    funder: Signer<'info>,
+    #[account(mut)]
+    from_a: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    from_b: Box<Account<'info, TokenAccount>>,
 
+    // Program signers.
     #[account(
         seeds = [
             pool.to_account_info().key.as_ref()
         ],
-        bump = pool_nonce,
+        bump = pool.nonce,
     )]
     pool_signer: UncheckedAccount<'info>,
 
-    #[account(zero)]
-    pool: Box<Account<'info, Pool>>,
-
+    // Misc.
     token_program: Program<'info, Token>,
+
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.nonce,
+        constraint = !config.global_freeze @ ErrorCode::GlobalFreeze,
+    )]
+    config: Box<Account<'info, ProgramConfig>>,
 }
 
 #[derive(Accounts)]
-pub struct CreateUser<'info> {
-    // Stake instance.
+pub struct ClaimReward<'info> {
+    // Global accounts for the staking instance.
     #[account(
         mut,
-        constraint = !pool.paused,
+        has_one = staking_vault,
+        has_one = reward_a_vault,
+        has_one = reward_b_vault,
     )]
     pool: Box<Account<'info, Pool>>,
-    // Member.
+    #[account(mut)]
+    staking_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    reward_a_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    reward_b_vault: Box<Account<'info, TokenAccount>>,
+
+    // User.
     #[account(
-        init,
-        payer = owner,
+        mut,
+        has_one = owner,
+        has_one = pool,
         seeds = [
-            owner.key.as_ref(),
+            owner.to_account_info().key.as_ref(),
             pool.to_account_info().key.as_ref()
         ],
-        bump,
+        bump = user.nonce,
     )]
     user: Box<Account<'info, User>>,
     owner: Signer<'info>,
+    #[account(mut)]
+    reward_a_account: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    reward_b_account: Box<Account<'info, TokenAccount>>,
+
+    // Program signers.
+    #[account(
+        seeds = [
+            pool.to_account_info().key.as_ref()
+        ],
+        bump = pool.nonce,
+    )]
+    pool_signer: UncheckedAccount<'info>,
+
     // Misc.
-    system_program: Program<'info, System>,
+    token_program: Program<'info, Token>,
+
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.nonce,
+        constraint = !config.global_freeze @ ErrorCode::GlobalFreeze,
+    )]
+    config: Box<Account<'info, ProgramConfig>>,
 }
 
 #[derive(Accounts)]
-pub struct Pause<'info> {
+pub struct Compound<'info> {
+    // Global accounts for the staking instance.
+    #[account(
+        mut,
+        has_one = staking_vault,
+        has_one = reward_a_vault,
+        has_one = reward_b_vault,
+    )]
+    pool: Box<Account<'info, Pool>>,
     #[account(mut)]
-    x_token_pool_vault: Box<Account<'info, TokenAccount>>,
+    staking_vault: Box<Account<'info, TokenAccount>>,
     #[account(mut)]
-    x_token_receiver: Box<Account<'info, TokenAccount>>,
+    reward_a_vault: Box<Account<'info, TokenAccount>>,
+    #[account(mut)]
+    reward_b_vault: Box<Account<'info, TokenAccount>>,
 
+    // User.
     #[account(
         mut,
-        has_one = authority,
-        has_one = x_token_pool_vault,
-        constraint = !pool.paused,
-        constraint = pool.reward_duration_end < clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap(),
-        //constraint = pool.reward_duration_end > 0,
+        has_one = owner,
+        has_one = pool,
+        seeds = [
+            owner.to_account_info().key.as_ref(),
+            pool.to_account_info().key.as_ref()
+        ],
+        bump = user.nonce,
     )]
-    pool: Box<Account<'info, Pool>>,
-    authority: Signer<'info>,
+    user: Box<Account<'info, User>>,
+    owner: Signer<'info>,
 
+    // Program signers.
     #[account(
         seeds = [
             pool.to_account_info().key.as_ref()
@@ -696,32 +3030,81 @@ pub struct Pause<'info> {
         bump = pool.nonce,
     )]
     pool_signer: UncheckedAccount<'info>,
+
+    // Misc.
     token_program: Program<'info, Token>,
+
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.nonce,
+        constraint = !config.global_freeze @ ErrorCode::GlobalFreeze,
+    )]
+    config: Box<Account<'info, ProgramConfig>>,
 }
 
 #[derive(Accounts)]
-pub struct Unpause<'info> {
+pub struct FundV3<'info> {
+    // Global accounts for the staking instance.
     #[account(
         mut,
-        constraint = x_token_pool_vault.mint == X_STEP_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap(),
-        constraint = x_token_pool_vault.owner == pool_signer.key(),
+        has_one = staking_vault,
+        constraint = !pool.paused,
     )]
-    x_token_pool_vault: Box<Account<'info, TokenAccount>>,
+    pool: Box<Account<'info, Pool>>,
+    #[account(mut)]
+    staking_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        //require signed funder auth - otherwise constant micro fund could hold funds hostage
+        constraint = funder.key() == pool.authority || pool.funders.iter().any(|x| *x == funder.key()),
+    )]
+    funder: Signer<'info>,
+
+    // Program signers.
+    #[account(
+        seeds = [
+            pool.to_account_info().key.as_ref()
+        ],
+        bump = pool.nonce,
+    )]
+    pool_signer: UncheckedAccount<'info>,
+
+    // Misc.
+    token_program: Program<'info, Token>,
+
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.nonce,
+        constraint = !config.global_freeze @ ErrorCode::GlobalFreeze,
+    )]
+    config: Box<Account<'info, ProgramConfig>>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimRewardV3<'info> {
+    // Global accounts for the staking instance.
     #[account(
         mut,
-        constraint = x_token_depositor.mint == X_STEP_TOKEN_MINT_PUBKEY.parse::<Pubkey>().unwrap()
+        has_one = staking_vault,
     )]
-    x_token_depositor: Box<Account<'info, TokenAccount>>,
-    x_token_deposit_authority: Signer<'info>,
+    pool: Box<Account<'info, Pool>>,
+    #[account(mut)]
+    staking_vault: Box<Account<'info, TokenAccount>>,
 
+    // User.
     #[account(
         mut,
-        has_one = authority,
-        constraint = pool.paused,
+        has_one = owner,
+        has_one = pool,
+        seeds = [
+            owner.to_account_info().key.as_ref(),
+            pool.to_account_info().key.as_ref()
+        ],
+        bump = user.nonce,
     )]
-    pool: Box<Account<'info, Pool>>,
-    authority: Signer<'info>,
+    user: Box<Account<'info, User>>,
+    owner: Signer<'info>,
 
+    // Program signers.
     #[account(
         seeds = [
             pool.to_account_info().key.as_ref()
@@ -729,38 +3112,41 @@ pub struct Unpause<'info> {
         bump = pool.nonce,
     )]
     pool_signer: UncheckedAccount<'info>,
+
+    // Misc.
     token_program: Program<'info, Token>,
+
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.nonce,
+        constraint = !config.global_freeze @ ErrorCode::GlobalFreeze,
+    )]
+    config: Box<Account<'info, ProgramConfig>>,
 }
 
 #[derive(Accounts)]
-pub struct Stake<'info> {
+pub struct ClaimFor<'info> {
     // Global accounts for the staking instance.
     #[account(
         mut,
         has_one = staking_vault,
     )]
     pool: Box<Account<'info, Pool>>,
-    #[account(
-        mut,
-        constraint = staking_vault.owner == *pool_signer.key,
-    )]
+    #[account(mut)]
     staking_vault: Box<Account<'info, TokenAccount>>,
 
     // User.
     #[account(
         mut,
-        has_one = owner,
         has_one = pool,
         seeds = [
-            owner.key.as_ref(),
+            user.owner.as_ref(),
             pool.to_account_info().key.as_ref()
         ],
         bump = user.nonce,
     )]
     user: Box<Account<'info, User>>,
-    owner: Signer<'info>,
-    #[account(mut)]
-    stake_from_account: Box<Account<'info, TokenAccount>>,
+    claimer: Signer<'info>,
 
     // Program signers.
     #[account(
@@ -773,45 +3159,38 @@ pub struct Stake<'info> {
 
     // Misc.
     token_program: Program<'info, Token>,
+
+    #[account(
+        seeds = [CONFIG_SEED],
+        bump = config.nonce,
+        constraint = !config.global_freeze @ ErrorCode::GlobalFreeze,
+    )]
+    config: Box<Account<'info, ProgramConfig>>,
 }
 
 #[derive(Accounts)]
-pub struct FunderChange<'info> {
-    // Global accounts for the staking instance.
+pub struct SetRewardRecipient<'info> {
     #[account(
         mut,
-        has_one = authority,
+        has_one = owner,
     )]
-    pool: Box<Account<'info, Pool>>,
-    authority: Signer<'info>,
+    user: Box<Account<'info, User>>,
+    owner: Signer<'info>,
 }
 
 #[derive(Accounts)]
-pub struct Fund<'info> {
-    // Global accounts for the staking instance.
+pub struct SweepDonatedStake<'info> {
     #[account(
         mut,
+        has_one = authority,
         has_one = staking_vault,
-        has_one = reward_a_vault,
-        has_one = reward_b_vault,
-        constraint = !pool.paused,
     )]
     pool: Box<Account<'info, Pool>>,
+    authority: Signer<'info>,
     #[account(mut)]
     staking_vault: Box<Account<'info, TokenAccount>>,
     #[account(mut)]
-    reward_a_vault: Box<Account<'info, TokenAccount>>,
-    #[account(mut)]
-    reward_b_vault: Box<Account<'info, TokenAccount>>,
-    #[account(
-        //require signed funder auth - otherwise constant micro fund could hold funds hostage
-        constraint = funder.key() == pool.authority || pool.funders.iter().any(|x| *x == funder.key()),
-    )]
-    funder: Signer<'info>,
-    #[account(mut)]
-    from_a: Box<Account<'info, TokenAccount>>,
-    #[account(mut)]
-    from_b: Box<Account<'info, TokenAccount>>,
+    receiver: Box<Account<'info, TokenAccount>>,
 
     // Program signers.
     #[account(
@@ -827,39 +3206,19 @@ pub struct Fund<'info> {
 }
 
 #[derive(Accounts)]
-pub struct ClaimReward<'info> {
-    // Global accounts for the staking instance.
+pub struct SweepUnallocatedRewards<'info> {
     #[account(
         mut,
+        has_one = authority,
         has_one = staking_vault,
-        has_one = reward_a_vault,
-        has_one = reward_b_vault,
     )]
     pool: Box<Account<'info, Pool>>,
-    #[account(mut)]
+    authority: Signer<'info>,
     staking_vault: Box<Account<'info, TokenAccount>>,
     #[account(mut)]
-    reward_a_vault: Box<Account<'info, TokenAccount>>,
-    #[account(mut)]
-    reward_b_vault: Box<Account<'info, TokenAccount>>,
-
-    // User.
-    #[account(
-        mut,
-        has_one = owner,
-        has_one = pool,
-        seeds = [
-            owner.to_account_info().key.as_ref(),
-            pool.to_account_info().key.as_ref()
-        ],
-        bump = user.nonce,
-    )]
-    user: Box<Account<'info, User>>,
-    owner: Signer<'info>,
-    #[account(mut)]
-    reward_a_account: Box<Account<'info, TokenAccount>>,
+    reward_vault: Box<Account<'info, TokenAccount>>,
     #[account(mut)]
-    reward_b_account: Box<Account<'info, TokenAccount>>,
+    receiver: Box<Account<'info, TokenAccount>>,
 
     // Program signers.
     #[account(
@@ -889,8 +3248,11 @@ pub struct CloseUser<'info> {
         ],
         bump = user.nonce,
         constraint = user.balance_staked == 0,
+        constraint = user.balance_unbonding == 0,
+        constraint = user.penalty_reward_pending == 0,
         constraint = user.reward_a_per_token_pending == 0,
         constraint = user.reward_b_per_token_pending == 0,
+        constraint = user.reward_per_token_pending.iter().all(|x| *x == 0),
     )]
     user: Account<'info, User>,
     owner: Signer<'info>,
@@ -915,7 +3277,7 @@ pub struct ClosePool<'info> {
         has_one = reward_b_vault,
         constraint = pool.paused,
         constraint = pool.reward_duration_end > 0,
-        constraint = pool.reward_duration_end < sysvar::clock::Clock::get().unwrap().unix_timestamp.try_into().unwrap(),
+        constraint = pool.reward_duration_end < current_timestamp()?,
         constraint = pool.user_stake_count == 0,
     )]
     pool: Account<'info, Pool>,
@@ -938,7 +3300,85 @@ pub struct ClosePool<'info> {
     token_program: Program<'info, Token>,
 }
 
+#[derive(Accounts)]
+pub struct ClosePoolV3<'info> {
+    #[account(mut)]
+    refundee: UncheckedAccount<'info>,
+    #[account(mut)]
+    staking_refundee: Box<Account<'info, TokenAccount>>,
+    #[account(
+        mut,
+        close = refundee,
+        has_one = authority,
+        has_one = staking_vault,
+        constraint = pool.paused,
+        constraint = pool.reward_duration_end > 0,
+        constraint = pool.reward_duration_end < current_timestamp()?,
+        constraint = pool.user_stake_count == 0,
+    )]
+    pool: Account<'info, Pool>,
+    authority: Signer<'info>,
+    #[account(mut,
+        constraint = staking_vault.amount == 0,
+    )]
+    staking_vault: Box<Account<'info, TokenAccount>>,
+    #[account(
+        seeds = [
+            pool.to_account_info().key.as_ref()
+        ],
+        bump = pool.nonce,
+    )]
+    pool_signer: UncheckedAccount<'info>,
+    token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct MigratePool<'info> {
+    #[account(
+        mut,
+        owner = crate::ID,
+    )]
+    pool: UncheckedAccount<'info>,
+    #[account(mut)]
+    payer: Signer<'info>,
+    system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateUser<'info> {
+    #[account(
+        mut,
+        owner = crate::ID,
+    )]
+    user: UncheckedAccount<'info>,
+    #[account(mut)]
+    payer: Signer<'info>,
+    system_program: Program<'info, System>,
+}
+
+/// Settings shared by every pool of the program, at the `CONFIG_SEED` address
+#[account]
+#[derive(Default)]
+pub struct ProgramConfig {
+    /// Account allowed to update the config.
+    pub admin: Pubkey,
+    /// Mint of the token deposited to initialize or unpause a pool.
+    pub deposit_mint: Pubkey,
+    /// The amount deposited to initialize or unpause a pool.
+    pub deposit_requirement: u64,
+    /// The shortest reward period a pool may be funded over.
+    pub min_duration: u64,
+    /// Bump of the config address.
+    pub nonce: u8,
+    /// Stops staking, funding and claiming in every pool while set. Users may still
+    /// unstake.
+    pub global_freeze: bool,
+    /// Account proposed to take over as admin.
+    pub pending_admin: Pubkey,
+}
+
 #[account]
+#[derive(Default)]
 pub struct Pool {
     /// Priviledged account.
     pub authority: Pubkey,
@@ -982,8 +3422,85 @@ pub struct Pool {
     pub funders: [Pubkey; 4],
     //the version of the pool
     pub version: PoolVersion,
+    /// Total amount staked by users. Tokens sent directly to the
+    /// staking vault are not included.
+    pub total_staked: u64,
+    /// Whether total staked is tracked; pools created before it existed
+    /// seed it from the staking vault on first use.
+    pub total_staked_tracked: bool,
+    //trailer for future use
+    pub trailer: [u8; 22],
+    /// Reward streams of a multi reward pool, length of MAX_REWARDS.
+    /// Pools prior to V3 use the reward A and B fields instead.
+    pub rewards: [RewardInfo; 8],
+    /// Amount of reward A of the queued reward period.
+    pub scheduled_reward_a_amount: u64,
+    /// Amount of reward B of the queued reward period.
+    pub scheduled_reward_b_amount: u64,
+    /// The timestamp at which the queued reward period starts, 0 when none is queued.
+    pub scheduled_start_ts: u64,
+    /// The timestamp at which the queued reward period ends.
+    pub scheduled_end_ts: u64,
+    /// Shortest period stake may be locked for, V5 pools only.
+    pub min_lock_duration: u64,
+    /// Longest period stake may be locked for, earning the max boost, V5 pools only.
+    pub max_lock_duration: u64,
+    /// Boost to the weight of stake locked for the longest period, in basis points.
+    pub max_lock_boost_bps: u16,
+    /// Total boost weight of users' locks, rewards are shared by total staked plus this.
+    pub total_boost_weight: u64,
+    /// The period unstaked tokens unbond for before they can be withdrawn.
+    pub unbonding_period: u64,
+    /// Total amount unbonding, held in the staking vault but not earning.
+    pub total_unbonding: u64,
+    /// Penalty on unstaking right after staking, in basis points of the amount unstaked.
+    pub early_exit_penalty_bps: u16,
+    /// The period after staking over which the early exit penalty decays to zero.
+    pub early_exit_penalty_window: u64,
+    /// Token account receiving early exit penalties, when default they are shared by stakers.
+    pub penalty_treasury: Pubkey,
+    /// Last calculated early exit penalty reward per pool token.
+    pub penalty_per_token_stored: u128,
+    /// Total early exit penalty rewards unclaimed, held in the staking vault.
+    pub total_penalty_rewards: u64,
+    /// Authority proposed by the current one, default when no transfer is pending.
+    /// Appended rather than taken from the trailer, which at 22 bytes cannot hold a pubkey.
+    pub pending_authority: Pubkey,
+    /// Rewards earned by users and not yet claimed, rounded up, per reward in the order
+    /// the pool holds them.
+    pub reward_owed: [u64; 8],
+    /// A bit per reward, set once `reward_owed` accounts for all the reward owes. Pools
+    /// created before it was tracked seed it from the reward vault when first swept.
+    pub reward_owed_tracked: u8,
+    /// Who may open a user account on the pool.
+    pub gate: PoolGate,
+    /// The key `gate` checks against; an authority, Merkle root, mint or collection mint.
+    pub gate_key: Pubkey,
+    /// The most a user may stake, zero for no cap.
+    pub max_stake_per_user: u64,
+    /// The most staked in the pool in total, zero for no cap.
+    pub max_total_stake: u64,
+}
+
+/// A reward token emitted by a multi reward pool
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct RewardInfo {
+    /// Mint of the reward token.
+    pub mint: Pubkey,
+    /// Vault to store reward tokens.
+    pub vault: Pubkey,
+    /// Rate of reward distribution.
+    pub rate: u64,
+    /// Last calculated reward per pool token.
+    pub per_token_stored: u128,
+    /// Duration of the reward's distribution, V4 pools only.
+    pub reward_duration: u64,
+    /// Timestamp of the reward's distribution end, V4 pools only.
+    pub reward_duration_end: u64,
+    /// Last time the reward's distribution was updated, V4 pools only.
+    pub last_update_time: u64,
     //trailer for future use
-    pub trailer: [u8; 31],
+    pub trailer: [u8; 8],
 }
 
 #[account]
@@ -1005,6 +3522,28 @@ pub struct User {
     pub balance_staked: u64,
     /// Signer nonce.
     pub nonce: u8,
+    /// The amount of each multi reward pool reward claimed, length of MAX_REWARDS.
+    pub reward_per_token_complete: [u128; 8],
+    /// The amount of each multi reward pool reward pending claim, length of MAX_REWARDS.
+    pub reward_per_token_pending: [u64; 8],
+    /// The amount of balance staked that is locked.
+    pub balance_locked: u64,
+    /// The timestamp at which locked stake unlocks.
+    pub lock_end_ts: u64,
+    /// Reward weight added by the lock, on top of the amount staked.
+    pub boost_weight: u64,
+    /// The amount unstaked that is unbonding.
+    pub balance_unbonding: u64,
+    /// The timestamp at which unbonding tokens can be withdrawn.
+    pub unbonding_end_ts: u64,
+    /// The time the balance staked was staked, averaged by amount.
+    pub stake_ts: u64,
+    /// The amount of early exit penalty rewards claimed.
+    pub penalty_per_token_complete: u128,
+    /// The amount of early exit penalty rewards pending claim.
+    pub penalty_reward_pending: u64,
+    /// The wallet rewards claimed on the user's behalf are paid to, the owner when default.
+    pub reward_recipient: Pubkey,
 }
 
 #[error]
@@ -1017,7 +3556,7 @@ pub enum ErrorCode {
     SingleStakeTokenBCannotBeFunded,
     #[msg("Pool is paused.")]
     PoolPaused,
-    #[msg("Duration cannot be shorter than one day.")]
+    #[msg("Duration is shorter than the program's minimum.")]
     DurationTooShort,
     #[msg("Provided funder is already authorized to fund.")]
     FunderAlreadyAuthorized,
@@ -1027,15 +3566,455 @@ pub enum ErrorCode {
     CannotDeauthorizePoolAuthority,
     #[msg("Authority not found for deauthorization.")]
     CannotDeauthorizeMissingAuthority,
+    #[msg("There are no donated staking tokens to sweep.")]
+    NoDonatedStakeToSweep,
+    #[msg("Instruction is not supported by this pool version.")]
+    PoolVersionNotSupported,
+    #[msg("Reward has already been added to the pool.")]
+    RewardAlreadyAdded,
+    #[msg("Maximum rewards already added.")]
+    MaxRewards,
+    #[msg("Reward index is not a reward of the pool.")]
+    InvalidRewardIndex,
+    #[msg("Reward accounts do not match the pool's rewards.")]
+    InvalidRewardAccounts,
+    #[msg("Account is not a migratable account of this program.")]
+    InvalidAccountToMigrate,
+    #[msg("Reward period must start now or later and end after it starts.")]
+    InvalidRewardPeriod,
+    #[msg("A reward period is already scheduled.")]
+    RewardPeriodAlreadyScheduled,
+    #[msg("Reward period has ended, fund to start a new one.")]
+    RewardPeriodEnded,
+    #[msg("Lock duration is outside of the pool's lock durations.")]
+    InvalidLockDuration,
+    #[msg("Insufficient unlocked stake to lock.")]
+    InsufficientStakeToLock,
+    #[msg("Lock cannot end earlier than the current lock.")]
+    LockCannotBeShortened,
+    #[msg("Stake is locked.")]
+    StakeLocked,
+    #[msg("Lock has not expired.")]
+    LockNotExpired,
+    #[msg("Unbonding period has not passed.")]
+    UnbondingNotComplete,
+    #[msg("Early exit penalty must be at most 10000 bps, with a window if set.")]
+    InvalidEarlyExitPenalty,
+    #[msg("Penalty treasury must be the pool's, a token account of its staking mint.")]
+    InvalidPenaltyTreasury,
+    #[msg("Pool has no reward of the staking token to compound.")]
+    NoCompoundableReward,
+    #[msg("Math operation overflowed.")]
+    MathOverflow,
+    #[msg("Reward rate is too high, fund less or over a longer duration.")]
+    RateTooHigh,
+    #[msg("Clock is behind the pool's last update.")]
+    ClockWentBackwards,
+    #[msg("New authority must differ from the current one.")]
+    InvalidNewAuthority,
+    #[msg("No authority transfer is pending.")]
+    NoPendingAuthority,
+    #[msg("Signer is not the pending authority.")]
+    NotPendingAuthority,
+    #[msg("There are no unallocated rewards to sweep.")]
+    NoUnallocatedRewardsToSweep,
+    #[msg("Staking vault cannot be swept as a reward vault.")]
+    CannotSweepStakingVault,
+    #[msg("A gated pool needs a gate key.")]
+    InvalidGate,
+    #[msg("Owner is not allowed to join this gated pool.")]
+    NotAllowedToJoin,
+    #[msg("Stake would exceed the pool's cap per user.")]
+    UserStakeCapExceeded,
+    #[msg("Stake would exceed the pool's total stake cap.")]
+    PoolStakeCapExceeded,
+    #[msg("Signer is not the program's upgrade authority.")]
+    NotUpgradeAuthority,
+    #[msg("Program is frozen, only exits are allowed.")]
+    GlobalFreeze,
+    #[msg("Staking vault holds less than the pool's stake, unbonding and penalty rewards.")]
+    StakingVaultShortfall,
+    #[msg("Lock boost must be at most 40000 bps.")]
+    InvalidLockBoost,
+}
+
+impl Pool {
+    /// The reward streams added to a multi reward pool
+    pub fn reward_infos(&self) -> &[RewardInfo] {
+        let count = self
+            .rewards
+            .iter()
+            .take_while(|r| r.vault != Pubkey::default())
+            .count();
+        &self.rewards[..count]
+    }
+
+    /// Stores the reward per token calculated for each reward
+    pub fn set_reward_per_token_stored(&mut self, reward_per_token: &[u128]) {
+        if self.version.is_multi_reward() {
+            for (reward, stored) in self.rewards.iter_mut().zip(reward_per_token) {
+                reward.per_token_stored = *stored;
+            }
+        } else {
+            self.reward_a_per_token_stored = reward_per_token[0];
+            if self.reward_a_vault != self.reward_b_vault {
+                self.reward_b_per_token_stored = reward_per_token[1];
+            }
+        }
+    }
+
+    /// The reward per token stored for each reward
+    pub fn reward_per_token_stored(&self) -> Vec<u128> {
+        if self.version.is_multi_reward() {
+            self.reward_infos()
+                .iter()
+                .map(|r| r.per_token_stored)
+                .collect()
+        } else {
+            vec![
+                self.reward_a_per_token_stored,
+                self.reward_b_per_token_stored,
+            ]
+        }
+    }
+
+    /// The vault holding each reward
+    pub fn reward_vaults(&self) -> Vec<Pubkey> {
+        if self.version.is_multi_reward() {
+            self.reward_infos().iter().map(|r| r.vault).collect()
+        } else {
+            vec![self.reward_a_vault, self.reward_b_vault]
+        }
+    }
+
+    /// The mint of each reward
+    pub fn reward_mints(&self) -> Vec<Pubkey> {
+        if self.version.is_multi_reward() {
+            self.reward_infos().iter().map(|r| r.mint).collect()
+        } else {
+            vec![self.reward_a_mint, self.reward_b_mint]
+        }
+    }
+
+    /// Adds what stakers earned as the reward per token rose from `per_token_before` to
+    /// the rewards owed, rounding up so that the sum of users' earnings never exceeds it
+    pub fn add_reward_owed(&mut self, per_token_before: &[u128]) -> Result<()> {
+        let total_weight = u128::from(self.total_staked) + u128::from(self.total_boost_weight);
+        let per_token_after = self.reward_per_token_stored();
+        for (i, (before, after)) in per_token_before.iter().zip(per_token_after).enumerate() {
+            let earned = U192::from(after.checked_sub(*before).ok_or(ErrorCode::MathOverflow)?)
+                .checked_mul(total_weight.into())
+                .ok_or(ErrorCode::MathOverflow)?
+                .checked_add((PRECISION - 1).into())
+                .ok_or(ErrorCode::MathOverflow)?
+                .checked_div(PRECISION.into())
+                .ok_or(ErrorCode::MathOverflow)?;
+            //an amount too large to owe leaves nothing to sweep
+            let earned = earned.try_into().unwrap_or(u64::MAX);
+            self.reward_owed[i] = self.reward_owed[i].saturating_add(earned);
+        }
+
+        Ok(())
+    }
+
+    /// Checks staking `amount` more onto `balance_staked` keeps the user and the pool
+    /// within the stake caps that are set
+    pub fn check_stake_caps(&self, balance_staked: u64, amount: u64) -> Result<()> {
+        let exceeds = |staked: u64, cap: u64| cap > 0 && staked.saturating_add(amount) > cap;
+        if exceeds(balance_staked, self.max_stake_per_user) {
+            return Err(ErrorCode::UserStakeCapExceeded.into());
+        }
+        if exceeds(self.total_staked, self.max_total_stake) {
+            return Err(ErrorCode::PoolStakeCapExceeded.into());
+        }
+
+        Ok(())
+    }
+
+    /// Settles a claim of `amount` of a reward against the rewards owed
+    pub fn settle_reward_owed(&mut self, reward_index: usize, amount: u64) {
+        self.reward_owed[reward_index] = self.reward_owed[reward_index].saturating_sub(amount);
+    }
+
+    /// Starts a new reward period at `current_time`, funded with an amount per reward given
+    /// the reward vaults' balances before funding. Remaining rewards are spread over the new
+    /// period, which ends a reward duration from now; on a V4 pool only the funded rewards
+    /// are rescheduled. Rewards must be settled up to `current_time`. **This may upgrade
+    /// the pool**, see `rate_after_funding`.
+    pub fn start_reward_period(
+        &mut self,
+        reward_vault_amounts: &[u64],
+        amounts: &[u64],
+        current_time: u64,
+    ) -> Result<()> {
+        let calc = get_calculator(self);
+        let rates = calc.rate_after_funding(self, reward_vault_amounts, amounts, current_time)?;
+        if self.version.is_multi_reward() {
+            for (reward, rate) in self.rewards.iter_mut().zip(rates) {
+                reward.rate = rate;
+            }
+        } else {
+            self.reward_a_rate = rates[0];
+            self.reward_b_rate = rates[1];
+        }
+
+        self.last_update_time = current_time;
+        if self.version.has_independent_schedules() {
+            for (reward, _) in self
+                .rewards
+                .iter_mut()
+                .zip(amounts)
+                .filter(|(_, a)| **a > 0)
+            {
+                reward.last_update_time = current_time;
+                reward.reward_duration_end = current_time
+                    .checked_add(reward.reward_duration)
+                    .ok_or(ErrorCode::MathOverflow)?;
+            }
+            //the pool ends when its last reward does
+            self.reward_duration_end = self
+                .reward_infos()
+                .iter()
+                .map(|r| r.reward_duration_end)
+                .max()
+                .unwrap_or(0);
+        } else {
+            self.reward_duration_end = current_time
+                .checked_add(self.reward_duration)
+                .ok_or(ErrorCode::MathOverflow)?;
+        }
+
+        Ok(())
+    }
+
+    /// Adds an amount per reward to the current reward period, keeping its end date.
+    /// Rewards must be settled up to `current_time`.
+    pub fn top_up_reward_period(&mut self, amounts: &[u64], current_time: u64) -> Result<()> {
+        let calc = get_calculator(self);
+        let rates = calc.rate_after_top_up(self, amounts, current_time)?;
+        self.reward_a_rate = rates[0];
+        self.reward_b_rate = rates[1];
+
+        Ok(())
+    }
+
+    /// The early exit penalty on unstaking `amount`, decaying linearly from the max penalty
+    /// at the user's stake time to nothing at the end of the penalty window. It is waived
+    /// when no stakers would remain to share it.
+    pub fn early_exit_penalty(&self, user: &User, amount: u64, current_time: u64) -> Result<u64> {
+        let elapsed = current_time.saturating_sub(user.stake_ts);
+        if self.early_exit_penalty_bps == 0 || elapsed >= self.early_exit_penalty_window {
+            return Ok(0);
+        }
+        if self.penalty_treasury == Pubkey::default() && self.total_staked == 0 {
+            return Ok(0);
+        }
+
+        (amount as u128)
+            .checked_mul(self.early_exit_penalty_bps.into())
+            .ok_or(ErrorCode::MathOverflow)?
+            .checked_mul((self.early_exit_penalty_window - elapsed).into())
+            .ok_or(ErrorCode::MathOverflow)?
+            .checked_div(self.early_exit_penalty_window.into())
+            .ok_or(ErrorCode::MathOverflow)?
+            .checked_div(BASIS_POINTS.into())
+            .ok_or(ErrorCode::MathOverflow)?
+            .try_into()
+            .map_err(|_| ErrorCode::MathOverflow.into()) //back to u64
+    }
+
+    /// Shares an early exit penalty, held in the staking vault, among the staked tokens.
+    /// Only the amount the shares add up to is owed, the rounding remainder is left in the
+    /// vault for `sweep_donated_stake`.
+    pub fn distribute_penalty(&mut self, penalty: u64) -> Result<()> {
+        let per_token = (penalty as u128)
+            .checked_mul(PRECISION)
+            .ok_or(ErrorCode::MathOverflow)?
+            .checked_div(self.total_staked.into())
+            .ok_or(ErrorCode::MathOverflow)?;
+        let distributed: u64 = per_token
+            .checked_mul(self.total_staked.into())
+            .ok_or(ErrorCode::MathOverflow)?
+            .checked_div(PRECISION)
+            .ok_or(ErrorCode::MathOverflow)?
+            .try_into()
+            .map_err(|_| ErrorCode::MathOverflow)?;
+
+        self.penalty_per_token_stored = self
+            .penalty_per_token_stored
+            .checked_add(per_token)
+            .ok_or(ErrorCode::MathOverflow)?;
+        self.total_penalty_rewards = self
+            .total_penalty_rewards
+            .checked_add(distributed)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        Ok(())
+    }
+
+    /// Marks rewards as updated up to the current time, or the end of their reward period
+    pub fn set_last_update_time(&mut self, current_time: u64) {
+        self.last_update_time = last_time_reward_applicable(current_time, self.reward_duration_end);
+        if self.version.has_independent_schedules() {
+            for reward in self.rewards.iter_mut() {
+                reward.last_update_time =
+                    last_time_reward_applicable(current_time, reward.reward_duration_end);
+            }
+        }
+    }
+}
+
+impl User {
+    /// Stores the amount earned for each reward as pending, and marks the pool's
+    /// current reward per token as complete
+    pub fn set_reward_earned(&mut self, pool: &Pool, earned: &[u64]) {
+        if pool.version.is_multi_reward() {
+            for (i, (reward, pending)) in pool.rewards.iter().zip(earned).enumerate() {
+                self.reward_per_token_pending[i] = *pending;
+                self.reward_per_token_complete[i] = reward.per_token_stored;
+            }
+        } else {
+            self.reward_a_per_token_pending = earned[0];
+            self.reward_a_per_token_complete = pool.reward_a_per_token_stored;
+
+            self.reward_b_per_token_pending = earned[1];
+            self.reward_b_per_token_complete = pool.reward_b_per_token_stored;
+        }
+    }
+
+    /// Stores the user's share of early exit penalties as pending
+    pub fn set_penalty_reward_earned(&mut self, pool: &Pool) -> Result<()> {
+        self.penalty_reward_pending = earned_amount(
+            self.balance_staked.into(),
+            pool.penalty_per_token_stored,
+            self.penalty_per_token_complete,
+            self.penalty_reward_pending,
+        )?;
+        self.penalty_per_token_complete = pool.penalty_per_token_stored;
+
+        Ok(())
+    }
+
+    /// Averages the stake time of the balance staked with `amount` staked now
+    pub fn record_stake(&mut self, amount: u64, current_time: u64) -> Result<()> {
+        let balance_staked = self
+            .balance_staked
+            .checked_add(amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+        self.stake_ts = (self.balance_staked as u128)
+            .checked_mul(self.stake_ts.into())
+            .ok_or(ErrorCode::MathOverflow)?
+            .checked_add(
+                (amount as u128)
+                    .checked_mul(current_time.into())
+                    .ok_or(ErrorCode::MathOverflow)?,
+            )
+            .ok_or(ErrorCode::MathOverflow)?
+            .checked_div(balance_staked.into())
+            .ok_or(ErrorCode::MathOverflow)?
+            .try_into()
+            .map_err(|_| ErrorCode::MathOverflow)?;
+
+        Ok(())
+    }
+
+    /// The weight of the user's share of rewards, the amount staked plus any lock boost
+    pub fn reward_weight(&self) -> u128 {
+        u128::from(self.balance_staked) + u128::from(self.boost_weight)
+    }
+
+    /// Whether the user has locked stake whose lock has ended by `current_time`
+    pub fn lock_expired(&self, current_time: u64) -> bool {
+        self.balance_locked > 0 && self.lock_end_ts <= current_time
+    }
+
+    /// The wallet rewards claimed on the user's behalf are paid to
+    pub fn reward_wallet(&self) -> Pubkey {
+        if self.reward_recipient == Pubkey::default() {
+            self.owner
+        } else {
+            self.reward_recipient
+        }
+    }
+
+    /// The amount pending claim of the pool's reward at `reward_index`
+    pub fn reward_pending_mut(&mut self, pool: &Pool, reward_index: usize) -> &mut u64 {
+        if pool.version.is_multi_reward() {
+            &mut self.reward_per_token_pending[reward_index]
+        } else if reward_index == 0 {
+            &mut self.reward_a_per_token_pending
+        } else {
+            &mut self.reward_b_per_token_pending
+        }
+    }
+
+    /// Drops the user's rewards, releasing them from what the pool owes, and marks the
+    /// pool's stored rewards per token as complete. Rewards are settled up to the pool's
+    /// stored rewards per token, without accruing any since its last update; if settling
+    /// overflows only the already pending rewards are released, so stake can still leave.
+    pub fn forfeit_rewards(&mut self, pool: &mut Pool) {
+        if let Ok(earned) = get_calculator(pool).user_earned_amount(pool, self) {
+            self.set_reward_earned(pool, &earned);
+        }
+        //on overflow the penalty reward pending is left as it was
+        let _ = self.set_penalty_reward_earned(pool);
+
+        if pool.version.is_multi_reward() {
+            for (i, reward) in pool.rewards.iter().enumerate() {
+                self.reward_per_token_complete[i] = reward.per_token_stored;
+            }
+            for (i, pending) in self.reward_per_token_pending.iter_mut().enumerate() {
+                pool.settle_reward_owed(i, *pending);
+                *pending = 0;
+            }
+        } else {
+            pool.settle_reward_owed(0, self.reward_a_per_token_pending);
+            pool.settle_reward_owed(1, self.reward_b_per_token_pending);
+            self.reward_a_per_token_pending = 0;
+            self.reward_a_per_token_complete = pool.reward_a_per_token_stored;
+            self.reward_b_per_token_pending = 0;
+            self.reward_b_per_token_complete = pool.reward_b_per_token_stored;
+        }
+
+        pool.total_penalty_rewards = pool
+            .total_penalty_rewards
+            .saturating_sub(self.penalty_reward_pending);
+        self.penalty_reward_pending = 0;
+        self.penalty_per_token_complete = pool.penalty_per_token_stored;
+    }
+
+    /// Unlocks the user's locked stake, whether or not the lock has ended, and removes its
+    /// boost from the pool without failing. For exits that forfeit rewards.
+    pub fn forfeit_lock(&mut self, pool: &mut Pool) {
+        pool.total_boost_weight = pool.total_boost_weight.saturating_sub(self.boost_weight);
+        self.balance_locked = 0;
+        self.lock_end_ts = 0;
+        self.boost_weight = 0;
+    }
+
+    /// Unlocks the user's locked stake and removes its boost from the pool.
+    /// Rewards must be updated first.
+    pub fn release_lock(&mut self, pool: &mut Pool) -> Result<()> {
+        pool.total_boost_weight = pool
+            .total_boost_weight
+            .checked_sub(self.boost_weight)
+            .ok_or(ErrorCode::MathOverflow)?;
+        self.balance_locked = 0;
+        self.lock_end_ts = 0;
+        self.boost_weight = 0;
+
+        Ok(())
+    }
 }
 
 impl Debug for Pool {
     /// writes a subset of pool fields for debugging
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::result::Result<(), std::fmt::Error> {
         if cfg!(feature = "verbose") {
-            write!(f, "version: {:?} paused: {} reward_duration: {} reward_duration_end: {} reward_a_rate: {} reward_b_rate: {} reward_a_per_token_stored {} reward_b_per_token_stored {}",
+            write!(f, "version: {:?} paused: {} total_staked: {} reward_duration: {} reward_duration_end: {} reward_a_rate: {} reward_b_rate: {} reward_a_per_token_stored {} reward_b_per_token_stored {}",
                 self.version,
                 self.paused,
+                self.total_staked,
                 self.reward_duration,
                 self.reward_duration_end,
                 self.reward_a_rate,
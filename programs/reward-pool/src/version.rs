@@ -12,17 +12,104 @@ pub enum PoolVersion {
     V1 = 0,
     /// a V2 pool uses the rate field as a ANNUAL lamport rate
     V2 = 2,
+    /// a V3 pool holds up to `MAX_REWARDS` reward streams in `Pool.rewards`, each with an
+    /// ANNUAL lamport rate, instead of the A and B reward fields
+    V3 = 3,
+    /// a V4 pool is a V3 pool where each reward stream has its own reward duration, period end
+    /// and last update time, so funding one reward does not change the schedule of the others
+    V4 = 4,
+    /// a V5 pool is a V2 pool where users may lock stake for a boosted reward weight,
+    /// rewards are shared by weight rather than the amount staked
+    V5 = 5,
+}
+
+impl Default for PoolVersion {
+    /// zeroed account data is a V1 pool
+    fn default() -> Self {
+        PoolVersion::V1
+    }
+}
+
+impl PoolVersion {
+    /// Whether the pool emits from its `rewards` streams rather than the A and B reward fields
+    pub fn is_multi_reward(&self) -> bool {
+        matches!(self, PoolVersion::V3 | PoolVersion::V4)
+    }
+
+    /// Whether the pool emits reward A and B at an ANNUAL lamport rate
+    pub fn has_annual_rate(&self) -> bool {
+        matches!(self, PoolVersion::V2 | PoolVersion::V5)
+    }
+
+    /// Whether each of the pool's `rewards` streams runs on its own schedule
+    pub fn has_independent_schedules(&self) -> bool {
+        *self == PoolVersion::V4
+    }
 }
 
 impl Pool {
     /// Will upgrade the pool if an upgrade is available and able to be done
-    pub fn upgrade_if_needed(&mut self) {
+    pub fn upgrade_if_needed(&mut self) -> Result<()> {
         if self.version == PoolVersion::V1 {
-            self.reward_a_rate = self.reward_a_rate.checked_mul(SECONDS_IN_YEAR).unwrap();
-            self.reward_b_rate = self.reward_b_rate.checked_mul(SECONDS_IN_YEAR).unwrap();
+            self.reward_a_rate = self
+                .reward_a_rate
+                .checked_mul(SECONDS_IN_YEAR)
+                .ok_or(ErrorCode::RateTooHigh)?;
+            self.reward_b_rate = self
+                .reward_b_rate
+                .checked_mul(SECONDS_IN_YEAR)
+                .ok_or(ErrorCode::RateTooHigh)?;
             self.version = PoolVersion::V2;
 
             msg!("pool upgraded to v2");
         }
+
+        Ok(())
+    }
+
+    /// Will begin tracking total staked if the pool was created before it was tracked.
+    /// Such pools seed it from the staking vault balance, which is what the reward
+    /// math used prior.
+    pub fn track_total_staked_if_needed(&mut self, staking_vault: &TokenAccount) {
+        if !self.total_staked_tracked {
+            self.total_staked = staking_vault.amount;
+            self.total_staked_tracked = true;
+
+            msg!("pool total staked tracked - {}", self.total_staked);
+        }
+    }
+
+    /// Starts emitting the queued reward period, any rewards remaining from the current
+    /// period are spread over the new one. Rewards must be settled up to its start.
+    pub fn activate_scheduled_period(&mut self) -> Result<()> {
+        let start_ts = self.scheduled_start_ts;
+        let end_ts = self.scheduled_end_ts;
+        let duration = end_ts
+            .checked_sub(start_ts)
+            .ok_or(ErrorCode::MathOverflow)?;
+
+        let rate = |amount: u64, rate: u64| -> Result<u64> {
+            annual_rate(amount, rate, self.reward_duration_end, duration, start_ts)
+        };
+        let reward_a_rate = rate(self.scheduled_reward_a_amount, self.reward_a_rate)?;
+        let reward_b_rate = rate(self.scheduled_reward_b_amount, self.reward_b_rate)?;
+
+        self.reward_a_rate = reward_a_rate;
+        self.reward_b_rate = reward_b_rate;
+        self.last_update_time = start_ts;
+        self.reward_duration_end = end_ts;
+
+        self.scheduled_reward_a_amount = 0;
+        self.scheduled_reward_b_amount = 0;
+        self.scheduled_start_ts = 0;
+        self.scheduled_end_ts = 0;
+
+        msg!(
+            "scheduled reward period started - {} to {}",
+            start_ts,
+            end_ts
+        );
+
+        Ok(())
     }
 }
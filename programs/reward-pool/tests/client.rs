@@ -0,0 +1,110 @@
+mod harness;
+
+use reward_pool::calculator::pending_rewards;
+use reward_pool::{Pool, User};
+
+use harness::fixture::{PoolFixture, UserFixture};
+use harness::ProgramTest;
+
+const DAY: u64 = 86_400;
+
+fn staked_user(t: &mut ProgramTest, f: &PoolFixture, amount: u64) -> UserFixture {
+    let u = f.create_wallet(t, amount, 0);
+    t.process(f.create_user(&u)).unwrap();
+    t.process(f.stake(&u, amount)).unwrap();
+    u
+}
+
+/// The rewards predicted from the accounts as they are now, for a claim at `time`
+fn predict(t: &ProgramTest, f: &PoolFixture, u: &UserFixture, time: u64) -> Vec<u64> {
+    let pool: Pool = t.get_account(&f.pool);
+    let user: User = t.get_account(&u.user);
+    pending_rewards(&pool, &user, time).unwrap()
+}
+
+fn claimed(t: &ProgramTest, u: &UserFixture) -> Vec<u64> {
+    u.reward_accounts
+        .iter()
+        .map(|account| t.token_balance(account))
+        .collect()
+}
+
+#[test]
+fn pending_rewards_match_claims() {
+    let mut t = ProgramTest::new();
+    let f = PoolFixture::new(&mut t, 2);
+    t.process(f.initialize_pool(DAY, 0, 0, None)).unwrap();
+    let funder = f.create_wallet(&mut t, 0, 1_000_000_000);
+    t.process(f.authorize_funder(funder.owner)).unwrap();
+    let u = staked_user(&mut t, &f, 1_234);
+    staked_user(&mut t, &f, 5_678);
+
+    //amounts and times that do not divide evenly, across a scheduled period taking over
+    t.process(f.fund(&funder, 123_456_789, 98_765_432)).unwrap();
+    let start_ts = t.now() + DAY / 3;
+    t.process(f.fund_scheduled(&funder, 55_555_555, 0, start_ts, start_ts + 2 * DAY))
+        .unwrap();
+
+    let mut paid = vec![0, 0];
+    for time in [
+        t.now() + 1_001,
+        start_ts + 7,
+        start_ts + DAY + 13,
+        start_ts + 3 * DAY,
+    ] {
+        let pending = predict(&t, &f, &u, time);
+        t.warp_to(time);
+        t.process(f.claim(&u)).unwrap();
+        paid = paid.iter().zip(&pending).map(|(p, n)| p + n).collect();
+        assert_eq!(claimed(&t, &u), paid);
+    }
+}
+
+#[test]
+fn pending_rewards_match_claims_on_multi_reward_pools() {
+    let mut t = ProgramTest::new();
+    let f = PoolFixture::new(&mut t, 3);
+    t.process(f.initialize_pool_v4(DAY)).unwrap();
+    for i in 0..3 {
+        t.process(f.add_reward(i)).unwrap();
+    }
+    t.process(f.set_reward_duration(2, 3 * DAY)).unwrap();
+    let funder = f.create_wallet(&mut t, 0, 1_000_000_000);
+    t.process(f.authorize_funder(funder.owner)).unwrap();
+    let u = staked_user(&mut t, &f, 777);
+    staked_user(&mut t, &f, 3_333);
+
+    t.process(f.fund_v3(&funder, vec![11_111_111, 0, 333_333_333]))
+        .unwrap();
+    let pending = predict(&t, &f, &u, t.now() + DAY + DAY / 2);
+    t.warp_forward(DAY + DAY / 2);
+    t.process(f.claim_v3(&u, vec![0, 1, 2])).unwrap();
+    assert_eq!(claimed(&t, &u), pending);
+}
+
+#[test]
+fn pending_rewards_match_claims_with_lock_boosts() {
+    let mut t = ProgramTest::new();
+    let f = PoolFixture::new(&mut t, 2);
+    t.process(f.initialize_pool(DAY, 0, 0, None)).unwrap();
+    t.process(f.configure_lockup(DAY / 4, 4 * DAY, 5_000))
+        .unwrap();
+    let funder = f.create_wallet(&mut t, 0, 1_000_000_000);
+    t.process(f.authorize_funder(funder.owner)).unwrap();
+    let u = staked_user(&mut t, &f, 10_000);
+    staked_user(&mut t, &f, 4_321);
+    t.process(f.lock_stake(&u, 6_000, DAY / 2)).unwrap();
+
+    //the second claim is past the lock end, which releases the boost on the way
+    t.process(f.fund(&funder, 987_654_321, 0)).unwrap();
+    let mut paid = vec![0, 0];
+    for time in [t.now() + DAY / 7, t.now() + DAY - 3] {
+        let pending = predict(&t, &f, &u, time);
+        t.warp_to(time);
+        t.process(f.claim(&u)).unwrap();
+        paid = paid.iter().zip(&pending).map(|(p, n)| p + n).collect();
+        assert_eq!(claimed(&t, &u), paid);
+    }
+    let user: User = t.get_account(&u.user);
+    assert_eq!(user.boost_weight, 0);
+}
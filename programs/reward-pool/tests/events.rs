@@ -0,0 +1,558 @@
+mod harness;
+
+use anchor_lang::prelude::*;
+use anchor_lang::{Discriminator, Event};
+use reward_pool::events::*;
+use reward_pool::{Pool, PoolVersion, ProgramConfig, User};
+
+use harness::fixture::{
+    accept_config_admin, config_address, propose_config_admin, set_global_freeze, PoolFixture,
+};
+use harness::ProgramTest;
+
+const DAY: u64 = 86_400;
+/// Funding that emits exactly 1000 tokens a second over a day
+const FUNDING: u64 = 86_400_000;
+
+fn events(t: &ProgramTest) -> Vec<PoolEvent> {
+    decode_events(&t.logs())
+}
+
+#[test]
+fn pool_lifecycle_emits_events() {
+    let mut t = ProgramTest::new();
+    let f = PoolFixture::new(&mut t, 2);
+    t.process(f.initialize_pool(DAY, 0, 0, None)).unwrap();
+    assert_eq!(
+        events(&t),
+        vec![PoolEvent::PoolInitialized(PoolInitialized {
+            pool: f.pool,
+            authority: f.authority,
+            staking_mint: f.staking_mint,
+            reward_mints: f.reward_mints.clone(),
+            reward_duration: DAY,
+        })]
+    );
+
+    let funder = f.create_wallet(&mut t, 0, FUNDING);
+    t.process(f.authorize_funder(funder.owner)).unwrap();
+    assert_eq!(
+        events(&t),
+        vec![PoolEvent::FunderAuthorized(FunderAuthorized {
+            pool: f.pool,
+            funder: funder.owner,
+        })]
+    );
+
+    let u = f.create_wallet(&mut t, 1000, 0);
+    t.process(f.create_user(&u)).unwrap();
+    t.process(f.stake(&u, 1000)).unwrap();
+    assert_eq!(
+        events(&t),
+        vec![PoolEvent::Staked(Staked {
+            pool: f.pool,
+            user: u.user,
+            owner: u.owner,
+            amount: 1000,
+            balance_staked: 1000,
+            total_staked: 1000,
+            reward_a_per_token_stored: 0,
+            reward_b_per_token_stored: 0,
+        })]
+    );
+
+    t.process(f.fund(&funder, FUNDING, 0)).unwrap();
+    let pool: Pool = t.get_account(&f.pool);
+    assert_eq!(
+        events(&t),
+        vec![PoolEvent::Funded(Funded {
+            pool: f.pool,
+            funder: funder.owner,
+            amount_a: FUNDING,
+            amount_b: 0,
+            reward_a_rate: FUNDING * 365,
+            reward_b_rate: 0,
+            reward_a_per_token_stored: 0,
+            reward_b_per_token_stored: 0,
+            reward_duration_end: t.now() + DAY,
+        })]
+    );
+    assert_eq!(pool.reward_a_rate, FUNDING * 365);
+
+    t.warp_forward(DAY / 2);
+    t.process(f.claim(&u)).unwrap();
+    let pool: Pool = t.get_account(&f.pool);
+    assert_eq!(
+        events(&t),
+        vec![PoolEvent::Claimed(Claimed {
+            pool: f.pool,
+            user: u.user,
+            owner: u.owner,
+            amount_a: FUNDING / 2,
+            amount_b: 0,
+            reward_a_per_token_stored: pool.reward_a_per_token_stored,
+            reward_b_per_token_stored: 0,
+        })]
+    );
+
+    t.warp_forward(DAY);
+    t.process(f.unstake(&u, 1000, None)).unwrap();
+    let pool: Pool = t.get_account(&f.pool);
+    assert_eq!(
+        events(&t),
+        vec![PoolEvent::Unstaked(Unstaked {
+            pool: f.pool,
+            user: u.user,
+            owner: u.owner,
+            amount: 1000,
+            penalty: 0,
+            unbonding: false,
+            balance_staked: 0,
+            total_staked: 0,
+            reward_a_per_token_stored: pool.reward_a_per_token_stored,
+            reward_b_per_token_stored: 0,
+        })]
+    );
+    t.process(f.claim(&u)).unwrap();
+
+    t.process(f.deauthorize_funder(funder.owner)).unwrap();
+    assert_eq!(
+        events(&t),
+        vec![PoolEvent::FunderDeauthorized(FunderDeauthorized {
+            pool: f.pool,
+            funder: funder.owner,
+        })]
+    );
+
+    t.process(f.pause()).unwrap();
+    assert_eq!(
+        events(&t),
+        vec![PoolEvent::PoolPaused(PoolPaused { pool: f.pool })]
+    );
+
+    t.process(f.close_user(&u)).unwrap();
+    assert_eq!(
+        events(&t),
+        vec![PoolEvent::UserClosed(UserClosed {
+            pool: f.pool,
+            user: u.user,
+            owner: u.owner,
+        })]
+    );
+
+    let refundee = f.create_wallet(&mut t, 0, 0);
+    t.process(f.close_pool(&refundee)).unwrap();
+    assert_eq!(
+        events(&t),
+        vec![PoolEvent::PoolClosed(PoolClosed {
+            pool: f.pool,
+            refundee: refundee.owner,
+        })]
+    );
+}
+
+#[test]
+fn unpause_emits_event() {
+    let mut t = ProgramTest::new();
+    let mut f = PoolFixture::new(&mut t, 2);
+    t.process(f.initialize_pool(DAY, 0, 0, None)).unwrap();
+    t.process(f.pause()).unwrap();
+    f.renew_x_token_pool_vault(&mut t);
+
+    t.process(f.unpause()).unwrap();
+    assert_eq!(
+        events(&t),
+        vec![PoolEvent::PoolUnpaused(PoolUnpaused { pool: f.pool })]
+    );
+
+    //no events are decoded from transactions that fail
+    let u = f.create_wallet(&mut t, 1000, 0);
+    t.process(f.create_user(&u)).unwrap();
+    t.process(f.stake(&u, 1000)).unwrap();
+    assert!(t.process(f.unstake(&u, 2000, None)).is_err());
+    assert_eq!(events(&t), vec![]);
+    let user: User = t.get_account(&u.user);
+    assert_eq!(user.balance_staked, 1000);
+}
+
+#[test]
+fn funding_and_compounding_emit_events() {
+    let mut t = ProgramTest::new();
+    let f = PoolFixture::new(&mut t, 2).with_staking_reward_a(&mut t);
+    t.process(f.initialize_pool(DAY, 0, 0, None)).unwrap();
+    let funder = f.create_wallet(&mut t, 0, 3 * FUNDING);
+    t.process(f.authorize_funder(funder.owner)).unwrap();
+    let u = f.create_wallet(&mut t, 1000, 0);
+    t.process(f.create_user(&u)).unwrap();
+    t.process(f.stake(&u, 1000)).unwrap();
+    t.process(f.fund(&funder, FUNDING, 0)).unwrap();
+
+    t.warp_forward(DAY / 2);
+    t.process(f.top_up(&funder, FUNDING, 0)).unwrap();
+    let pool: Pool = t.get_account(&f.pool);
+    assert_eq!(
+        events(&t),
+        vec![PoolEvent::ToppedUp(ToppedUp {
+            pool: f.pool,
+            funder: funder.owner,
+            amount_a: FUNDING,
+            amount_b: 0,
+            reward_a_rate: pool.reward_a_rate,
+            reward_b_rate: 0,
+            reward_a_per_token_stored: pool.reward_a_per_token_stored,
+            reward_b_per_token_stored: 0,
+            reward_duration_end: t.now() + DAY / 2,
+        })]
+    );
+
+    let start_ts = t.now() + DAY;
+    t.process(f.fund_scheduled(&funder, FUNDING, 0, start_ts, start_ts + DAY))
+        .unwrap();
+    assert_eq!(
+        events(&t),
+        vec![PoolEvent::FundingScheduled(FundingScheduled {
+            pool: f.pool,
+            funder: funder.owner,
+            amount_a: FUNDING,
+            amount_b: 0,
+            start_ts,
+            end_ts: start_ts + DAY,
+        })]
+    );
+
+    t.process(f.compound(&u)).unwrap();
+    let pool: Pool = t.get_account(&f.pool);
+    assert_eq!(
+        events(&t),
+        vec![PoolEvent::Compounded(Compounded {
+            pool: f.pool,
+            user: u.user,
+            owner: u.owner,
+            amount: FUNDING / 2,
+            balance_staked: 1000 + FUNDING / 2,
+            total_staked: pool.total_staked,
+        })]
+    );
+    assert_eq!(pool.total_staked, 1000 + FUNDING / 2);
+}
+
+#[test]
+fn multi_reward_funding_and_claims_emit_events() {
+    let mut t = ProgramTest::new();
+    let f = PoolFixture::new(&mut t, 2);
+    t.process(f.initialize_pool_v3(DAY)).unwrap();
+    t.process(f.add_reward(0)).unwrap();
+    t.process(f.add_reward(1)).unwrap();
+    let funder = f.create_wallet(&mut t, 0, FUNDING);
+    t.process(f.authorize_funder(funder.owner)).unwrap();
+    let u = f.create_wallet(&mut t, 1000, 0);
+    t.process(f.create_user(&u)).unwrap();
+    t.process(f.stake(&u, 1000)).unwrap();
+
+    t.process(f.fund_v3(&funder, vec![FUNDING, 0])).unwrap();
+    assert_eq!(
+        events(&t),
+        vec![PoolEvent::FundedV3(FundedV3 {
+            pool: f.pool,
+            funder: funder.owner,
+            amounts: vec![FUNDING, 0],
+            reward_rates: vec![FUNDING * 365, 0],
+            reward_per_token_stored: vec![0, 0],
+        })]
+    );
+
+    t.warp_forward(DAY / 2);
+    t.process(f.claim_v3(&u, vec![1, 0])).unwrap();
+    let pool: Pool = t.get_account(&f.pool);
+    assert_eq!(
+        events(&t),
+        vec![PoolEvent::ClaimedV3(ClaimedV3 {
+            pool: f.pool,
+            user: u.user,
+            owner: u.owner,
+            reward_indexes: vec![1, 0],
+            amounts: vec![0, FUNDING / 2],
+            reward_per_token_stored: pool.reward_per_token_stored(),
+        })]
+    );
+}
+
+#[test]
+fn unbonding_penalty_and_lock_actions_emit_events() {
+    let mut t = ProgramTest::new();
+    let f = PoolFixture::new(&mut t, 2);
+    t.process(f.initialize_pool(DAY, 1_000, DAY, None)).unwrap();
+    t.process(f.set_unbonding_period(DAY)).unwrap();
+    let stayer = f.create_wallet(&mut t, 1000, 0);
+    let leaver = f.create_wallet(&mut t, 1000, 0);
+    for u in [&stayer, &leaver] {
+        t.process(f.create_user(u)).unwrap();
+        t.process(f.stake(u, 1000)).unwrap();
+    }
+
+    //the 10% penalty has decayed to 5% halfway through the window
+    t.warp_forward(DAY / 2);
+    t.process(f.unstake(&leaver, 1000, None)).unwrap();
+    t.process(f.cancel_unbonding(&leaver)).unwrap();
+    assert_eq!(
+        events(&t),
+        vec![PoolEvent::UnbondingCancelled(UnbondingCancelled {
+            pool: f.pool,
+            user: leaver.user,
+            owner: leaver.owner,
+            amount: 950,
+            balance_staked: 950,
+            total_staked: 1950,
+        })]
+    );
+
+    t.process(f.claim_penalty_rewards(&stayer)).unwrap();
+    assert_eq!(
+        events(&t),
+        vec![PoolEvent::PenaltyRewardsClaimed(PenaltyRewardsClaimed {
+            pool: f.pool,
+            user: stayer.user,
+            owner: stayer.owner,
+            amount: 49,
+        })]
+    );
+
+    t.process(f.configure_lockup(DAY, 4 * DAY, 10_000)).unwrap();
+    t.process(f.lock_stake(&stayer, 1000, 4 * DAY)).unwrap();
+    assert_eq!(
+        events(&t),
+        vec![PoolEvent::StakeLocked(StakeLocked {
+            pool: f.pool,
+            user: stayer.user,
+            owner: stayer.owner,
+            amount: 1000,
+            balance_locked: 1000,
+            lock_end_ts: t.now() + 4 * DAY,
+            boost_weight: 1000,
+            total_boost_weight: 1000,
+        })]
+    );
+
+    t.warp_forward(DAY);
+    t.process(f.unstake(&leaver, 950, None)).unwrap();
+    t.warp_forward(DAY);
+    t.process(f.withdraw_unbonded(&leaver)).unwrap();
+    assert_eq!(
+        events(&t),
+        vec![PoolEvent::UnbondedWithdrawn(UnbondedWithdrawn {
+            pool: f.pool,
+            user: leaver.user,
+            owner: leaver.owner,
+            amount: 950,
+        })]
+    );
+}
+
+#[test]
+fn pool_settings_emit_events() {
+    let mut t = ProgramTest::new();
+    let f = PoolFixture::new(&mut t, 2);
+    t.process(f.initialize_pool(DAY, 0, 0, None)).unwrap();
+
+    t.process(f.set_unbonding_period(DAY)).unwrap();
+    assert_eq!(
+        events(&t),
+        vec![PoolEvent::UnbondingPeriodSet(UnbondingPeriodSet {
+            pool: f.pool,
+            unbonding_period: DAY,
+        })]
+    );
+
+    t.process(f.set_stake_caps(1000, 5000)).unwrap();
+    assert_eq!(
+        events(&t),
+        vec![PoolEvent::StakeCapsSet(StakeCapsSet {
+            pool: f.pool,
+            max_stake_per_user: 1000,
+            max_total_stake: 5000,
+        })]
+    );
+
+    let treasury = f.create_wallet(&mut t, 0, 0).staking_account;
+    t.process(f.set_penalty_treasury(Some(treasury))).unwrap();
+    assert_eq!(
+        events(&t),
+        vec![PoolEvent::PenaltyTreasurySet(PenaltyTreasurySet {
+            pool: f.pool,
+            penalty_treasury: treasury,
+        })]
+    );
+
+    t.process(f.configure_lockup(DAY, 4 * DAY, 10_000)).unwrap();
+    assert_eq!(
+        events(&t),
+        vec![PoolEvent::LockupConfigured(LockupConfigured {
+            pool: f.pool,
+            min_lock_duration: DAY,
+            max_lock_duration: 4 * DAY,
+            max_lock_boost_bps: 10_000,
+            version: PoolVersion::V5,
+        })]
+    );
+
+    let u = f.create_wallet(&mut t, 1000, 0);
+    t.process(f.create_user(&u)).unwrap();
+    t.process(f.stake(&u, 1000)).unwrap();
+    t.process(f.lock_stake(&u, 1000, 4 * DAY)).unwrap();
+    t.warp_forward(4 * DAY);
+    t.process(f.release_lock(&u)).unwrap();
+    assert_eq!(
+        events(&t),
+        vec![PoolEvent::LockReleased(LockReleased {
+            pool: f.pool,
+            user: u.user,
+            owner: u.owner,
+            boost_weight: 1000,
+            total_boost_weight: 0,
+        })]
+    );
+}
+
+#[test]
+fn multi_reward_settings_emit_events() {
+    let mut t = ProgramTest::new();
+    let f = PoolFixture::new(&mut t, 2);
+    t.process(f.initialize_pool_v4(DAY)).unwrap();
+
+    t.process(f.add_reward(1)).unwrap();
+    assert_eq!(
+        events(&t),
+        vec![PoolEvent::RewardAdded(RewardAdded {
+            pool: f.pool,
+            reward_index: 0,
+            reward_mint: f.reward_mints[1],
+            reward_vault: f.reward_vaults[1],
+            reward_duration: DAY,
+        })]
+    );
+
+    t.process(f.set_reward_duration(0, 2 * DAY)).unwrap();
+    assert_eq!(
+        events(&t),
+        vec![PoolEvent::RewardDurationSet(RewardDurationSet {
+            pool: f.pool,
+            reward_index: 0,
+            reward_duration: 2 * DAY,
+        })]
+    );
+}
+
+#[test]
+fn sweeps_emit_events() {
+    let mut t = ProgramTest::new();
+    let f = PoolFixture::new(&mut t, 2);
+    t.process(f.initialize_pool(DAY, 0, 0, None)).unwrap();
+    let funder = f.create_wallet(&mut t, 0, FUNDING);
+    t.process(f.authorize_funder(funder.owner)).unwrap();
+    let receiver = f.create_wallet(&mut t, 0, 0);
+
+    t.mint_to(&f.staking_vault, 500);
+    t.process(f.sweep_donated_stake(receiver.staking_account))
+        .unwrap();
+    assert_eq!(
+        events(&t),
+        vec![PoolEvent::DonatedStakeSwept(DonatedStakeSwept {
+            pool: f.pool,
+            receiver: receiver.staking_account,
+            amount: 500,
+        })]
+    );
+
+    //with nothing staked none of the funding is earned
+    t.process(f.fund(&funder, FUNDING, 0)).unwrap();
+    t.warp_forward(DAY);
+    t.process(f.sweep_unallocated_rewards(0, receiver.reward_accounts[0]))
+        .unwrap();
+    assert_eq!(
+        events(&t),
+        vec![PoolEvent::UnallocatedRewardsSwept(
+            UnallocatedRewardsSwept {
+                pool: f.pool,
+                reward_index: 0,
+                receiver: receiver.reward_accounts[0],
+                amount: FUNDING,
+            }
+        )]
+    );
+}
+
+#[test]
+fn config_admin_actions_emit_events() {
+    let mut t = ProgramTest::new();
+    PoolFixture::new(&mut t, 2);
+    let admin = t.get_account::<ProgramConfig>(&config_address()).admin;
+    let new_admin = t.create_wallet();
+
+    t.process(set_global_freeze(admin, true)).unwrap();
+    assert_eq!(
+        events(&t),
+        vec![PoolEvent::GlobalFreezeSet(GlobalFreezeSet {
+            admin,
+            global_freeze: true,
+        })]
+    );
+
+    t.process(propose_config_admin(admin, new_admin)).unwrap();
+    assert_eq!(
+        events(&t),
+        vec![PoolEvent::ConfigAdminProposed(ConfigAdminProposed {
+            admin,
+            pending_admin: new_admin,
+        })]
+    );
+
+    t.process(accept_config_admin(new_admin)).unwrap();
+    assert_eq!(
+        events(&t),
+        vec![PoolEvent::ConfigAdminTransferred(ConfigAdminTransferred {
+            previous_admin: admin,
+            admin: new_admin,
+        })]
+    );
+}
+
+#[test]
+fn decode_events_reads_only_the_programs_own_logs() {
+    let paused = PoolPaused {
+        pool: Pubkey::new_unique(),
+    };
+    let log = format!("Program log: {}", base64::encode(paused.data()));
+    let other_program = Pubkey::new_unique();
+
+    let logs = vec![
+        format!("Program {} invoke [1]", other_program),
+        //an event of the same layout logged by another program
+        log.clone(),
+        format!("Program {} invoke [2]", reward_pool::ID),
+        "Program log: Instruction: Pause".to_string(),
+        log.clone(),
+        format!("Program {} invoke [3]", spl_token::ID),
+        log.clone(),
+        format!("Program {} success", spl_token::ID),
+        "Program log: bm90IGFuIGV2ZW50".to_string(),
+        format!(
+            "Program {} consumed 5000 of 200000 compute units",
+            reward_pool::ID
+        ),
+        format!("Program {} success", reward_pool::ID),
+        log,
+        format!("Program {} success", other_program),
+    ];
+    assert_eq!(
+        decode_events(&logs),
+        vec![PoolEvent::PoolPaused(paused.clone())]
+    );
+
+    let mut data = PoolPaused::discriminator().to_vec();
+    assert_eq!(decode_event_data(&data), None);
+    data.extend_from_slice(paused.pool.as_ref());
+    assert_eq!(
+        decode_event_data(&data),
+        Some(PoolEvent::PoolPaused(paused))
+    );
+}
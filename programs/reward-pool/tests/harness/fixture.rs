@@ -0,0 +1,970 @@
+//! Pools and users set up on a [`ProgramTest`] ledger, with builders for every
+//! `reward_pool` instruction run against them.
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{instruction::Instruction, system_program};
+use anchor_lang::{InstructionData, ToAccountMetas};
+use anchor_spl::associated_token::get_associated_token_address;
+use reward_pool::{
+    accounts, instruction, program_data_address, token_metadata, Pool, PoolGate, CONFIG_SEED,
+};
+
+use super::ProgramTest;
+
+/// The deposit and shortest reward period of xSTEP pools, the program config as Step
+/// Finance initializes it and as every test ledger starts with.
+pub const X_STEP_TOKEN_MINT_PUBKEY: &str = "xStpgUCss9piqeFUk2iLVcvJEGhAdJxJQuwLkXP555G";
+pub const X_STEP_DEPOSIT_REQUIREMENT: u64 = 10_000_000_000_000;
+pub const MIN_DURATION: u64 = 86400;
+
+fn build(accounts: impl ToAccountMetas, data: impl InstructionData) -> Instruction {
+    Instruction {
+        program_id: reward_pool::ID,
+        accounts: accounts.to_account_metas(None),
+        data: data.data(),
+    }
+}
+
+fn with_remaining(mut ix: Instruction, remaining: &[Pubkey]) -> Instruction {
+    ix.accounts
+        .extend(remaining.iter().map(|key| AccountMeta::new(*key, false)));
+    ix
+}
+
+/// The address of the program config.
+pub fn config_address() -> Pubkey {
+    Pubkey::find_program_address(&[CONFIG_SEED], &reward_pool::ID).0
+}
+
+pub fn initialize_config(
+    upgrade_authority: Pubkey,
+    admin: Pubkey,
+    deposit_mint: Pubkey,
+    deposit_requirement: u64,
+    min_duration: u64,
+) -> Instruction {
+    build(
+        accounts::InitializeConfig {
+            config: config_address(),
+            program_data: program_data_address(),
+            upgrade_authority,
+            system_program: system_program::ID,
+        },
+        instruction::InitializeConfig {
+            admin,
+            deposit_mint,
+            deposit_requirement,
+            min_duration,
+        },
+    )
+}
+
+pub fn update_config(
+    admin: Pubkey,
+    deposit_mint: Pubkey,
+    deposit_requirement: u64,
+    min_duration: u64,
+) -> Instruction {
+    build(
+        accounts::UpdateConfig {
+            config: config_address(),
+            admin,
+        },
+        instruction::UpdateConfig {
+            deposit_mint,
+            deposit_requirement,
+            min_duration,
+        },
+    )
+}
+
+pub fn set_global_freeze(admin: Pubkey, global_freeze: bool) -> Instruction {
+    build(
+        accounts::UpdateConfig {
+            config: config_address(),
+            admin,
+        },
+        instruction::SetGlobalFreeze { global_freeze },
+    )
+}
+
+pub fn propose_config_admin(admin: Pubkey, new_admin: Pubkey) -> Instruction {
+    build(
+        accounts::UpdateConfig {
+            config: config_address(),
+            admin,
+        },
+        instruction::ProposeConfigAdmin { new_admin },
+    )
+}
+
+pub fn cancel_config_admin_transfer(admin: Pubkey) -> Instruction {
+    build(
+        accounts::UpdateConfig {
+            config: config_address(),
+            admin,
+        },
+        instruction::CancelConfigAdminTransfer {},
+    )
+}
+
+pub fn accept_config_admin(pending_admin: Pubkey) -> Instruction {
+    build(
+        accounts::AcceptConfigAdmin {
+            config: config_address(),
+            pending_admin,
+        },
+        instruction::AcceptConfigAdmin {},
+    )
+}
+
+/// Creates the program config with the xSTEP deposit, returning its admin, who is also
+/// the program's upgrade authority.
+pub fn create_config(t: &mut ProgramTest) -> Pubkey {
+    let admin = t.create_wallet();
+    t.set_upgrade_authority(&admin);
+    t.process(initialize_config(
+        admin,
+        admin,
+        X_STEP_TOKEN_MINT_PUBKEY.parse().unwrap(),
+        X_STEP_DEPOSIT_REQUIREMENT,
+        MIN_DURATION,
+    ))
+    .unwrap();
+    admin
+}
+
+/// A wallet with token accounts for a pool's staking and reward mints, and the address
+/// of its user account on that pool.
+pub struct UserFixture {
+    pub owner: Pubkey,
+    pub user: Pubkey,
+    pub staking_account: Pubkey,
+    pub reward_accounts: Vec<Pubkey>,
+}
+
+/// A pool account along with the mints and vaults it is initialized over. Two reward
+/// mints make an A/B pool; multi reward pools take theirs on with `add_reward`.
+pub struct PoolFixture {
+    pub pool: Pubkey,
+    pub pool_signer: Pubkey,
+    pub nonce: u8,
+    pub authority: Pubkey,
+    pub x_token_pool_vault: Pubkey,
+    pub x_token_depositor: Pubkey,
+    pub staking_mint: Pubkey,
+    pub staking_vault: Pubkey,
+    pub reward_mints: Vec<Pubkey>,
+    pub reward_vaults: Vec<Pubkey>,
+    pub gate: PoolGate,
+    pub gate_key: Pubkey,
+    pub max_stake_per_user: u64,
+    pub max_total_stake: u64,
+}
+
+impl PoolFixture {
+    pub fn new(t: &mut ProgramTest, reward_count: usize) -> Self {
+        let pool = Pubkey::new_unique();
+        let (pool_signer, nonce) = Pubkey::find_program_address(&[pool.as_ref()], &reward_pool::ID);
+        t.create_program_account(pool, 8 + Pool::default().try_to_vec().unwrap().len());
+
+        let x_token_mint: Pubkey = X_STEP_TOKEN_MINT_PUBKEY.parse().unwrap();
+        if t.account(&x_token_mint).is_none() {
+            t.create_mint_at(x_token_mint, 9);
+        }
+        if t.account(&config_address()).is_none() {
+            create_config(t);
+        }
+        let authority = t.create_wallet();
+        let x_token_pool_vault = t.create_token_account(&x_token_mint, &pool_signer, 0);
+        let x_token_depositor =
+            t.create_token_account(&x_token_mint, &authority, X_STEP_DEPOSIT_REQUIREMENT);
+
+        let staking_mint = t.create_mint(9);
+        let staking_vault = t.create_token_account(&staking_mint, &pool_signer, 0);
+        let reward_mints: Vec<Pubkey> = (0..reward_count).map(|_| t.create_mint(6)).collect();
+        let reward_vaults = reward_mints
+            .iter()
+            .map(|mint| t.create_token_account(mint, &pool_signer, 0))
+            .collect();
+
+        PoolFixture {
+            pool,
+            pool_signer,
+            nonce,
+            authority,
+            x_token_pool_vault,
+            x_token_depositor,
+            staking_mint,
+            staking_vault,
+            reward_mints,
+            reward_vaults,
+            gate: PoolGate::Open,
+            gate_key: Pubkey::default(),
+            max_stake_per_user: 0,
+            max_total_stake: 0,
+        }
+    }
+
+    /// Gates the pool, once initialized, by `gate` checked against `gate_key`.
+    pub fn with_gate(mut self, gate: PoolGate, gate_key: Pubkey) -> Self {
+        self.gate = gate;
+        self.gate_key = gate_key;
+        self
+    }
+
+    /// Caps stake per user and in total, once initialized.
+    pub fn with_stake_caps(mut self, max_stake_per_user: u64, max_total_stake: u64) -> Self {
+        self.max_stake_per_user = max_stake_per_user;
+        self.max_total_stake = max_total_stake;
+        self
+    }
+
+    /// Makes the staking token the pool's reward A, as in a pool that compounds.
+    pub fn with_staking_reward_a(mut self, t: &mut ProgramTest) -> Self {
+        self.reward_mints[0] = self.staking_mint;
+        self.reward_vaults[0] = t.create_token_account(&self.staking_mint, &self.pool_signer, 0);
+        self
+    }
+
+    /// Replaces the xSTEP vault `pause` closes with a new one, ready for `unpause`.
+    pub fn renew_x_token_pool_vault(&mut self, t: &mut ProgramTest) {
+        let x_token_mint: Pubkey = X_STEP_TOKEN_MINT_PUBKEY.parse().unwrap();
+        self.x_token_pool_vault = t.create_token_account(&x_token_mint, &self.pool_signer, 0);
+    }
+
+    /// Creates a wallet holding `staking_amount` of the staking token and `reward_amount`
+    /// of each reward token.
+    pub fn create_wallet(
+        &self,
+        t: &mut ProgramTest,
+        staking_amount: u64,
+        reward_amount: u64,
+    ) -> UserFixture {
+        let owner = t.create_wallet();
+        let (user, _) =
+            Pubkey::find_program_address(&[owner.as_ref(), self.pool.as_ref()], &reward_pool::ID);
+        let staking_account = t.create_token_account(&self.staking_mint, &owner, staking_amount);
+        let reward_accounts = self
+            .reward_mints
+            .iter()
+            .map(|mint| t.create_token_account(mint, &owner, reward_amount))
+            .collect();
+        UserFixture {
+            owner,
+            user,
+            staking_account,
+            reward_accounts,
+        }
+    }
+
+    /// Initializes an A/B pool, passing the penalty treasury if penalties go to one.
+    pub fn initialize_pool(
+        &self,
+        reward_duration: u64,
+        early_exit_penalty_bps: u16,
+        early_exit_penalty_window: u64,
+        penalty_treasury: Option<Pubkey>,
+    ) -> Instruction {
+        let remaining: Vec<Pubkey> = penalty_treasury.into_iter().collect();
+        let ix = build(
+            accounts::InitializePool {
+                authority: self.authority,
+                x_token_pool_vault: self.x_token_pool_vault,
+                x_token_depositor: self.x_token_depositor,
+                x_token_deposit_authority: self.authority,
+                staking_mint: self.staking_mint,
+                staking_vault: self.staking_vault,
+                reward_a_mint: self.reward_mints[0],
+                reward_a_vault: self.reward_vaults[0],
+                reward_b_mint: self.reward_mints[1],
+                reward_b_vault: self.reward_vaults[1],
+                pool_signer: self.pool_signer,
+                pool: self.pool,
+                token_program: spl_token::ID,
+                config: config_address(),
+            },
+            instruction::InitializePool {
+                pool_nonce: self.nonce,
+                reward_duration,
+                early_exit_penalty_bps,
+                early_exit_penalty_window,
+                gate: self.gate,
+                gate_key: self.gate_key,
+                max_stake_per_user: self.max_stake_per_user,
+                max_total_stake: self.max_total_stake,
+            },
+        );
+        with_remaining(ix, &remaining)
+    }
+
+    fn initialize_pool_v3_accounts(&self) -> accounts::InitializePoolV3 {
+        accounts::InitializePoolV3 {
+            authority: self.authority,
+            x_token_pool_vault: self.x_token_pool_vault,
+            x_token_depositor: self.x_token_depositor,
+            x_token_deposit_authority: self.authority,
+            staking_mint: self.staking_mint,
+            staking_vault: self.staking_vault,
+            pool_signer: self.pool_signer,
+            pool: self.pool,
+            token_program: spl_token::ID,
+            config: config_address(),
+        }
+    }
+
+    pub fn initialize_pool_v3(&self, reward_duration: u64) -> Instruction {
+        build(
+            self.initialize_pool_v3_accounts(),
+            instruction::InitializePoolV3 {
+                pool_nonce: self.nonce,
+                reward_duration,
+                gate: self.gate,
+                gate_key: self.gate_key,
+                max_stake_per_user: self.max_stake_per_user,
+                max_total_stake: self.max_total_stake,
+            },
+        )
+    }
+
+    pub fn initialize_pool_v4(&self, reward_duration: u64) -> Instruction {
+        build(
+            self.initialize_pool_v3_accounts(),
+            instruction::InitializePoolV4 {
+                pool_nonce: self.nonce,
+                reward_duration,
+                gate: self.gate,
+                gate_key: self.gate_key,
+                max_stake_per_user: self.max_stake_per_user,
+                max_total_stake: self.max_total_stake,
+            },
+        )
+    }
+
+    pub fn add_reward(&self, reward_index: usize) -> Instruction {
+        build(
+            accounts::AddReward {
+                pool: self.pool,
+                authority: self.authority,
+                reward_mint: self.reward_mints[reward_index],
+                reward_vault: self.reward_vaults[reward_index],
+                pool_signer: self.pool_signer,
+            },
+            instruction::AddReward {},
+        )
+    }
+
+    pub fn set_reward_duration(&self, reward_index: u8, reward_duration: u64) -> Instruction {
+        build(
+            accounts::SetRewardDuration {
+                pool: self.pool,
+                authority: self.authority,
+                config: config_address(),
+            },
+            instruction::SetRewardDuration {
+                reward_index,
+                reward_duration,
+            },
+        )
+    }
+
+    fn create_user_accounts(&self, user: &UserFixture) -> accounts::CreateUser {
+        accounts::CreateUser {
+            pool: self.pool,
+            user: user.user,
+            owner: user.owner,
+            system_program: system_program::ID,
+        }
+    }
+
+    /// Creates the user, co-signed by the gate authority of a pool gated by one.
+    pub fn create_user(&self, user: &UserFixture) -> Instruction {
+        let mut ix = build(
+            self.create_user_accounts(user),
+            instruction::CreateUser { _nonce: 0 },
+        );
+        if self.gate == PoolGate::Authority {
+            ix.accounts
+                .push(AccountMeta::new_readonly(self.gate_key, true));
+        }
+        ix
+    }
+
+    pub fn create_user_holding(
+        &self,
+        user: &UserFixture,
+        token_account: Pubkey,
+        token_mint: Pubkey,
+    ) -> Instruction {
+        let (metadata, _) = Pubkey::find_program_address(
+            &[
+                b"metadata",
+                token_metadata::ID.as_ref(),
+                token_mint.as_ref(),
+            ],
+            &token_metadata::ID,
+        );
+        with_remaining(self.create_user(user), &[token_account, metadata])
+    }
+
+    pub fn create_user_with_proof(&self, user: &UserFixture, proof: Vec<[u8; 32]>) -> Instruction {
+        build(
+            self.create_user_accounts(user),
+            instruction::CreateUserWithProof { proof },
+        )
+    }
+
+    pub fn pause(&self) -> Instruction {
+        build(
+            accounts::Pause {
+                x_token_pool_vault: self.x_token_pool_vault,
+                x_token_receiver: self.x_token_depositor,
+                pool: self.pool,
+                authority: self.authority,
+                pool_signer: self.pool_signer,
+                token_program: spl_token::ID,
+            },
+            instruction::Pause {},
+        )
+    }
+
+    pub fn unpause(&self) -> Instruction {
+        build(
+            accounts::Unpause {
+                x_token_pool_vault: self.x_token_pool_vault,
+                x_token_depositor: self.x_token_depositor,
+                x_token_deposit_authority: self.authority,
+                pool: self.pool,
+                authority: self.authority,
+                pool_signer: self.pool_signer,
+                token_program: spl_token::ID,
+                config: config_address(),
+            },
+            instruction::Unpause {},
+        )
+    }
+
+    fn stake_accounts(&self, user: &UserFixture) -> accounts::Stake {
+        accounts::Stake {
+            pool: self.pool,
+            staking_vault: self.staking_vault,
+            user: user.user,
+            owner: user.owner,
+            stake_from_account: user.staking_account,
+            pool_signer: self.pool_signer,
+            token_program: spl_token::ID,
+            config: config_address(),
+        }
+    }
+
+    fn unstake_accounts(&self, user: &UserFixture) -> accounts::Unstake {
+        accounts::Unstake {
+            pool: self.pool,
+            staking_vault: self.staking_vault,
+            user: user.user,
+            owner: user.owner,
+            stake_from_account: user.staking_account,
+            pool_signer: self.pool_signer,
+            token_program: spl_token::ID,
+        }
+    }
+
+    pub fn stake(&self, user: &UserFixture, amount: u64) -> Instruction {
+        build(self.stake_accounts(user), instruction::Stake { amount })
+    }
+
+    /// Stakes `payer`'s tokens for `owner`, opening their user account if needed.
+    pub fn stake_for(
+        &self,
+        payer: &UserFixture,
+        owner: Pubkey,
+        amount: u64,
+        proof: Vec<[u8; 32]>,
+    ) -> Instruction {
+        let (user, _) =
+            Pubkey::find_program_address(&[owner.as_ref(), self.pool.as_ref()], &reward_pool::ID);
+        let mut ix = build(
+            accounts::StakeFor {
+                pool: self.pool,
+                staking_vault: self.staking_vault,
+                user,
+                owner,
+                payer: payer.owner,
+                stake_from_account: payer.staking_account,
+                pool_signer: self.pool_signer,
+                token_program: spl_token::ID,
+                system_program: system_program::ID,
+                config: config_address(),
+            },
+            instruction::StakeFor { amount, proof },
+        );
+        if self.gate == PoolGate::Authority {
+            ix.accounts
+                .push(AccountMeta::new_readonly(self.gate_key, true));
+        }
+        ix
+    }
+
+    /// Unstakes, passing the penalty treasury when the pool sends penalties to one.
+    pub fn unstake(
+        &self,
+        user: &UserFixture,
+        spt_amount: u64,
+        penalty_treasury: Option<Pubkey>,
+    ) -> Instruction {
+        let remaining: Vec<Pubkey> = penalty_treasury.into_iter().collect();
+        with_remaining(
+            build(
+                self.unstake_accounts(user),
+                instruction::Unstake { spt_amount },
+            ),
+            &remaining,
+        )
+    }
+
+    pub fn emergency_unstake(&self, user: &UserFixture) -> Instruction {
+        build(
+            self.unstake_accounts(user),
+            instruction::EmergencyUnstake {},
+        )
+    }
+
+    pub fn withdraw_unbonded(&self, user: &UserFixture) -> Instruction {
+        build(
+            self.unstake_accounts(user),
+            instruction::WithdrawUnbonded {},
+        )
+    }
+
+    pub fn cancel_unbonding(&self, user: &UserFixture) -> Instruction {
+        build(
+            accounts::CancelUnbonding {
+                pool: self.pool,
+                user: user.user,
+                owner: user.owner,
+                config: config_address(),
+            },
+            instruction::CancelUnbonding {},
+        )
+    }
+
+    pub fn claim_penalty_rewards(&self, user: &UserFixture) -> Instruction {
+        build(
+            self.stake_accounts(user),
+            instruction::ClaimPenaltyRewards {},
+        )
+    }
+
+    pub fn lock_stake(&self, user: &UserFixture, amount: u64, lock_duration: u64) -> Instruction {
+        build(
+            accounts::LockStake {
+                pool: self.pool,
+                user: user.user,
+                owner: user.owner,
+                config: config_address(),
+            },
+            instruction::LockStake {
+                amount,
+                lock_duration,
+            },
+        )
+    }
+
+    pub fn release_lock(&self, user: &UserFixture) -> Instruction {
+        build(
+            accounts::ReleaseLock {
+                pool: self.pool,
+                user: user.user,
+            },
+            instruction::ReleaseLock {},
+        )
+    }
+
+    pub fn configure_lockup(
+        &self,
+        min_lock_duration: u64,
+        max_lock_duration: u64,
+        max_lock_boost_bps: u16,
+    ) -> Instruction {
+        build(
+            accounts::ConfigureLockup {
+                pool: self.pool,
+                authority: self.authority,
+                staking_vault: self.staking_vault,
+            },
+            instruction::ConfigureLockup {
+                min_lock_duration,
+                max_lock_duration,
+                max_lock_boost_bps,
+            },
+        )
+    }
+
+    pub fn set_unbonding_period(&self, unbonding_period: u64) -> Instruction {
+        build(
+            accounts::SetUnbondingPeriod {
+                pool: self.pool,
+                authority: self.authority,
+            },
+            instruction::SetUnbondingPeriod { unbonding_period },
+        )
+    }
+
+    pub fn set_stake_caps(&self, max_stake_per_user: u64, max_total_stake: u64) -> Instruction {
+        build(
+            accounts::SetStakeCaps {
+                pool: self.pool,
+                authority: self.authority,
+            },
+            instruction::SetStakeCaps {
+                max_stake_per_user,
+                max_total_stake,
+            },
+        )
+    }
+
+    pub fn set_penalty_treasury(&self, penalty_treasury: Option<Pubkey>) -> Instruction {
+        let remaining: Vec<Pubkey> = penalty_treasury.into_iter().collect();
+        with_remaining(
+            build(
+                accounts::SetPenaltyTreasury {
+                    pool: self.pool,
+                    authority: self.authority,
+                },
+                instruction::SetPenaltyTreasury {},
+            ),
+            &remaining,
+        )
+    }
+
+    pub fn authorize_funder(&self, funder_to_add: Pubkey) -> Instruction {
+        build(
+            accounts::FunderChange {
+                pool: self.pool,
+                authority: self.authority,
+            },
+            instruction::AuthorizeFunder { funder_to_add },
+        )
+    }
+
+    pub fn deauthorize_funder(&self, funder_to_remove: Pubkey) -> Instruction {
+        build(
+            accounts::FunderChange {
+                pool: self.pool,
+                authority: self.authority,
+            },
+            instruction::DeauthorizeFunder { funder_to_remove },
+        )
+    }
+
+    pub fn propose_authority(&self, new_authority: Pubkey) -> Instruction {
+        build(
+            accounts::AuthorityChange {
+                pool: self.pool,
+                authority: self.authority,
+            },
+            instruction::ProposeAuthority { new_authority },
+        )
+    }
+
+    pub fn cancel_authority_transfer(&self) -> Instruction {
+        build(
+            accounts::AuthorityChange {
+                pool: self.pool,
+                authority: self.authority,
+            },
+            instruction::CancelAuthorityTransfer {},
+        )
+    }
+
+    pub fn accept_authority(&self, pending_authority: Pubkey) -> Instruction {
+        build(
+            accounts::AcceptAuthority {
+                pool: self.pool,
+                pending_authority,
+            },
+            instruction::AcceptAuthority {},
+        )
+    }
+
+    fn fund_accounts(&self, funder: &UserFixture) -> accounts::Fund {
+        accounts::Fund {
+            pool: self.pool,
+            staking_vault: self.staking_vault,
+            reward_a_vault: self.reward_vaults[0],
+            reward_b_vault: self.reward_vaults[1],
+            funder: funder.owner,
+            from_a: funder.reward_accounts[0],
+            from_b: funder.reward_accounts[1],
+            pool_signer: self.pool_signer,
+            token_program: spl_token::ID,
+            config: config_address(),
+        }
+    }
+
+    pub fn fund(&self, funder: &UserFixture, amount_a: u64, amount_b: u64) -> Instruction {
+        build(
+            self.fund_accounts(funder),
+            instruction::Fund { amount_a, amount_b },
+        )
+    }
+
+    pub fn top_up(&self, funder: &UserFixture, amount_a: u64, amount_b: u64) -> Instruction {
+        build(
+            self.fund_accounts(funder),
+            instruction::TopUp { amount_a, amount_b },
+        )
+    }
+
+    pub fn fund_scheduled(
+        &self,
+        funder: &UserFixture,
+        amount_a: u64,
+        amount_b: u64,
+        start_ts: u64,
+        end_ts: u64,
+    ) -> Instruction {
+        build(
+            self.fund_accounts(funder),
+            instruction::FundScheduled {
+                amount_a,
+                amount_b,
+                start_ts,
+                end_ts,
+            },
+        )
+    }
+
+    pub fn claim(&self, user: &UserFixture) -> Instruction {
+        build(
+            accounts::ClaimReward {
+                pool: self.pool,
+                staking_vault: self.staking_vault,
+                reward_a_vault: self.reward_vaults[0],
+                reward_b_vault: self.reward_vaults[1],
+                user: user.user,
+                owner: user.owner,
+                reward_a_account: user.reward_accounts[0],
+                reward_b_account: user.reward_accounts[1],
+                pool_signer: self.pool_signer,
+                token_program: spl_token::ID,
+                config: config_address(),
+            },
+            instruction::Claim {},
+        )
+    }
+
+    pub fn compound(&self, user: &UserFixture) -> Instruction {
+        build(
+            accounts::Compound {
+                pool: self.pool,
+                staking_vault: self.staking_vault,
+                reward_a_vault: self.reward_vaults[0],
+                reward_b_vault: self.reward_vaults[1],
+                user: user.user,
+                owner: user.owner,
+                pool_signer: self.pool_signer,
+                token_program: spl_token::ID,
+                config: config_address(),
+            },
+            instruction::Compound {},
+        )
+    }
+
+    /// Funds a multi reward pool, passing a vault and source account for every non zero
+    /// amount.
+    pub fn fund_v3(&self, funder: &UserFixture, amounts: Vec<u64>) -> Instruction {
+        let remaining: Vec<Pubkey> = amounts
+            .iter()
+            .enumerate()
+            .filter(|(_, amount)| **amount > 0)
+            .flat_map(|(i, _)| [self.reward_vaults[i], funder.reward_accounts[i]])
+            .collect();
+        with_remaining(
+            build(
+                accounts::FundV3 {
+                    pool: self.pool,
+                    staking_vault: self.staking_vault,
+                    funder: funder.owner,
+                    pool_signer: self.pool_signer,
+                    token_program: spl_token::ID,
+                    config: config_address(),
+                },
+                instruction::FundV3 { amounts },
+            ),
+            &remaining,
+        )
+    }
+
+    pub fn claim_v3(&self, user: &UserFixture, reward_indexes: Vec<u8>) -> Instruction {
+        let remaining: Vec<Pubkey> = reward_indexes
+            .iter()
+            .flat_map(|i| {
+                [
+                    self.reward_vaults[*i as usize],
+                    user.reward_accounts[*i as usize],
+                ]
+            })
+            .collect();
+        with_remaining(
+            build(
+                accounts::ClaimRewardV3 {
+                    pool: self.pool,
+                    staking_vault: self.staking_vault,
+                    user: user.user,
+                    owner: user.owner,
+                    pool_signer: self.pool_signer,
+                    token_program: spl_token::ID,
+                    config: config_address(),
+                },
+                instruction::ClaimV3 { reward_indexes },
+            ),
+            &remaining,
+        )
+    }
+
+    /// Claims all of `user`'s rewards as `claimer`, into the associated token accounts of
+    /// `recipient`.
+    pub fn claim_for(&self, user: &UserFixture, claimer: Pubkey, recipient: Pubkey) -> Instruction {
+        let remaining: Vec<Pubkey> = self
+            .reward_vaults
+            .iter()
+            .zip(&self.reward_mints)
+            .flat_map(|(vault, mint)| [*vault, get_associated_token_address(&recipient, mint)])
+            .collect();
+        with_remaining(
+            build(
+                accounts::ClaimFor {
+                    pool: self.pool,
+                    staking_vault: self.staking_vault,
+                    user: user.user,
+                    claimer,
+                    pool_signer: self.pool_signer,
+                    token_program: spl_token::ID,
+                    config: config_address(),
+                },
+                instruction::ClaimFor {},
+            ),
+            &remaining,
+        )
+    }
+
+    pub fn set_reward_recipient(
+        &self,
+        user: &UserFixture,
+        reward_recipient: Pubkey,
+    ) -> Instruction {
+        build(
+            accounts::SetRewardRecipient {
+                user: user.user,
+                owner: user.owner,
+            },
+            instruction::SetRewardRecipient { reward_recipient },
+        )
+    }
+
+    pub fn sweep_donated_stake(&self, receiver: Pubkey) -> Instruction {
+        build(
+            accounts::SweepDonatedStake {
+                pool: self.pool,
+                authority: self.authority,
+                staking_vault: self.staking_vault,
+                receiver,
+                pool_signer: self.pool_signer,
+                token_program: spl_token::ID,
+            },
+            instruction::SweepDonatedStake {},
+        )
+    }
+
+    pub fn sweep_unallocated_rewards(&self, reward_index: u8, receiver: Pubkey) -> Instruction {
+        build(
+            accounts::SweepUnallocatedRewards {
+                pool: self.pool,
+                authority: self.authority,
+                staking_vault: self.staking_vault,
+                reward_vault: self.reward_vaults[reward_index as usize],
+                receiver,
+                pool_signer: self.pool_signer,
+                token_program: spl_token::ID,
+            },
+            instruction::SweepUnallocatedRewards { reward_index },
+        )
+    }
+
+    pub fn close_user(&self, user: &UserFixture) -> Instruction {
+        build(
+            accounts::CloseUser {
+                pool: self.pool,
+                user: user.user,
+                owner: user.owner,
+            },
+            instruction::CloseUser {},
+        )
+    }
+
+    /// Closes an A/B pool, refunding its vaults to the token accounts of `refundee`.
+    pub fn close_pool(&self, refundee: &UserFixture) -> Instruction {
+        build(
+            accounts::ClosePool {
+                refundee: refundee.owner,
+                staking_refundee: refundee.staking_account,
+                reward_a_refundee: refundee.reward_accounts[0],
+                reward_b_refundee: refundee.reward_accounts[1],
+                pool: self.pool,
+                authority: self.authority,
+                staking_vault: self.staking_vault,
+                reward_a_vault: self.reward_vaults[0],
+                reward_b_vault: self.reward_vaults[1],
+                pool_signer: self.pool_signer,
+                token_program: spl_token::ID,
+            },
+            instruction::ClosePool {},
+        )
+    }
+
+    /// Closes a multi reward pool holding the first `reward_count` rewards, refunding its
+    /// vaults to the token accounts of `refundee`.
+    pub fn close_pool_v3(&self, refundee: &UserFixture, reward_count: usize) -> Instruction {
+        let remaining: Vec<Pubkey> = (0..reward_count)
+            .flat_map(|i| [self.reward_vaults[i], refundee.reward_accounts[i]])
+            .collect();
+        with_remaining(
+            build(
+                accounts::ClosePoolV3 {
+                    refundee: refundee.owner,
+                    staking_refundee: refundee.staking_account,
+                    pool: self.pool,
+                    authority: self.authority,
+                    staking_vault: self.staking_vault,
+                    pool_signer: self.pool_signer,
+                    token_program: spl_token::ID,
+                },
+                instruction::ClosePoolV3 {},
+            ),
+            &remaining,
+        )
+    }
+
+    pub fn migrate_pool(&self, payer: Pubkey) -> Instruction {
+        build(
+            accounts::MigratePool {
+                pool: self.pool,
+                payer,
+                system_program: system_program::ID,
+            },
+            instruction::MigratePool {},
+        )
+    }
+
+    pub fn migrate_user(&self, user: &UserFixture) -> Instruction {
+        build(
+            accounts::MigrateUser {
+                user: user.user,
+                payer: user.owner,
+                system_program: system_program::ID,
+            },
+            instruction::MigrateUser {},
+        )
+    }
+}
@@ -0,0 +1,539 @@
+//! An in process runtime for driving the reward pool program from native tests.
+//!
+//! The workspace has to build from an offline crate cache, which has the solana 1.9
+//! runtime but not `solana-program-test` 1.9 or the banks crates it needs, so there is no
+//! `BanksClient` to test against and instructions are run straight through the program
+//! entrypoint instead.
+//! Accounts are serialized into the same input buffer the BPF loader hands a program and
+//! read back out afterwards, and the syscalls the program relies on are stubbed: CPIs go
+//! to the in process system and token programs, and the `Clock` sysvar only moves when a
+//! test warps it, so reward timing can be asserted exactly.
+#![allow(dead_code)]
+
+pub mod fixture;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::mem::size_of;
+use std::sync::Once;
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::{
+    bpf_loader, bpf_loader_upgradeable,
+    entrypoint::{self, ProgramResult, MAX_PERMITTED_DATA_INCREASE, SUCCESS},
+    instruction::Instruction,
+    program_option::COption,
+    program_pack::Pack,
+    program_stubs::{self, SyscallStubs},
+    system_instruction::SystemInstruction,
+    system_program,
+};
+use anchor_lang::{AccountDeserialize, AccountSerialize};
+use anchor_spl::associated_token::get_associated_token_address;
+use spl_token::state::{Account as TokenAccount, AccountState, Mint};
+
+/// Unix time every test starts from.
+pub const START_TIME: u64 = 1_640_995_200;
+
+/// Lamports given to every wallet the harness creates.
+pub const WALLET_LAMPORTS: u64 = 100_000_000_000;
+
+thread_local! {
+    static CLOCK: RefCell<Clock> = RefCell::new(Clock::default());
+    /// Log messages of the last transaction, as the runtime reports them
+    static LOGS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+}
+
+fn log(message: String) {
+    LOGS.with(|logs| logs.borrow_mut().push(message));
+}
+
+/// Logs the outcome of an invocation the way the runtime does
+fn log_result(program_id: &Pubkey, result: &ProgramResult) {
+    match result {
+        Ok(()) => log(format!("Program {} success", program_id)),
+        Err(err) => log(format!("Program {} failed: {}", program_id, err)),
+    }
+}
+
+fn set_clock(unix_timestamp: u64) {
+    CLOCK.with(|clock| {
+        let mut clock = clock.borrow_mut();
+        clock.unix_timestamp = unix_timestamp as i64;
+        clock.slot = unix_timestamp;
+    });
+}
+
+struct TestSyscallStubs;
+
+impl SyscallStubs for TestSyscallStubs {
+    fn sol_log(&self, message: &str) {
+        log(format!("Program log: {}", message));
+    }
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        CLOCK.with(|clock| unsafe { *(var_addr as *mut Clock) = clock.borrow().clone() });
+        SUCCESS
+    }
+
+    fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+        unsafe { *(var_addr as *mut Rent) = Rent::default() };
+        SUCCESS
+    }
+
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        //only the reward pool makes CPIs, so only its PDAs can sign
+        let signers: Vec<Pubkey> = signers_seeds
+            .iter()
+            .map(|seeds| Pubkey::create_program_address(seeds, &reward_pool::ID).unwrap())
+            .collect();
+
+        let mut accounts = Vec::with_capacity(instruction.accounts.len());
+        for meta in instruction.accounts.iter() {
+            let mut info = account_infos
+                .iter()
+                .find(|info| *info.key == meta.pubkey)
+                .unwrap_or_else(|| panic!("CPI account {} was not passed", meta.pubkey))
+                .clone();
+            info.is_signer = info.is_signer || signers.contains(&meta.pubkey);
+            if meta.is_signer && !info.is_signer {
+                return Err(ProgramError::MissingRequiredSignature);
+            }
+            if meta.is_writable && !info.is_writable {
+                return Err(ProgramError::InvalidArgument);
+            }
+            accounts.push(info);
+        }
+
+        log(format!("Program {} invoke [2]", instruction.program_id));
+        let result = if instruction.program_id == spl_token::ID {
+            spl_token::processor::Processor::process(
+                &instruction.program_id,
+                &accounts,
+                &instruction.data,
+            )
+        } else if instruction.program_id == system_program::ID {
+            process_system_instruction(&accounts, &instruction.data)
+        } else {
+            panic!("unsupported CPI to {}", instruction.program_id)
+        };
+        log_result(&instruction.program_id, &result);
+        result
+    }
+}
+
+/// The slice of the system program the reward pool calls into.
+fn process_system_instruction(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    match bincode::deserialize(data).unwrap() {
+        SystemInstruction::CreateAccount {
+            lamports,
+            space,
+            owner,
+        } => {
+            let (from, to) = (&accounts[0], &accounts[1]);
+            if to.lamports() > 0 || !to.data_is_empty() {
+                return Err(ProgramError::AccountAlreadyInitialized);
+            }
+            transfer_lamports(from, to, lamports)?;
+            to.realloc(space as usize, true)?;
+            to.assign(&owner);
+        }
+        SystemInstruction::Transfer { lamports } => {
+            transfer_lamports(&accounts[0], &accounts[1], lamports)?
+        }
+        SystemInstruction::Allocate { space } => accounts[0].realloc(space as usize, true)?,
+        SystemInstruction::Assign { owner } => accounts[0].assign(&owner),
+        other => panic!("unsupported system instruction {:?}", other),
+    }
+    Ok(())
+}
+
+fn transfer_lamports(from: &AccountInfo, to: &AccountInfo, lamports: u64) -> ProgramResult {
+    if *from.owner != system_program::ID || !from.is_signer {
+        return Err(ProgramError::MissingRequiredSignature);
+    }
+    let mut from_lamports = from.try_borrow_mut_lamports()?;
+    **from_lamports = from_lamports
+        .checked_sub(lamports)
+        .ok_or(ProgramError::InsufficientFunds)?;
+    **to.try_borrow_mut_lamports()? += lamports;
+    Ok(())
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TestAccount {
+    pub lamports: u64,
+    pub data: Vec<u8>,
+    pub owner: Pubkey,
+    pub executable: bool,
+}
+
+/// Where an account's mutable fields sit in a serialized input buffer.
+struct SerializedAccount {
+    key: Pubkey,
+    is_writable: bool,
+    owner: usize,
+    lamports: usize,
+    data_len: usize,
+}
+
+/// The accounts of a test ledger together with the clock it runs at.
+pub struct ProgramTest {
+    accounts: HashMap<Pubkey, TestAccount>,
+}
+
+impl ProgramTest {
+    pub fn new() -> Self {
+        static STUBS: Once = Once::new();
+        STUBS.call_once(|| {
+            program_stubs::set_syscall_stubs(Box::new(TestSyscallStubs));
+        });
+        set_clock(START_TIME);
+
+        let mut test = ProgramTest {
+            accounts: HashMap::new(),
+        };
+        for program_id in [reward_pool::ID, spl_token::ID, system_program::ID] {
+            test.accounts.insert(
+                program_id,
+                TestAccount {
+                    lamports: 1,
+                    data: vec![],
+                    owner: bpf_loader::ID,
+                    executable: true,
+                },
+            );
+        }
+        test
+    }
+
+    pub fn now(&self) -> u64 {
+        CLOCK.with(|clock| clock.borrow().unix_timestamp as u64)
+    }
+
+    pub fn warp_to(&mut self, unix_timestamp: u64) {
+        set_clock(unix_timestamp);
+    }
+
+    pub fn warp_forward(&mut self, seconds: u64) {
+        self.warp_to(self.now() + seconds);
+    }
+
+    /// The log messages of the last transaction processed.
+    pub fn logs(&self) -> Vec<String> {
+        LOGS.with(|logs| logs.borrow().clone())
+    }
+
+    /// Runs `instruction` as its own transaction, with every account it marks as a signer
+    /// taken to have signed and the first of them paying the fee, which makes it writable.
+    /// As with the runtime, nothing the instruction wrote is kept when it fails.
+    pub fn process(&mut self, mut instruction: Instruction) -> ProgramResult {
+        if let Some(fee_payer) = instruction.accounts.iter_mut().find(|m| m.is_signer) {
+            fee_payer.is_writable = true;
+        }
+        let (mut input, serialized) = self.serialize(&instruction);
+        LOGS.with(|logs| logs.borrow_mut().clear());
+        log(format!("Program {} invoke [1]", reward_pool::ID));
+        let result = unsafe {
+            let (program_id, accounts, data) =
+                entrypoint::deserialize(input.as_mut_ptr() as *mut u8);
+            reward_pool::entry(program_id, &accounts, data)
+        };
+        log_result(&reward_pool::ID, &result);
+        if result.is_ok() {
+            self.commit(&input, &serialized);
+        }
+        result
+    }
+
+    /// Lays out the instruction's accounts exactly as the BPF loader does.
+    fn serialize(&self, instruction: &Instruction) -> (Vec<u64>, Vec<SerializedAccount>) {
+        fn push_u64(buffer: &mut Vec<u8>, value: u64) {
+            buffer.extend_from_slice(&value.to_le_bytes());
+        }
+
+        let mut buffer: Vec<u8> = Vec::new();
+        let mut serialized: Vec<SerializedAccount> = Vec::new();
+        push_u64(&mut buffer, instruction.accounts.len() as u64);
+
+        for (i, meta) in instruction.accounts.iter().enumerate() {
+            //a repeated account refers back to the position it was first passed at
+            let first = instruction
+                .accounts
+                .iter()
+                .position(|m| m.pubkey == meta.pubkey)
+                .unwrap();
+            if first < i {
+                buffer.push(first as u8);
+                buffer.extend_from_slice(&[0; 7]);
+                continue;
+            }
+
+            //as in a transaction, an account passed more than once takes the union of its flags
+            let metas = instruction
+                .accounts
+                .iter()
+                .filter(|m| m.pubkey == meta.pubkey);
+            let (is_signer, is_writable) = metas.fold((false, false), |(s, w), m| {
+                (s || m.is_signer, w || m.is_writable)
+            });
+
+            let account = self
+                .accounts
+                .get(&meta.pubkey)
+                .cloned()
+                .unwrap_or(TestAccount {
+                    lamports: 0,
+                    data: vec![],
+                    owner: system_program::ID,
+                    executable: false,
+                });
+            buffer.push(u8::MAX);
+            buffer.push(is_signer as u8);
+            buffer.push(is_writable as u8);
+            buffer.push(account.executable as u8);
+            buffer.extend_from_slice(&[0; 4]);
+            buffer.extend_from_slice(meta.pubkey.as_ref());
+            let owner = buffer.len();
+            buffer.extend_from_slice(account.owner.as_ref());
+            let lamports = buffer.len();
+            push_u64(&mut buffer, account.lamports);
+            let data_len = buffer.len();
+            push_u64(&mut buffer, account.data.len() as u64);
+            buffer.extend_from_slice(&account.data);
+            buffer.resize(buffer.len() + MAX_PERMITTED_DATA_INCREASE, 0);
+            buffer.resize(buffer.len() + (8 - buffer.len() % 8) % 8, 0);
+            push_u64(&mut buffer, 0); //rent epoch
+
+            serialized.push(SerializedAccount {
+                key: meta.pubkey,
+                is_writable,
+                owner,
+                lamports,
+                data_len,
+            });
+        }
+
+        push_u64(&mut buffer, instruction.data.len() as u64);
+        buffer.extend_from_slice(&instruction.data);
+        buffer.extend_from_slice(instruction.program_id.as_ref());
+
+        //back the buffer with u64s so the program sees it aligned
+        let mut input = vec![0u64; buffer.len().div_ceil(8)];
+        unsafe {
+            std::ptr::copy_nonoverlapping(
+                buffer.as_ptr(),
+                input.as_mut_ptr() as *mut u8,
+                buffer.len(),
+            );
+        }
+        (input, serialized)
+    }
+
+    /// Writes the accounts an instruction left behind back to the ledger, holding it to
+    /// the runtime's rules on lamports and read only accounts.
+    fn commit(&mut self, input: &[u64], serialized: &[SerializedAccount]) {
+        let bytes =
+            unsafe { std::slice::from_raw_parts(input.as_ptr() as *const u8, input.len() * 8) };
+        let read_u64 = |offset: usize| {
+            u64::from_le_bytes(bytes[offset..offset + size_of::<u64>()].try_into().unwrap())
+        };
+
+        let mut lamports_before = 0u128;
+        let mut lamports_after = 0u128;
+        for s in serialized.iter() {
+            let before = self.accounts.get(&s.key).cloned();
+            let data_len = read_u64(s.data_len) as usize;
+            let data_start = s.data_len + size_of::<u64>();
+            let after = TestAccount {
+                lamports: read_u64(s.lamports),
+                data: bytes[data_start..data_start + data_len].to_vec(),
+                owner: Pubkey::new(&bytes[s.owner..s.owner + 32]),
+                executable: before.as_ref().map(|a| a.executable).unwrap_or(false),
+            };
+
+            lamports_before += before.as_ref().map(|a| a.lamports).unwrap_or(0) as u128;
+            lamports_after += after.lamports as u128;
+
+            let unchanged = match &before {
+                Some(before) => *before == after,
+                None => after.lamports == 0 && after.data.is_empty(),
+            };
+            if unchanged {
+                continue;
+            }
+            assert!(s.is_writable, "read only account {} was modified", s.key);
+
+            //the runtime purges accounts left without lamports
+            if after.lamports == 0 {
+                self.accounts.remove(&s.key);
+            } else {
+                self.accounts.insert(s.key, after);
+            }
+        }
+        assert_eq!(
+            lamports_before, lamports_after,
+            "lamports were not conserved"
+        );
+    }
+
+    pub fn set_account(&mut self, key: Pubkey, lamports: u64, data: Vec<u8>, owner: Pubkey) {
+        self.accounts.insert(
+            key,
+            TestAccount {
+                lamports,
+                data,
+                owner,
+                executable: false,
+            },
+        );
+    }
+
+    pub fn account(&self, key: &Pubkey) -> Option<&TestAccount> {
+        self.accounts.get(key)
+    }
+
+    pub fn lamports(&self, key: &Pubkey) -> u64 {
+        self.account(key).map(|a| a.lamports).unwrap_or(0)
+    }
+
+    /// Deserializes an anchor account, checking its discriminator.
+    pub fn get_account<T: AccountDeserialize>(&self, key: &Pubkey) -> T {
+        let account = self
+            .account(key)
+            .unwrap_or_else(|| panic!("account {} does not exist", key));
+        T::try_deserialize(&mut account.data.as_slice()).unwrap()
+    }
+
+    /// Overwrites the state of an existing anchor account, for setting up accounts as an
+    /// earlier version of the program left them.
+    pub fn set_anchor_account<T: AccountSerialize>(&mut self, key: &Pubkey, state: &T) {
+        let mut data = Vec::new();
+        state.try_serialize(&mut data).unwrap();
+        let account = self.accounts.get_mut(key).unwrap();
+        account.data[..data.len()].copy_from_slice(&data);
+    }
+
+    /// Writes the program data account of an upgradeable deploy of the program, the
+    /// upgrade authority `initialize_config` checks.
+    pub fn set_upgrade_authority(&mut self, upgrade_authority: &Pubkey) {
+        //bincode of UpgradeableLoaderState::ProgramData { slot: 0, upgrade_authority_address }
+        let mut data = 3u32.to_le_bytes().to_vec();
+        data.extend_from_slice(&0u64.to_le_bytes());
+        data.push(1);
+        data.extend_from_slice(upgrade_authority.as_ref());
+        self.set_account(
+            reward_pool::program_data_address(),
+            1,
+            data,
+            bpf_loader_upgradeable::ID,
+        );
+    }
+
+    /// Creates a system owned wallet.
+    pub fn create_wallet(&mut self) -> Pubkey {
+        let wallet = Pubkey::new_unique();
+        self.set_account(wallet, WALLET_LAMPORTS, vec![], system_program::ID);
+        wallet
+    }
+
+    /// Creates an account the program itself will initialize, the way a client would
+    /// allocate it with a system `create_account` before calling the program.
+    pub fn create_program_account(&mut self, key: Pubkey, space: usize) {
+        let lamports = Rent::default().minimum_balance(space);
+        self.set_account(key, lamports, vec![0; space], reward_pool::ID);
+    }
+
+    pub fn create_mint(&mut self, decimals: u8) -> Pubkey {
+        let key = Pubkey::new_unique();
+        self.create_mint_at(key, decimals);
+        key
+    }
+
+    pub fn create_mint_at(&mut self, key: Pubkey, decimals: u8) {
+        let mint = Mint {
+            mint_authority: COption::None,
+            supply: 0,
+            decimals,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        };
+        let mut data = vec![0; Mint::LEN];
+        Mint::pack(mint, &mut data).unwrap();
+        let lamports = Rent::default().minimum_balance(Mint::LEN);
+        self.set_account(key, lamports, data, spl_token::ID);
+    }
+
+    pub fn create_token_account(&mut self, mint: &Pubkey, owner: &Pubkey, amount: u64) -> Pubkey {
+        let key = Pubkey::new_unique();
+        self.create_token_account_at(key, mint, owner, amount);
+        key
+    }
+
+    /// Creates `owner`'s associated token account of `mint`.
+    pub fn create_associated_token_account(&mut self, mint: &Pubkey, owner: &Pubkey) -> Pubkey {
+        let key = get_associated_token_address(owner, mint);
+        self.create_token_account_at(key, mint, owner, 0);
+        key
+    }
+
+    pub fn create_token_account_at(
+        &mut self,
+        key: Pubkey,
+        mint: &Pubkey,
+        owner: &Pubkey,
+        amount: u64,
+    ) {
+        let account = TokenAccount {
+            mint: *mint,
+            owner: *owner,
+            amount: 0,
+            delegate: COption::None,
+            state: AccountState::Initialized,
+            is_native: COption::None,
+            delegated_amount: 0,
+            close_authority: COption::None,
+        };
+        let mut data = vec![0; TokenAccount::LEN];
+        TokenAccount::pack(account, &mut data).unwrap();
+        let lamports = Rent::default().minimum_balance(TokenAccount::LEN);
+        self.set_account(key, lamports, data, spl_token::ID);
+        self.mint_to(&key, amount);
+    }
+
+    /// Mints straight into a token account, keeping the mint's supply in step.
+    pub fn mint_to(&mut self, token_account: &Pubkey, amount: u64) {
+        let mut account = self.token_account(token_account);
+        account.amount += amount;
+        let data = &mut self.accounts.get_mut(token_account).unwrap().data;
+        TokenAccount::pack(account, data).unwrap();
+
+        let mint_data = &mut self.accounts.get_mut(&account.mint).unwrap().data;
+        let mut mint = Mint::unpack(mint_data).unwrap();
+        mint.supply += amount;
+        Mint::pack(mint, mint_data).unwrap();
+    }
+
+    pub fn token_account(&self, key: &Pubkey) -> TokenAccount {
+        let account = self
+            .account(key)
+            .unwrap_or_else(|| panic!("token account {} does not exist", key));
+        TokenAccount::unpack(&account.data).unwrap()
+    }
+
+    pub fn token_balance(&self, key: &Pubkey) -> u64 {
+        self.token_account(key).amount
+    }
+}
+
+/// Asserts an instruction failed with the given program or anchor error.
+#[track_caller]
+pub fn assert_error(result: ProgramResult, error: impl Into<ProgramError>) {
+    assert_eq!(result, Err(error.into()));
+}
@@ -0,0 +1,198 @@
+mod harness;
+
+use anchor_lang::error::ErrorCode as AnchorErrorCode;
+use reward_pool::{ErrorCode, Pool};
+
+use harness::fixture::{PoolFixture, UserFixture, MIN_DURATION, X_STEP_DEPOSIT_REQUIREMENT};
+use harness::{assert_error, ProgramTest};
+
+const DAY: u64 = 86_400;
+/// Funding that emits exactly 1000 tokens a second over a day
+const FUNDING: u64 = 86_400_000;
+
+/// Initializes a multi reward pool holding `reward_count` rewards, with an authorized
+/// funder and a user staking 1000
+fn setup(
+    t: &mut ProgramTest,
+    independent_schedules: bool,
+    reward_count: usize,
+) -> (PoolFixture, UserFixture, UserFixture) {
+    let f = PoolFixture::new(t, reward_count);
+    if independent_schedules {
+        t.process(f.initialize_pool_v4(DAY)).unwrap();
+    } else {
+        t.process(f.initialize_pool_v3(DAY)).unwrap();
+    }
+    for i in 0..reward_count {
+        t.process(f.add_reward(i)).unwrap();
+    }
+    let funder = f.create_wallet(t, 0, 10 * FUNDING);
+    t.process(f.authorize_funder(funder.owner)).unwrap();
+    let u = f.create_wallet(t, 1000, 0);
+    t.process(f.create_user(&u)).unwrap();
+    t.process(f.stake(&u, 1000)).unwrap();
+    (f, funder, u)
+}
+
+fn claimed(t: &ProgramTest, u: &UserFixture) -> Vec<u64> {
+    u.reward_accounts
+        .iter()
+        .map(|account| t.token_balance(account))
+        .collect()
+}
+
+#[test]
+fn initialize_pool_v3_and_add_rewards() {
+    let mut t = ProgramTest::new();
+    let (f, _, _) = setup(&mut t, false, 3);
+
+    let pool: Pool = t.get_account(&f.pool);
+    assert!(pool.version.is_multi_reward());
+    assert!(!pool.version.has_independent_schedules());
+    assert_eq!(
+        t.token_balance(&f.x_token_pool_vault),
+        X_STEP_DEPOSIT_REQUIREMENT
+    );
+    let rewards = pool.reward_infos();
+    assert_eq!(rewards.len(), 3);
+    for (i, reward) in rewards.iter().enumerate() {
+        assert_eq!(reward.mint, f.reward_mints[i]);
+        assert_eq!(reward.vault, f.reward_vaults[i]);
+        assert_eq!(reward.reward_duration, DAY);
+    }
+
+    assert_error(t.process(f.add_reward(1)), ErrorCode::RewardAlreadyAdded);
+}
+
+#[test]
+fn rewards_are_funded_and_claimed_by_index() {
+    let mut t = ProgramTest::new();
+    let (f, funder, u) = setup(&mut t, false, 3);
+
+    t.process(f.fund_v3(&funder, vec![FUNDING, 0, 2 * FUNDING]))
+        .unwrap();
+    assert_eq!(t.token_balance(&f.reward_vaults[0]), FUNDING);
+    assert_eq!(t.token_balance(&f.reward_vaults[2]), 2 * FUNDING);
+
+    t.warp_forward(DAY / 2);
+    t.process(f.claim_v3(&u, vec![0])).unwrap();
+    assert_eq!(claimed(&t, &u), vec![FUNDING / 2, 0, 0]);
+
+    let mut missing_account = f.claim_v3(&u, vec![2]);
+    missing_account.accounts.pop();
+    assert_error(t.process(missing_account), ErrorCode::InvalidRewardAccounts);
+
+    //unclaimed rewards stay pending
+    t.warp_forward(DAY);
+    t.process(f.claim_v3(&u, vec![0, 1, 2])).unwrap();
+    assert_eq!(claimed(&t, &u), vec![FUNDING, 0, 2 * FUNDING]);
+}
+
+#[test]
+fn claim_for_pays_every_reward_to_the_owner() {
+    let mut t = ProgramTest::new();
+    let (f, funder, u) = setup(&mut t, false, 3);
+    let keeper = t.create_wallet();
+    let owner_accounts: Vec<_> = f
+        .reward_mints
+        .iter()
+        .map(|mint| t.create_associated_token_account(mint, &u.owner))
+        .collect();
+
+    t.process(f.fund_v3(&funder, vec![FUNDING, 0, 2 * FUNDING]))
+        .unwrap();
+    t.warp_forward(DAY / 2);
+    let mut missing_reward = f.claim_for(&u, keeper, u.owner);
+    missing_reward
+        .accounts
+        .truncate(missing_reward.accounts.len() - 2);
+    assert_error(t.process(missing_reward), ErrorCode::InvalidRewardAccounts);
+
+    t.process(f.claim_for(&u, keeper, u.owner)).unwrap();
+    let paid: Vec<u64> = owner_accounts
+        .iter()
+        .map(|account| t.token_balance(account))
+        .collect();
+    assert_eq!(paid, vec![FUNDING / 2, 0, FUNDING]);
+}
+
+#[test]
+fn independent_schedules_emit_over_their_own_durations() {
+    let mut t = ProgramTest::new();
+    let (f, funder, u) = setup(&mut t, true, 2);
+
+    assert_error(
+        t.process(f.set_reward_duration(1, MIN_DURATION - 1)),
+        ErrorCode::DurationTooShort,
+    );
+    assert_error(
+        t.process(f.set_reward_duration(2, DAY)),
+        ErrorCode::InvalidRewardIndex,
+    );
+    t.process(f.set_reward_duration(1, 5 * DAY)).unwrap();
+
+    t.process(f.fund_v3(&funder, vec![FUNDING, 5 * FUNDING]))
+        .unwrap();
+    let start = t.now();
+    let pool: Pool = t.get_account(&f.pool);
+    assert_eq!(pool.rewards[0].reward_duration_end, start + DAY);
+    assert_eq!(pool.rewards[1].reward_duration_end, start + 5 * DAY);
+    assert_eq!(pool.reward_duration_end, start + 5 * DAY);
+
+    t.warp_forward(DAY + DAY / 2);
+    t.process(f.claim_v3(&u, vec![0, 1])).unwrap();
+    assert_eq!(claimed(&t, &u), vec![FUNDING, FUNDING + FUNDING / 2]);
+
+    //funding one reward leaves the schedule of the other alone
+    t.process(f.fund_v3(&funder, vec![FUNDING, 0])).unwrap();
+    let pool: Pool = t.get_account(&f.pool);
+    assert_eq!(pool.rewards[0].reward_duration_end, t.now() + DAY);
+    assert_eq!(pool.rewards[1].reward_duration_end, start + 5 * DAY);
+
+    t.warp_to(start + 5 * DAY);
+    t.process(f.claim_v3(&u, vec![0, 1])).unwrap();
+    assert_eq!(claimed(&t, &u), vec![2 * FUNDING, 5 * FUNDING]);
+}
+
+#[test]
+fn set_reward_duration_requires_independent_schedules() {
+    let mut t = ProgramTest::new();
+    let (f, _, _) = setup(&mut t, false, 2);
+
+    assert_error(
+        t.process(f.set_reward_duration(0, 2 * DAY)),
+        AnchorErrorCode::ConstraintRaw,
+    );
+}
+
+#[test]
+fn close_pool_v3_refunds_every_vault() {
+    let mut t = ProgramTest::new();
+    let (f, funder, u) = setup(&mut t, false, 2);
+    t.process(f.fund_v3(&funder, vec![FUNDING, FUNDING]))
+        .unwrap();
+
+    t.warp_forward(DAY + 1);
+    t.process(f.claim_v3(&u, vec![0])).unwrap();
+    t.process(f.unstake(&u, 1000, None)).unwrap();
+    //reward 1 was never claimed, so the user cannot be closed
+    assert_error(t.process(f.close_user(&u)), AnchorErrorCode::ConstraintRaw);
+    t.process(f.claim_v3(&u, vec![1])).unwrap();
+    t.process(f.close_user(&u)).unwrap();
+    t.process(f.pause()).unwrap();
+
+    let refundee = f.create_wallet(&mut t, 0, 0);
+    t.mint_to(&f.reward_vaults[1], 7);
+    let mut missing_vault = f.close_pool_v3(&refundee, 2);
+    missing_vault
+        .accounts
+        .truncate(missing_vault.accounts.len() - 2);
+    assert_error(t.process(missing_vault), ErrorCode::InvalidRewardAccounts);
+
+    t.process(f.close_pool_v3(&refundee, 2)).unwrap();
+    assert!(t.account(&f.pool).is_none());
+    assert!(t.account(&f.staking_vault).is_none());
+    assert!(t.account(&f.reward_vaults[0]).is_none());
+    assert!(t.account(&f.reward_vaults[1]).is_none());
+    assert_eq!(claimed(&t, &refundee), vec![0, 7]);
+}
@@ -0,0 +1,896 @@
+mod harness;
+
+use anchor_lang::error::ErrorCode as AnchorErrorCode;
+use anchor_lang::prelude::*;
+use reward_pool::{token_metadata, ErrorCode, Pool, PoolGate, ProgramConfig, User};
+
+use harness::fixture::{
+    accept_config_admin, cancel_config_admin_transfer, config_address, initialize_config,
+    propose_config_admin, set_global_freeze, update_config, PoolFixture, UserFixture, MIN_DURATION,
+    X_STEP_DEPOSIT_REQUIREMENT, X_STEP_TOKEN_MINT_PUBKEY,
+};
+use harness::{assert_error, ProgramTest};
+
+const DAY: u64 = 86_400;
+/// Funding that emits exactly 1000 tokens a second over a day
+const FUNDING: u64 = 86_400_000;
+
+/// Initializes an A/B pool with a day long reward period and an authorized funder
+fn setup(t: &mut ProgramTest) -> (PoolFixture, UserFixture) {
+    let f = PoolFixture::new(t, 2);
+    t.process(f.initialize_pool(DAY, 0, 0, None)).unwrap();
+    let funder = f.create_wallet(t, 0, 10 * FUNDING);
+    t.process(f.authorize_funder(funder.owner)).unwrap();
+    (f, funder)
+}
+
+fn staked_user(t: &mut ProgramTest, f: &PoolFixture, amount: u64) -> UserFixture {
+    let u = f.create_wallet(t, amount, 0);
+    t.process(f.create_user(&u)).unwrap();
+    t.process(f.stake(&u, amount)).unwrap();
+    u
+}
+
+fn claimed(t: &ProgramTest, u: &UserFixture) -> (u64, u64) {
+    (
+        t.token_balance(&u.reward_accounts[0]),
+        t.token_balance(&u.reward_accounts[1]),
+    )
+}
+
+#[test]
+fn initialize_pool_takes_xstep_deposit() {
+    let mut t = ProgramTest::new();
+    let (f, _) = setup(&mut t);
+
+    let pool: Pool = t.get_account(&f.pool);
+    assert_eq!(pool.authority, f.authority);
+    assert_eq!(pool.nonce, f.nonce);
+    assert!(pool.version.has_annual_rate());
+    assert!(pool.total_staked_tracked);
+    assert_eq!(pool.staking_vault, f.staking_vault);
+    assert_eq!(pool.reward_a_vault, f.reward_vaults[0]);
+    assert_eq!(pool.reward_b_vault, f.reward_vaults[1]);
+    assert_eq!(pool.reward_duration, DAY);
+    assert_eq!(pool.reward_duration_end, 0);
+    assert_eq!(
+        t.token_balance(&f.x_token_pool_vault),
+        X_STEP_DEPOSIT_REQUIREMENT
+    );
+    assert_eq!(t.token_balance(&f.x_token_depositor), 0);
+}
+
+#[test]
+fn initialize_pool_rejects_invalid_config() {
+    let mut t = ProgramTest::new();
+    let f = PoolFixture::new(&mut t, 2);
+
+    assert_error(
+        t.process(f.initialize_pool(MIN_DURATION - 1, 0, 0, None)),
+        ErrorCode::DurationTooShort,
+    );
+    assert_error(
+        t.process(f.initialize_pool(DAY, 10_001, DAY, None)),
+        ErrorCode::InvalidEarlyExitPenalty,
+    );
+    assert_error(
+        t.process(f.initialize_pool(DAY, 500, 0, None)),
+        ErrorCode::InvalidEarlyExitPenalty,
+    );
+
+    //failed attempts leave the pool account free to initialize
+    t.process(f.initialize_pool(DAY, 0, 0, None)).unwrap();
+    let pool: Pool = t.get_account(&f.pool);
+    assert_eq!(pool.reward_duration, DAY);
+}
+
+#[test]
+fn rewards_accrue_exactly_over_warped_time() {
+    let mut t = ProgramTest::new();
+    let (f, funder) = setup(&mut t);
+    let u = staked_user(&mut t, &f, 1000);
+
+    t.process(f.fund(&funder, FUNDING, 2 * FUNDING)).unwrap();
+    let pool: Pool = t.get_account(&f.pool);
+    assert_eq!(pool.reward_duration_end, t.now() + DAY);
+
+    t.warp_forward(DAY / 2);
+    t.process(f.claim(&u)).unwrap();
+    assert_eq!(claimed(&t, &u), (FUNDING / 2, FUNDING));
+
+    //nothing more is earned in the same second
+    t.process(f.claim(&u)).unwrap();
+    assert_eq!(claimed(&t, &u), (FUNDING / 2, FUNDING));
+
+    //or after the period ends
+    t.warp_forward(DAY);
+    t.process(f.claim(&u)).unwrap();
+    assert_eq!(claimed(&t, &u), (FUNDING, 2 * FUNDING));
+    assert_eq!(t.token_balance(&f.reward_vaults[0]), 0);
+    assert_eq!(t.token_balance(&f.reward_vaults[1]), 0);
+}
+
+#[test]
+fn rewards_are_shared_by_stake_from_when_staked() {
+    let mut t = ProgramTest::new();
+    let (f, funder) = setup(&mut t);
+    let early = staked_user(&mut t, &f, 1000);
+    t.process(f.fund(&funder, FUNDING, 0)).unwrap();
+
+    t.warp_forward(DAY / 2);
+    let late = staked_user(&mut t, &f, 3000);
+
+    t.warp_forward(DAY);
+    t.process(f.claim(&early)).unwrap();
+    t.process(f.claim(&late)).unwrap();
+    assert_eq!(claimed(&t, &early), (43_200_000 + 10_800_000, 0));
+    assert_eq!(claimed(&t, &late), (32_400_000, 0));
+}
+
+#[test]
+fn refunding_redistributes_remaining_rewards() {
+    let mut t = ProgramTest::new();
+    let (f, funder) = setup(&mut t);
+    let u = staked_user(&mut t, &f, 1000);
+    t.process(f.fund(&funder, FUNDING, 0)).unwrap();
+
+    //the half left over is emitted with the new funding over a new day
+    t.warp_forward(DAY / 2);
+    t.process(f.fund(&funder, FUNDING, 0)).unwrap();
+    let pool: Pool = t.get_account(&f.pool);
+    assert_eq!(pool.reward_duration_end, t.now() + DAY);
+
+    t.warp_forward(DAY / 2);
+    t.process(f.claim(&u)).unwrap();
+    assert_eq!(claimed(&t, &u), (43_200_000 + 64_800_000, 0));
+
+    t.warp_forward(DAY);
+    t.process(f.claim(&u)).unwrap();
+    assert_eq!(claimed(&t, &u), (2 * FUNDING, 0));
+}
+
+#[test]
+fn funding_past_the_max_rate_fails_with_an_error() {
+    let mut t = ProgramTest::new();
+    let (f, _) = setup(&mut t);
+    let whale = f.create_wallet(&mut t, 0, u64::MAX / 2);
+    t.process(f.authorize_funder(whale.owner)).unwrap();
+    staked_user(&mut t, &f, 1000);
+
+    //the annual rate of a day long period is 365 times the funding
+    let max_funding = u64::MAX / 365;
+    assert_error(
+        t.process(f.fund(&whale, max_funding + 1, 0)),
+        ErrorCode::RateTooHigh,
+    );
+    t.process(f.fund(&whale, max_funding, 0)).unwrap();
+    assert_error(t.process(f.top_up(&whale, 1, 0)), ErrorCode::RateTooHigh);
+}
+
+#[test]
+fn only_authorized_funders_fund() {
+    let mut t = ProgramTest::new();
+    let (f, funder) = setup(&mut t);
+    let stranger = f.create_wallet(&mut t, 0, FUNDING);
+
+    assert_error(
+        t.process(f.fund(&stranger, FUNDING, 0)),
+        AnchorErrorCode::ConstraintRaw,
+    );
+    assert_error(
+        t.process(f.authorize_funder(funder.owner)),
+        ErrorCode::FunderAlreadyAuthorized,
+    );
+    assert_error(
+        t.process(f.authorize_funder(f.authority)),
+        ErrorCode::FunderAlreadyAuthorized,
+    );
+    for _ in 0..3 {
+        t.process(f.authorize_funder(Pubkey::new_unique())).unwrap();
+    }
+    assert_error(
+        t.process(f.authorize_funder(stranger.owner)),
+        ErrorCode::MaxFunders,
+    );
+
+    t.process(f.deauthorize_funder(funder.owner)).unwrap();
+    assert_error(
+        t.process(f.fund(&funder, FUNDING, 0)),
+        AnchorErrorCode::ConstraintRaw,
+    );
+    assert_error(
+        t.process(f.deauthorize_funder(funder.owner)),
+        ErrorCode::CannotDeauthorizeMissingAuthority,
+    );
+    assert_error(
+        t.process(f.deauthorize_funder(f.authority)),
+        ErrorCode::CannotDeauthorizePoolAuthority,
+    );
+
+    t.process(f.authorize_funder(stranger.owner)).unwrap();
+    t.process(f.fund(&stranger, FUNDING, 0)).unwrap();
+    assert_eq!(t.token_balance(&f.reward_vaults[0]), FUNDING);
+}
+
+#[test]
+fn authority_transfers_in_two_steps() {
+    let mut t = ProgramTest::new();
+    let (f, funder) = setup(&mut t);
+    let new_authority = t.create_wallet();
+
+    assert_error(
+        t.process(f.accept_authority(new_authority)),
+        ErrorCode::NotPendingAuthority,
+    );
+    assert_error(
+        t.process(f.cancel_authority_transfer()),
+        ErrorCode::NoPendingAuthority,
+    );
+    assert_error(
+        t.process(f.propose_authority(f.authority)),
+        ErrorCode::InvalidNewAuthority,
+    );
+
+    //a cancelled proposal can no longer be accepted
+    t.process(f.propose_authority(new_authority)).unwrap();
+    t.process(f.cancel_authority_transfer()).unwrap();
+    assert_error(
+        t.process(f.accept_authority(new_authority)),
+        ErrorCode::NotPendingAuthority,
+    );
+
+    t.process(f.propose_authority(new_authority)).unwrap();
+    assert_error(
+        t.process(f.accept_authority(funder.owner)),
+        ErrorCode::NotPendingAuthority,
+    );
+    let pool: Pool = t.get_account(&f.pool);
+    assert_eq!(pool.authority, f.authority);
+    assert_eq!(pool.pending_authority, new_authority);
+    t.process(f.accept_authority(new_authority)).unwrap();
+
+    let pool: Pool = t.get_account(&f.pool);
+    assert_eq!(pool.authority, new_authority);
+    assert_eq!(pool.pending_authority, Pubkey::default());
+    assert!(pool.funders.contains(&funder.owner));
+    assert_error(
+        t.process(f.authorize_funder(Pubkey::new_unique())),
+        AnchorErrorCode::ConstraintHasOne,
+    );
+    t.process(f.fund(&funder, FUNDING, 0)).unwrap();
+
+    //a funder taking over funds as the authority, freeing its funder slot
+    let f = PoolFixture {
+        authority: new_authority,
+        ..f
+    };
+    t.process(f.propose_authority(funder.owner)).unwrap();
+    t.process(f.accept_authority(funder.owner)).unwrap();
+    let pool: Pool = t.get_account(&f.pool);
+    assert_eq!(pool.authority, funder.owner);
+    assert!(!pool.funders.contains(&funder.owner));
+    t.process(f.fund(&funder, FUNDING, 0)).unwrap();
+}
+
+/// Stands in for the Metaplex metadata of an NFT `mint` in `collection`
+fn set_nft_metadata(t: &mut ProgramTest, mint: &Pubkey, collection: &Pubkey, verified: bool) {
+    let (address, _) = Pubkey::find_program_address(
+        &[b"metadata", token_metadata::ID.as_ref(), mint.as_ref()],
+        &token_metadata::ID,
+    );
+    let names = (
+        4u8,
+        Pubkey::new_unique(),
+        *mint,
+        "NFT",
+        "NFT",
+        "https://nft",
+    );
+    let rest = (
+        0u16,
+        None::<Vec<(Pubkey, bool, u8)>>,
+        false,
+        true,
+        None::<u8>,
+        None::<u8>,
+        Some((verified, *collection)),
+    );
+    let mut data = names.try_to_vec().unwrap();
+    data.extend(rest.try_to_vec().unwrap());
+    let lamports = Rent::default().minimum_balance(data.len());
+    t.set_account(address, lamports, data, token_metadata::ID);
+}
+
+#[test]
+fn gated_pools_admit_only_allowed_owners() {
+    let mut t = ProgramTest::new();
+    let f = PoolFixture::new(&mut t, 2).with_gate(PoolGate::Mint, Pubkey::default());
+    assert_error(
+        t.process(f.initialize_pool(DAY, 0, 0, None)),
+        ErrorCode::InvalidGate,
+    );
+
+    //the gate authority co-signs
+    let gatekeeper = t.create_wallet();
+    let f = PoolFixture::new(&mut t, 2).with_gate(PoolGate::Authority, gatekeeper);
+    t.process(f.initialize_pool(DAY, 0, 0, None)).unwrap();
+    let u = f.create_wallet(&mut t, 0, 0);
+    let mut without_gatekeeper = f.create_user(&u);
+    without_gatekeeper.accounts.pop();
+    assert_error(t.process(without_gatekeeper), ErrorCode::NotAllowedToJoin);
+    t.process(f.create_user(&u)).unwrap();
+
+    //the owner holds the gate's mint
+    let pass = t.create_mint(0);
+    let f = PoolFixture::new(&mut t, 2).with_gate(PoolGate::Mint, pass);
+    t.process(f.initialize_pool_v4(DAY)).unwrap();
+    let u = f.create_wallet(&mut t, 0, 0);
+    let empty = t.create_token_account(&pass, &u.owner, 0);
+    let someone_elses = t.create_token_account(&pass, &Pubkey::new_unique(), 1);
+    for account in [empty, someone_elses] {
+        assert_error(
+            t.process(f.create_user_holding(&u, account, pass)),
+            ErrorCode::NotAllowedToJoin,
+        );
+    }
+    assert_error(t.process(f.create_user(&u)), ErrorCode::NotAllowedToJoin);
+    let held = t.create_token_account(&pass, &u.owner, 1);
+    t.process(f.create_user_holding(&u, held, pass)).unwrap();
+
+    //the owner holds an NFT of the gate's verified collection
+    let collection = t.create_mint(0);
+    let f = PoolFixture::new(&mut t, 2).with_gate(PoolGate::Collection, collection);
+    t.process(f.initialize_pool(DAY, 0, 0, None)).unwrap();
+    let u = f.create_wallet(&mut t, 0, 0);
+    let nft = t.create_mint(0);
+    let held = t.create_token_account(&nft, &u.owner, 1);
+    set_nft_metadata(&mut t, &nft, &collection, false);
+    assert_error(
+        t.process(f.create_user_holding(&u, held, nft)),
+        ErrorCode::NotAllowedToJoin,
+    );
+    set_nft_metadata(&mut t, &nft, &collection, true);
+    t.process(f.create_user_holding(&u, held, nft)).unwrap();
+    let pool: Pool = t.get_account(&f.pool);
+    assert_eq!(pool.gate, PoolGate::Collection);
+    assert_eq!(pool.gate_key, collection);
+    assert_eq!(pool.user_stake_count, 1);
+}
+
+#[test]
+fn top_up_keeps_period_end() {
+    let mut t = ProgramTest::new();
+    let (f, funder) = setup(&mut t);
+    let u = staked_user(&mut t, &f, 1000);
+
+    assert_error(
+        t.process(f.top_up(&funder, FUNDING, 0)),
+        ErrorCode::RewardPeriodEnded,
+    );
+
+    t.process(f.fund(&funder, FUNDING, 0)).unwrap();
+    let end = t.now() + DAY;
+
+    t.warp_forward(DAY / 2);
+    t.process(f.top_up(&funder, FUNDING, 0)).unwrap();
+    let pool: Pool = t.get_account(&f.pool);
+    assert_eq!(pool.reward_duration_end, end);
+
+    //the funding and the half left over are emitted by the same end
+    t.warp_to(end);
+    t.process(f.claim(&u)).unwrap();
+    assert_eq!(claimed(&t, &u), (2 * FUNDING, 0));
+
+    t.warp_forward(1);
+    assert_error(
+        t.process(f.top_up(&funder, FUNDING, 0)),
+        ErrorCode::RewardPeriodEnded,
+    );
+}
+
+#[test]
+fn fund_scheduled_takes_over_at_its_start() {
+    let mut t = ProgramTest::new();
+    let (f, funder) = setup(&mut t);
+    let u = staked_user(&mut t, &f, 1000);
+
+    t.process(f.fund(&funder, FUNDING, 0)).unwrap();
+    let start_ts = t.now() + DAY / 2;
+    let end_ts = start_ts + DAY;
+
+    assert_error(
+        t.process(f.fund_scheduled(&funder, FUNDING, 0, t.now() - 1, end_ts)),
+        ErrorCode::InvalidRewardPeriod,
+    );
+    assert_error(
+        t.process(f.fund_scheduled(&funder, FUNDING, 0, start_ts, start_ts + 1)),
+        ErrorCode::DurationTooShort,
+    );
+    t.process(f.fund_scheduled(&funder, FUNDING, 0, start_ts, end_ts))
+        .unwrap();
+    assert_error(
+        t.process(f.fund_scheduled(&funder, FUNDING, 0, end_ts, end_ts + DAY)),
+        ErrorCode::RewardPeriodAlreadyScheduled,
+    );
+    assert_eq!(t.token_balance(&f.reward_vaults[0]), 2 * FUNDING);
+
+    //the current period emits until the scheduled one starts
+    t.warp_forward(DAY / 4);
+    t.process(f.claim(&u)).unwrap();
+    assert_eq!(claimed(&t, &u), (FUNDING / 4, 0));
+
+    //which then emits its funding along with what was left of the current period
+    t.warp_to(start_ts + DAY / 2);
+    t.process(f.claim(&u)).unwrap();
+    assert_eq!(claimed(&t, &u), (FUNDING / 2 + 64_800_000, 0));
+    let pool: Pool = t.get_account(&f.pool);
+    assert_eq!(pool.reward_duration_end, end_ts);
+    assert_eq!(pool.scheduled_start_ts, 0);
+
+    t.warp_to(end_ts + 1);
+    t.process(f.claim(&u)).unwrap();
+    assert_eq!(claimed(&t, &u), (2 * FUNDING, 0));
+}
+
+#[test]
+fn pause_refunds_deposit_and_unpause_retakes_it() {
+    let mut t = ProgramTest::new();
+    let (mut f, funder) = setup(&mut t);
+    let u = staked_user(&mut t, &f, 1000);
+    t.process(f.fund(&funder, FUNDING, 0)).unwrap();
+
+    assert_error(t.process(f.pause()), AnchorErrorCode::ConstraintRaw);
+
+    t.warp_forward(DAY + 1);
+    let vault_rent = t.lamports(&f.x_token_pool_vault);
+    let authority_lamports = t.lamports(&f.authority);
+    t.process(f.pause()).unwrap();
+    assert!(t.account(&f.x_token_pool_vault).is_none());
+    assert_eq!(t.lamports(&f.authority), authority_lamports + vault_rent);
+    assert_eq!(
+        t.token_balance(&f.x_token_depositor),
+        X_STEP_DEPOSIT_REQUIREMENT
+    );
+
+    assert_error(t.process(f.stake(&u, 1)), ErrorCode::PoolPaused);
+    assert_error(
+        t.process(f.fund(&funder, FUNDING, 0)),
+        AnchorErrorCode::ConstraintRaw,
+    );
+    //paused pools still pay out
+    t.process(f.claim(&u)).unwrap();
+    assert_eq!(claimed(&t, &u), (FUNDING, 0));
+
+    f.renew_x_token_pool_vault(&mut t);
+    t.process(f.unpause()).unwrap();
+    assert_eq!(t.token_balance(&f.x_token_depositor), 0);
+    assert_eq!(
+        t.token_balance(&f.x_token_pool_vault),
+        X_STEP_DEPOSIT_REQUIREMENT
+    );
+    let pool: Pool = t.get_account(&f.pool);
+    assert!(!pool.paused);
+    assert_eq!(pool.x_token_pool_vault, f.x_token_pool_vault);
+    t.process(f.fund(&funder, FUNDING, 0)).unwrap();
+}
+
+#[test]
+fn program_config_sets_the_deposit_and_minimum_duration() {
+    let mut t = ProgramTest::new();
+    let upgrade_authority = t.create_wallet();
+    t.set_upgrade_authority(&upgrade_authority);
+    let x_token_mint: Pubkey = X_STEP_TOKEN_MINT_PUBKEY.parse().unwrap();
+    let someone = t.create_wallet();
+    assert_error(
+        t.process(initialize_config(someone, someone, x_token_mint, 5, DAY)),
+        ErrorCode::NotUpgradeAuthority,
+    );
+    assert_error(
+        t.process(initialize_config(
+            upgrade_authority,
+            someone,
+            x_token_mint,
+            5,
+            0,
+        )),
+        ErrorCode::DurationTooShort,
+    );
+    t.process(initialize_config(
+        upgrade_authority,
+        someone,
+        x_token_mint,
+        5,
+        2 * DAY,
+    ))
+    .unwrap();
+    let config: ProgramConfig = t.get_account(&config_address());
+    assert_eq!(config.admin, someone);
+    assert_eq!(config.deposit_requirement, 5);
+    assert_eq!(config.min_duration, 2 * DAY);
+
+    let mut f = PoolFixture::new(&mut t, 2);
+    assert_error(
+        t.process(f.initialize_pool(DAY, 0, 0, None)),
+        ErrorCode::DurationTooShort,
+    );
+    t.process(f.initialize_pool(2 * DAY, 0, 0, None)).unwrap();
+    assert_eq!(t.token_balance(&f.x_token_pool_vault), 5);
+
+    //only the admin updates, and pools unpause with the new deposit
+    let deposit_mint = t.create_mint(6);
+    assert_error(
+        t.process(update_config(upgrade_authority, deposit_mint, 7, DAY)),
+        AnchorErrorCode::ConstraintHasOne,
+    );
+    t.process(update_config(someone, deposit_mint, 7, DAY))
+        .unwrap();
+    t.process(f.pause()).unwrap();
+    f.renew_x_token_pool_vault(&mut t);
+    assert_error(t.process(f.unpause()), AnchorErrorCode::ConstraintRaw);
+
+    f.x_token_pool_vault = t.create_token_account(&deposit_mint, &f.pool_signer, 0);
+    f.x_token_depositor = t.create_token_account(&deposit_mint, &f.authority, 7);
+    t.process(f.unpause()).unwrap();
+    assert_eq!(t.token_balance(&f.x_token_pool_vault), 7);
+    assert_eq!(t.token_balance(&f.x_token_depositor), 0);
+}
+
+#[cfg(feature = "localnet")]
+#[test]
+fn localnet_programs_without_program_data_let_anyone_create_the_config() {
+    let mut t = ProgramTest::new();
+    let x_token_mint: Pubkey = X_STEP_TOKEN_MINT_PUBKEY.parse().unwrap();
+    let someone = t.create_wallet();
+    t.process(initialize_config(someone, someone, x_token_mint, 5, DAY))
+        .unwrap();
+    let config: ProgramConfig = t.get_account(&config_address());
+    assert_eq!(config.admin, someone);
+}
+
+#[test]
+fn global_freeze_leaves_only_exits_open() {
+    let mut t = ProgramTest::new();
+    let (f, funder) = setup(&mut t);
+    let u = staked_user(&mut t, &f, 1000);
+    t.process(f.set_unbonding_period(DAY)).unwrap();
+    t.process(f.fund(&funder, FUNDING, 0)).unwrap();
+    t.warp_forward(DAY / 2);
+    let admin = t.get_account::<ProgramConfig>(&config_address()).admin;
+
+    assert_error(
+        t.process(set_global_freeze(f.authority, true)),
+        AnchorErrorCode::ConstraintHasOne,
+    );
+    t.process(set_global_freeze(admin, true)).unwrap();
+    t.mint_to(&u.staking_account, 1);
+    assert_error(t.process(f.stake(&u, 1)), ErrorCode::GlobalFreeze);
+    let newcomer = f.create_wallet(&mut t, 0, 0);
+    assert_error(
+        t.process(f.stake_for(&u, newcomer.owner, 1, vec![])),
+        ErrorCode::GlobalFreeze,
+    );
+    assert!(t.account(&newcomer.user).is_none());
+    assert_error(
+        t.process(f.fund(&funder, FUNDING, 0)),
+        ErrorCode::GlobalFreeze,
+    );
+    assert_error(t.process(f.claim(&u)), ErrorCode::GlobalFreeze);
+    assert_error(t.process(f.compound(&u)), ErrorCode::GlobalFreeze);
+    assert_error(
+        t.process(f.claim_penalty_rewards(&u)),
+        ErrorCode::GlobalFreeze,
+    );
+    assert_error(
+        t.process(f.lock_stake(&u, 1000, DAY)),
+        ErrorCode::GlobalFreeze,
+    );
+    t.process(f.unstake(&u, 1000, None)).unwrap();
+    assert_error(t.process(f.cancel_unbonding(&u)), ErrorCode::GlobalFreeze);
+    t.warp_forward(DAY);
+    t.process(f.withdraw_unbonded(&u)).unwrap();
+    assert_eq!(t.token_balance(&u.staking_account), 1001);
+
+    //rewards earned before the freeze are kept for once it lifts
+    t.process(set_global_freeze(admin, false)).unwrap();
+    t.process(f.claim(&u)).unwrap();
+    assert_eq!(claimed(&t, &u), (FUNDING / 2, 0));
+}
+
+#[test]
+fn config_admin_transfers_in_two_steps() {
+    let mut t = ProgramTest::new();
+    let f = PoolFixture::new(&mut t, 2);
+    let admin = t.get_account::<ProgramConfig>(&config_address()).admin;
+    let new_admin = t.create_wallet();
+
+    assert_error(
+        t.process(propose_config_admin(new_admin, new_admin)),
+        AnchorErrorCode::ConstraintHasOne,
+    );
+    assert_error(
+        t.process(propose_config_admin(admin, admin)),
+        ErrorCode::InvalidNewAuthority,
+    );
+    t.process(propose_config_admin(admin, new_admin)).unwrap();
+    t.process(cancel_config_admin_transfer(admin)).unwrap();
+    assert_error(
+        t.process(accept_config_admin(new_admin)),
+        ErrorCode::NotPendingAuthority,
+    );
+
+    t.process(propose_config_admin(admin, new_admin)).unwrap();
+    assert_error(
+        t.process(accept_config_admin(f.authority)),
+        ErrorCode::NotPendingAuthority,
+    );
+    t.process(accept_config_admin(new_admin)).unwrap();
+    let config: ProgramConfig = t.get_account(&config_address());
+    assert_eq!(config.admin, new_admin);
+    assert_eq!(config.pending_admin, Pubkey::default());
+    assert_error(
+        t.process(set_global_freeze(admin, true)),
+        AnchorErrorCode::ConstraintHasOne,
+    );
+    t.process(set_global_freeze(new_admin, true)).unwrap();
+}
+
+#[test]
+fn compound_stakes_staking_token_rewards() {
+    let mut t = ProgramTest::new();
+    let f = PoolFixture::new(&mut t, 2).with_staking_reward_a(&mut t);
+    t.process(f.initialize_pool(DAY, 0, 0, None)).unwrap();
+    let funder = f.create_wallet(&mut t, 0, FUNDING);
+    t.process(f.authorize_funder(funder.owner)).unwrap();
+    let u = staked_user(&mut t, &f, 1000);
+    t.process(f.fund(&funder, FUNDING, FUNDING)).unwrap();
+
+    t.warp_forward(DAY / 2);
+    t.process(f.compound(&u)).unwrap();
+
+    let user: User = t.get_account(&u.user);
+    let pool: Pool = t.get_account(&f.pool);
+    assert_eq!(user.balance_staked, 1000 + FUNDING / 2);
+    assert_eq!(pool.total_staked, 1000 + FUNDING / 2);
+    assert_eq!(t.token_balance(&f.staking_vault), 1000 + FUNDING / 2);
+    assert_eq!(t.token_balance(&f.reward_vaults[0]), FUNDING / 2);
+    //reward B is not the staking token, so it stays pending
+    assert_eq!(user.reward_a_per_token_pending, 0);
+    assert_eq!(user.reward_b_per_token_pending, FUNDING / 2);
+
+    let other = PoolFixture::new(&mut t, 2);
+    t.process(other.initialize_pool(DAY, 0, 0, None)).unwrap();
+    let u = staked_user(&mut t, &other, 1000);
+    assert_error(
+        t.process(other.compound(&u)),
+        ErrorCode::NoCompoundableReward,
+    );
+}
+
+#[test]
+fn claim_for_pays_only_the_reward_recipient() {
+    let mut t = ProgramTest::new();
+    let (f, funder) = setup(&mut t);
+    let u = staked_user(&mut t, &f, 1000);
+    let keeper = t.create_wallet();
+    let owner_accounts: Vec<Pubkey> = f
+        .reward_mints
+        .iter()
+        .map(|mint| t.create_associated_token_account(mint, &u.owner))
+        .collect();
+
+    t.process(f.fund(&funder, FUNDING, FUNDING)).unwrap();
+    t.warp_forward(DAY / 2);
+    assert_error(
+        t.process(f.claim_for(&u, keeper, keeper)),
+        ErrorCode::InvalidRewardAccounts,
+    );
+    t.process(f.claim_for(&u, keeper, u.owner)).unwrap();
+    assert_eq!(t.token_balance(&owner_accounts[0]), FUNDING / 2);
+    assert_eq!(t.token_balance(&owner_accounts[1]), FUNDING / 2);
+
+    //only the owner sets where their rewards go
+    let cold = t.create_wallet();
+    let cold_accounts: Vec<Pubkey> = f
+        .reward_mints
+        .iter()
+        .map(|mint| t.create_associated_token_account(mint, &cold))
+        .collect();
+    let other = staked_user(&mut t, &f, 1000);
+    let mut set_as_other = f.set_reward_recipient(&u, keeper);
+    set_as_other.accounts[1].pubkey = other.owner;
+    assert_error(t.process(set_as_other), AnchorErrorCode::ConstraintHasOne);
+    t.process(f.set_reward_recipient(&u, cold)).unwrap();
+    let user: User = t.get_account(&u.user);
+    assert_eq!(user.reward_recipient, cold);
+
+    t.warp_forward(DAY / 2);
+    assert_error(
+        t.process(f.claim_for(&u, keeper, u.owner)),
+        ErrorCode::InvalidRewardAccounts,
+    );
+    t.process(f.claim_for(&u, keeper, cold)).unwrap();
+    assert_eq!(t.token_balance(&cold_accounts[0]), FUNDING / 4);
+    assert_eq!(t.token_balance(&cold_accounts[1]), FUNDING / 4);
+    assert_eq!(t.token_balance(&owner_accounts[0]), FUNDING / 2);
+
+    //the default key pays the owner again
+    t.process(f.set_reward_recipient(&u, Pubkey::default()))
+        .unwrap();
+    t.process(f.claim_for(&u, keeper, u.owner)).unwrap();
+}
+
+#[test]
+fn sweep_donated_stake_takes_only_donations() {
+    let mut t = ProgramTest::new();
+    let (f, _) = setup(&mut t);
+    staked_user(&mut t, &f, 1000);
+    let receiver = f.create_wallet(&mut t, 0, 0);
+
+    assert_error(
+        t.process(f.sweep_donated_stake(receiver.staking_account)),
+        ErrorCode::NoDonatedStakeToSweep,
+    );
+
+    t.mint_to(&f.staking_vault, 500);
+    t.process(f.sweep_donated_stake(receiver.staking_account))
+        .unwrap();
+    assert_eq!(t.token_balance(&receiver.staking_account), 500);
+    assert_eq!(t.token_balance(&f.staking_vault), 1000);
+
+    let mut pool: Pool = t.get_account(&f.pool);
+    pool.total_staked = 1001;
+    t.set_anchor_account(&f.pool, &pool);
+    assert_error(
+        t.process(f.sweep_donated_stake(receiver.staking_account)),
+        ErrorCode::StakingVaultShortfall,
+    );
+}
+
+#[test]
+fn sweep_unallocated_rewards_takes_only_what_is_not_owed() {
+    let mut t = ProgramTest::new();
+    let (f, funder) = setup(&mut t);
+    let receiver = f.create_wallet(&mut t, 0, 0);
+    t.process(f.fund(&funder, FUNDING, 0)).unwrap();
+    assert_error(
+        t.process(f.sweep_unallocated_rewards(0, receiver.reward_accounts[0])),
+        ErrorCode::NoUnallocatedRewardsToSweep,
+    );
+
+    //nobody stakes for the first half of the period, so half the funding is never earned
+    t.warp_forward(DAY / 2);
+    let u = staked_user(&mut t, &f, 1000);
+    t.warp_forward(DAY / 4);
+    t.process(f.sweep_unallocated_rewards(0, receiver.reward_accounts[0]))
+        .unwrap();
+    assert_eq!(t.token_balance(&receiver.reward_accounts[0]), FUNDING / 2);
+    assert_error(
+        t.process(f.sweep_unallocated_rewards(1, receiver.reward_accounts[1])),
+        ErrorCode::NoUnallocatedRewardsToSweep,
+    );
+
+    t.warp_forward(DAY);
+    t.process(f.claim(&u)).unwrap();
+    assert_eq!(claimed(&t, &u), (FUNDING / 2, 0));
+    assert_eq!(t.token_balance(&f.reward_vaults[0]), 0);
+
+    //a pool from before rewards owed were tracked counts them from its vault on first sweep
+    let mut pool: Pool = t.get_account(&f.pool);
+    pool.reward_owed_tracked = 0;
+    t.set_anchor_account(&f.pool, &pool);
+    t.mint_to(&f.reward_vaults[0], 500);
+    t.process(f.sweep_unallocated_rewards(0, receiver.reward_accounts[0]))
+        .unwrap();
+    assert_eq!(t.token_balance(&receiver.reward_accounts[0]), FUNDING / 2);
+    assert_error(
+        t.process(f.sweep_unallocated_rewards(0, receiver.reward_accounts[0])),
+        ErrorCode::NoUnallocatedRewardsToSweep,
+    );
+    t.mint_to(&f.reward_vaults[0], 300);
+    t.process(f.sweep_unallocated_rewards(0, receiver.reward_accounts[0]))
+        .unwrap();
+    assert_eq!(
+        t.token_balance(&receiver.reward_accounts[0]),
+        FUNDING / 2 + 300
+    );
+
+    //a pool paying its staking token may pay it from the staking vault itself
+    let mut f = PoolFixture::new(&mut t, 2);
+    f.reward_mints[0] = f.staking_mint;
+    f.reward_vaults[0] = f.staking_vault;
+    t.process(f.initialize_pool(DAY, 0, 0, None)).unwrap();
+    assert_error(
+        t.process(f.sweep_unallocated_rewards(0, receiver.staking_account)),
+        ErrorCode::CannotSweepStakingVault,
+    );
+}
+
+#[test]
+fn close_user_and_close_pool_refund_everything() {
+    let mut t = ProgramTest::new();
+    let (f, funder) = setup(&mut t);
+    let u = staked_user(&mut t, &f, 1000);
+    t.process(f.fund(&funder, FUNDING, 0)).unwrap();
+    t.warp_forward(DAY + 1);
+
+    assert_error(t.process(f.close_user(&u)), AnchorErrorCode::ConstraintRaw);
+    t.process(f.unstake(&u, 1000, None)).unwrap();
+    assert_eq!(t.token_balance(&u.staking_account), 1000);
+    //rewards earned must be claimed first
+    assert_error(t.process(f.close_user(&u)), AnchorErrorCode::ConstraintRaw);
+    t.process(f.claim(&u)).unwrap();
+
+    t.process(f.pause()).unwrap();
+    let refundee = f.create_wallet(&mut t, 0, 0);
+    assert_error(
+        t.process(f.close_pool(&refundee)),
+        AnchorErrorCode::ConstraintRaw,
+    );
+
+    let user_rent = t.lamports(&u.user);
+    let owner_lamports = t.lamports(&u.owner);
+    t.process(f.close_user(&u)).unwrap();
+    assert!(t.account(&u.user).is_none());
+    assert_eq!(t.lamports(&u.owner), owner_lamports + user_rent);
+    let pool: Pool = t.get_account(&f.pool);
+    assert_eq!(pool.user_stake_count, 0);
+
+    let refundee_lamports = t.lamports(&refundee.owner);
+    let rent = t.lamports(&f.pool)
+        + t.lamports(&f.staking_vault)
+        + t.lamports(&f.reward_vaults[0])
+        + t.lamports(&f.reward_vaults[1]);
+    t.process(f.close_pool(&refundee)).unwrap();
+    assert!(t.account(&f.pool).is_none());
+    assert!(t.account(&f.staking_vault).is_none());
+    assert!(t.account(&f.reward_vaults[0]).is_none());
+    assert!(t.account(&f.reward_vaults[1]).is_none());
+    assert_eq!(t.lamports(&refundee.owner), refundee_lamports + rent);
+}
+
+#[test]
+fn migrate_grows_accounts_from_earlier_versions() {
+    let mut t = ProgramTest::new();
+    let (f, _) = setup(&mut t);
+    let u = staked_user(&mut t, &f, 1000);
+
+    //stand in for accounts created before the latest fields were appended
+    let payer = f.create_wallet(&mut t, 0, 0);
+    for (key, trimmed) in [(f.pool, 200), (u.user, 40)] {
+        let account = t.account(&key).unwrap().clone();
+        let len = account.data.len() - trimmed;
+        let lamports = Rent::default().minimum_balance(len);
+        t.set_account(key, lamports, account.data[..len].to_vec(), account.owner);
+    }
+
+    let payer_lamports = t.lamports(&payer.owner);
+    let pool_lamports = t.lamports(&f.pool);
+    t.process(f.migrate_pool(payer.owner)).unwrap();
+    let pool_len = 8 + Pool::default().try_to_vec().unwrap().len();
+    let pool_rent = Rent::default().minimum_balance(pool_len);
+    assert_eq!(t.account(&f.pool).unwrap().data.len(), pool_len);
+    assert_eq!(t.lamports(&f.pool), pool_rent);
+    assert_eq!(
+        t.lamports(&payer.owner),
+        payer_lamports - (pool_rent - pool_lamports)
+    );
+    let pool: Pool = t.get_account(&f.pool);
+    assert_eq!(pool.total_staked, 1000);
+
+    t.process(f.migrate_user(&u)).unwrap();
+    let user_len = 8 + User::default().try_to_vec().unwrap().len();
+    assert_eq!(t.account(&u.user).unwrap().data.len(), user_len);
+    let user: User = t.get_account(&u.user);
+    assert_eq!(user.balance_staked, 1000);
+
+    //current accounts are left as they are
+    t.process(f.migrate_pool(payer.owner)).unwrap();
+    assert_eq!(t.lamports(&f.pool), pool_rent);
+
+    let mut migrate_user_as_pool = f.migrate_pool(payer.owner);
+    migrate_user_as_pool.accounts[0].pubkey = u.user;
+    assert_error(
+        t.process(migrate_user_as_pool),
+        ErrorCode::InvalidAccountToMigrate,
+    );
+}
@@ -0,0 +1,421 @@
+mod harness;
+
+use anchor_lang::prelude::ProgramError;
+use proptest::collection::vec;
+use proptest::prelude::*;
+use reward_pool::calculator::{get_calculator, pending_rewards};
+use reward_pool::{ErrorCode, Pool, User};
+use spl_math::uint::U192;
+
+use harness::fixture::{PoolFixture, UserFixture};
+use harness::ProgramTest;
+
+const DAY: u64 = 86_400;
+const SECONDS_IN_YEAR: u64 = 365 * DAY;
+const PRECISION: u128 = u64::MAX as u128;
+
+const USERS: usize = 3;
+const MAX_OPS: usize = 40;
+const MAX_STAKE: u64 = 1_000_000_000_000;
+const MAX_FUNDING: u64 = 10_000_000_000_000;
+
+#[derive(Clone, Debug)]
+enum Op {
+    Stake { user: usize, amount: u64 },
+    Unstake { user: usize, percent: u64 },
+    Fund { amount_a: u64, amount_b: u64 },
+    Claim { user: usize },
+    Warp { seconds: u64 },
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        (0..USERS, 1..=MAX_STAKE).prop_map(|(user, amount)| Op::Stake { user, amount }),
+        (0..USERS, 1..=100u64).prop_map(|(user, percent)| Op::Unstake { user, percent }),
+        (1..=MAX_FUNDING, 0..=MAX_FUNDING)
+            .prop_map(|(amount_a, amount_b)| Op::Fund { amount_a, amount_b }),
+        (0..USERS).prop_map(|user| Op::Claim { user }),
+        (0..=3 * DAY).prop_map(|seconds| Op::Warp { seconds }),
+    ]
+}
+
+/// A reward A and B pool with its stakers, and the total it was funded with
+struct Side {
+    f: PoolFixture,
+    funder: UserFixture,
+    users: Vec<UserFixture>,
+    funded: [u64; 2],
+}
+
+impl Side {
+    fn new(t: &mut ProgramTest, reward_duration: u64) -> Self {
+        let f = PoolFixture::new(t, 2);
+        t.process(f.initialize_pool(reward_duration, 0, 0, None))
+            .unwrap();
+        let funder = f.create_wallet(t, 0, 1 << 62);
+        t.process(f.authorize_funder(funder.owner)).unwrap();
+        let users = (0..USERS)
+            .map(|_| {
+                let u = f.create_wallet(t, 1 << 62, 0);
+                t.process(f.create_user(&u)).unwrap();
+                u
+            })
+            .collect();
+        Side {
+            f,
+            funder,
+            users,
+            funded: [0, 0],
+        }
+    }
+
+    /// Turns the pool into a V1 pool that was funded with `amounts` just now, emitting
+    /// per second as V1 did
+    fn make_v1(&mut self, t: &mut ProgramTest, reward_duration: u64, amounts: [u64; 2]) {
+        let mut pool: Pool = t.get_account(&self.f.pool);
+        pool.version = Pool::default().version;
+        pool.reward_a_rate = amounts[0] / reward_duration;
+        pool.reward_b_rate = amounts[1] / reward_duration;
+        pool.last_update_time = t.now();
+        pool.reward_duration_end = t.now() + reward_duration;
+        t.set_anchor_account(&self.f.pool, &pool);
+        t.mint_to(&self.f.reward_vaults[0], amounts[0]);
+        t.mint_to(&self.f.reward_vaults[1], amounts[1]);
+        self.funded = amounts;
+    }
+
+    fn fund(&mut self, t: &mut ProgramTest, amount_a: u64, amount_b: u64) {
+        t.process(self.f.fund(&self.funder, amount_a, amount_b))
+            .unwrap();
+        self.funded[0] += amount_a;
+        self.funded[1] += amount_b;
+    }
+
+    fn apply(&mut self, t: &mut ProgramTest, op: &Op) {
+        match *op {
+            Op::Stake { user, amount } => {
+                t.process(self.f.stake(&self.users[user], amount)).unwrap();
+            }
+            Op::Unstake { user, percent } => {
+                let staked = t.get_account::<User>(&self.users[user].user).balance_staked;
+                let amount = staked * percent / 100;
+                if amount > 0 {
+                    t.process(self.f.unstake(&self.users[user], amount, None))
+                        .unwrap();
+                }
+            }
+            Op::Fund { amount_a, amount_b } => self.fund(t, amount_a, amount_b),
+            Op::Claim { user } => {
+                t.process(self.f.claim(&self.users[user])).unwrap();
+            }
+            Op::Warp { .. } => {}
+        }
+    }
+
+    /// What each user has been paid plus what they could claim now, per reward
+    fn accrued(&self, t: &ProgramTest) -> Vec<[u64; 2]> {
+        let pool: Pool = t.get_account(&self.f.pool);
+        self.users
+            .iter()
+            .map(|u| {
+                let user: User = t.get_account(&u.user);
+                let pending = pending_rewards(&pool, &user, t.now()).unwrap();
+                [
+                    t.token_balance(&u.reward_accounts[0]) + pending[0],
+                    t.token_balance(&u.reward_accounts[1]) + pending[1],
+                ]
+            })
+            .collect()
+    }
+
+    fn assert_solvent(&self, t: &ProgramTest) -> Result<(), TestCaseError> {
+        let accrued = self.accrued(t);
+        for i in 0..2 {
+            let total: u128 = accrued.iter().map(|a| a[i] as u128).sum();
+            prop_assert!(
+                total <= self.funded[i] as u128,
+                "reward {} accrued {} of {} funded",
+                i,
+                total,
+                self.funded[i]
+            );
+        }
+        Ok(())
+    }
+}
+
+fn warp(t: &mut ProgramTest, op: &Op) {
+    if let Op::Warp { seconds } = *op {
+        t.warp_forward(seconds);
+    }
+}
+
+proptest! {
+    #[test]
+    fn paid_and_pending_never_exceed_funding(
+        reward_duration in DAY..=7 * DAY,
+        ops in vec(op(), 1..=MAX_OPS),
+    ) {
+        let mut t = ProgramTest::new();
+        let mut side = Side::new(&mut t, reward_duration);
+
+        for op in ops.iter() {
+            warp(&mut t, op);
+            side.apply(&mut t, op);
+            side.assert_solvent(&t)?;
+        }
+    }
+
+    /// A V1 pool emits its funding at a per second rate rounded down, and a V2 pool at an
+    /// annual rate rounded down, so over a period they part by at most the duration plus
+    /// the funding's share of the year. Beyond that, each settlement rounds every user's
+    /// rewards down by at most a token per stake and per earned amount.
+    #[test]
+    fn upgraded_v1_pools_track_v2_pools(
+        reward_duration in DAY..=7 * DAY,
+        amount_a in DAY..=MAX_FUNDING,
+        amount_b in DAY..=MAX_FUNDING,
+        ops in vec(op(), 1..=MAX_OPS),
+    ) {
+        let mut t = ProgramTest::new();
+        let mut v1 = Side::new(&mut t, reward_duration);
+        let mut v2 = Side::new(&mut t, reward_duration);
+        v1.make_v1(&mut t, reward_duration, [amount_a, amount_b]);
+        v2.fund(&mut t, amount_a, amount_b);
+
+        let bounds: Vec<u64> = [amount_a, amount_b]
+            .iter()
+            .map(|amount| {
+                reward_duration
+                    + amount * reward_duration / SECONDS_IN_YEAR
+                    + 2 * (ops.len() as u64 + 1)
+            })
+            .collect();
+        for op in ops.iter() {
+            warp(&mut t, op);
+            v1.apply(&mut t, op);
+            v2.apply(&mut t, op);
+            v1.assert_solvent(&t)?;
+            v2.assert_solvent(&t)?;
+
+            for (a, b) in v1.accrued(&t).iter().zip(v2.accrued(&t)) {
+                for i in 0..2 {
+                    prop_assert!(
+                        a[i].max(b[i]) - a[i].min(b[i]) <= bounds[i],
+                        "reward {} accrued {} on v1 and {} on v2",
+                        i,
+                        a[i],
+                        b[i]
+                    );
+                }
+            }
+        }
+    }
+}
+
+//The reward math fails with a typed error wherever a result does not fit, rather than
+//panicking, so clients can tell why a transaction failed. The inputs that fail are:
+// - a V1 reward per token past u128 once the elapsed seconds times the per second rate
+//   pass u64, as PRECISION is u64::MAX
+// - a V2 reward per token past u128, for large rates over a small total staked
+// - reward per token stored overflowing as it accrues
+// - earned amounts past u64, or whose product with the staked balance passes u128
+// - a funding whose annual rate passes u64, which for a day long period is any funding
+//   above u64::MAX / 365
+// - upgrading a V1 pool whose per second rate is above u64::MAX / SECONDS_IN_YEAR
+// - reward per token when the clock is behind the pool's last update
+
+/// A pool emitting reward A at `rate` since `last_update_time`, with no reward B
+fn emitting_pool(rate: u64, last_update_time: u64, reward_per_token_stored: u128) -> Pool {
+    Pool {
+        reward_duration: DAY,
+        reward_duration_end: u64::MAX,
+        last_update_time,
+        reward_a_rate: rate,
+        reward_a_per_token_stored: reward_per_token_stored,
+        ..Pool::default()
+    }
+}
+
+/// An upgraded V1 pool, which is a V2 pool
+fn v2_pool(rate: u64, last_update_time: u64, reward_per_token_stored: u128) -> Pool {
+    let mut pool = emitting_pool(0, last_update_time, reward_per_token_stored);
+    pool.upgrade_if_needed().unwrap();
+    pool.reward_a_rate = rate;
+    pool
+}
+
+/// The result of reward math as the program reports it
+fn reported<T>(result: reward_pool::Result<T>) -> Result<T, ProgramError> {
+    result.map_err(ProgramError::from)
+}
+
+/// The expected result of reward math, failing with `error` where it does not fit
+fn expected<T>(value: Option<T>, error: ErrorCode) -> Result<T, ProgramError> {
+    value.ok_or_else(|| error.into())
+}
+
+/// The exact reward A per token, or None where it does not fit
+fn expected_reward_per_token(
+    stored: u128,
+    emitted: U192,
+    divisor: u64,
+    total_staked: u64,
+) -> Option<u128> {
+    let accrued = emitted / U192::from(divisor) / U192::from(total_staked);
+    if accrued > U192::from(u128::MAX) {
+        return None;
+    }
+    stored.checked_add(accrued.as_u128())
+}
+
+proptest! {
+    #[test]
+    fn v1_reward_per_token_fails_only_on_overflow(
+        rate in any::<u64>(),
+        elapsed in 0..1u64 << 40,
+        total_staked in 1..=u64::MAX,
+        stored in prop_oneof![0..=u64::MAX as u128, any::<u128>()],
+    ) {
+        let pool = emitting_pool(rate, 0, stored);
+        let emitted = U192::from(elapsed) * U192::from(rate) * U192::from(PRECISION);
+        let reward_per_token = if emitted > U192::from(u128::MAX) {
+            None
+        } else {
+            expected_reward_per_token(stored, emitted, 1, total_staked)
+        };
+
+        let result = get_calculator(&pool).reward_per_token(&pool, total_staked, elapsed);
+        prop_assert_eq!(
+            reported(result.map(|r| r[0])),
+            expected(reward_per_token, ErrorCode::MathOverflow)
+        );
+    }
+
+    #[test]
+    fn v2_reward_per_token_fails_only_on_overflow(
+        rate in any::<u64>(),
+        elapsed in 0..1u64 << 40,
+        total_staked in 1..=u64::MAX,
+        stored in prop_oneof![0..=u64::MAX as u128, any::<u128>()],
+    ) {
+        let pool = v2_pool(rate, 0, stored);
+        let emitted = U192::from(elapsed) * U192::from(rate) * U192::from(PRECISION);
+        let reward_per_token =
+            expected_reward_per_token(stored, emitted, SECONDS_IN_YEAR, total_staked);
+
+        let result = get_calculator(&pool).reward_per_token(&pool, total_staked, elapsed);
+        prop_assert_eq!(
+            reported(result.map(|r| r[0])),
+            expected(reward_per_token, ErrorCode::MathOverflow)
+        );
+    }
+
+    #[test]
+    fn reward_per_token_fails_when_the_clock_goes_backwards(
+        upgraded in any::<bool>(),
+        rate in any::<u64>(),
+        rewind in 1..=DAY,
+        total_staked in 0..=MAX_STAKE,
+    ) {
+        let pool = if upgraded {
+            v2_pool(rate, DAY, 0)
+        } else {
+            emitting_pool(rate, DAY, 0)
+        };
+
+        let result = get_calculator(&pool).reward_per_token(&pool, total_staked, DAY - rewind);
+        //an empty pool returns what is stored without looking at the clock
+        prop_assert_eq!(
+            reported(result.map(|r| r[0])),
+            expected((total_staked == 0).then_some(0), ErrorCode::ClockWentBackwards)
+        );
+    }
+
+    #[test]
+    fn user_earned_amount_fails_only_on_overflow(
+        upgraded in any::<bool>(),
+        balance_staked in any::<u64>(),
+        complete in any::<u128>(),
+        owed in prop_oneof![0..=u64::MAX as u128, any::<u128>()],
+        pending in any::<u64>(),
+    ) {
+        let stored = complete.saturating_add(owed);
+        let pool = if upgraded {
+            v2_pool(0, 0, stored)
+        } else {
+            emitting_pool(0, 0, stored)
+        };
+        let user = User {
+            balance_staked,
+            reward_a_per_token_complete: complete,
+            reward_a_per_token_pending: pending,
+            ..User::default()
+        };
+        let product = U192::from(balance_staked) * U192::from(stored - complete);
+        let earned = if product > U192::from(u128::MAX) {
+            None
+        } else {
+            let earned = product / U192::from(PRECISION) + U192::from(pending);
+            Some(earned).filter(|e| *e <= U192::from(u64::MAX)).map(|e| e.as_u64())
+        };
+
+        let result = get_calculator(&pool).user_earned_amount(&pool, &user);
+        prop_assert_eq!(
+            reported(result.map(|e| e[0])),
+            expected(earned, ErrorCode::MathOverflow)
+        );
+    }
+
+    #[test]
+    fn v2_funding_fails_only_when_the_rate_overflows(
+        reward_duration in DAY..=SECONDS_IN_YEAR,
+        rate in any::<u64>(),
+        remaining in 0..=SECONDS_IN_YEAR,
+        funding in any::<u64>(),
+    ) {
+        let mut pool = v2_pool(rate, 0, 0);
+        pool.reward_duration = reward_duration;
+        pool.reward_duration_end = DAY + remaining;
+        let leftover = remaining as u128 * rate as u128 / SECONDS_IN_YEAR as u128;
+        let annual_rate =
+            (funding as u128 + leftover) * SECONDS_IN_YEAR as u128 / reward_duration as u128;
+        let annual_rate = Some(annual_rate)
+            .filter(|r| funding as u128 + leftover <= u64::MAX as u128 && *r <= u64::MAX as u128)
+            .map(|r| r as u64);
+
+        let result =
+            get_calculator(&pool).rate_after_funding(&mut pool, &[0, 0], &[funding, 0], DAY);
+        prop_assert_eq!(
+            reported(result.map(|r| r[0])),
+            expected(annual_rate, ErrorCode::RateTooHigh)
+        );
+    }
+
+    #[test]
+    fn v2_funding_emits_all_but_a_token_over_any_duration(
+        reward_duration in DAY..=SECONDS_IN_YEAR,
+        funding in 0..=MAX_FUNDING,
+    ) {
+        let mut pool = v2_pool(0, 0, 0);
+        pool.reward_duration = reward_duration;
+
+        let rate = get_calculator(&pool)
+            .rate_after_funding(&mut pool, &[0, 0], &[funding, 0], DAY)
+            .unwrap()[0];
+        let emitted = rate as u128 * reward_duration as u128 / SECONDS_IN_YEAR as u128;
+        prop_assert!(emitted <= funding as u128);
+        prop_assert!(funding as u128 - emitted <= 1);
+    }
+
+    #[test]
+    fn v1_upgrade_fails_only_when_the_annual_rate_overflows(rate in any::<u64>()) {
+        let mut pool = emitting_pool(rate, 0, 0);
+
+        let result = pool.upgrade_if_needed().map(|_| pool.reward_a_rate);
+        prop_assert_eq!(
+            reported(result),
+            expected(rate.checked_mul(SECONDS_IN_YEAR), ErrorCode::RateTooHigh)
+        );
+    }
+}
@@ -0,0 +1,682 @@
+mod harness;
+
+use anchor_lang::error::ErrorCode as AnchorErrorCode;
+use anchor_lang::prelude::AccountMeta;
+use anchor_lang::solana_program::system_program;
+use reward_pool::{ErrorCode, Pool, PoolGate, User, MAX_LOCK_BOOST_BPS};
+
+use harness::fixture::{PoolFixture, UserFixture};
+use harness::{assert_error, ProgramTest};
+
+const DAY: u64 = 86_400;
+/// Funding that emits exactly 1000 tokens a second over a day
+const FUNDING: u64 = 86_400_000;
+
+fn staked_user(t: &mut ProgramTest, f: &PoolFixture, amount: u64) -> UserFixture {
+    let u = f.create_wallet(t, amount, 0);
+    t.process(f.create_user(&u)).unwrap();
+    t.process(f.stake(&u, amount)).unwrap();
+    u
+}
+
+#[test]
+fn locked_stake_earns_boosted_rewards() {
+    let mut t = ProgramTest::new();
+    let f = PoolFixture::new(&mut t, 2);
+    t.process(f.initialize_pool(DAY, 0, 0, None)).unwrap();
+    let funder = f.create_wallet(&mut t, 0, FUNDING);
+    t.process(f.authorize_funder(funder.owner)).unwrap();
+    let locker = staked_user(&mut t, &f, 1000);
+    let other = staked_user(&mut t, &f, 2000);
+
+    assert_error(
+        t.process(f.lock_stake(&locker, 1000, DAY)),
+        ErrorCode::PoolVersionNotSupported,
+    );
+    assert_error(
+        t.process(f.configure_lockup(DAY, 0, 10_000)),
+        ErrorCode::InvalidLockDuration,
+    );
+    assert_error(
+        t.process(f.configure_lockup(DAY, 4 * DAY, MAX_LOCK_BOOST_BPS + 1)),
+        ErrorCode::InvalidLockBoost,
+    );
+    t.process(f.configure_lockup(DAY, 4 * DAY, 10_000)).unwrap();
+
+    assert_error(
+        t.process(f.lock_stake(&locker, 1000, DAY - 1)),
+        ErrorCode::InvalidLockDuration,
+    );
+    assert_error(
+        t.process(f.lock_stake(&locker, 1001, DAY)),
+        ErrorCode::InsufficientStakeToLock,
+    );
+    //locking for the max duration doubles the weight of the stake
+    t.process(f.lock_stake(&locker, 1000, 4 * DAY)).unwrap();
+    let user: User = t.get_account(&locker.user);
+    assert_eq!(user.boost_weight, 1000);
+    assert_eq!(user.lock_end_ts, t.now() + 4 * DAY);
+    let pool: Pool = t.get_account(&f.pool);
+    assert_eq!(pool.total_boost_weight, 1000);
+
+    assert_error(
+        t.process(f.lock_stake(&locker, 0, DAY)),
+        ErrorCode::LockCannotBeShortened,
+    );
+    assert_error(
+        t.process(f.unstake(&locker, 1, None)),
+        ErrorCode::StakeLocked,
+    );
+
+    t.process(f.fund(&funder, FUNDING, 0)).unwrap();
+    t.warp_forward(DAY + 1);
+    t.process(f.claim(&locker)).unwrap();
+    t.process(f.claim(&other)).unwrap();
+    assert_eq!(t.token_balance(&locker.reward_accounts[0]), FUNDING / 2);
+    assert_eq!(t.token_balance(&other.reward_accounts[0]), FUNDING / 2);
+
+    assert_error(
+        t.process(f.release_lock(&locker)),
+        ErrorCode::LockNotExpired,
+    );
+    t.warp_forward(3 * DAY);
+    t.process(f.release_lock(&locker)).unwrap();
+    let user: User = t.get_account(&locker.user);
+    assert_eq!(user.balance_locked, 0);
+    assert_eq!(user.boost_weight, 0);
+    let pool: Pool = t.get_account(&f.pool);
+    assert_eq!(pool.total_boost_weight, 0);
+
+    t.process(f.unstake(&locker, 1000, None)).unwrap();
+    assert_eq!(t.token_balance(&locker.staking_account), 1000);
+}
+
+#[test]
+fn boosts_stop_earning_at_the_lock_end() {
+    let mut t = ProgramTest::new();
+    let f = PoolFixture::new(&mut t, 2);
+    t.process(f.initialize_pool(DAY, 0, 0, None)).unwrap();
+    t.process(f.configure_lockup(1, DAY, 10_000)).unwrap();
+    let funder = f.create_wallet(&mut t, 0, FUNDING);
+    t.process(f.authorize_funder(funder.owner)).unwrap();
+    let locker = staked_user(&mut t, &f, 1000);
+    let other = staked_user(&mut t, &f, 1500);
+
+    //half of the max duration boosts the locker to 1500 for the first half of the period
+    t.process(f.lock_stake(&locker, 1000, DAY / 2)).unwrap();
+    t.process(f.fund(&funder, FUNDING, 0)).unwrap();
+
+    //nobody touched the pool at the lock end, yet the boost only counts up to it
+    t.warp_forward(DAY);
+    t.process(f.claim(&locker)).unwrap();
+    let user: User = t.get_account(&locker.user);
+    assert_eq!((user.balance_locked, user.boost_weight), (0, 0));
+    let pool: Pool = t.get_account(&f.pool);
+    assert_eq!(pool.total_boost_weight, 0);
+    assert_error(
+        t.process(f.release_lock(&locker)),
+        ErrorCode::LockNotExpired,
+    );
+
+    t.process(f.claim(&other)).unwrap();
+    assert_eq!(
+        t.token_balance(&locker.reward_accounts[0]),
+        FUNDING / 4 + FUNDING / 5
+    );
+    assert_eq!(
+        t.token_balance(&other.reward_accounts[0]),
+        FUNDING / 4 + FUNDING * 3 / 10
+    );
+    t.process(f.unstake(&locker, 1000, None)).unwrap();
+}
+
+#[test]
+fn boosted_weight_beyond_a_u64_still_accrues() {
+    let mut t = ProgramTest::new();
+    let f = PoolFixture::new(&mut t, 2);
+    t.process(f.initialize_pool(DAY, 0, 0, None)).unwrap();
+    t.process(f.configure_lockup(DAY, 4 * DAY, 10_000)).unwrap();
+    let funder = f.create_wallet(&mut t, 0, FUNDING);
+    t.process(f.authorize_funder(funder.owner)).unwrap();
+    let whale = staked_user(&mut t, &f, u64::MAX / 2);
+    let other = staked_user(&mut t, &f, 1000);
+
+    //stake plus boosts overflow a u64
+    t.process(f.lock_stake(&whale, u64::MAX / 2, 4 * DAY))
+        .unwrap();
+    let pool: Pool = t.get_account(&f.pool);
+    assert!(pool
+        .total_staked
+        .checked_add(pool.total_boost_weight)
+        .is_none());
+
+    t.process(f.fund(&funder, FUNDING, 0)).unwrap();
+    t.warp_forward(DAY);
+    t.process(f.claim(&whale)).unwrap();
+    t.process(f.claim(&other)).unwrap();
+    //all but rounding goes to the whale
+    assert_eq!(t.token_balance(&whale.reward_accounts[0]), FUNDING - 2);
+    t.process(f.unstake(&other, 1000, None)).unwrap();
+}
+
+#[test]
+fn unbonding_stake_is_held_until_withdrawn() {
+    let mut t = ProgramTest::new();
+    let f = PoolFixture::new(&mut t, 2);
+    t.process(f.initialize_pool(DAY, 0, 0, None)).unwrap();
+    let funder = f.create_wallet(&mut t, 0, FUNDING);
+    t.process(f.authorize_funder(funder.owner)).unwrap();
+    t.process(f.set_unbonding_period(DAY)).unwrap();
+    let u = staked_user(&mut t, &f, 1000);
+
+    t.process(f.unstake(&u, 400, None)).unwrap();
+    let user: User = t.get_account(&u.user);
+    assert_eq!(user.balance_staked, 600);
+    assert_eq!(user.balance_unbonding, 400);
+    assert_eq!(user.unbonding_end_ts, t.now() + DAY);
+    assert_eq!(t.token_balance(&u.staking_account), 0);
+
+    t.process(f.cancel_unbonding(&u)).unwrap();
+    let user: User = t.get_account(&u.user);
+    assert_eq!(user.balance_staked, 1000);
+    assert_eq!(user.balance_unbonding, 0);
+    assert_error(
+        t.process(f.cancel_unbonding(&u)),
+        ErrorCode::AmountMustBeGreaterThanZero,
+    );
+
+    //unbonding stake stops earning
+    t.process(f.fund(&funder, FUNDING, 0)).unwrap();
+    t.warp_forward(DAY / 2);
+    t.process(f.unstake(&u, 1000, None)).unwrap();
+    t.warp_forward(DAY / 2);
+    t.process(f.claim(&u)).unwrap();
+    assert_eq!(t.token_balance(&u.reward_accounts[0]), FUNDING / 2);
+
+    assert_error(
+        t.process(f.withdraw_unbonded(&u)),
+        ErrorCode::UnbondingNotComplete,
+    );
+    t.warp_forward(DAY / 2);
+    t.process(f.withdraw_unbonded(&u)).unwrap();
+    assert_eq!(t.token_balance(&u.staking_account), 1000);
+    let pool: Pool = t.get_account(&f.pool);
+    assert_eq!(pool.total_unbonding, 0);
+    assert_error(
+        t.process(f.withdraw_unbonded(&u)),
+        ErrorCode::AmountMustBeGreaterThanZero,
+    );
+}
+
+#[test]
+fn early_exit_penalties_are_shared_by_remaining_stakers() {
+    let mut t = ProgramTest::new();
+    let f = PoolFixture::new(&mut t, 2);
+    t.process(f.initialize_pool(DAY, 1_000, DAY, None)).unwrap();
+    let stayer = staked_user(&mut t, &f, 1000);
+    let leaver = staked_user(&mut t, &f, 1000);
+
+    //halfway through the window the 10% penalty has decayed to 5%
+    t.warp_forward(DAY / 2);
+    t.process(f.unstake(&leaver, 1000, None)).unwrap();
+    assert_eq!(t.token_balance(&leaver.staking_account), 950);
+    //shares are floored per token, only what they add up to is owed
+    let pool: Pool = t.get_account(&f.pool);
+    assert_eq!(pool.total_penalty_rewards, 49);
+
+    t.process(f.claim_penalty_rewards(&stayer)).unwrap();
+    assert_eq!(t.token_balance(&stayer.staking_account), 49);
+    assert_error(
+        t.process(f.claim_penalty_rewards(&stayer)),
+        ErrorCode::AmountMustBeGreaterThanZero,
+    );
+    let pool: Pool = t.get_account(&f.pool);
+    assert_eq!(pool.total_penalty_rewards, 0);
+
+    //and is gone once the window passes
+    t.warp_forward(DAY / 2);
+    t.process(f.unstake(&stayer, 1000, None)).unwrap();
+    assert_eq!(t.token_balance(&stayer.staking_account), 1049);
+
+    //the rounding dust is swept, letting the pool close
+    let receiver = f.create_wallet(&mut t, 0, 0);
+    t.process(f.sweep_donated_stake(receiver.staking_account))
+        .unwrap();
+    assert_eq!(t.token_balance(&receiver.staking_account), 1);
+    assert_eq!(t.token_balance(&f.staking_vault), 0);
+
+    let funder = f.create_wallet(&mut t, 0, FUNDING);
+    t.process(f.authorize_funder(funder.owner)).unwrap();
+    t.process(f.fund(&funder, FUNDING, 0)).unwrap();
+    t.process(f.close_user(&stayer)).unwrap();
+    t.process(f.close_user(&leaver)).unwrap();
+    t.warp_forward(DAY + 1);
+    t.process(f.pause()).unwrap();
+    t.process(f.close_pool(&receiver)).unwrap();
+    assert!(t.account(&f.pool).is_none());
+}
+
+#[test]
+fn penalty_rewards_left_by_rounding_are_swept_once_every_user_leaves() {
+    let mut t = ProgramTest::new();
+    let f = PoolFixture::new(&mut t, 2);
+    t.process(f.initialize_pool(DAY, 1_000, DAY, None)).unwrap();
+    let stayers = [
+        (staked_user(&mut t, &f, 333), 333),
+        (staked_user(&mut t, &f, 333), 333),
+        (staked_user(&mut t, &f, 334), 334),
+    ];
+    let leaver = staked_user(&mut t, &f, 1000);
+
+    t.warp_forward(DAY / 2);
+    t.process(f.unstake(&leaver, 1000, None)).unwrap();
+    t.process(f.close_user(&leaver)).unwrap();
+    t.warp_forward(DAY / 2);
+
+    //each share floors to 16 of the 49 owed, stranding 1 once everyone has claimed
+    for (u, amount) in &stayers {
+        t.process(f.claim_penalty_rewards(u)).unwrap();
+        assert_eq!(t.token_balance(&u.staking_account), 16);
+        t.process(f.unstake(u, *amount, None)).unwrap();
+        t.process(f.close_user(u)).unwrap();
+    }
+    let pool: Pool = t.get_account(&f.pool);
+    assert_eq!(pool.total_penalty_rewards, 1);
+
+    let receiver = f.create_wallet(&mut t, 0, 0);
+    t.process(f.sweep_donated_stake(receiver.staking_account))
+        .unwrap();
+    assert_eq!(t.token_balance(&receiver.staking_account), 2);
+    assert_eq!(t.token_balance(&f.staking_vault), 0);
+    let pool: Pool = t.get_account(&f.pool);
+    assert_eq!(pool.total_penalty_rewards, 0);
+}
+
+#[test]
+fn early_exit_penalties_go_to_the_treasury() {
+    let mut t = ProgramTest::new();
+    let f = PoolFixture::new(&mut t, 2);
+    let treasury = f.create_wallet(&mut t, 0, 0).staking_account;
+    t.process(f.initialize_pool(DAY, 1_000, DAY, Some(treasury)))
+        .unwrap();
+    let u = staked_user(&mut t, &f, 1000);
+
+    assert_error(
+        t.process(f.unstake(&u, 1000, None)),
+        ErrorCode::InvalidPenaltyTreasury,
+    );
+    assert_error(
+        t.process(f.unstake(&u, 1000, Some(u.staking_account))),
+        ErrorCode::InvalidPenaltyTreasury,
+    );
+    t.process(f.unstake(&u, 1000, Some(treasury))).unwrap();
+    assert_eq!(t.token_balance(&u.staking_account), 900);
+    assert_eq!(t.token_balance(&treasury), 100);
+}
+
+#[test]
+fn penalty_treasuries_are_token_accounts_of_the_staking_mint() {
+    let mut t = ProgramTest::new();
+    let f = PoolFixture::new(&mut t, 2);
+    let wallet = f.create_wallet(&mut t, 0, 0);
+    for not_a_treasury in [wallet.reward_accounts[0], wallet.owner] {
+        assert_error(
+            t.process(f.initialize_pool(DAY, 1_000, DAY, Some(not_a_treasury))),
+            ErrorCode::InvalidPenaltyTreasury,
+        );
+    }
+    t.process(f.initialize_pool(DAY, 1_000, DAY, Some(wallet.staking_account)))
+        .unwrap();
+    let stayer = staked_user(&mut t, &f, 1000);
+    let first = staked_user(&mut t, &f, 1000);
+    let second = staked_user(&mut t, &f, 1000);
+
+    //once the treasury is closed the authority replaces it
+    t.set_account(wallet.staking_account, 0, vec![], system_program::ID);
+    assert!(t
+        .process(f.unstake(&first, 1000, Some(wallet.staking_account)))
+        .is_err());
+    assert_error(
+        t.process(f.set_penalty_treasury(Some(wallet.reward_accounts[0]))),
+        ErrorCode::InvalidPenaltyTreasury,
+    );
+    let treasury = f.create_wallet(&mut t, 0, 0).staking_account;
+    t.process(f.set_penalty_treasury(Some(treasury))).unwrap();
+    assert_eq!(t.get_account::<Pool>(&f.pool).penalty_treasury, treasury);
+    t.process(f.unstake(&first, 1000, Some(treasury))).unwrap();
+    assert_eq!(t.token_balance(&treasury), 100);
+
+    //without a treasury penalties are shared by the stakers again
+    t.process(f.set_penalty_treasury(None)).unwrap();
+    t.process(f.unstake(&second, 1000, None)).unwrap();
+    t.process(f.claim_penalty_rewards(&stayer)).unwrap();
+    assert_eq!(t.token_balance(&stayer.staking_account), 99);
+}
+
+#[test]
+fn stake_caps_limit_each_user_and_the_pool() {
+    let mut t = ProgramTest::new();
+    let f = PoolFixture::new(&mut t, 2).with_stake_caps(1000, 1500);
+    t.process(f.initialize_pool(DAY, 0, 0, None)).unwrap();
+    let first = staked_user(&mut t, &f, 1000);
+    let second = f.create_wallet(&mut t, 1000, 0);
+    t.process(f.create_user(&second)).unwrap();
+
+    t.mint_to(&first.staking_account, 1);
+    assert_error(
+        t.process(f.stake(&first, 1)),
+        ErrorCode::UserStakeCapExceeded,
+    );
+    assert_error(
+        t.process(f.stake(&second, 501)),
+        ErrorCode::PoolStakeCapExceeded,
+    );
+    t.process(f.stake(&second, 500)).unwrap();
+
+    //only the authority sets caps, and lowered caps leave stake already over them
+    let mut by_user = f.set_stake_caps(0, 0);
+    by_user.accounts[1].pubkey = first.owner;
+    assert_error(t.process(by_user), AnchorErrorCode::ConstraintHasOne);
+    t.process(f.set_stake_caps(400, 0)).unwrap();
+    let pool: Pool = t.get_account(&f.pool);
+    assert_eq!((pool.max_stake_per_user, pool.max_total_stake), (400, 0));
+    assert_eq!(pool.total_staked, 1500);
+    assert_error(
+        t.process(f.stake(&second, 1)),
+        ErrorCode::UserStakeCapExceeded,
+    );
+
+    //nor can unbonding stake be put back over them
+    t.process(f.set_unbonding_period(DAY)).unwrap();
+    t.process(f.unstake(&first, 100, None)).unwrap();
+    assert_error(
+        t.process(f.cancel_unbonding(&first)),
+        ErrorCode::UserStakeCapExceeded,
+    );
+
+    t.process(f.set_stake_caps(0, 0)).unwrap();
+    t.process(f.cancel_unbonding(&first)).unwrap();
+    t.process(f.stake(&first, 1)).unwrap();
+    let user: User = t.get_account(&first.user);
+    assert_eq!(user.balance_staked, 1001);
+}
+
+#[test]
+fn emergency_unstake_recovers_stake_when_rewards_cannot_be_calculated() {
+    let mut t = ProgramTest::new();
+    let f = PoolFixture::new(&mut t, 2);
+    t.process(f.initialize_pool(DAY, 0, 0, None)).unwrap();
+    let funder = f.create_wallet(&mut t, 0, FUNDING);
+    t.process(f.authorize_funder(funder.owner)).unwrap();
+    let u = staked_user(&mut t, &f, 1000);
+    let other = staked_user(&mut t, &f, 1000);
+
+    t.process(f.fund(&funder, FUNDING, 0)).unwrap();
+    t.warp_forward(DAY / 2);
+    t.process(f.unstake(&u, 500, None)).unwrap();
+    let user: User = t.get_account(&u.user);
+    assert_eq!(user.reward_a_per_token_pending, FUNDING / 4);
+    let owed = t.get_account::<Pool>(&f.pool).reward_owed[0];
+
+    //a reward per token about to overflow breaks every reward calculation
+    let mut pool: Pool = t.get_account(&f.pool);
+    pool.reward_a_per_token_stored = u128::MAX;
+    t.set_anchor_account(&f.pool, &pool);
+    t.warp_forward(1);
+    assert!(t.process(f.unstake(&u, 500, None)).is_err());
+
+    t.process(f.emergency_unstake(&u)).unwrap();
+    assert_eq!(t.token_balance(&u.staking_account), 1000);
+    let user: User = t.get_account(&u.user);
+    assert_eq!(user.balance_staked, 0);
+    assert_eq!(user.reward_a_per_token_pending, 0);
+    assert_eq!(user.reward_a_per_token_complete, u128::MAX);
+    let pool: Pool = t.get_account(&f.pool);
+    assert_eq!(pool.total_staked, 1000);
+    assert_eq!(pool.reward_owed[0], owed - FUNDING / 4);
+
+    assert_error(
+        t.process(f.emergency_unstake(&u)),
+        ErrorCode::AmountMustBeGreaterThanZero,
+    );
+    t.process(f.emergency_unstake(&other)).unwrap();
+    assert_eq!(t.token_balance(&other.staking_account), 1000);
+}
+
+#[test]
+fn rewards_forfeited_by_emergency_unstake_can_be_swept() {
+    let mut t = ProgramTest::new();
+    let f = PoolFixture::new(&mut t, 2);
+    t.process(f.initialize_pool(DAY, 1_000, DAY, None)).unwrap();
+    let funder = f.create_wallet(&mut t, 0, FUNDING);
+    t.process(f.authorize_funder(funder.owner)).unwrap();
+    let u = staked_user(&mut t, &f, 1000);
+    let other = staked_user(&mut t, &f, 1000);
+    let leaver = staked_user(&mut t, &f, 2000);
+    let receiver = f.create_wallet(&mut t, 0, 0);
+
+    //the leaver's 5% penalty is shared by the two others
+    t.process(f.fund(&funder, FUNDING, 0)).unwrap();
+    t.warp_forward(DAY / 2);
+    t.process(f.unstake(&leaver, 2000, None)).unwrap();
+    let pool: Pool = t.get_account(&f.pool);
+    assert_eq!(pool.total_penalty_rewards, 99);
+
+    //the user's rewards and penalty share, never settled, are released as they leave
+    t.warp_forward(DAY / 2);
+    t.process(f.emergency_unstake(&u)).unwrap();
+    let pool: Pool = t.get_account(&f.pool);
+    assert_eq!(pool.total_penalty_rewards, 50);
+    t.process(f.sweep_donated_stake(receiver.staking_account))
+        .unwrap();
+    assert_eq!(t.token_balance(&receiver.staking_account), 50);
+
+    t.warp_forward(1);
+    t.process(f.claim(&other)).unwrap();
+    t.process(f.claim(&leaver)).unwrap();
+    t.process(f.sweep_unallocated_rewards(0, receiver.reward_accounts[0]))
+        .unwrap();
+    let earned =
+        t.token_balance(&other.reward_accounts[0]) + t.token_balance(&leaver.reward_accounts[0]);
+    assert_eq!(
+        t.token_balance(&receiver.reward_accounts[0]),
+        FUNDING - earned
+    );
+    assert_eq!(t.token_balance(&f.reward_vaults[0]), 0);
+}
+
+#[test]
+fn emergency_unstake_keeps_the_early_exit_penalty_in_the_vault() {
+    let mut t = ProgramTest::new();
+    let f = PoolFixture::new(&mut t, 2);
+    t.process(f.initialize_pool(DAY, 1_000, DAY, None)).unwrap();
+    t.process(f.set_unbonding_period(DAY)).unwrap();
+    let stayer = staked_user(&mut t, &f, 1000);
+    let leaver = staked_user(&mut t, &f, 1000);
+    let receiver = f.create_wallet(&mut t, 0, 0);
+
+    //halfway through the window the 10% penalty has decayed to 5%
+    t.warp_forward(DAY / 2);
+    t.process(f.emergency_unstake(&leaver)).unwrap();
+    let user: User = t.get_account(&leaver.user);
+    assert_eq!(user.balance_staked, 0);
+    assert_eq!(user.balance_unbonding, 950);
+    assert_eq!(user.unbonding_end_ts, t.now() + DAY);
+    let pool: Pool = t.get_account(&f.pool);
+    assert_eq!(pool.total_penalty_rewards, 0);
+    assert_eq!(pool.total_unbonding, 950);
+    assert_eq!(t.token_balance(&leaver.staking_account), 0);
+
+    t.warp_forward(DAY);
+    t.process(f.withdraw_unbonded(&leaver)).unwrap();
+    assert_eq!(t.token_balance(&leaver.staking_account), 950);
+    assert_error(
+        t.process(f.claim_penalty_rewards(&stayer)),
+        ErrorCode::AmountMustBeGreaterThanZero,
+    );
+    t.process(f.sweep_donated_stake(receiver.staking_account))
+        .unwrap();
+    assert_eq!(t.token_balance(&receiver.staking_account), 50);
+}
+
+#[test]
+fn emergency_unstake_ignores_locks_and_the_penalty_treasury() {
+    let mut t = ProgramTest::new();
+    let f = PoolFixture::new(&mut t, 2);
+    let treasury = f.create_wallet(&mut t, 0, 0).staking_account;
+    t.process(f.initialize_pool(DAY, 1_000, DAY, Some(treasury)))
+        .unwrap();
+    t.process(f.configure_lockup(DAY, 4 * DAY, 10_000)).unwrap();
+    let locker = staked_user(&mut t, &f, 1000);
+    let other = staked_user(&mut t, &f, 1000);
+    t.process(f.lock_stake(&locker, 1000, 4 * DAY)).unwrap();
+    t.process(f.lock_stake(&other, 1000, 2 * DAY)).unwrap();
+
+    //the treasury account is closed, so penalties can no longer be paid into it
+    t.set_account(treasury, 0, vec![], system_program::ID);
+    assert!(t.process(f.unstake(&other, 1000, Some(treasury))).is_err());
+
+    //the locker leaves before the lock ends, giving up its boost
+    t.process(f.emergency_unstake(&locker)).unwrap();
+    assert_eq!(t.token_balance(&locker.staking_account), 900);
+    let user: User = t.get_account(&locker.user);
+    assert_eq!(
+        (user.balance_locked, user.lock_end_ts, user.boost_weight),
+        (0, 0, 0)
+    );
+    let pool: Pool = t.get_account(&f.pool);
+    assert_eq!(pool.total_boost_weight, 500);
+    assert_eq!(pool.total_staked, 1000);
+    assert_eq!(t.token_balance(&f.staking_vault), 1100);
+}
+
+#[test]
+fn stake_for_is_averaged_into_the_owners_penalty_window() {
+    let mut t = ProgramTest::new();
+    let f = PoolFixture::new(&mut t, 2);
+    t.process(f.initialize_pool(DAY, 1_000, DAY, None)).unwrap();
+    staked_user(&mut t, &f, 1000);
+    let owner = staked_user(&mut t, &f, 1000);
+    let payer = f.create_wallet(&mut t, 1000, 0);
+    let staked_at = t.now();
+
+    //an equal deposit halfway through the window moves the stake time a quarter of it
+    t.warp_forward(DAY / 2);
+    t.process(f.stake_for(&payer, owner.owner, 1000, vec![]))
+        .unwrap();
+    let user: User = t.get_account(&owner.user);
+    assert_eq!(user.balance_staked, 2000);
+    assert_eq!(user.stake_ts, staked_at + DAY / 4);
+
+    //so three quarters of the 10% penalty remains
+    t.process(f.unstake(&owner, 2000, None)).unwrap();
+    assert_eq!(t.token_balance(&owner.staking_account), 1850);
+}
+
+#[test]
+fn stake_for_from_a_second_wallet_pays_the_full_penalty() {
+    let mut t = ProgramTest::new();
+    let f = PoolFixture::new(&mut t, 2);
+    t.process(f.initialize_pool(DAY, 1_000, DAY, None)).unwrap();
+    staked_user(&mut t, &f, 1000);
+    let owner = f.create_wallet(&mut t, 0, 0);
+    t.process(f.create_user(&owner)).unwrap();
+    let second_wallet = f.create_wallet(&mut t, 1000, 0);
+
+    t.warp_forward(DAY);
+    t.process(f.stake_for(&second_wallet, owner.owner, 1000, vec![]))
+        .unwrap();
+    let user: User = t.get_account(&owner.user);
+    assert_eq!(user.stake_ts, t.now());
+
+    t.process(f.unstake(&owner, 1000, None)).unwrap();
+    assert_eq!(t.token_balance(&owner.staking_account), 900);
+}
+
+#[test]
+fn stake_for_opening_the_owners_account_starts_their_penalty_window() {
+    let mut t = ProgramTest::new();
+    let f = PoolFixture::new(&mut t, 2);
+    t.process(f.initialize_pool(DAY, 1_000, DAY, None)).unwrap();
+    staked_user(&mut t, &f, 1000);
+    let owner = f.create_wallet(&mut t, 0, 0);
+    let payer = f.create_wallet(&mut t, 1000, 0);
+
+    t.warp_forward(DAY);
+    t.process(f.stake_for(&payer, owner.owner, 1000, vec![]))
+        .unwrap();
+    let user: User = t.get_account(&owner.user);
+    assert_eq!(user.stake_ts, t.now());
+
+    t.process(f.unstake(&owner, 1000, None)).unwrap();
+    assert_eq!(t.token_balance(&owner.staking_account), 900);
+}
+
+#[test]
+fn stake_for_credits_the_owner_who_alone_unstakes() {
+    let mut t = ProgramTest::new();
+    let gatekeeper = t.create_wallet();
+    let f = PoolFixture::new(&mut t, 2).with_gate(PoolGate::Authority, gatekeeper);
+    t.process(f.initialize_pool(DAY, 0, 0, None)).unwrap();
+    let payer = f.create_wallet(&mut t, 2000, 0);
+    let owner = f.create_wallet(&mut t, 0, 0);
+
+    //opening the owner's account passes the gate for them
+    let mut without_gatekeeper = f.stake_for(&payer, owner.owner, 1000, vec![]);
+    without_gatekeeper.accounts.pop();
+    assert_error(t.process(without_gatekeeper), ErrorCode::NotAllowedToJoin);
+    t.process(f.stake_for(&payer, owner.owner, 1000, vec![]))
+        .unwrap();
+    t.process(f.stake_for(&payer, owner.owner, 500, vec![]))
+        .unwrap();
+    let user: User = t.get_account(&owner.user);
+    assert_eq!(user.owner, owner.owner);
+    assert_eq!(user.balance_staked, 1500);
+    let pool: Pool = t.get_account(&f.pool);
+    assert_eq!(pool.total_staked, 1500);
+    assert_eq!(pool.user_stake_count, 1);
+    assert_eq!(t.token_balance(&payer.staking_account), 500);
+
+    let mut unstake_as_payer = f.unstake(&owner, 1500, None);
+    unstake_as_payer.accounts[3].pubkey = payer.owner;
+    assert_error(
+        t.process(unstake_as_payer),
+        AnchorErrorCode::ConstraintSeeds,
+    );
+    t.process(f.unstake(&owner, 1500, None)).unwrap();
+    assert_eq!(t.token_balance(&owner.staking_account), 1500);
+}
+
+#[test]
+fn stake_for_checks_the_gate_against_the_owner() {
+    let mut t = ProgramTest::new();
+    let pass = t.create_mint(0);
+    let f = PoolFixture::new(&mut t, 2).with_gate(PoolGate::Mint, pass);
+    t.process(f.initialize_pool(DAY, 0, 0, None)).unwrap();
+    let payer = f.create_wallet(&mut t, 2000, 0);
+    let owner = f.create_wallet(&mut t, 0, 0);
+    let payers_pass = t.create_token_account(&pass, &payer.owner, 1);
+    let owners_pass = t.create_token_account(&pass, &owner.owner, 1);
+
+    //a payer holding the pass cannot open an account for an owner who does not
+    let mut with_payers_pass = f.stake_for(&payer, owner.owner, 1000, vec![]);
+    with_payers_pass
+        .accounts
+        .push(AccountMeta::new_readonly(payers_pass, false));
+    assert_error(t.process(with_payers_pass), ErrorCode::NotAllowedToJoin);
+    assert!(t.account(&owner.user).is_none());
+
+    let mut with_owners_pass = f.stake_for(&payer, owner.owner, 1000, vec![]);
+    with_owners_pass
+        .accounts
+        .push(AccountMeta::new_readonly(owners_pass, false));
+    t.process(with_owners_pass).unwrap();
+    //the gate is only checked as the account opens
+    t.process(f.stake_for(&payer, owner.owner, 1000, vec![]))
+        .unwrap();
+    let user: User = t.get_account(&owner.user);
+    assert_eq!(user.owner, owner.owner);
+    assert_eq!(user.balance_staked, 2000);
+    let pool: Pool = t.get_account(&f.pool);
+    assert_eq!(pool.user_stake_count, 1);
+}
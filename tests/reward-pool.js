@@ -4,7 +4,7 @@ const serumCmn = require("@project-serum/common");
 const { TOKEN_PROGRAM_ID, Token } = require("@solana/spl-token");
 const TokenInstructions = require("@project-serum/serum").TokenInstructions;
 const utils = require("./utils");
-const { User, claimForUsers } = require("./user");
+const { User, claimForUsers, configPubkey } = require("./user");
 const fs = require('fs');
 
 let program = anchor.workspace.RewardPool;
@@ -29,7 +29,7 @@ setProvider(provider);
 
 describe('Multiuser Reward Pool', () => {
 
-  let EXPECTED_POOL_CREATE_COST = 12_546_920;
+  let EXPECTED_POOL_CREATE_COST = 21_219_080;
 
   const rewardDuration = new anchor.BN(10);
   const rewardDuration2 = new anchor.BN(30);
@@ -49,6 +49,16 @@ describe('Multiuser Reward Pool', () => {
   let poolKeypair = anchor.web3.Keypair.generate();
   let poolKeypair2 = anchor.web3.Keypair.generate();
   let poolKeypair3 = anchor.web3.Keypair.generate();
+  let poolKeypair4 = anchor.web3.Keypair.generate();
+  let poolKeypair5 = anchor.web3.Keypair.generate();
+  let poolKeypair6 = anchor.web3.Keypair.generate();
+  let poolKeypair7 = anchor.web3.Keypair.generate();
+  let poolKeypair8 = anchor.web3.Keypair.generate();
+  let poolKeypair9 = anchor.web3.Keypair.generate();
+  let poolKeypair10 = anchor.web3.Keypair.generate();
+  let scheduledUser;
+  let gatedFunder;
+  let gatedUser;
 
   it("Initialize mints", async () => {
     console.log("Program ID: ", program.programId.toString());
@@ -71,6 +81,33 @@ describe('Multiuser Reward Pool', () => {
     stakingMint3 = await utils.createMint(provider, 2);
   });
 
+  it("Initializes the program config", async () => {
+    //the local validator loads the program as not upgradeable, so the program data does
+    //not exist and the localnet feature lets the wallet create the config
+    const [programData, _nonce] = await anchor.web3.PublicKey.findProgramAddress(
+      [program.programId.toBuffer()],
+      new anchor.web3.PublicKey("BPFLoaderUpgradeab1e11111111111111111111111")
+    );
+    let accounts = {
+      config: await configPubkey(program),
+      programData,
+      upgradeAuthority: provider.wallet.publicKey,
+      systemProgram: anchor.web3.SystemProgram.programId,
+    };
+    await program.rpc.initializeConfig(
+      provider.wallet.publicKey,
+      xMintPubkey,
+      new anchor.BN(10_000_000_000_000),
+      new anchor.BN(1),
+      { accounts }
+    );
+
+    let config = await program.account.programConfig.fetch(accounts.config);
+    assert(config.depositMint.equals(xMintPubkey));
+    assert(config.depositRequirement.eq(new anchor.BN(10_000_000_000_000)));
+    assert(config.minDuration.eqn(1));
+  });
+
   it("Initialize users", async () => {
     users = [1, 2, 3, 4, 5].map(a => new User(a));
     users2 = [11, 12].map(a => new User(a));
@@ -580,6 +617,21 @@ describe('Multiuser Reward Pool', () => {
       await users[2].stakeTokens(250_000_000);
   });
 
+  it('User emergency unstakes, forfeiting pending rewards', async () => {
+      let user = await program.account.user.fetch(users[2].userPubkey);
+      let staked = user.balanceStaked.toNumber();
+      let balanceBefore = await getTokenBalance(users[2].stakingPubkey);
+
+      await users[2].emergencyUnstake();
+      user = await program.account.user.fetch(users[2].userPubkey);
+      assert.strictEqual(user.balanceStaked.toNumber(), 0);
+      assert.strictEqual(user.rewardAPerTokenPending.toNumber(), 0);
+      assert.strictEqual(user.rewardBPerTokenPending.toNumber(), 0);
+      assert(await getTokenBalance(users[2].stakingPubkey) > balanceBefore);
+
+      await users[2].stakeTokens(staked);
+  });
+
   it("Tries to close a pool with active user", async () => {
     try {
       await funders[1].closePool();
@@ -588,6 +640,41 @@ describe('Multiuser Reward Pool', () => {
     }
   });
 
+  it('Pool 2 authority sweeps donated stake', async () => {
+    try {
+      await funders[1].sweepDonatedStake();
+      assert.fail("swept with nothing donated");
+    } catch (e) { }
+
+    //user 12 sends tokens straight to the vault, they are not staked
+    await users2[1].stakingMintObject.transfer(users2[1].stakingPubkey, funders[1].admin.stakingMintVault, users2[1].keypair, [], 1_000);
+    let pool = await program.account.pool.fetch(funders[1].admin.poolKeypair.publicKey);
+    assert.strictEqual(pool.totalStaked.toNumber(), 750_000);
+
+    await funders[1].sweepDonatedStake();
+    assert.strictEqual(1_000, parseInt((await provider.connection.getTokenAccountBalance(funders[1].stakingPubkey)).value.amount));
+    assert.strictEqual(750_000, parseInt((await provider.connection.getTokenAccountBalance(funders[1].admin.stakingMintVault)).value.amount));
+  });
+
+  it('Pool 2 authority sweeps unallocated rewards', async () => {
+    let rawBalance = async (pubkey) => parseInt((await provider.connection.getTokenAccountBalance(pubkey)).value.amount);
+
+    //funder 2 sends reward A straight to the vault, nobody is owed it
+    let vault = funders[1].admin.mintAVault;
+    let vaultBefore = await rawBalance(vault);
+    await funders[1].mintAObject.transfer(funders[1].mintAPubkey, vault, funders[1].keypair, [], 1_000);
+    let receiverBefore = await rawBalance(funders[1].mintAPubkey);
+
+    await funders[1].sweepUnallocatedRewards(0);
+    assert(await rawBalance(funders[1].mintAPubkey) >= receiverBefore + 1_000);
+    assert(await rawBalance(vault) <= vaultBefore);
+
+    try {
+      await funders[1].sweepUnallocatedRewards(0);
+      assert.fail("swept with nothing unallocated");
+    } catch (e) { }
+  });
+
   it('Pool 2 users unstake all, all close', async () => {
     await Promise.all(users2.map(a => a.unstakeTokens(250_000)));
     //this dude had another 250k staked
@@ -625,6 +712,428 @@ describe('Multiuser Reward Pool', () => {
     assert.strictEqual(bv, null);
   });
 
+  //multi reward pool, funder 2's A and B mints are its rewards
+  it("Creates a multi reward pool", async () => {
+    await funders[1].initializePoolV3(poolKeypair4, rewardDuration);
+    await funders[1].addReward(funders[1].mintAObject);
+    await funders[1].addReward(funders[1].mintBObject);
+
+    try {
+      await funders[1].addReward(funders[1].mintBObject);
+      assert.fail("added the same reward twice");
+    } catch (e) { }
+
+    let pool = await program.account.pool.fetch(poolKeypair4.publicKey);
+    assert.strictEqual(pool.rewards[0].mint.toString(), mintB.publicKey.toString());
+    assert.strictEqual(pool.rewards[1].mint.toString(), mintC.publicKey.toString());
+  });
+
+  it('Multi reward pool users stake', async () => {
+    await Promise.all(users2.map(a => a.createUserStakingAccount(poolKeypair4.publicKey)));
+    await Promise.all(users2.map(a => a.stakeTokens(100_000)));
+  });
+
+  it('Funder funds only the second reward', async () => {
+    await funders[1].fundV3([0, 1_000], [funders[1].mintAPubkey, funders[1].mintBPubkey]);
+
+    let pool = await program.account.pool.fetch(poolKeypair4.publicKey);
+    assert(pool.rewards[0].rate.eqn(0));
+    assert(pool.rewards[1].rate.gtn(0));
+  });
+
+  it('waits', async () => {
+    await wait(11); //pool 4 done
+  });
+
+  it('Multi reward pool users claim the second reward', async () => {
+    let rawBalance = async (pubkey) => parseInt((await provider.connection.getTokenAccountBalance(pubkey)).value.amount);
+    let beforeA = await Promise.all(users2.map(a => rawBalance(a.mintAPubkey)));
+    let beforeB = await Promise.all(users2.map(a => rawBalance(a.mintBPubkey)));
+
+    await Promise.all(users2.map(a => a.claimV3([1], [a.mintBPubkey])));
+
+    let afterA = await Promise.all(users2.map(a => rawBalance(a.mintAPubkey)));
+    let afterB = await Promise.all(users2.map(a => rawBalance(a.mintBPubkey)));
+    let remain = await rawBalance(funders[1].admin.rewardVaults[1]);
+
+    //nothing of the first reward, an even split of the second
+    assert.deepStrictEqual(afterA, beforeA);
+    let earned = afterB.map((b, i) => b - beforeB[i]);
+    earned.forEach(e => assert(e > 490 && e <= 500));
+    assert.strictEqual(earned[0] + earned[1] + remain, 1_000);
+  });
+
+  it('Multi reward pool closes', async () => {
+    await Promise.all(users2.map(a => a.unstakeTokens(100_000)));
+    await Promise.all(users2.map(a => a.closeUser()));
+
+    await funders[1].pausePool();
+    await funders[1].closePoolV3([funders[1].mintAPubkey, funders[1].mintBPubkey]);
+
+    let pool = await provider.connection.getAccountInfo(poolKeypair4.publicKey);
+    assert.strictEqual(pool, null);
+  });
+
+  //multi reward pool where each reward runs on its own schedule
+  it("Creates a pool with independent reward schedules", async () => {
+    await funders[1].initializePoolV3(poolKeypair5, rewardDuration, true);
+    await funders[1].addReward(funders[1].mintAObject);
+    await funders[1].addReward(funders[1].mintBObject);
+    await funders[1].setRewardDuration(1, 20);
+
+    let pool = await program.account.pool.fetch(poolKeypair5.publicKey);
+    assert(pool.rewards[0].rewardDuration.eq(rewardDuration));
+    assert(pool.rewards[1].rewardDuration.eqn(20));
+  });
+
+  it('Funding one reward leaves the schedule of the other', async () => {
+    await funders[1].fundV3([1_000], [funders[1].mintAPubkey]);
+    let pool = await program.account.pool.fetch(poolKeypair5.publicKey);
+    let firstEnd = pool.rewards[0].rewardDurationEnd;
+    assert(firstEnd.gtn(0));
+    assert(pool.rewards[1].rewardDurationEnd.eqn(0));
+
+    await funders[1].fundV3([0, 1_000], [funders[1].mintAPubkey, funders[1].mintBPubkey]);
+    pool = await program.account.pool.fetch(poolKeypair5.publicKey);
+    assert(pool.rewards[0].rewardDurationEnd.eq(firstEnd));
+    assert(pool.rewards[1].rewardDurationEnd.gt(firstEnd));
+    assert(pool.rewardDurationEnd.eq(pool.rewards[1].rewardDurationEnd));
+  });
+
+
+  //pool 3's funder returns with a campaign that starts later
+  it('Funder schedules a future reward period', async () => {
+    await funders[2].initializePool(poolKeypair6, rewardDuration3, true);
+
+    scheduledUser = new User(98);
+    let user = scheduledUser;
+    await user.init(10_000_000_000, xMintPubkey, 0, stakingMint3.publicKey, 500_000, mintB.publicKey, 0, mintB.publicKey, 0);
+    await user.createUserStakingAccount(poolKeypair6.publicKey);
+    await user.stakeTokens(100_000);
+
+    let now = await provider.connection.getBlockTime(await provider.connection.getSlot());
+    let startTs = now + 3;
+    let endTs = now + 8;
+
+    try {
+      await funders[2].fundScheduled(1_000, 0, now - 10, endTs);
+      assert.fail("scheduled a period in the past");
+    } catch (e) { }
+
+    await funders[2].fundScheduled(1_000, 0, startTs, endTs);
+
+    try {
+      await funders[2].fundScheduled(1_000, 0, startTs, endTs);
+      assert.fail("scheduled a second period");
+    } catch (e) { }
+
+    let pool = await program.account.pool.fetch(poolKeypair6.publicKey);
+    assert(pool.rewardARate.eqn(0));
+    assert(pool.scheduledStartTs.eqn(startTs));
+
+    await wait(5);
+
+    //the claim brings the scheduled period on
+    let [ra] = await user.claim();
+    pool = await program.account.pool.fetch(poolKeypair6.publicKey);
+    assert(pool.rewardARate.gtn(0));
+    assert(pool.rewardDurationEnd.eqn(endTs));
+    assert(pool.scheduledStartTs.eqn(0));
+    assert(ra > 0);
+  });
+
+  it('Funder tops up without moving the end date', async () => {
+    let before = await program.account.pool.fetch(poolKeypair6.publicKey);
+    await funders[2].topUp(1_000, 0);
+    let after = await program.account.pool.fetch(poolKeypair6.publicKey);
+
+    assert(after.rewardDurationEnd.eq(before.rewardDurationEnd));
+    assert(after.rewardARate.gt(before.rewardARate));
+
+    await wait(4); //pool 6 done
+
+    try {
+      await funders[2].topUp(1_000, 0);
+      assert.fail("topped up an ended period");
+    } catch (e) { }
+  });
+
+  it('Pool 6 enables lock ups', async () => {
+    await funders[2].configureLockup(1, 100, 10_000);
+
+    let pool = await program.account.pool.fetch(poolKeypair6.publicKey);
+    assert(pool.version.v5);
+  });
+
+  it('User locks stake for a boost', async () => {
+    try {
+      await scheduledUser.lockStake(50_000, 101);
+      assert.fail("locked for longer than the max lock duration");
+    } catch (e) { }
+
+    await scheduledUser.lockStake(50_000, 2);
+
+    //locked for 2% of the max lock, so a 2% boost of 100%
+    let user = await program.account.user.fetch(scheduledUser.userPubkey);
+    assert(user.balanceLocked.eqn(50_000));
+    assert(user.boostWeight.eqn(1_000));
+    let pool = await program.account.pool.fetch(poolKeypair6.publicKey);
+    assert(pool.totalBoostWeight.eqn(1_000));
+
+    try {
+      await scheduledUser.unstakeTokens(100_000);
+      assert.fail("unstaked locked stake");
+    } catch (e) { }
+    await scheduledUser.unstakeTokens(50_000);
+  });
+
+  it('User unstakes once unlocked', async () => {
+    await wait(3);
+    await funders[2].releaseLock(scheduledUser.userPubkey);
+
+    let pool = await program.account.pool.fetch(poolKeypair6.publicKey);
+    assert(pool.totalBoostWeight.eqn(0));
+    await scheduledUser.unstakeTokens(50_000);
+  });
+
+  it('Pool 6 unstakes unbond before withdrawal', async () => {
+    await funders[2].setUnbondingPeriod(3);
+    await scheduledUser.stakeTokens(10_000);
+    let before = await getTokenBalance(scheduledUser.stakingPubkey);
+
+    await scheduledUser.unstakeTokens(10_000);
+    let user = await program.account.user.fetch(scheduledUser.userPubkey);
+    assert(user.balanceStaked.eqn(0));
+    assert(user.balanceUnbonding.eqn(10_000));
+    assert.strictEqual(await getTokenBalance(scheduledUser.stakingPubkey), before);
+
+    try {
+      await scheduledUser.withdrawUnbonded();
+      assert.fail("withdrew before the unbonding period passed");
+    } catch (e) { }
+
+    //back into stake, then out again
+    await scheduledUser.cancelUnbonding();
+    user = await program.account.user.fetch(scheduledUser.userPubkey);
+    assert(user.balanceStaked.eqn(10_000));
+    assert(user.balanceUnbonding.eqn(0));
+    await scheduledUser.unstakeTokens(10_000);
+
+    await wait(4);
+    await scheduledUser.withdrawUnbonded();
+    assert(await getTokenBalance(scheduledUser.stakingPubkey) > before);
+  });
+
+  //pool 6 is done, its xSTEP moves to a pool with early exit penalties
+  it('Creates a pool with an early exit penalty', async () => {
+    await funders[2].pausePool();
+    await funders[2].initializePool(poolKeypair7, rewardDuration3, true, 5_000, 100);
+
+    let pool = await program.account.pool.fetch(poolKeypair7.publicKey);
+    assert.strictEqual(pool.earlyExitPenaltyBps, 5_000);
+    assert.strictEqual(pool.penaltyTreasury.toString(), anchor.web3.PublicKey.default.toString());
+  });
+
+  it('Early exit is penalized for the remaining stakers', async () => {
+    let exiter = new User(97);
+    await exiter.init(10_000_000_000, xMintPubkey, 0, stakingMint3.publicKey, 500_000, mintB.publicKey, 0, mintB.publicKey, 0);
+    await scheduledUser.createUserStakingAccount(poolKeypair7.publicKey);
+    await exiter.createUserStakingAccount(poolKeypair7.publicKey);
+    await scheduledUser.stakeTokens(100_000);
+    await exiter.stakeTokens(100_000);
+
+    let rawBalance = async (pubkey) => parseInt((await provider.connection.getTokenAccountBalance(pubkey)).value.amount);
+    let before = await rawBalance(exiter.stakingPubkey);
+    await exiter.unstakeTokens(100_000);
+    let returned = await rawBalance(exiter.stakingPubkey) - before;
+
+    //up to half, decayed a little for the time since staking
+    let penalty = 100_000 - returned;
+    assert(penalty > 45_000 && penalty <= 50_000);
+    let pool = await program.account.pool.fetch(poolKeypair7.publicKey);
+    assert(pool.totalPenaltyRewards.eqn(penalty));
+
+    //the only remaining staker gets it all, less rounding
+    before = await rawBalance(scheduledUser.stakingPubkey);
+    await scheduledUser.claimPenaltyRewards();
+    let claimed = await rawBalance(scheduledUser.stakingPubkey) - before;
+    assert(claimed >= penalty - 1 && claimed <= penalty);
+  });
+
+  //pool 8 rewards the token it stakes
+  it('User compounds rewards of the staking token', async () => {
+    let funder = new User(30);
+    await funder.init(10_000_000_000, xMintPubkey, 10_000_000_000_000, stakingMint3.publicKey, 0, stakingMint3.publicKey, 1_000_000, stakingMint3.publicKey, 0);
+    await funder.initializePool(poolKeypair8, rewardDuration3, true);
+
+    let user = new User(96);
+    await user.init(10_000_000_000, xMintPubkey, 0, stakingMint3.publicKey, 500_000, stakingMint3.publicKey, 0, stakingMint3.publicKey, 0);
+    await user.createUserStakingAccount(poolKeypair8.publicKey);
+    await user.stakeTokens(100_000);
+    await funder.fund(1_000_000, 0);
+
+    try {
+      await users[0].compound();
+      assert.fail("compounded on a pool not rewarding its staking token");
+    } catch (e) { }
+
+    await wait(2);
+    let before = await getTokenBalance(user.stakingPubkey);
+    await user.compound();
+
+    let userObject = await program.account.user.fetch(user.userPubkey);
+    assert(userObject.balanceStaked.gtn(100_000));
+    assert(userObject.rewardAPerTokenPending.eqn(0));
+    assert.strictEqual(await getTokenBalance(user.stakingPubkey), before);
+  });
+
+  it('Pool authority transfers in two steps', async () => {
+    let newAuthority = new User(32);
+    await newAuthority.init(10_000_000_000, xMintPubkey, 0, stakingMint.publicKey, 0, mintA.publicKey, 0, mintB.publicKey, 0);
+
+    await funders[0].proposeAuthority(newAuthority.pubkey);
+    await funders[0].cancelAuthorityTransfer();
+    try {
+      await newAuthority.acceptAuthority(poolKeypair.publicKey);
+      assert.fail("accepted a cancelled transfer");
+    } catch (e) { }
+
+    await funders[0].proposeAuthority(newAuthority.pubkey);
+    await newAuthority.acceptAuthority(poolKeypair.publicKey);
+    let poolObject = await program.account.pool.fetch(poolKeypair.publicKey);
+    assert(poolObject.authority.equals(newAuthority.pubkey));
+    assert.strictEqual(poolObject.pendingAuthority.toString(), anchor.web3.PublicKey.default.toString());
+    try {
+      await funders[0].authorizeFunder(anchor.web3.Keypair.generate().publicKey);
+      assert.fail("previous authority authorized a funder");
+    } catch (e) { }
+
+    //and back again
+    await newAuthority.proposeAuthority(funders[0].pubkey);
+    await funders[0].acceptAuthority(poolKeypair.publicKey);
+  });
+
+  //pool 9 lets in only users its gatekeeper co-signs for
+  it('Gated pool admits only users its gatekeeper co-signs for', async () => {
+    let funder = new User(33);
+    await funder.init(10_000_000_000, xMintPubkey, 10_000_000_000_000, stakingMint3.publicKey, 0, mintB.publicKey, 0, mintB.publicKey, 0);
+    let gatekeeper = anchor.web3.Keypair.generate();
+    await funder.initializePool(poolKeypair9, rewardDuration3, true, 0, 0, null, { authority: {} }, gatekeeper.publicKey);
+
+    let user = new User(97);
+    await user.init(10_000_000_000, xMintPubkey, 0, stakingMint3.publicKey, 500_000, mintB.publicKey, 0, mintB.publicKey, 0);
+    try {
+      await user.createUserStakingAccount(poolKeypair9.publicKey);
+      assert.fail("joined without the gatekeeper");
+    } catch (e) { }
+
+    await user.createUserStakingAccount(
+      poolKeypair9.publicKey,
+      [{ pubkey: gatekeeper.publicKey, isSigner: true, isWritable: false }],
+      [gatekeeper]
+    );
+    let pool = await program.account.pool.fetch(poolKeypair9.publicKey);
+    assert.strictEqual(pool.userStakeCount, 1);
+    assert.strictEqual(pool.gateKey.toString(), gatekeeper.publicKey.toString());
+    gatedFunder = funder;
+    gatedUser = user;
+  });
+
+  it('Stake caps limit what a user may stake', async () => {
+    await gatedFunder.setStakeCaps(300_000, 1_000_000);
+    let pool = await program.account.pool.fetch(poolKeypair9.publicKey);
+    assert(pool.maxStakePerUser.eqn(300_000));
+    assert(pool.maxTotalStake.eqn(1_000_000));
+
+    try {
+      await gatedUser.stakeTokens(400_000);
+      assert.fail("staked over the cap");
+    } catch (e) { }
+    await gatedUser.stakeTokens(300_000);
+    try {
+      await gatedUser.stakeTokens(1);
+      assert.fail("staked over the cap");
+    } catch (e) { }
+
+    await gatedFunder.setStakeCaps(0, 0);
+    await gatedUser.stakeTokens(100_000);
+  });
+
+  it('Program admin freezes every pool, leaving only exits', async () => {
+    let accounts = {
+      config: await configPubkey(program),
+      admin: provider.wallet.publicKey,
+    };
+    await program.rpc.setGlobalFreeze(true, { accounts });
+    try {
+      await gatedUser.stakeTokens(1);
+      assert.fail("staked while frozen");
+    } catch (e) { }
+    await gatedUser.unstakeTokens(100_000);
+
+    await program.rpc.setGlobalFreeze(false, { accounts });
+    await gatedUser.stakeTokens(100_000);
+    let config = await program.account.programConfig.fetch(accounts.config);
+    assert(!config.globalFreeze);
+  });
+
+  //pool 10 has its rewards claimed by a keeper
+  it('Keeper claims for a user, only into their reward recipient', async () => {
+    let funder = new User(34);
+    await funder.init(10_000_000_000, xMintPubkey, 10_000_000_000_000, stakingMint3.publicKey, 0, mintA.publicKey, 1_000_000, mintB.publicKey, 0);
+    await funder.initializePool(poolKeypair10, rewardDuration3, false);
+
+    let user = new User(94);
+    await user.init(10_000_000_000, xMintPubkey, 0, stakingMint3.publicKey, 500_000, mintA.publicKey, 0, mintB.publicKey, 0);
+    await user.createUserStakingAccount(poolKeypair10.publicKey);
+    await user.stakeTokens(100_000);
+    let cold = new User(95);
+    await cold.init(10_000_000_000, xMintPubkey, 0, stakingMint3.publicKey, 0, mintA.publicKey, 0, mintB.publicKey, 0);
+    await funder.fund(1_000_000, 0);
+
+    await wait(2);
+    try {
+      await funder.claimFor(user, funder);
+      assert.fail("claimed into the keeper's accounts");
+    } catch (e) { }
+    await funder.claimFor(user, user);
+    assert(await getTokenBalance(user.mintAPubkey) > 0);
+
+    await user.setRewardRecipient(cold.pubkey);
+    await wait(1);
+    try {
+      await funder.claimFor(user, user);
+      assert.fail("claimed into the owner's accounts once a recipient was set");
+    } catch (e) { }
+    await funder.claimFor(user, cold);
+    assert(await getTokenBalance(cold.mintAPubkey) > 0);
+  });
+
+  it('Payer stakes for another wallet, who alone unstakes', async () => {
+    let payer = new User(93);
+    await payer.init(10_000_000_000, xMintPubkey, 0, stakingMint3.publicKey, 500_000, mintA.publicKey, 0, mintB.publicKey, 0);
+    let owner = new User(92);
+    await owner.init(10_000_000_000, xMintPubkey, 0, stakingMint3.publicKey, 0, mintA.publicKey, 0, mintB.publicKey, 0);
+
+    let userPubkey = await payer.stakeFor(owner.pubkey, 200_000, poolKeypair10.publicKey);
+    await payer.stakeFor(owner.pubkey, 100_000, poolKeypair10.publicKey);
+    let userObject = await program.account.user.fetch(userPubkey);
+    assert(userObject.owner.equals(owner.pubkey));
+    assert(userObject.balanceStaked.eqn(300_000));
+
+    //the payer holds no position of their own to unstake from
+    payer.poolPubkey = poolKeypair10.publicKey;
+    payer.userPubkey = userPubkey;
+    try {
+      await payer.unstakeTokens(300_000);
+      assert.fail("payer unstaked the owner's stake");
+    } catch (e) { }
+
+    owner.poolPubkey = poolKeypair10.publicKey;
+    owner.userPubkey = userPubkey;
+    await owner.unstakeTokens(300_000);
+    assert.strictEqual(await getTokenBalance(owner.stakingPubkey), 3000);
+  });
 });  
 
 async function getTokenBalance(pubkey) {
@@ -17,6 +17,15 @@ async function claimForUsers(users) {
         });
 }
 
+//the program config every pool takes its xSTEP deposit and shortest reward period from
+async function configPubkey(program) {
+    const [configPubkey, _nonce] = await anchor.web3.PublicKey.findProgramAddress(
+        [Buffer.from("config")],
+        program.programId
+    );
+    return configPubkey;
+}
+
 ///user can be an admin or a staker. either way, call init - then can call other methods
 class User {
     constructor(a) { this.id = a; }
@@ -56,7 +65,12 @@ class User {
         if (initialStaking > 0) {
             await this.stakingMintObject.mintTo(this.stakingPubkey, envProvider.wallet.payer, [], initialStaking);
         }
-        this.mintAPubkey = await this.mintAObject.createAssociatedTokenAccount(this.pubkey);
+        //staking the reward token, will use the same account
+        if (mintA.equals(stakingMint)) {
+            this.mintAPubkey = this.stakingPubkey;
+        } else {
+            this.mintAPubkey = await this.mintAObject.createAssociatedTokenAccount(this.pubkey);
+        }
         if (initialA > 0) {
             await this.mintAObject.mintTo(this.mintAPubkey, envProvider.wallet.payer, [], initialA);
         }
@@ -71,7 +85,7 @@ class User {
         }
     }
 
-    async initializePool(poolKeypair, rewardDuration, singleStake) {
+    async initializePool(poolKeypair, rewardDuration, singleStake, earlyExitPenaltyBps = 0, earlyExitPenaltyWindow = 0, penaltyTreasury = null, gate = { open: {} }, gateKey = anchor.web3.PublicKey.default, maxStakePerUser = 0, maxTotalStake = 0) {
         const [
             _poolSigner,
             _nonce,
@@ -101,9 +115,21 @@ class User {
             mintBVault
         };
 
+        //a pool sending early exit penalties to a treasury is given its token account
+        let remainingAccounts = [];
+        if (penaltyTreasury) {
+            remainingAccounts.push({ pubkey: penaltyTreasury, isWritable: false, isSigner: false });
+        }
+
         await this.program.rpc.initializePool(
             poolNonce,
             rewardDuration,
+            earlyExitPenaltyBps,
+            new anchor.BN(earlyExitPenaltyWindow),
+            gate,
+            gateKey,
+            new anchor.BN(maxStakePerUser),
+            new anchor.BN(maxTotalStake),
             {
                 accounts: {
                     authority: this.provider.wallet.publicKey,
@@ -119,7 +145,9 @@ class User {
                     poolSigner: poolSigner,
                     pool: this.poolPubkey,
                     tokenProgram: TOKEN_PROGRAM_ID,
+                    config: await configPubkey(this.program),
                 },
+                remainingAccounts,
                 signers: [poolKeypair],
                 instructions: [
                     await this.program.account.pool.createInstruction(poolKeypair, ),
@@ -133,7 +161,91 @@ class User {
 
     }
 
-    async createUserStakingAccount(poolPubkey) {
+    async initializePoolV3(poolKeypair, rewardDuration, independentSchedules = false, gate = { open: {} }, gateKey = anchor.web3.PublicKey.default, maxStakePerUser = 0, maxTotalStake = 0) {
+        const [
+            _poolSigner,
+            _nonce,
+        ] = await anchor.web3.PublicKey.findProgramAddress(
+            [poolKeypair.publicKey.toBuffer()],
+            this.program.programId
+        );
+        let poolSigner = _poolSigner;
+        let poolNonce = _nonce;
+
+        let xTokenPoolVault = await this.xTokenMintObject.createAccount(poolSigner);
+        let stakingMintVault = await this.stakingMintObject.createAccount(poolSigner);
+
+        this.poolPubkey = poolKeypair.publicKey;
+        this.admin = {
+            poolKeypair,
+            poolSigner,
+            poolNonce,
+            xTokenPoolVault,
+            stakingMintVault,
+            rewardVaults: [],
+        };
+
+        let initializePool = independentSchedules ? this.program.rpc.initializePoolV4 : this.program.rpc.initializePoolV3;
+        await initializePool(
+            poolNonce,
+            rewardDuration,
+            gate,
+            gateKey,
+            new anchor.BN(maxStakePerUser),
+            new anchor.BN(maxTotalStake),
+            {
+                accounts: {
+                    authority: this.provider.wallet.publicKey,
+                    xTokenPoolVault: xTokenPoolVault,
+                    xTokenDepositor: this.xTokenPubkey,
+                    xTokenDepositAuthority: this.provider.wallet.publicKey,
+                    stakingMint: this.stakingMintObject.publicKey,
+                    stakingVault: stakingMintVault,
+                    poolSigner: poolSigner,
+                    pool: this.poolPubkey,
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                    config: await configPubkey(this.program),
+                },
+                signers: [poolKeypair],
+                instructions: [
+                    await this.program.account.pool.createInstruction(poolKeypair, ),
+                ],
+            }
+        );
+    }
+
+    async addReward(mintObject) {
+        let rewardVault = await mintObject.createAccount(this.admin.poolSigner);
+
+        await this.program.rpc.addReward(
+            {
+                accounts: {
+                    pool: this.poolPubkey,
+                    authority: this.provider.wallet.publicKey,
+                    rewardMint: mintObject.publicKey,
+                    rewardVault,
+                    poolSigner: this.admin.poolSigner,
+                },
+            });
+
+        this.admin.rewardVaults.push(rewardVault);
+    }
+
+
+    async setRewardDuration(rewardIndex, rewardDuration) {
+        await this.program.rpc.setRewardDuration(
+            rewardIndex,
+            new anchor.BN(rewardDuration),
+            {
+                accounts: {
+                    pool: this.poolPubkey,
+                    authority: this.provider.wallet.publicKey,
+                    config: await configPubkey(this.program),
+                },
+            });
+    }
+    //gated pools take a co-signing gate authority, or the token account proving the owner may join
+    async createUserStakingAccount(poolPubkey, remainingAccounts = [], signers = []) {
         this.poolPubkey = poolPubkey;
 
         const [
@@ -154,6 +266,8 @@ class User {
                 owner: this.provider.wallet.publicKey,
                 systemProgram: anchor.web3.SystemProgram.programId,
             },
+            remainingAccounts,
+            signers,
         });
     }
 
@@ -185,11 +299,58 @@ class User {
                     // Misc.
                     clock: anchor.web3.SYSVAR_CLOCK_PUBKEY,
                     tokenProgram: TOKEN_PROGRAM_ID,
+                    config: await configPubkey(this.program),
                 },
             }
         );
     }
 
+    //stakes this wallet's tokens for `owner`, opening their user account if needed
+    async stakeFor(owner, amount, poolPubkey) {
+        let poolObject = await this.program.account.pool.fetch(poolPubkey);
+
+        const [
+            _poolSigner,
+            _nonce,
+        ] = await anchor.web3.PublicKey.findProgramAddress(
+            [poolPubkey.toBuffer()],
+            this.program.programId
+        );
+        let poolSigner = _poolSigner;
+
+        const [
+            userPubkey,
+            _userNonce,
+        ] = await anchor.web3.PublicKey.findProgramAddress(
+            [owner.toBuffer(), poolPubkey.toBuffer()],
+            this.program.programId
+        );
+
+        await this.program.rpc.stakeFor(
+            new anchor.BN(amount),
+            [],
+            {
+                accounts: {
+                    // Stake instance.
+                    pool: poolPubkey,
+                    stakingVault: poolObject.stakingVault,
+                    // User.
+                    user: userPubkey,
+                    owner,
+                    payer: this.provider.wallet.publicKey,
+                    stakeFromAccount: this.stakingPubkey,
+                    // Program signers.
+                    poolSigner,
+                    // Misc.
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                    systemProgram: anchor.web3.SystemProgram.programId,
+                    config: await configPubkey(this.program),
+                },
+            }
+        );
+        return userPubkey;
+    }
+
     async pausePool(authority) {
         let poolObject = await this.program.account.pool.fetch(this.poolPubkey);
 
@@ -241,6 +402,7 @@ class User {
                     authority: authority ?? this.provider.wallet.publicKey,
                     poolSigner: poolSigner,
                     tokenProgram: TOKEN_PROGRAM_ID,
+                    config: await configPubkey(this.program),
                 },
             }
         );
@@ -258,6 +420,12 @@ class User {
         );
         let poolSigner = _poolSigner;
 
+        //pools sending early exit penalties to a treasury need it passed
+        let remainingAccounts = [];
+        if (!poolObject.penaltyTreasury.equals(anchor.web3.PublicKey.default)) {
+            remainingAccounts.push({ pubkey: poolObject.penaltyTreasury, isWritable: true, isSigner: false });
+        }
+
         await this.program.rpc.unstake(
             new anchor.BN(amount),
             {
@@ -275,6 +443,133 @@ class User {
                     clock: anchor.web3.SYSVAR_CLOCK_PUBKEY,
                     tokenProgram: TOKEN_PROGRAM_ID,
                 },
+                remainingAccounts,
+            });
+    }
+
+    async claimPenaltyRewards() {
+        let poolObject = await this.program.account.pool.fetch(this.poolPubkey);
+
+        const [
+            _poolSigner,
+            _nonce,
+        ] = await anchor.web3.PublicKey.findProgramAddress(
+            [this.poolPubkey.toBuffer()],
+            this.program.programId
+        );
+        let poolSigner = _poolSigner;
+
+        await this.program.rpc.claimPenaltyRewards(
+            {
+                accounts: {
+                    // Stake instance.
+                    pool: this.poolPubkey,
+                    stakingVault: poolObject.stakingVault,
+                    // User.
+                    user: this.userPubkey,
+                    owner: this.provider.wallet.publicKey,
+                    stakeFromAccount: this.stakingPubkey,
+                    // Program signers.
+                    poolSigner,
+                    // Misc.
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                    config: await configPubkey(this.program),
+                },
+            });
+    }
+
+    async withdrawUnbonded() {
+        let poolObject = await this.program.account.pool.fetch(this.poolPubkey);
+
+        const [
+            _poolSigner,
+            _nonce,
+        ] = await anchor.web3.PublicKey.findProgramAddress(
+            [this.poolPubkey.toBuffer()],
+            this.program.programId
+        );
+        let poolSigner = _poolSigner;
+
+        await this.program.rpc.withdrawUnbonded(
+            {
+                accounts: {
+                    // Stake instance.
+                    pool: this.poolPubkey,
+                    stakingVault: poolObject.stakingVault,
+                    // User.
+                    user: this.userPubkey,
+                    owner: this.provider.wallet.publicKey,
+                    stakeFromAccount: this.stakingPubkey,
+                    // Program signers.
+                    poolSigner,
+                    // Misc.
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                },
+            });
+    }
+
+    async emergencyUnstake() {
+        let poolObject = await this.program.account.pool.fetch(this.poolPubkey);
+
+        const [
+            _poolSigner,
+            _nonce,
+        ] = await anchor.web3.PublicKey.findProgramAddress(
+            [this.poolPubkey.toBuffer()],
+            this.program.programId
+        );
+        let poolSigner = _poolSigner;
+
+        await this.program.rpc.emergencyUnstake(
+            {
+                accounts: {
+                    // Stake instance.
+                    pool: this.poolPubkey,
+                    stakingVault: poolObject.stakingVault,
+                    // User.
+                    user: this.userPubkey,
+                    owner: this.provider.wallet.publicKey,
+                    stakeFromAccount: this.stakingPubkey,
+                    // Program signers.
+                    poolSigner,
+                    // Misc.
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                },
+            });
+    }
+
+    async cancelUnbonding() {
+        await this.program.rpc.cancelUnbonding(
+            {
+                accounts: {
+                    pool: this.poolPubkey,
+                    user: this.userPubkey,
+                    owner: this.provider.wallet.publicKey,
+                    config: await configPubkey(this.program),
+                },
+            });
+    }
+
+    async setUnbondingPeriod(unbondingPeriod) {
+        await this.program.rpc.setUnbondingPeriod(
+            new anchor.BN(unbondingPeriod),
+            {
+                accounts: {
+                    pool: this.poolPubkey,
+                    authority: this.provider.wallet.publicKey,
+                },
+            });
+    }
+
+    async setStakeCaps(maxStakePerUser, maxTotalStake) {
+        await this.program.rpc.setStakeCaps(
+            new anchor.BN(maxStakePerUser),
+            new anchor.BN(maxTotalStake),
+            {
+                accounts: {
+                    pool: this.poolPubkey,
+                    authority: this.provider.wallet.publicKey,
+                },
             });
     }
 
@@ -300,6 +595,36 @@ class User {
             });
     }
 
+    async proposeAuthority(newAuthority) {
+        await this.program.rpc.proposeAuthority(
+            newAuthority,
+            {
+                accounts: {
+                    pool: this.poolPubkey,
+                    authority: this.provider.wallet.publicKey,
+                },
+            });
+    }
+
+    async cancelAuthorityTransfer() {
+        await this.program.rpc.cancelAuthorityTransfer({
+            accounts: {
+                pool: this.poolPubkey,
+                authority: this.provider.wallet.publicKey,
+            },
+        });
+    }
+
+    async acceptAuthority(poolPubkey) {
+        await this.program.rpc.acceptAuthority({
+            accounts: {
+                pool: poolPubkey,
+                pendingAuthority: this.provider.wallet.publicKey,
+            },
+        });
+        this.poolPubkey = poolPubkey;
+    }
+
     async fund(amountA, amountB, poolPubkey) {
         let pubkeyToUse = poolPubkey ?? this.poolPubkey;
         let poolObject = await this.program.account.pool.fetch(pubkeyToUse);
@@ -331,6 +656,197 @@ class User {
                     // Misc.
                     clock: anchor.web3.SYSVAR_CLOCK_PUBKEY,
                     tokenProgram: TOKEN_PROGRAM_ID,
+                    config: await configPubkey(this.program),
+                },
+            });
+    }
+
+    async configureLockup(minLockDuration, maxLockDuration, maxLockBoostBps) {
+        let poolObject = await this.program.account.pool.fetch(this.poolPubkey);
+
+        await this.program.rpc.configureLockup(
+            new anchor.BN(minLockDuration),
+            new anchor.BN(maxLockDuration),
+            maxLockBoostBps,
+            {
+                accounts: {
+                    pool: this.poolPubkey,
+                    authority: this.provider.wallet.publicKey,
+                    stakingVault: poolObject.stakingVault,
+                },
+            });
+    }
+
+    async topUp(amountA, amountB) {
+        let poolObject = await this.program.account.pool.fetch(this.poolPubkey);
+
+        const [
+            _poolSigner,
+            _nonce,
+        ] = await anchor.web3.PublicKey.findProgramAddress(
+            [this.poolPubkey.toBuffer()],
+            this.program.programId
+        );
+        let poolSigner = _poolSigner;
+
+        await this.program.rpc.topUp(
+            new anchor.BN(amountA),
+            new anchor.BN(amountB),
+            {
+                accounts: {
+                    // Stake instance.
+                    pool: this.poolPubkey,
+                    stakingVault: poolObject.stakingVault,
+                    rewardAVault: poolObject.rewardAVault,
+                    rewardBVault: poolObject.rewardBVault,
+                    funder: this.provider.wallet.publicKey,
+                    fromA: this.mintAPubkey,
+                    fromB: this.mintBPubkey,
+                    // Program signers.
+                    poolSigner,
+                    // Misc.
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                    config: await configPubkey(this.program),
+                },
+            });
+    }
+
+    //startTs and endTs are unix timestamps of the queued reward period
+    async fundScheduled(amountA, amountB, startTs, endTs) {
+        let poolObject = await this.program.account.pool.fetch(this.poolPubkey);
+
+        const [
+            _poolSigner,
+            _nonce,
+        ] = await anchor.web3.PublicKey.findProgramAddress(
+            [this.poolPubkey.toBuffer()],
+            this.program.programId
+        );
+        let poolSigner = _poolSigner;
+
+        await this.program.rpc.fundScheduled(
+            new anchor.BN(amountA),
+            new anchor.BN(amountB),
+            new anchor.BN(startTs),
+            new anchor.BN(endTs),
+            {
+                accounts: {
+                    // Stake instance.
+                    pool: this.poolPubkey,
+                    stakingVault: poolObject.stakingVault,
+                    rewardAVault: poolObject.rewardAVault,
+                    rewardBVault: poolObject.rewardBVault,
+                    funder: this.provider.wallet.publicKey,
+                    fromA: this.mintAPubkey,
+                    fromB: this.mintBPubkey,
+                    // Program signers.
+                    poolSigner,
+                    // Misc.
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                    config: await configPubkey(this.program),
+                },
+            });
+    }
+
+    //amounts are ordered as the pool's rewards, fromAccounts are the token accounts to fund each from
+    async fundV3(amounts, fromAccounts) {
+        let poolObject = await this.program.account.pool.fetch(this.poolPubkey);
+
+        const [
+            _poolSigner,
+            _nonce,
+        ] = await anchor.web3.PublicKey.findProgramAddress(
+            [this.poolPubkey.toBuffer()],
+            this.program.programId
+        );
+        let poolSigner = _poolSigner;
+
+        let remainingAccounts = [];
+        amounts.forEach((amount, i) => {
+            if (amount > 0) {
+                remainingAccounts.push({ pubkey: poolObject.rewards[i].vault, isWritable: true, isSigner: false });
+                remainingAccounts.push({ pubkey: fromAccounts[i], isWritable: true, isSigner: false });
+            }
+        });
+
+        await this.program.rpc.fundV3(
+            amounts.map(a => new anchor.BN(a)),
+            {
+                accounts: {
+                    // Stake instance.
+                    pool: this.poolPubkey,
+                    stakingVault: poolObject.stakingVault,
+                    funder: this.provider.wallet.publicKey,
+                    // Program signers.
+                    poolSigner,
+                    // Misc.
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                    config: await configPubkey(this.program),
+                },
+                remainingAccounts,
+            });
+    }
+
+    //rewardIndexes are the pool's rewards to claim, toAccounts the token accounts to receive each
+    async claimV3(rewardIndexes, toAccounts) {
+        let poolObject = await this.program.account.pool.fetch(this.poolPubkey);
+
+        const [
+            _poolSigner,
+            _nonce,
+        ] = await anchor.web3.PublicKey.findProgramAddress(
+            [this.poolPubkey.toBuffer()],
+            this.program.programId
+        );
+        let poolSigner = _poolSigner;
+
+        let remainingAccounts = [];
+        rewardIndexes.forEach((idx, i) => {
+            remainingAccounts.push({ pubkey: poolObject.rewards[idx].vault, isWritable: true, isSigner: false });
+            remainingAccounts.push({ pubkey: toAccounts[i], isWritable: true, isSigner: false });
+        });
+
+        await this.program.rpc.claimV3(
+            Buffer.from(rewardIndexes),
+            {
+                accounts: {
+                    // Stake instance.
+                    pool: this.poolPubkey,
+                    stakingVault: poolObject.stakingVault,
+                    // User.
+                    user: this.userPubkey,
+                    owner: this.provider.wallet.publicKey,
+                    // Program signers.
+                    poolSigner,
+                    // Misc.
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                    config: await configPubkey(this.program),
+                },
+                remainingAccounts,
+            });
+    }
+
+    async lockStake(amount, lockDuration) {
+        await this.program.rpc.lockStake(
+            new anchor.BN(amount),
+            new anchor.BN(lockDuration),
+            {
+                accounts: {
+                    pool: this.poolPubkey,
+                    user: this.userPubkey,
+                    owner: this.provider.wallet.publicKey,
+                    config: await configPubkey(this.program),
+                },
+            });
+    }
+
+    //anyone may release an expired lock, userPubkey is the user account holding it
+    async releaseLock(userPubkey) {
+        await this.program.rpc.releaseLock(
+            {
+                accounts: {
+                    pool: this.poolPubkey,
+                    user: userPubkey,
                 },
             });
     }
@@ -357,20 +873,27 @@ class User {
         var singleStaking = poolObject.rewardAMint.toString() == poolObject.rewardBMint.toString();
 
         let poolVersion = poolObject.version;
+        let annualRate = poolVersion.v2 || poolVersion.v5;
 
-        let vaultBalance = await rewardsPoolAnchorProgram.provider.connection.getTokenAccountBalance(poolObject.stakingVault);
-        vaultBalance = new anchor.BN(parseInt(vaultBalance.value.amount));
+        //pools that have not yet tracked total staked still use the vault balance
+        let totalStaked = new anchor.BN(poolObject.totalStaked);
+        if (!poolObject.totalStakedTracked) {
+            let vaultBalance = await rewardsPoolAnchorProgram.provider.connection.getTokenAccountBalance(poolObject.stakingVault);
+            totalStaked = new anchor.BN(parseInt(vaultBalance.value.amount));
+        }
+        //rewards are shared by weight, lock boosts included
+        totalStaked = totalStaked.add(new anchor.BN(poolObject.totalBoostWeight));
 
         //a function that gives the total rewards emitted over the whole pool since last update
         let fnAllRewardsPerToken = () => {
             var lastApplicable = Math.min(Math.floor(Date.now() / 1000), poolObject.rewardDurationEnd);
             var elapsed = new anchor.BN(lastApplicable - lastUpdate);
-            var currentARewardPerToken = rewardAPerToken.add(elapsed.mul(rewardARate).mul(U64_MAX).div(poolVersion.v2 ? SECONDS_IN_YEAR : new anchor.BN(1)).div(vaultBalance));
+            var currentARewardPerToken = rewardAPerToken.add(elapsed.mul(rewardARate).mul(U64_MAX).div(annualRate ? SECONDS_IN_YEAR : new anchor.BN(1)).div(totalStaked));
             var currentBRewardPerToken;
             if (singleStaking) {
                 currentBRewardPerToken = new anchor.BN(0);
             } else {
-                currentBRewardPerToken = rewardBPerToken.add(elapsed.mul(rewardBRate).mul(U64_MAX).div(poolVersion.v2 ? SECONDS_IN_YEAR : new anchor.BN(1)).div(vaultBalance));
+                currentBRewardPerToken = rewardBPerToken.add(elapsed.mul(rewardBRate).mul(U64_MAX).div(annualRate ? SECONDS_IN_YEAR : new anchor.BN(1)).div(totalStaked));
             }
             return [currentARewardPerToken, currentBRewardPerToken];
         };
@@ -386,7 +909,7 @@ class User {
         let completeB = new anchor.BN(userObject.rewardBPerTokenComplete);
         let pendingA = new anchor.BN(userObject.rewardAPerTokenPending);
         let pendingB = new anchor.BN(userObject.rewardBPerTokenPending);
-        let balanceStaked = new anchor.BN(userObject.balanceStaked);
+        let balanceStaked = new anchor.BN(userObject.balanceStaked).add(new anchor.BN(userObject.boostWeight));
 
         //a function that gives a user's total unclaimed rewards since last update
         let currentPending = () => {
@@ -434,6 +957,7 @@ class User {
                 // Misc.
                 clock: anchor.web3.SYSVAR_CLOCK_PUBKEY,
                 tokenProgram: TOKEN_PROGRAM_ID,
+                config: await configPubkey(this.program),
             },
         });
 
@@ -448,6 +972,85 @@ class User {
         ];
     }
 
+    //claims all of a user's rewards on their behalf, into the associated token accounts of
+    //the user's reward recipient, `recipient`
+    async claimFor(user, recipient) {
+        let poolObject = await this.program.account.pool.fetch(user.poolPubkey);
+
+        const [
+            _poolSigner,
+            _nonce,
+        ] = await anchor.web3.PublicKey.findProgramAddress(
+            [user.poolPubkey.toBuffer()],
+            this.program.programId
+        );
+        let poolSigner = _poolSigner;
+
+        await this.program.rpc.claimFor({
+            accounts: {
+                // Stake instance.
+                pool: user.poolPubkey,
+                stakingVault: poolObject.stakingVault,
+                // User.
+                user: user.userPubkey,
+                claimer: this.provider.wallet.publicKey,
+                // Program signers.
+                poolSigner,
+                // Misc.
+                tokenProgram: TOKEN_PROGRAM_ID,
+                config: await configPubkey(this.program),
+            },
+            remainingAccounts: [
+                { pubkey: poolObject.rewardAVault, isWritable: true, isSigner: false },
+                { pubkey: recipient.mintAPubkey, isWritable: true, isSigner: false },
+                { pubkey: poolObject.rewardBVault, isWritable: true, isSigner: false },
+                { pubkey: recipient.mintBPubkey, isWritable: true, isSigner: false },
+            ],
+        });
+    }
+
+    async setRewardRecipient(rewardRecipient) {
+        await this.program.rpc.setRewardRecipient(
+            rewardRecipient,
+            {
+                accounts: {
+                    user: this.userPubkey,
+                    owner: this.provider.wallet.publicKey,
+                },
+            });
+    }
+
+    async compound() {
+        let poolObject = await this.program.account.pool.fetch(this.poolPubkey);
+
+        const [
+            _poolSigner,
+            _nonce,
+        ] = await anchor.web3.PublicKey.findProgramAddress(
+            [this.poolPubkey.toBuffer()],
+            this.program.programId
+        );
+        let poolSigner = _poolSigner;
+
+        await this.program.rpc.compound({
+            accounts: {
+                // Stake instance.
+                pool: this.poolPubkey,
+                stakingVault: poolObject.stakingVault,
+                rewardAVault: poolObject.rewardAVault,
+                rewardBVault: poolObject.rewardBVault,
+                // User.
+                user: this.userPubkey,
+                owner: this.provider.wallet.publicKey,
+                // Program signers.
+                poolSigner,
+                // Misc.
+                tokenProgram: TOKEN_PROGRAM_ID,
+                config: await configPubkey(this.program),
+            },
+        });
+    }
+
     //a transaction to stake, claim, unstake all at once - should net nothing
     async snipe(amount) {
         let poolObject = await this.program.account.pool.fetch(this.poolPubkey);
@@ -501,6 +1104,12 @@ class User {
             },
         });
         
+        //pools sending early exit penalties to a treasury need it passed
+        let remainingAccounts = [];
+        if (!poolObject.penaltyTreasury.equals(anchor.web3.PublicKey.default)) {
+            remainingAccounts.push({ pubkey: poolObject.penaltyTreasury, isWritable: true, isSigner: false });
+        }
+
         await this.program.rpc.unstake(
             new anchor.BN(amount),
             {
@@ -517,7 +1126,9 @@ class User {
                     // Misc.
                     clock: anchor.web3.SYSVAR_CLOCK_PUBKEY,
                     tokenProgram: TOKEN_PROGRAM_ID,
+                    config: await configPubkey(this.program),
                 },
+                remainingAccounts,
                 instructions: [
                     ixStake,
                     ixClaim,
@@ -531,6 +1142,58 @@ class User {
         return [amtA.value.uiAmount, amtB.value.uiAmount];
     }
 
+    async sweepDonatedStake() {
+        let poolObject = await this.program.account.pool.fetch(this.poolPubkey);
+
+        const [
+            _poolSigner,
+            _nonce,
+        ] = await anchor.web3.PublicKey.findProgramAddress(
+            [this.poolPubkey.toBuffer()],
+            this.program.programId
+        );
+        let poolSigner = _poolSigner;
+
+        await this.program.rpc.sweepDonatedStake(
+            {
+                accounts: {
+                    pool: this.poolPubkey,
+                    authority: this.provider.wallet.publicKey,
+                    stakingVault: poolObject.stakingVault,
+                    receiver: this.stakingPubkey,
+                    poolSigner,
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                },
+            });
+    }
+
+    async sweepUnallocatedRewards(rewardIndex) {
+        let poolObject = await this.program.account.pool.fetch(this.poolPubkey);
+
+        const [
+            _poolSigner,
+            _nonce,
+        ] = await anchor.web3.PublicKey.findProgramAddress(
+            [this.poolPubkey.toBuffer()],
+            this.program.programId
+        );
+        let poolSigner = _poolSigner;
+
+        await this.program.rpc.sweepUnallocatedRewards(
+            rewardIndex,
+            {
+                accounts: {
+                    pool: this.poolPubkey,
+                    authority: this.provider.wallet.publicKey,
+                    stakingVault: poolObject.stakingVault,
+                    rewardVault: rewardIndex == 0 ? poolObject.rewardAVault : poolObject.rewardBVault,
+                    receiver: rewardIndex == 0 ? this.mintAPubkey : this.mintBPubkey,
+                    poolSigner,
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                },
+            });
+    }
+
     async closeUser() {
         await this.program.rpc.closeUser(
             {
@@ -580,9 +1243,43 @@ class User {
                 },
             });
     }
+
+    async closePoolV3(rewardRefundees) {
+        let poolObject = await this.program.account.pool.fetch(this.poolPubkey);
+
+        const [
+            _poolSigner,
+            _nonce,
+        ] = await anchor.web3.PublicKey.findProgramAddress(
+            [this.poolPubkey.toBuffer()],
+            this.program.programId
+        );
+        let poolSigner = _poolSigner;
+
+        let remainingAccounts = [];
+        this.admin.rewardVaults.forEach((vault, i) => {
+            remainingAccounts.push({ pubkey: vault, isWritable: true, isSigner: false });
+            remainingAccounts.push({ pubkey: rewardRefundees[i], isWritable: true, isSigner: false });
+        });
+
+        await this.program.rpc.closePoolV3(
+            {
+                accounts: {
+                    refundee: this.provider.wallet.publicKey,
+                    stakingRefundee: this.stakingPubkey,
+                    pool: this.poolPubkey,
+                    authority: this.provider.wallet.publicKey,
+                    stakingVault: poolObject.stakingVault,
+                    poolSigner,
+                    tokenProgram: TOKEN_PROGRAM_ID,
+                },
+                remainingAccounts,
+            });
+    }
 }
 
 module.exports = {
     claimForUsers,
+    configPubkey,
     User
 };